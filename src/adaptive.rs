@@ -0,0 +1,112 @@
+// Adaptive frame rate: when output reports take up most of a frame (a congested Bluetooth
+// link, usually), frames come too fast for the writes and the animation stutters while
+// they pile up. The frame rate is stepped down until writes fit again, and back up toward
+// --fps once they have been quick for a while
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+// A write taking more of the frame than this holds up the next one
+const SLOW_SHARE: f32 = 0.8;
+// Stepping down aims for writes to take at most this share of a frame, and stepping up
+// only happens when they would at the faster rate too
+const HEADROOM: f32 = 0.5;
+// Slow writes in a row before stepping down, so one hiccup doesn't cost frames
+const SLOW_WRITES: u32 = 10;
+// How long writes have to keep up before stepping up again
+const RECOVER_AFTER: Duration = Duration::from_secs(10);
+// Each step down keeps this much of the rate, each step up undoes one
+const STEP: f32 = 0.75;
+// Weight of the newest write in the running latency average
+const SMOOTHING: f32 = 0.2;
+
+// The [adaptive_fps] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdaptiveFpsConfig {
+    pub enabled: bool,
+    // Never goes below this, however slow the writes
+    pub min_fps: u32,
+}
+
+impl Default for AdaptiveFpsConfig {
+    fn default() -> Self {
+        Self { enabled: true, min_fps: 15 }
+    }
+}
+
+pub struct AdaptiveRate {
+    max_fps: f32,
+    min_fps: f32,
+    fps: f32,
+    // Running average of the write latency, in seconds
+    latency: f32,
+    slow_writes: u32,
+    fast_since: Option<Instant>,
+}
+
+impl AdaptiveRate {
+    pub fn new(config: &AdaptiveFpsConfig, max_fps: u32) -> Result<Self, String> {
+        if config.min_fps == 0 {
+            return Err("adaptive_fps.min_fps must be at least 1".into());
+        }
+        Ok(Self {
+            max_fps: max_fps as f32,
+            // A --fps below the floor is simply never lowered
+            min_fps: config.min_fps.min(max_fps) as f32,
+            fps: max_fps as f32,
+            latency: 0.0,
+            slow_writes: 0,
+            fast_since: None,
+        })
+    }
+
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs_f32(1.0 / self.fps)
+    }
+
+    // Call once per frame with how long its write took, None when nothing was written.
+    // Returns the new rate when it changes
+    pub fn update(&mut self, write: Option<Duration>) -> Option<f32> {
+        let write = write?.as_secs_f32();
+        self.latency += (write - self.latency) * SMOOTHING;
+
+        if self.latency > SLOW_SHARE / self.fps {
+            self.fast_since = None;
+            self.slow_writes += 1;
+            if self.slow_writes < SLOW_WRITES {
+                return None;
+            }
+            self.slow_writes = 0;
+            let fitting = HEADROOM / self.latency;
+            let fps = (self.fps * STEP).min(fitting).round().max(self.min_fps);
+            if fps >= self.fps {
+                return None;
+            }
+            self.fps = fps;
+            return Some(fps);
+        }
+
+        self.slow_writes = 0;
+        let faster = (self.fps / STEP).round().min(self.max_fps);
+        if faster <= self.fps || self.latency > HEADROOM / faster {
+            self.fast_since = None;
+            return None;
+        }
+        if self.fast_since.get_or_insert_with(Instant::now).elapsed() < RECOVER_AFTER {
+            return None;
+        }
+        self.fast_since = None;
+        self.fps = faster;
+        Some(faster)
+    }
+
+    // The rate has been lowered from --fps
+    pub fn limited(&self) -> bool {
+        self.fps < self.max_fps
+    }
+
+    // Mean time a write takes lately
+    pub fn latency(&self) -> Duration {
+        Duration::from_secs_f32(self.latency)
+    }
+}
@@ -0,0 +1,280 @@
+// Keyframe animations for --animation, read from a TOML file (or JSON, by the .json
+// extension) with the same fields:
+//
+//   name = "Heartbeat"
+//   repeat = 0              # passes to play, 0 loops forever; the last color stays after
+//   direction = "normal"    # or "alternate", playing every other pass backwards
+//
+//   [[keyframes]]
+//   at_ms = 0               # the first keyframe is at 0, the others in increasing order
+//   color = "#300000"       # a name, hex or R,G,B, as in a palette
+//   easing = "ease-out"     # how this color turns into the next one's
+//
+// Easings follow CSS: linear (the default), ease, ease-in, ease-out, ease-in-out,
+// cubic-bezier(x1, y1, x2, y2) and step, which holds the color until the next keyframe.
+// One pass lasts until the last keyframe, so a loop that should run smoothly into its
+// start ends on the color it starts with.
+//
+// An animated GIF (by the .gif extension) plays too: each frame becomes a keyframe with
+// the frame's average color, held for the frame's delay, looping forever.
+use image::AnimationDecoder;
+use image::codecs::gif::GifDecoder;
+use serde::Deserialize;
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use dualsense_rainbow::colors;
+use crate::effects::mix;
+use crate::palette;
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct AnimationFile {
+    name: Option<String>,
+    #[serde(default)]
+    repeat: u32,
+    #[serde(default)]
+    direction: Direction,
+    keyframes: Vec<KeyframeFile>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct KeyframeFile {
+    at_ms: u64,
+    color: String,
+    easing: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    #[default]
+    Normal,
+    Alternate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    Step,
+    // The CSS curve through (0, 0), (x1, y1), (x2, y2) and (1, 1)
+    CubicBezier(f32, f32, f32, f32),
+}
+
+impl FromStr for Easing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        match s.as_str() {
+            "linear" => return Ok(Easing::Linear),
+            "step" => return Ok(Easing::Step),
+            "ease" => return Ok(Easing::CubicBezier(0.25, 0.1, 0.25, 1.0)),
+            "ease-in" => return Ok(Easing::CubicBezier(0.42, 0.0, 1.0, 1.0)),
+            "ease-out" => return Ok(Easing::CubicBezier(0.0, 0.0, 0.58, 1.0)),
+            "ease-in-out" => return Ok(Easing::CubicBezier(0.42, 0.0, 0.58, 1.0)),
+            _ => {},
+        }
+
+        let unknown = || format!("unknown easing '{}' (expected linear, step, ease, ease-in, ease-out, ease-in-out or cubic-bezier(x1, y1, x2, y2))", s);
+        let points = s.strip_prefix("cubic-bezier(").and_then(|rest| rest.strip_suffix(')')).ok_or_else(unknown)?;
+        let points: Vec<f32> = points.split(',').map(|n| n.trim().parse().map_err(|_| unknown())).collect::<Result<_, _>>()?;
+        let &[x1, y1, x2, y2] = points.as_slice() else {
+            return Err(unknown());
+        };
+        if !(0.0..=1.0).contains(&x1) || !(0.0..=1.0).contains(&x2) {
+            return Err(format!("cubic-bezier x values must be between 0 and 1, got {} and {}", x1, x2));
+        }
+        Ok(Easing::CubicBezier(x1, y1, x2, y2))
+    }
+}
+
+impl Easing {
+    // Progress 0..1 through a segment to the share of the color change made by then
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::Step => 0.0,
+            Easing::CubicBezier(x1, y1, x2, y2) => {
+                let bezier = |a: f32, b: f32, s: f32| 3.0 * a * s * (1.0 - s).powi(2) + 3.0 * b * s * s * (1.0 - s) + s.powi(3);
+                // x grows with s when both x values are in 0..1, so bisection always finds it
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..24 {
+                    let mid = (low + high) / 2.0;
+                    if bezier(x1, x2, mid) < t {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+                bezier(y1, y2, (low + high) / 2.0)
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Keyframe {
+    at: Duration,
+    color: (u8, u8, u8),
+    easing: Easing,
+}
+
+// A checked animation, ready to play
+#[derive(Clone, Debug)]
+pub struct Animation {
+    pub name: String,
+    keyframes: Vec<Keyframe>,
+    repeat: u32,
+    direction: Direction,
+}
+
+// Browsers play GIF frames with a shorter delay than this at 100 ms, and GIFs are made
+// to look right in browsers
+const GIF_MIN_DELAY: Duration = Duration::from_millis(20);
+const GIF_DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+impl Animation {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gif")) {
+            return Self::from_gif(path).map_err(|e| format!("invalid animation {}: {}", path.display(), e));
+        }
+        let text = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let file: AnimationFile = match json {
+            true => serde_json::from_str(&text).map_err(|e| e.to_string()),
+            false => toml::from_str(&text).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("invalid animation {}: {}", path.display(), e))?;
+        let fallback_name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        Self::new(file, fallback_name).map_err(|e| format!("invalid animation {}: {}", path.display(), e))
+    }
+
+    fn new(file: AnimationFile, fallback_name: String) -> Result<Self, String> {
+        if file.keyframes.is_empty() {
+            return Err("no keyframes".into());
+        }
+        let mut keyframes: Vec<Keyframe> = Vec::with_capacity(file.keyframes.len());
+        for (i, keyframe) in file.keyframes.iter().enumerate() {
+            let at = Duration::from_millis(keyframe.at_ms);
+            match keyframes.last() {
+                None if !at.is_zero() => return Err(format!("the first keyframe is at {} ms, not 0", keyframe.at_ms)),
+                Some(previous) if at <= previous.at => {
+                    return Err(format!("keyframe {} at {} ms is not after the one before it", i + 1, keyframe.at_ms));
+                },
+                _ => {},
+            }
+            let color = palette::parse_color(&keyframe.color).map_err(|e| format!("keyframe {}: {}", i + 1, e))?;
+            let easing = match &keyframe.easing {
+                Some(easing) => easing.parse().map_err(|e| format!("keyframe {}: {}", i + 1, e))?,
+                None => Easing::Linear,
+            };
+            keyframes.push(Keyframe { at, color, easing });
+        }
+        Ok(Self { name: file.name.unwrap_or(fallback_name), keyframes, repeat: file.repeat, direction: file.direction })
+    }
+
+    fn from_gif(path: &Path) -> Result<Self, String> {
+        let file = fs::File::open(path).map_err(|e| e.to_string())?;
+        let frames = GifDecoder::new(BufReader::new(file)).map_err(|e| e.to_string())?.into_frames();
+        let mut keyframes = Vec::new();
+        let mut at = Duration::ZERO;
+        for frame in frames {
+            let frame = frame.map_err(|e| e.to_string())?;
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            let delay = Duration::from_secs_f64(numerator as f64 / denominator.max(1) as f64 / 1000.0);
+            let delay = if delay < GIF_MIN_DELAY { GIF_DEFAULT_DELAY } else { delay };
+            keyframes.push(Keyframe { at, color: average_color(frame.buffer()), easing: Easing::Step });
+            at += delay;
+        }
+        let Some(last) = keyframes.last() else {
+            return Err("no frames".into());
+        };
+        // Ends the last frame's delay, holding its color like the others
+        keyframes.push(Keyframe { at, color: last.color, easing: Easing::Step });
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(Self { name, keyframes, repeat: 0, direction: Direction::Normal })
+    }
+
+    // One pass, from the first keyframe to the last
+    pub fn duration(&self) -> Duration {
+        self.keyframes.last().map_or(Duration::ZERO, |keyframe| keyframe.at)
+    }
+
+    pub fn color_at(&self, elapsed: Duration) -> (u8, u8, u8) {
+        let duration = self.duration();
+        if duration.is_zero() {
+            return self.keyframes[0].color;
+        }
+        let passes = elapsed.as_secs_f64() / duration.as_secs_f64();
+        let pass = passes.floor() as u64;
+        let finished = self.repeat > 0 && pass >= self.repeat as u64;
+        // Where in the pass it is, 0..1; after the last pass it stays at that pass's end
+        let (pass, mut position) = match finished {
+            true => (self.repeat as u64 - 1, 1.0),
+            false => (pass, passes.fract()),
+        };
+        if self.direction == Direction::Alternate && pass % 2 == 1 {
+            position = 1.0 - position;
+        }
+
+        let at = duration.mul_f64(position);
+        let next = self.keyframes.partition_point(|keyframe| keyframe.at <= at);
+        match (self.keyframes.get(next.wrapping_sub(1)), self.keyframes.get(next)) {
+            (Some(from), Some(to)) => {
+                let t = (at - from.at).as_secs_f32() / (to.at - from.at).as_secs_f32();
+                mix(from.color, to.color, from.easing.apply(t))
+            },
+            (Some(last), None) => last.color,
+            (None, _) => self.keyframes[0].color,
+        }
+    }
+
+    fn summary(&self) -> String {
+        let passes = match (self.repeat, self.direction) {
+            (0, Direction::Normal) => "loops forever".to_string(),
+            (0, Direction::Alternate) => "loops forever, alternating".to_string(),
+            (1, _) => "plays once".to_string(),
+            (n, Direction::Normal) => format!("plays {} times", n),
+            (n, Direction::Alternate) => format!("plays {} times, alternating", n),
+        };
+        format!("{}: {} keyframes over {:.2}s, {}", self.name, self.keyframes.len(), self.duration().as_secs_f32(), passes)
+    }
+}
+
+// The mean of a frame's pixels weighted by how opaque they are, black when it's all clear
+fn average_color(frame: &image::RgbaImage) -> (u8, u8, u8) {
+    let (mut sum, mut weight) = ([0.0f64; 3], 0.0f64);
+    for pixel in frame.pixels() {
+        let alpha = pixel[3] as f64 / 255.0;
+        (0..3).for_each(|c| sum[c] += pixel[c] as f64 * alpha);
+        weight += alpha;
+    }
+    if weight == 0.0 {
+        return (0, 0, 0);
+    }
+    let channel = |c: usize| (sum[c] / weight).round() as u8;
+    (channel(0), channel(1), channel(2))
+}
+
+// `animation check`: loads every file and reports what it found or what is wrong
+pub fn check(files: &[PathBuf]) -> Result<(), String> {
+    let mut failed = 0;
+    for file in files {
+        match Animation::load(file) {
+            Ok(animation) => println!("{}✓ {}{} {}", colors::GREEN, file.display(), colors::RESET, animation.summary()),
+            Err(e) => {
+                println!("{}✗{} {}", colors::RED, colors::RESET, e);
+                failed += 1;
+            },
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        n => Err(format!("{} of {} animations are invalid", n, files.len())),
+    }
+}
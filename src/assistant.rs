@@ -0,0 +1,138 @@
+// Small authenticated HTTP API shaped like a smart bulb, for Home Assistant's RESTful
+// integrations and local Alexa/Google bridges ("turn the controller purple").
+//
+//   POST /api/turn_on          {"color": "purple", "brightness": 128}   (both optional)
+//   POST /api/turn_off
+//   POST /api/set_color        {"color": "purple" | "#a000ff" | [160, 0, 255]}
+//   POST /api/set_brightness   {"brightness": 0-255} or {"brightness_pct": 0-100}
+//   GET  /api/state            {"on": true, "color": [r, g, b], "brightness": 0-255, ..., "stats": {...}}
+//
+// Every request needs "Authorization: Bearer <token>" with the token from the config.
+// Everything is turned into control commands, so it behaves exactly like `ctl`.
+use crossbeam_channel::Sender;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+
+use crate::control::{self, ControlCommand, Request, Setting};
+use crate::http::{self, read_request, respond};
+use crate::palette::Palette;
+use crate::tuning::Param;
+
+// The [assistant] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AssistantConfig {
+    pub address: String,
+    pub token: String,
+}
+
+impl Default for AssistantConfig {
+    fn default() -> Self {
+        Self { address: "127.0.0.1:47822".to_string(), token: String::new() }
+    }
+}
+
+pub fn start(config: &AssistantConfig, requests: Sender<Request>) -> Result<SocketAddr, String> {
+    if config.token.trim().is_empty() {
+        return Err("assistant.token must be set".into());
+    }
+    let listener = TcpListener::bind(&config.address).map_err(|e| format!("assistant API {}: {}", config.address, e))?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+
+    let token = config.token.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (token, requests) = (token.clone(), requests.clone());
+            thread::spawn(move || {
+                let _ = serve(stream, &token, &requests);
+            });
+        }
+    });
+    Ok(address)
+}
+
+fn serve(stream: TcpStream, token: &str, requests: &Sender<Request>) -> io::Result<()> {
+    let request = match read_request(&stream) {
+        Ok(request) => request,
+        Err(e) => return respond(&stream, "400 Bad Request", &json!({ "error": e.to_string() })),
+    };
+
+    if !request.header("authorization").and_then(|value| value.strip_prefix("Bearer ")).is_some_and(|given| same_token(given, token)) {
+        return respond(&stream, "401 Unauthorized", &json!({ "error": "missing or wrong bearer token" }));
+    }
+
+    let body: Value = match request.body.is_empty() {
+        true => json!({}),
+        false => match serde_json::from_slice(&request.body) {
+            Ok(body) => body,
+            Err(e) => return respond(&stream, "400 Bad Request", &json!({ "error": format!("invalid JSON: {}", e) })),
+        },
+    };
+
+    let commands = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/api/state") => Ok(vec![]),
+        ("POST", "/api/turn_on") => turn_on(&body),
+        ("POST", "/api/turn_off") => Ok(vec![ControlCommand::Power(false)]),
+        ("POST", "/api/set_color") => color_command(&body).map(|command| vec![command]),
+        ("POST", "/api/set_brightness") => brightness_command(&body).map(|command| vec![command]),
+        _ => return respond(&stream, "404 Not Found", &json!({ "error": "unknown endpoint" })),
+    };
+
+    // Apply the commands in order, then answer with the resulting state
+    let result = commands.and_then(|commands| {
+        commands.into_iter().chain([ControlCommand::Status]).try_fold(None, |_, command| control::submit(requests, command))
+    });
+    match result {
+        Ok(Some(status)) => {
+            let body = json!({
+                "on": status.on,
+                "color": [status.color.0, status.color.1, status.color.2],
+                "brightness": (status.brightness * 255.0).round() as u8,
+                "effect": status.effect.name(),
+                "profile": status.profile,
+                "stats": http::stats_json(&status.stats),
+            });
+            respond(&stream, "200 OK", &body)
+        },
+        Ok(None) => respond(&stream, "200 OK", &json!({})),
+        Err(e) => respond(&stream, "400 Bad Request", &json!({ "error": e })),
+    }
+}
+
+// Looks at every byte whatever the first difference, so how long the answer takes doesn't
+// tell how much of a guessed token was right
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// turn_on may carry a color and brightness, like Home Assistant's light.turn_on
+fn turn_on(body: &Value) -> Result<Vec<ControlCommand>, String> {
+    let mut commands = Vec::new();
+    if body.get("color").is_some() {
+        commands.push(color_command(body)?);
+    }
+    if body.get("brightness").is_some() || body.get("brightness_pct").is_some() {
+        commands.push(brightness_command(body)?);
+    }
+    commands.push(ControlCommand::Power(true));
+    Ok(commands)
+}
+
+fn color_command(body: &Value) -> Result<ControlCommand, String> {
+    let color = http::color(body.get("color"))?;
+    // A one-color palette holds the running effect on that color
+    Ok(ControlCommand::SetPalette(Some(Palette::new(vec![color]))))
+}
+
+fn brightness_command(body: &Value) -> Result<ControlCommand, String> {
+    let level = match (body.get("brightness"), body.get("brightness_pct")) {
+        (Some(value), _) => value.as_f64().filter(|v| (0.0..=255.0).contains(v)).map(|v| v / 255.0),
+        (None, Some(value)) => value.as_f64().filter(|v| (0.0..=100.0).contains(v)).map(|v| v / 100.0),
+        (None, None) => None,
+    };
+    let level = level.ok_or("expected \"brightness\" 0-255 or \"brightness_pct\" 0-100")?;
+    Ok(ControlCommand::SetParams(vec![Setting::Level(Param::Brightness, level as f32)]))
+}
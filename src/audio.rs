@@ -0,0 +1,708 @@
+// --audio: the lightbar reacts to sound from the default input device, from what the
+// speakers play (WASAPI loopback on Windows), or on Linux from anything PipeWire can
+// record (see pipewire.rs). Bands of
+// frequencies each drive one thing, by default the bass pumps the brightness, the mids
+// push the hue around and the highs flash towards white.
+//
+// Every band is a band-pass filter whose level is normalized against its own recent
+// peak, so quiet and loud music both use the full range, then smoothed with separate
+// attack and release times.
+//
+// --music instead colors the lightbar itself from what the speakers play: an FFT of the
+// last few tens of milliseconds gives the bass energy for the brightness and the
+// spectral centroid (dull and boomy to bright and tinny) for the hue, red to violet.
+//
+// --beat finds the beats in the bass (see beat.rs) and flashes the next color of a
+// palette on each one, or steps to it and holds it until the next.
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use serde::Deserialize;
+use std::f32::consts::PI;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::error;
+
+use crate::beat::BeatDetector;
+use crate::palette;
+
+// Darkest the lightbar gets on silence when a band drives the brightness
+const MIN_BRIGHTNESS: f32 = 0.1;
+// Hue rotation at full level of a hue-offset band
+const HUE_RANGE: f32 = 180.0;
+// A band's peak halves over this many seconds of quieter sound
+const PEAK_HALF_LIFE: f32 = 10.0;
+// Levels below this count as silence, so hiss doesn't get normalized up to full range
+const NOISE_FLOOR: f32 = 0.005;
+// Samples per FFT, about 43 ms at 48 kHz
+const FFT_SIZE: usize = 2048;
+// Hue at the top of the centroid range, violet; the bottom is red
+const MUSIC_HUE_RANGE: f32 = 270.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Brightness,
+    HueOffset,
+    Flash,
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "brightness" => Ok(Target::Brightness),
+            "hue-offset" | "hue" => Ok(Target::HueOffset),
+            "flash" | "white-flash" => Ok(Target::Flash),
+            _ => Err(format!("unknown audio target '{}' (expected brightness, hue-offset or flash)", s)),
+        }
+    }
+}
+
+// One [[audio.bands]] entry of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BandConfig {
+    pub low_hz: f32,
+    pub high_hz: f32,
+    // brightness, hue-offset or flash
+    pub target: String,
+    // Scales the normalized level, above 1 reaches full effect on quieter passages
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+    // How fast the band follows a rise and a fall of its level
+    pub attack_ms: f32,
+    pub release_ms: f32,
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+// What the sound does, picked on the command line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    // --audio: the [[audio.bands]] shape the running effect
+    Bands,
+    // --music: the color from the spectrum of the output
+    Music,
+    // --beat: palette colors on the beat
+    Beat,
+}
+
+// What --beat does on a beat
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BeatAction {
+    // The next color at full brightness, fading to dark
+    Flash,
+    // The next color, held until the next beat
+    Step,
+}
+
+impl FromStr for BeatAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "flash" => Ok(BeatAction::Flash),
+            "step" => Ok(BeatAction::Step),
+            _ => Err(format!("unknown beat action '{}' (expected flash or step)", s)),
+        }
+    }
+}
+
+// Where the sound comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    // The default input device, a microphone or line in
+    Input,
+    // Whatever the default output device plays
+    Output,
+}
+
+impl FromStr for Capture {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "input" => Ok(Capture::Input),
+            "output" => Ok(Capture::Output),
+            _ => Err(format!("unknown audio capture '{}' (expected input or output)", s)),
+        }
+    }
+}
+
+// The [audio] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    // input or output
+    pub capture: String,
+    // PipeWire node to record instead, by name or part of its description, e.g. "firefox"
+    pub node: Option<String>,
+    pub bands: Vec<BandConfig>,
+    // --music and --beat, which ignore the bands
+    pub music: MusicConfig,
+    pub beat: BeatConfig,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        let band = |low_hz, high_hz, target: &str, attack_ms, release_ms| BandConfig {
+            low_hz,
+            high_hz,
+            target: target.to_string(),
+            gain: 1.0,
+            attack_ms,
+            release_ms,
+        };
+        Self {
+            capture: "input".to_string(),
+            node: None,
+            music: MusicConfig::default(),
+            beat: BeatConfig::default(),
+            bands: vec![
+                band(20.0, 250.0, "brightness", 10.0, 200.0),
+                band(250.0, 2000.0, "hue-offset", 50.0, 400.0),
+                band(4000.0, 16000.0, "flash", 5.0, 120.0),
+            ],
+        }
+    }
+}
+
+// The [audio.music] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct MusicConfig {
+    // The bass whose energy drives the brightness
+    pub bass_low_hz: f32,
+    pub bass_high_hz: f32,
+    // Spectral centroids at low_hz or below are red, at high_hz or above violet
+    pub centroid_low_hz: f32,
+    pub centroid_high_hz: f32,
+    // How fast the brightness follows a rise and a fall of the bass
+    pub attack_ms: f32,
+    pub decay_ms: f32,
+    // How long the hue takes to settle on a new centroid
+    pub hue_smoothing_ms: f32,
+}
+
+impl Default for MusicConfig {
+    fn default() -> Self {
+        Self {
+            bass_low_hz: 20.0,
+            bass_high_hz: 150.0,
+            centroid_low_hz: 150.0,
+            centroid_high_hz: 5000.0,
+            attack_ms: 10.0,
+            decay_ms: 250.0,
+            hue_smoothing_ms: 400.0,
+        }
+    }
+}
+
+// The [audio.beat] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct BeatConfig {
+    // flash or step
+    pub action: String,
+    // Gone through in order, one per beat
+    pub colors: Vec<String>,
+    // How long a flash takes to fade out
+    pub flash_ms: f32,
+    // Above 1 finds more beats, below 1 fewer
+    pub sensitivity: f32,
+    // Fewest milliseconds between two beats, 150 allows up to 400 BPM
+    pub min_interval_ms: f32,
+}
+
+impl Default for BeatConfig {
+    fn default() -> Self {
+        Self {
+            action: "flash".to_string(),
+            colors: ["red", "orange", "yellow", "green", "cyan", "blue", "purple", "magenta"].map(String::from).to_vec(),
+            flash_ms: 250.0,
+            sensitivity: 1.0,
+            min_interval_ms: 150.0,
+        }
+    }
+}
+
+// Second-order band-pass (RBJ cookbook, 0 dB peak gain)
+struct BandPass {
+    b0: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x: [f32; 2],
+    y: [f32; 2],
+}
+
+impl BandPass {
+    // Bands are cut off a little below Nyquist, where the filter stops behaving
+    fn highest_hz(sample_rate: f32) -> f32 {
+        sample_rate * 0.45
+    }
+
+    fn new(low_hz: f32, high_hz: f32, sample_rate: f32) -> Self {
+        let high_hz = high_hz.min(Self::highest_hz(sample_rate));
+        let center = (low_hz * high_hz).sqrt();
+        let q = center / (high_hz - low_hz);
+        let w0 = 2.0 * PI * center / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: alpha / a0,
+            b2: -alpha / a0,
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha) / a0,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b2 * self.x[1] - self.a1 * self.y[0] - self.a2 * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+// A level normalized against its own recent peak, then smoothed
+struct Envelope {
+    gain: f32,
+    // Seconds
+    attack: f32,
+    release: f32,
+    peak: f32,
+    level: f32,
+}
+
+impl Envelope {
+    fn new(gain: f32, attack_ms: f32, release_ms: f32) -> Self {
+        Self { gain, attack: attack_ms / 1000.0, release: release_ms / 1000.0, peak: NOISE_FLOOR, level: 0.0 }
+    }
+
+    // Smoothed level after `dt` with the RMS of the latest sound
+    fn update(&mut self, rms: f32, dt: f32) -> f32 {
+        self.peak = rms.max(self.peak * 0.5f32.powf(dt / PEAK_HALF_LIFE)).max(NOISE_FLOOR);
+        let wanted = match rms < NOISE_FLOOR {
+            true => 0.0,
+            false => (rms / self.peak * self.gain).min(1.0),
+        };
+        let time = if wanted > self.level { self.attack } else { self.release };
+        self.level += (wanted - self.level) * (1.0 - (-dt / time.max(0.001)).exp());
+        self.level
+    }
+}
+
+struct Band {
+    target: Target,
+    filter: BandPass,
+    // Filled by the capture callback, emptied every frame
+    energy: f64,
+    samples: u32,
+    rms: f32,
+    envelope: Envelope,
+}
+
+impl Band {
+    // Smoothed level after `dt`, from what the callback collected since the last frame
+    fn update(&mut self, dt: f32) -> f32 {
+        if self.samples > 0 {
+            self.rms = (self.energy / self.samples as f64).sqrt() as f32;
+            self.energy = 0.0;
+            self.samples = 0;
+        }
+        self.envelope.update(self.rms, dt)
+    }
+}
+
+// --music: the latest FFT_SIZE samples, analysed once a frame
+struct Spectrum {
+    sample_rate: f32,
+    // Ring buffer, `next` is the oldest sample
+    samples: Vec<f32>,
+    next: usize,
+    window: Vec<f32>,
+    // First and last FFT bin of the bass
+    bass: (usize, usize),
+    centroid_range: (f32, f32),
+    envelope: Envelope,
+    hue_smoothing: f32,
+    hue: f32,
+}
+
+impl Spectrum {
+    fn new(config: &MusicConfig, sample_rate: f32) -> Result<Self, String> {
+        let ordered = |low: f32, high: f32, what: &str| match low > 0.0 && low < high {
+            true => Ok(()),
+            false => Err(format!("audio.music {}_low_hz must be positive and below {}_high_hz", what, what)),
+        };
+        ordered(config.bass_low_hz, config.bass_high_hz, "bass")?;
+        ordered(config.centroid_low_hz, config.centroid_high_hz, "centroid")?;
+
+        let bin = |hz: f32| ((hz * FFT_SIZE as f32 / sample_rate).round() as usize).clamp(1, FFT_SIZE / 2 - 1);
+        Ok(Self {
+            sample_rate,
+            samples: vec![0.0; FFT_SIZE],
+            next: 0,
+            // Hann
+            window: (0..FFT_SIZE).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos()).collect(),
+            bass: (bin(config.bass_low_hz), bin(config.bass_high_hz)),
+            centroid_range: (config.centroid_low_hz, config.centroid_high_hz),
+            envelope: Envelope::new(1.0, config.attack_ms, config.decay_ms),
+            hue_smoothing: config.hue_smoothing_ms / 1000.0,
+            hue: 0.0,
+        })
+    }
+
+    fn feed(&mut self, sample: f32) {
+        self.samples[self.next] = sample;
+        self.next = (self.next + 1) % FFT_SIZE;
+    }
+
+    fn reaction(&mut self, dt: f32) -> Reaction {
+        let mut re: Vec<f32> = (0..FFT_SIZE).map(|i| self.samples[(self.next + i) % FFT_SIZE] * self.window[i]).collect();
+        let mut im = vec![0.0; FFT_SIZE];
+        fft(&mut re, &mut im);
+        let power: Vec<f32> = (0..FFT_SIZE / 2).map(|k| re[k] * re[k] + im[k] * im[k]).collect();
+
+        // Parseval, one-sided and corrected for the window's 0.375 mean power, gives the
+        // band's RMS on the same scale as the band-pass filters
+        let bass_power: f32 = power[self.bass.0..=self.bass.1].iter().sum();
+        let rms = (2.0 * bass_power / (FFT_SIZE as f32 * FFT_SIZE as f32 * 0.375)).sqrt();
+        let level = self.envelope.update(rms, dt);
+
+        // Silence keeps the last hue rather than jumping to red
+        let hz = |k: usize| k as f32 * self.sample_rate / FFT_SIZE as f32;
+        let (weighted, total) = power[1..].iter().enumerate().fold((0.0, 0.0), |(weighted, total), (k, p)| {
+            let magnitude = p.sqrt();
+            (weighted + hz(k + 1) * magnitude, total + magnitude)
+        });
+        if rms >= NOISE_FLOOR && total > 0.0 {
+            let (low, high) = self.centroid_range;
+            let position = ((weighted / total).max(low) / low).ln() / (high / low).ln();
+            let wanted = position.min(1.0) * MUSIC_HUE_RANGE;
+            self.hue += (wanted - self.hue) * (1.0 - (-dt / self.hue_smoothing.max(0.001)).exp());
+        }
+
+        Reaction {
+            brightness: MIN_BRIGHTNESS + (1.0 - MIN_BRIGHTNESS) * level,
+            color: Some(dualsense_rainbow::hsv_to_rgb(self.hue, 1.0, 1.0)),
+            hue_offset: 0.0,
+            flash: 0.0,
+        }
+    }
+}
+
+// --beat: the bass goes to the detector, beats are picked up once a frame
+struct BeatStrobe {
+    filter: BandPass,
+    detector: BeatDetector,
+    action: BeatAction,
+    colors: Vec<(u8, u8, u8)>,
+    index: usize,
+    // Set by the capture callback
+    beat: bool,
+    // Seconds since the last beat, and how long a flash lasts
+    since: f32,
+    flash: f32,
+}
+
+impl BeatStrobe {
+    fn new(config: &BeatConfig, sample_rate: f32) -> Result<Self, String> {
+        if config.colors.is_empty() {
+            return Err("audio.beat.colors needs at least one color".into());
+        }
+        let colors = config.colors.iter().map(|c| palette::parse_color(c)).collect::<Result<_, _>>()?;
+        Ok(Self {
+            filter: BandPass::new(30.0, 250.0, sample_rate),
+            detector: BeatDetector::new(sample_rate, config.sensitivity, config.min_interval_ms / 1000.0, NOISE_FLOOR),
+            action: config.action.parse()?,
+            colors,
+            index: 0,
+            beat: false,
+            since: f32::INFINITY,
+            flash: config.flash_ms / 1000.0,
+        })
+    }
+
+    fn feed(&mut self, sample: f32) {
+        let y = self.filter.process(sample);
+        self.beat |= self.detector.feed(y);
+    }
+
+    fn reaction(&mut self, dt: f32) -> Reaction {
+        if std::mem::take(&mut self.beat) {
+            self.index = (self.index + 1) % self.colors.len();
+            self.since = 0.0;
+        } else {
+            self.since += dt;
+        }
+        let brightness = match self.action {
+            // Dark until the first beat
+            BeatAction::Step if self.since.is_infinite() => 0.0,
+            BeatAction::Step => 1.0,
+            BeatAction::Flash => (1.0 - self.since / self.flash.max(0.001)).max(0.0),
+        };
+        Reaction { brightness, color: Some(self.colors[self.index]), hue_offset: 0.0, flash: 0.0 }
+    }
+}
+
+// In-place iterative radix-2 FFT, the length a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// What the capture callbacks feed
+enum Analysis {
+    Bands(Vec<Band>),
+    Music(Spectrum),
+    Beat(BeatStrobe),
+}
+
+impl Analysis {
+    fn new(config: &AudioConfig, mode: Mode, sample_rate: f32) -> Result<Self, String> {
+        match mode {
+            Mode::Bands => Ok(Analysis::Bands(build_bands(config, sample_rate)?)),
+            Mode::Music => Ok(Analysis::Music(Spectrum::new(&config.music, sample_rate)?)),
+            Mode::Beat => Ok(Analysis::Beat(BeatStrobe::new(&config.beat, sample_rate)?)),
+        }
+    }
+
+    // One mono sample
+    fn feed(&mut self, sample: f32) {
+        match self {
+            Analysis::Bands(bands) => {
+                for band in bands {
+                    let y = band.filter.process(sample);
+                    band.energy += (y * y) as f64;
+                    band.samples += 1;
+                }
+            },
+            Analysis::Music(spectrum) => spectrum.feed(sample),
+            Analysis::Beat(strobe) => strobe.feed(sample),
+        }
+    }
+}
+
+// What the sound does to the effect's color this frame
+#[derive(Debug, Clone, Copy)]
+pub struct Reaction {
+    brightness: f32,
+    // Replaces the effect's color, for --music and --beat
+    color: Option<(u8, u8, u8)>,
+    hue_offset: f32,
+    flash: f32,
+}
+
+impl Reaction {
+    pub fn apply(&self, (r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let (r, g, b) = match (self.color, max) {
+            (Some((r, g, b)), _) => {
+                let scale = |c: u8| (c as f32 * self.brightness).round() as u8;
+                (scale(r), scale(g), scale(b))
+            },
+            (None, 0) => (0, 0, 0),
+            (None, _) => {
+                let hue = (dualsense_rainbow::rgb_to_hue(r, g, b) + self.hue_offset).rem_euclid(360.0);
+                let saturation = (max - min) as f32 / max as f32;
+                dualsense_rainbow::hsv_to_rgb(hue, saturation, max as f32 / 255.0 * self.brightness)
+            },
+        };
+        crate::effects::blend_rgb((r, g, b), (255, 255, 255), self.flash * self.flash)
+    }
+}
+
+pub struct AudioReactive {
+    _stream: Option<Stream>,
+    #[cfg(target_os = "linux")]
+    _recorder: Option<crate::pipewire::Recorder>,
+    device: String,
+    analysis: Arc<Mutex<Analysis>>,
+}
+
+impl AudioReactive {
+    // --music always listens to the output
+    pub fn start(config: &AudioConfig, mode: Mode) -> Result<Self, String> {
+        if config.bands.is_empty() && mode == Mode::Bands {
+            return Err("[audio] needs at least one band".into());
+        }
+        let capture: Capture = match mode {
+            Mode::Music => Capture::Output,
+            Mode::Bands | Mode::Beat => config.capture.parse()?,
+        };
+
+        // PipeWire reaches the output and single applications where the input device can't
+        #[cfg(target_os = "linux")]
+        if capture == Capture::Output || config.node.is_some() {
+            let analysis = Arc::new(Mutex::new(Analysis::new(config, mode, crate::pipewire::SAMPLE_RATE as f32)?));
+            let shared = analysis.clone();
+            let recorder = crate::pipewire::Recorder::start(config.node.as_deref(), move |samples| {
+                if let Ok(mut analysis) = shared.lock() {
+                    for frame in samples.chunks(crate::pipewire::CHANNELS) {
+                        analysis.feed(frame.iter().sum::<f32>() / frame.len() as f32);
+                    }
+                }
+            })?;
+            let device = recorder.description().to_string();
+            return Ok(Self { _stream: None, _recorder: Some(recorder), device, analysis });
+        }
+        #[cfg(not(target_os = "linux"))]
+        if config.node.is_some() {
+            return Err("audio.node needs PipeWire, which is Linux only".into());
+        }
+
+        let host = cpal::default_host();
+        let (device, supported, name) = match capture {
+            Capture::Input => {
+                let device = host.default_input_device().ok_or("no audio input device")?;
+                let supported = device.default_input_config().map_err(|e| e.to_string())?;
+                let name = device.description().map(|d| d.name().to_string()).unwrap_or_else(|_| "default input".to_string());
+                (device, supported, name)
+            },
+            // WASAPI loopback: an input stream opened on an output device records its mix
+            #[cfg(windows)]
+            Capture::Output => {
+                let device = host.default_output_device().ok_or("no audio output device")?;
+                let supported = device.default_output_config().map_err(|e| e.to_string())?;
+                let name = device.description().map(|d| d.name().to_string()).unwrap_or_else(|_| "default output".to_string());
+                (device, supported, format!("{} (loopback)", name))
+            },
+            #[cfg(not(windows))]
+            Capture::Output => return Err("capturing the output is not supported on this platform, pick a loopback input device".into()),
+        };
+        let analysis = Arc::new(Mutex::new(Analysis::new(config, mode, supported.sample_rate() as f32)?));
+
+        let config = supported.config();
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, config, analysis.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, config, analysis.clone()),
+            SampleFormat::I32 => build_stream::<i32>(&device, config, analysis.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, config, analysis.clone()),
+            other => return Err(format!("unsupported sample format {:?}", other)),
+        }?;
+        stream.play().map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            _stream: Some(stream),
+            #[cfg(target_os = "linux")]
+            _recorder: None,
+            device: name,
+            analysis,
+        })
+    }
+
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    pub fn reaction(&self, dt: Duration) -> Reaction {
+        let mut reaction = Reaction { brightness: 1.0, color: None, hue_offset: 0.0, flash: 0.0 };
+        let Ok(mut analysis) = self.analysis.lock() else { return reaction };
+        let bands = match &mut *analysis {
+            Analysis::Bands(bands) => bands,
+            Analysis::Music(spectrum) => return spectrum.reaction(dt.as_secs_f32()),
+            Analysis::Beat(strobe) => return strobe.reaction(dt.as_secs_f32()),
+        };
+
+        let mut loudest: Option<f32> = None;
+        for band in bands.iter_mut() {
+            let level = band.update(dt.as_secs_f32());
+            match band.target {
+                Target::Brightness => loudest = Some(loudest.unwrap_or(0.0).max(level)),
+                Target::HueOffset => reaction.hue_offset += level * HUE_RANGE,
+                Target::Flash => reaction.flash = reaction.flash.max(level),
+            }
+        }
+        if let Some(level) = loudest {
+            reaction.brightness = MIN_BRIGHTNESS + (1.0 - MIN_BRIGHTNESS) * level;
+        }
+        reaction
+    }
+}
+
+fn build_bands(config: &AudioConfig, sample_rate: f32) -> Result<Vec<Band>, String> {
+    config
+        .bands
+        .iter()
+        .map(|band| {
+            if !(band.low_hz > 0.0 && band.low_hz < band.high_hz) {
+                return Err(format!("audio band {}-{} Hz: low_hz must be positive and below high_hz", band.low_hz, band.high_hz));
+            }
+            // What the filter will actually use, a low sample rate can leave nothing of the band
+            let highest = BandPass::highest_hz(sample_rate);
+            if band.low_hz >= highest {
+                return Err(format!(
+                    "audio band {}-{} Hz: at {} Hz sampling nothing above {} Hz can be picked up",
+                    band.low_hz, band.high_hz, sample_rate, highest
+                ));
+            }
+            Ok(Band {
+                target: band.target.parse()?,
+                filter: BandPass::new(band.low_hz, band.high_hz, sample_rate),
+                energy: 0.0,
+                samples: 0,
+                rms: 0.0,
+                envelope: Envelope::new(band.gain, band.attack_ms, band.release_ms),
+            })
+        })
+        .collect()
+}
+
+fn build_stream<T>(device: &cpal::Device, config: StreamConfig, analysis: Arc<Mutex<Analysis>>) -> Result<Stream, String>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _| {
+                let Ok(mut analysis) = analysis.lock() else { return };
+                for frame in data.chunks(channels) {
+                    analysis.feed(frame.iter().map(|s| s.to_sample::<f32>()).sum::<f32>() / channels as f32);
+                }
+            },
+            |e| error!("Audio capture error: {}", e),
+            None,
+        )
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,72 @@
+// Low battery alert: while the level is at or below a threshold and the pad isn't
+// charging, the lightbar blinks three times every few minutes, over whatever effect,
+// sync or overlay has it at the time
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+use dualsense_rainbow::input::BatteryStatus;
+
+// Each blink: on, then off for as long. Slow enough to survive the battery saver's
+// write interval
+const BLINK: Duration = Duration::from_millis(400);
+const BLINKS: u32 = 3;
+
+// The [battery_alert] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatteryAlertConfig {
+    // Battery percentage at or below which the alert repeats
+    pub below: u8,
+    pub every_minutes: f32,
+    pub color: String,
+}
+
+impl Default for BatteryAlertConfig {
+    fn default() -> Self {
+        Self { below: 15, every_minutes: 5.0, color: "red".to_string() }
+    }
+}
+
+pub struct BatteryAlert {
+    below: u8,
+    every: Duration,
+    color: (u8, u8, u8),
+    // When the last round of blinks started, None while the battery is fine
+    last_alert: Option<Instant>,
+}
+
+impl BatteryAlert {
+    pub fn new(config: &BatteryAlertConfig) -> Result<Self, String> {
+        let color = crate::palette::parse_color(&config.color).map_err(|e| format!("battery_alert.color: {}", e))?;
+        if !config.every_minutes.is_finite() || config.every_minutes <= 0.0 {
+            return Err(format!("battery_alert.every_minutes must be a positive number, got {}", config.every_minutes));
+        }
+        Ok(Self {
+            below: config.below,
+            every: Duration::from_secs_f32(config.every_minutes * 60.0),
+            color,
+            last_alert: None,
+        })
+    }
+
+    // The color to show instead of everything else right now, None outside an alert.
+    // The first round goes off as soon as the level drops to the threshold
+    pub fn overlay(&mut self, battery: Option<BatteryStatus>, now: Instant) -> Option<(u8, u8, u8)> {
+        let low = battery.is_some_and(|battery| !battery.charging && battery.level <= self.below);
+        if !low {
+            self.last_alert = None;
+            return None;
+        }
+        if self.last_alert.is_none_or(|last| now.duration_since(last) >= self.every) {
+            self.last_alert = Some(now);
+        }
+
+        let elapsed = self.last_alert.map(|last| now.duration_since(last))?;
+        let phase = (elapsed.as_secs_f32() / BLINK.as_secs_f32()) as u32;
+        match phase {
+            _ if phase >= BLINKS * 2 => None,
+            _ if phase.is_multiple_of(2) => Some(self.color),
+            _ => Some((0, 0, 0)),
+        }
+    }
+}
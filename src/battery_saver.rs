@@ -0,0 +1,72 @@
+// Stretches the battery over Bluetooth: below a threshold (and not charging)
+// reports go out less often, the lightbar dims and the animation holds still
+use serde::Deserialize;
+use std::time::Duration;
+
+use dualsense_rainbow::input::BatteryStatus;
+
+// Percent above the threshold the level has to climb back to, so the mode doesn't flap
+const HYSTERESIS: u8 = 5;
+
+// The [battery_saver] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatterySaverConfig {
+    pub enabled: bool,
+    // Battery percentage at or below which the saver kicks in
+    pub below: u8,
+    pub write_interval_ms: u64,
+    // Multiplies the normal brightness
+    pub brightness: f32,
+    // Hold the current color instead of animating
+    pub static_color: bool,
+}
+
+impl Default for BatterySaverConfig {
+    fn default() -> Self {
+        Self { enabled: true, below: 20, write_interval_ms: 250, brightness: 0.4, static_color: true }
+    }
+}
+
+pub struct BatterySaver {
+    config: BatterySaverConfig,
+    active: bool,
+}
+
+impl BatterySaver {
+    pub fn new(config: BatterySaverConfig) -> Result<Self, String> {
+        if !(0.0..=1.0).contains(&config.brightness) {
+            return Err(format!("battery_saver.brightness {} is outside 0.0-1.0", config.brightness));
+        }
+        Ok(Self { config, active: false })
+    }
+
+    // Returns Some(active) when the mode turns on or off
+    pub fn update(&mut self, usb: bool, battery: Option<BatteryStatus>) -> Option<bool> {
+        let active = match battery {
+            _ if !self.config.enabled || usb => false,
+            Some(battery) if battery.charging => false,
+            Some(battery) if self.active => battery.level <= self.config.below.saturating_add(HYSTERESIS),
+            Some(battery) => battery.level <= self.config.below,
+            // No reading yet, keep whatever we had
+            None => self.active,
+        };
+        if active == self.active {
+            return None;
+        }
+        self.active = active;
+        Some(active)
+    }
+
+    pub fn write_interval(&self) -> Duration {
+        Duration::from_millis(self.config.write_interval_ms)
+    }
+
+    pub fn brightness(&self) -> f32 {
+        if self.active { self.config.brightness } else { 1.0 }
+    }
+
+    pub fn holds_color(&self) -> bool {
+        self.active && self.config.static_color
+    }
+}
@@ -0,0 +1,75 @@
+// Energy-based beat detection for --beat. The samples are cut into blocks of about 21 ms;
+// a block whose energy stands out from the average of the last second is a beat. The
+// threshold drops when the energy varies a lot (punchy music) and rises when it is
+// steady (pads, a wall of noise), so both trigger on what sounds like the beat.
+use std::collections::VecDeque;
+
+const BLOCK: usize = 1024;
+
+pub struct BeatDetector {
+    sensitivity: f32,
+    // Blocks in the history, about a second's worth
+    history_len: usize,
+    history: VecDeque<f32>,
+    energy: f32,
+    samples: usize,
+    // Samples since the last beat, and the fewest allowed between two
+    since_beat: usize,
+    min_gap: usize,
+    // Blocks quieter than this are silence, not a beat
+    floor: f32,
+}
+
+impl BeatDetector {
+    // `sensitivity` above 1 finds more beats, below 1 fewer. `min_interval` is in seconds
+    pub fn new(sample_rate: f32, sensitivity: f32, min_interval: f32, floor: f32) -> Self {
+        let history_len = (sample_rate / BLOCK as f32).round().max(8.0) as usize;
+        Self {
+            sensitivity,
+            history_len,
+            history: VecDeque::with_capacity(history_len),
+            energy: 0.0,
+            samples: 0,
+            since_beat: usize::MAX / 2,
+            min_gap: (min_interval * sample_rate) as usize,
+            floor: floor * floor,
+        }
+    }
+
+    // One mono sample, true when it completes a block that is a beat
+    pub fn feed(&mut self, sample: f32) -> bool {
+        self.energy += sample * sample;
+        self.samples += 1;
+        self.since_beat += 1;
+        if self.samples < BLOCK {
+            return false;
+        }
+
+        let energy = self.energy / BLOCK as f32;
+        self.energy = 0.0;
+        self.samples = 0;
+        let beat = self.is_beat(energy);
+        if self.history.len() == self.history_len {
+            self.history.pop_front();
+        }
+        self.history.push_back(energy);
+        if beat {
+            self.since_beat = 0;
+        }
+        beat
+    }
+
+    fn is_beat(&self, energy: f32) -> bool {
+        // Half a second of history before anything counts
+        if self.history.len() < self.history_len / 2 || energy < self.floor || self.since_beat < self.min_gap {
+            return false;
+        }
+        let n = self.history.len() as f32;
+        let average = self.history.iter().sum::<f32>() / n;
+        let variance = self.history.iter().map(|e| (e - average).powi(2)).sum::<f32>() / n;
+        // Coefficient of variation, 0 for a steady level
+        let spread = (variance.sqrt() / average.max(f32::EPSILON)).min(1.0);
+        let threshold = 1.2 + 0.6 * (1.0 - spread);
+        energy > average * threshold / self.sensitivity.max(0.1)
+    }
+}
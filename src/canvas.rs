@@ -0,0 +1,101 @@
+// Every LED on the controller as one surface that effects paint into: the RGB lightbar,
+// a row of white player LEDs and the mic LED. An effect that only knows about the
+// lightbar leaves the rest untouched, and whatever else drives those LEDs (profiles,
+// the player LED chase, mic patterns) keeps them. How many LEDs there are comes from
+// the model's layout, so effects iterate the pixels instead of assuming five.
+use crate::effects;
+use dualsense_rainbow::report::MuteLed;
+
+// The LEDs a controller model has besides the lightbar
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LedLayout {
+    pub player_leds: usize,
+    pub mic_led: bool,
+}
+
+pub const DUALSENSE: LedLayout = LedLayout { player_leds: 5, mic_led: true };
+
+// White LEDs are only on or off, anything at or above half counts as lit
+const LIT: f32 = 0.5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Canvas {
+    pub lightbar: (u8, u8, u8),
+    // Left to right, 0.0-1.0; empty until an effect paints them
+    player: Vec<f32>,
+    player_leds: usize,
+    mic: Option<f32>,
+    mic_led: bool,
+}
+
+impl Canvas {
+    pub fn new(layout: LedLayout) -> Self {
+        Self {
+            lightbar: (0, 0, 0),
+            player: Vec::new(),
+            player_leds: layout.player_leds,
+            mic: None,
+            mic_led: layout.mic_led,
+        }
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.player_leds
+    }
+
+    // Out of range pixels are ignored, so effects don't need to check the layout
+    pub fn set_player(&mut self, index: usize, level: f32) {
+        if index >= self.player_leds {
+            return;
+        }
+        if self.player.is_empty() {
+            self.player = vec![0.0; self.player_leds];
+        }
+        self.player[index] = level.clamp(0.0, 1.0);
+    }
+
+    pub fn set_mic(&mut self, level: f32) {
+        if self.mic_led {
+            self.mic = Some(level.clamp(0.0, 1.0));
+        }
+    }
+
+    // Player LED bitmask (bit 0 leftmost) if the effect painted them
+    pub fn player_mask(&self) -> Option<u8> {
+        (!self.player.is_empty()).then(|| {
+            self.player
+                .iter()
+                .take(8)
+                .enumerate()
+                .filter(|(_, level)| **level >= LIT)
+                .fold(0, |mask, (i, _)| mask | 1 << i)
+        })
+    }
+
+    pub fn mute_led(&self) -> Option<MuteLed> {
+        self.mic.map(|level| if level >= LIT { MuteLed::On } else { MuteLed::Off })
+    }
+
+    // Crossfades towards `to`. A pixel only one side painted fades against dark
+    pub fn blend(&self, to: &Canvas, t: f32) -> Canvas {
+        let mix = |from: Option<f32>, to: Option<f32>| match (from, to) {
+            (None, None) => None,
+            (from, to) => {
+                let (from, to) = (from.unwrap_or(0.0), to.unwrap_or(0.0));
+                Some(from + (to - from) * t)
+            },
+        };
+
+        let mut canvas = Canvas {
+            lightbar: effects::mix(self.lightbar, to.lightbar, t),
+            mic: mix(self.mic, to.mic),
+            ..to.clone()
+        };
+        if !self.player.is_empty() || !to.player.is_empty() {
+            canvas.player = (0..self.player_leds)
+                .map(|i| mix(self.player.get(i).copied(), to.player.get(i).copied()).unwrap_or(0.0))
+                .collect();
+        }
+        canvas
+    }
+}
@@ -0,0 +1,103 @@
+// Output report captures, for --record and --replay: every report written with when it
+// was written, so a session can be played back on another firmware or connection and
+// the two compared.
+//
+// The file is little-endian: a header of the magic "DSRC", the format version (1) and
+// the product ID of the controller recorded, then for each report
+//
+//   u64  microseconds since the capture started
+//   u8   the connection it was written on, 0 USB and 1 Bluetooth
+//   u16  its length
+//        the report, report ID first
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::device::Model;
+use crate::error::Error;
+
+const MAGIC: &[u8; 4] = b"DSRC";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 7;
+const RECORD_HEADER_LEN: usize = 11;
+
+// Appends reports to a capture file as they are written
+pub struct CaptureWriter {
+    sink: BufWriter<File>,
+    start: Instant,
+}
+
+impl CaptureWriter {
+    pub fn create(path: &Path, model: Model) -> io::Result<Self> {
+        let mut sink = BufWriter::new(File::create(path)?);
+        sink.write_all(MAGIC)?;
+        sink.write_all(&[VERSION])?;
+        sink.write_all(&model.product_id().to_le_bytes())?;
+        sink.flush()?;
+        Ok(Self { sink, start: Instant::now() })
+    }
+
+    pub fn record(&mut self, usb_mode: bool, data: &[u8]) {
+        let micros = self.start.elapsed().as_micros() as u64;
+        // Like tracing, recording must never take the effect down
+        let _ = self.sink.write_all(&micros.to_le_bytes());
+        let _ = self.sink.write_all(&[if usb_mode { 0 } else { 1 }]);
+        let _ = self.sink.write_all(&(data.len() as u16).to_le_bytes());
+        let _ = self.sink.write_all(data);
+        let _ = self.sink.flush();
+    }
+}
+
+// One report of a capture
+#[derive(Clone, Debug)]
+pub struct CapturedReport {
+    // Since the capture started
+    pub at: Duration,
+    pub usb_mode: bool,
+    pub data: Vec<u8>,
+}
+
+// A capture file read back
+#[derive(Clone, Debug)]
+pub struct Capture {
+    pub model: Model,
+    pub reports: Vec<CapturedReport>,
+}
+
+impl Capture {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let bytes = fs::read(path)?;
+        let invalid = |what: &str| Error::Other(format!("{} is not a capture from --record: {}", path.display(), what));
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(invalid("no DSRC header"));
+        }
+        if bytes[4] != VERSION {
+            return Err(invalid(&format!("format version {}, this reads {}", bytes[4], VERSION)));
+        }
+        let product_id = u16::from_le_bytes([bytes[5], bytes[6]]);
+        let model = Model::from_product_id(product_id).ok_or_else(|| invalid(&format!("unknown product ID {:04X}", product_id)))?;
+
+        let mut reports = Vec::new();
+        let mut rest = &bytes[HEADER_LEN..];
+        while !rest.is_empty() {
+            // A capture cut short by a crash keeps everything before the torn report
+            if rest.len() < RECORD_HEADER_LEN {
+                break;
+            }
+            let micros = u64::from_le_bytes(rest[..8].try_into().unwrap_or_default());
+            let len = u16::from_le_bytes([rest[9], rest[10]]) as usize;
+            let Some(data) = rest.get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + len) else {
+                break;
+            };
+            reports.push(CapturedReport { at: Duration::from_micros(micros), usb_mode: rest[8] == 0, data: data.to_vec() });
+            rest = &rest[RECORD_HEADER_LEN + len..];
+        }
+        Ok(Self { model, reports })
+    }
+
+    // How long the capture runs, up to its last report
+    pub fn duration(&self) -> Duration {
+        self.reports.last().map_or(Duration::ZERO, |report| report.at)
+    }
+}
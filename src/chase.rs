@@ -0,0 +1,44 @@
+// Player LED chase that moves with the lightbar, so every light on the
+// controller reads as one animation: the faster the hue moves, the faster
+// the lit LED bounces across the five player LEDs
+use dualsense_rainbow::rgb_to_hue;
+
+const PLAYER_LED_COUNT: usize = 5;
+// One full bounce (0 -> 4 -> 0) per trip around the color wheel
+const STEPS_PER_BOUNCE: f32 = 2.0 * (PLAYER_LED_COUNT - 1) as f32;
+const DEGREES_PER_STEP: f32 = 360.0 / STEPS_PER_BOUNCE;
+
+#[derive(Default)]
+pub struct PlayerLedChase {
+    position: f32, // In steps, 0..STEPS_PER_BOUNCE
+    // Hue of the last lightbar color that had one
+    last_hue: Option<f32>,
+}
+
+impl PlayerLedChase {
+    // Follows the lightbar as it was actually sent, so the chase keeps its tempo whatever
+    // the frame rate: a solid color holds it still, and grays (no hue) pause it
+    pub fn follow(&mut self, (r, g, b): (u8, u8, u8)) -> u8 {
+        let hue = (r.max(g).max(b) != r.min(g).min(b)).then(|| rgb_to_hue(r, g, b));
+        let delta = match (self.last_hue, hue) {
+            // The short way round, so 350° to 10° is 20° and not 340°
+            (Some(last), Some(hue)) => (hue - last + 180.0).rem_euclid(360.0) - 180.0,
+            _ => 0.0,
+        };
+        self.last_hue = hue.or(self.last_hue);
+        self.advance(delta)
+    }
+
+    // Advances by the hue change of this frame (in degrees) and returns the LED mask
+    fn advance(&mut self, hue_delta: f32) -> u8 {
+        self.position = (self.position + hue_delta.abs() / DEGREES_PER_STEP).rem_euclid(STEPS_PER_BOUNCE);
+
+        let step = self.position as usize;
+        let led = if step < PLAYER_LED_COUNT {
+            step
+        } else {
+            STEPS_PER_BOUNCE as usize - step
+        };
+        1 << led
+    }
+}
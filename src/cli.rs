@@ -0,0 +1,626 @@
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+use crate::effects::EffectKind;
+use crate::logging::LogFormat;
+use crate::mic_led::MicPattern;
+use crate::palette;
+use dualsense_rainbow::ColorSpace;
+use dualsense_rainbow::report::PlayerLedBrightness;
+
+// Command line options
+#[derive(Parser, Debug)]
+#[command(version, about = "Smooth rainbow cycle for the DualSense lightbar")]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Config file to use instead of ~/.config/dualsense-rainbow/config.toml
+    #[arg(long, value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// More output: -v adds debug messages, -vv traces as well
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Less output: -q leaves warnings and errors, -qq only errors
+    #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "verbose")]
+    pub quiet: u8,
+
+    /// How messages are written: human (colored lines) or json (one object per line, for
+    /// log collectors)
+    #[arg(long, value_name = "FORMAT", default_value = "human", global = true)]
+    pub log_format: LogFormat,
+
+    /// Hexdump every HID report sent to or read from the controller.
+    /// Pass a file path to write the trace there instead of the terminal.
+    #[arg(long, value_name = "FILE")]
+    pub trace_hid: Option<Option<PathBuf>>,
+
+    /// Hexdump every output report sent, highlighting the bytes that changed since the
+    /// previous one. With --show-input, input reports too
+    #[arg(long, conflicts_with = "trace_hid")]
+    pub debug_reports: bool,
+
+    /// Save every output report sent, with when it was sent, to FILE for --replay
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Play back the output reports saved with --record, with their original timing,
+    /// instead of running an effect
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Print decoded input events (buttons, sticks, touchpad, battery) as they arrive
+    #[arg(long)]
+    pub show_input: bool,
+
+    /// Effect to run: rainbow, breathe, cycle, wave, solid, strobe, wipe, police, reactive, picker, motion,
+    /// system or lava (overrides effect in the config's [defaults])
+    #[arg(long, value_name = "NAME")]
+    pub effect: Option<EffectKind>,
+
+    /// One steady color instead of an effect: a name, hex like #ff8800 or R,G,B
+    /// like 255,136,0 (the solid effect with a one-color palette)
+    #[arg(long, value_name = "COLOR", value_parser = palette::parse_color, conflicts_with = "effect")]
+    pub color: Option<(u8, u8, u8)>,
+
+    /// Play a keyframe animation from a TOML or JSON file, or an animated GIF, instead of an effect
+    /// (check one with `animation check FILE`)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["effect", "color", "shuffle"])]
+    pub animation: Option<PathBuf>,
+
+    /// Run an effect written in Rhai: NAME.rhai from the config's scripts folder or
+    /// a path; the file is reloaded whenever it changes
+    #[cfg(feature = "scripting")]
+    #[arg(long, value_name = "SCRIPT", conflicts_with_all = ["effect", "color", "shuffle", "animation"])]
+    pub script: Option<String>,
+
+    /// Run an effect plugin compiled to WebAssembly: NAME.wasm from the config's
+    /// plugins folder or a path
+    #[cfg(feature = "plugins")]
+    #[arg(long, value_name = "PLUGIN", conflicts_with_all = ["effect", "color", "shuffle", "animation"])]
+    pub plugin: Option<String>,
+
+    /// Colors for the effect instead of the rainbow: a built-in palette (sunset,
+    /// ocean, cyberpunk, fire, forest), a palette file or an image to take the dominant
+    /// colors of (overrides palette in [defaults])
+    #[arg(long, value_name = "PALETTE", conflicts_with = "color")]
+    pub palette: Option<String>,
+
+    /// Hue degrees the effect advances per frame (overrides speed in the
+    /// config's [defaults], 1.5 when neither is set)
+    #[arg(long, value_name = "DEGREES", value_parser = parse_speed)]
+    pub speed: Option<f32>,
+
+    /// Frames rendered and sent per second, 60 by default. Speed is per frame,
+    /// so fewer frames also slow the animation down (overrides fps in the config)
+    #[arg(long, value_name = "FPS", value_parser = clap::value_parser!(u32).range(1..=240))]
+    pub fps: Option<u32>,
+
+    /// Lightbar brightness from 0.0 to 1.0 (overrides brightness in the config's [defaults])
+    #[arg(long, value_name = "LEVEL", value_parser = parse_brightness)]
+    pub brightness: Option<f32>,
+
+    /// Gamma curve applied to the lightbar after brightness, 1.0 (off) by default;
+    /// around 2.2 gives even steps in dim colors (overrides gamma in the config)
+    #[arg(long, value_name = "GAMMA", value_parser = parse_gamma)]
+    pub gamma: Option<f32>,
+
+    /// Color space crossfades, palettes and animations blend in: rgb (the default),
+    /// hsv, oklab or oklch; the last two look even all the way (overrides color_space
+    /// in the config)
+    #[arg(long, value_name = "SPACE")]
+    pub color_space: Option<ColorSpace>,
+
+    /// Mic LED pattern: off, on, pulse (hardware), breathe (software) or sync
+    /// (breathes in step with the lightbar, once per trip around the color wheel)
+    #[arg(long, value_name = "PATTERN", default_value = "off")]
+    pub mic_led: MicPattern,
+
+    /// Double-blink the mic LED whenever the mute button is pressed
+    #[arg(long)]
+    pub mic_blink_on_mute: bool,
+
+    /// Player LEDs to light, as a 5-character pattern like "-x-x-" or a bitmask 0-31
+    #[arg(long, value_name = "PATTERN", value_parser = parse_player_mask, default_value = "-----")]
+    pub player_leds: u8,
+
+    /// Player LED brightness: high, medium or low
+    #[arg(long, value_name = "LEVEL", default_value = "high")]
+    pub player_led_brightness: PlayerLedBrightness,
+
+    /// Switch player LEDs instantly instead of fading them in
+    #[arg(long)]
+    pub no_player_led_fade: bool,
+
+    /// Bounce a lit player LED back and forth in step with the lightbar's hue
+    /// (one round trip per color cycle, replaces --player-leds)
+    #[arg(long)]
+    pub player_led_chase: bool,
+
+    /// Run with a profile from the config's [profiles] table, ignoring the
+    /// [[schedule]] rules, [apps], [processes] and holiday themes
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Hop to a random effect every few minutes, crossfading between them
+    /// (the effect pool and timing come from the config's [shuffle] table)
+    #[arg(long)]
+    pub shuffle: bool,
+
+    /// Minutes between shuffled effects (overrides interval_minutes in the config)
+    #[arg(long, value_name = "MINUTES", requires = "shuffle")]
+    pub shuffle_interval: Option<f32>,
+
+    /// Share the controller with Steam Input, DS4Windows and similar tools: write
+    /// only the LED fields (never rumble, triggers or audio) at a reduced rate
+    #[arg(long)]
+    pub cooperate: bool,
+
+    /// Pulse the rumble motors in time with the rainbow, strongest each time the
+    /// lightbar passes red
+    #[arg(long, conflicts_with = "cooperate")]
+    pub rumble_demo: bool,
+
+    /// Controller to open when several are connected: its serial number (printed on
+    /// connect), its HID path or its number in `list` (overrides device in the config)
+    #[arg(long, alias = "serial", value_name = "DEVICE", conflicts_with = "all_controllers")]
+    pub device: Option<String>,
+
+    /// Drive every connected DualSense at once, all running the same effect in step
+    #[arg(long)]
+    pub all_controllers: bool,
+
+    /// Hue degrees between neighbouring controllers' lightbars with --all-controllers
+    /// (0 shows the same color everywhere)
+    #[arg(long, value_name = "DEGREES", default_value_t = 0.0, requires = "all_controllers")]
+    pub hue_offset: f32,
+
+    /// Take the controller over: forward its input to a virtual Xbox 360 pad
+    /// (uinput on Linux, ViGEmBus on Windows) and hide it from games, so the
+    /// lighting is never fought over
+    #[arg(long, conflicts_with = "cooperate")]
+    pub passthrough: bool,
+
+    /// Night mode: warm every color up in the evening and cool it down in the
+    /// morning, like f.lux (the times come from the config's [night] table)
+    #[arg(long)]
+    pub night: bool,
+
+    /// Dim the lightbar after this many seconds without controller input;
+    /// the next button press, stick or touchpad movement brightens it again
+    #[arg(long, value_name = "SECONDS")]
+    pub idle_dim: Option<f32>,
+
+    /// Turn the LEDs off after this many minutes without controller input;
+    /// the next button press, stick or touchpad movement turns them back on
+    #[arg(long, value_name = "MINUTES")]
+    pub idle_off: Option<f32>,
+
+    /// Keep full brightness and animation over Bluetooth even when the battery runs low
+    #[arg(long)]
+    pub no_battery_saver: bool,
+
+    /// Hold --fps even when writes can't keep up, instead of lowering the frame rate
+    #[arg(long)]
+    pub no_adaptive_fps: bool,
+
+    /// Always run the plain rainbow, even on dates with a built-in holiday theme
+    #[arg(long)]
+    pub no_holiday_themes: bool,
+
+    /// Minimum time between output reports over Bluetooth, in milliseconds.
+    /// Raising it trades animation smoothness for stability on congested links
+    #[arg(long, value_name = "MS")]
+    pub bt_report_interval: Option<u64>,
+
+    /// HID collection to send output reports to, as usage page and usage in hex.
+    /// Defaults to the gamepad collection (01:05); only needed when the pad exposes
+    /// several and the lightbar doesn't react
+    #[arg(long, value_name = "PAGE:USAGE", value_parser = parse_hid_usage)]
+    pub hid_usage: Option<(u16, u16)>,
+
+    /// Open the pad's HID interface with this number (3 over USB)
+    #[arg(long, value_name = "N")]
+    pub hid_interface: Option<i32>,
+
+    /// Left trigger effect: a name from the config's [triggers] table or
+    /// inline DSL like "feedback 0-9:4" (overrides left_trigger in the config)
+    #[arg(long, value_name = "EFFECT")]
+    pub left_trigger: Option<String>,
+
+    /// Right trigger effect, same format as --left-trigger
+    #[arg(long, value_name = "EFFECT")]
+    pub right_trigger: Option<String>,
+
+    /// Append a stats row (timestamp, fps, sent, errors, write latency, battery)
+    /// to this CSV file every --stats-interval seconds
+    #[arg(long, value_name = "FILE")]
+    pub stats_csv: Option<PathBuf>,
+
+    /// Seconds between exported stats samples (CSV and StatsD)
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    pub stats_interval: u64,
+
+    /// Reopen the controller when no output report could be written for this
+    /// many seconds (0 disables the watchdog)
+    #[arg(long, value_name = "SECS", default_value_t = 5.0)]
+    pub watchdog: f32,
+
+    /// Accept commands (effect, set-param, set-palette, apply-profile) on a
+    /// local TCP port so other programs can reconfigure the running instance.
+    /// Defaults to 127.0.0.1:47820
+    #[arg(long, value_name = "ADDR")]
+    pub control: Option<Option<String>>,
+
+    /// Accept the same commands on a Unix socket (a named pipe on Windows), by
+    /// default $XDG_RUNTIME_DIR/dualsense-rainbow.sock (\\.\pipe\dualsense-rainbow)
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<Option<PathBuf>>,
+
+    /// Serve a JSON REST API (GET /status, POST /color, POST /effect) and an event
+    /// WebSocket (GET /events) for home automation, Stream Deck plugins and
+    /// dashboards. Defaults to 127.0.0.1:47823
+    #[cfg(feature = "http-api")]
+    #[arg(long, value_name = "ADDR")]
+    pub http: Option<Option<String>>,
+
+    /// Also let web pages from this origin (e.g. http://dashboard.lan:8080) open the
+    /// /events WebSocket; pages served from the API's own address always can
+    #[cfg(feature = "http-api")]
+    #[arg(long, value_name = "ORIGIN", requires = "http")]
+    pub http_origin: Vec<String>,
+
+    /// Show up in OpenRGB as a device (add this address under SDK Client) so it
+    /// can sync the lightbar with everything else. Defaults to 127.0.0.1:6743
+    #[arg(long, value_name = "ADDR")]
+    pub openrgb: Option<Option<String>>,
+
+    /// Keep running in the background, detached from the terminal, taking commands
+    /// from `ctl` on the socket (see --socket); the output goes to a log file
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Read "health 37/100", "progress 0.62" and "clear" lines from stdin and show
+    /// them on the lightbar and player LEDs in place of the effect
+    #[arg(long)]
+    pub meter_stdin: bool,
+
+    /// Show lightbar colors received over UDP or TCP (from another instance's
+    /// --stream-to or any program) instead of the effect. Defaults to 0.0.0.0:47821
+    #[arg(long, value_name = "ADDR")]
+    pub follow: Option<Option<String>>,
+
+    /// Show the colors of a device in a running OpenRGB (by name, or part of it,
+    /// or by index) instead of the effect, so the lightbar follows the rest of the setup
+    #[arg(long, value_name = "DEVICE", conflicts_with = "follow")]
+    pub openrgb_mirror: Option<String>,
+
+    /// OpenRGB SDK server to mirror from. Defaults to 127.0.0.1:6742
+    #[arg(long, value_name = "ADDR", requires = "openrgb_mirror")]
+    pub openrgb_server: Option<String>,
+
+    /// Send every lightbar color as a UDP datagram to HOST:PORT, e.g. a
+    /// follower or a broadcast address like 192.168.1.255:47821
+    #[arg(long, value_name = "ADDR")]
+    pub stream_to: Option<String>,
+
+    /// React to sound from the default input device: bass drives the brightness,
+    /// mids the hue and highs flash white (bands are set in the config's [audio] table)
+    #[cfg(feature = "audio")]
+    #[arg(long)]
+    pub audio: bool,
+
+    /// Music mode: color the lightbar from what the speakers play, the bass setting
+    /// the brightness and the spectral centroid the hue (see [audio.music])
+    #[cfg(feature = "audio")]
+    #[arg(long, conflicts_with = "audio")]
+    pub music: bool,
+
+    /// Beat mode: flash (or step to) the next color of a palette on every beat of
+    /// the sound, from the same source as --audio (see [audio.beat])
+    #[cfg(feature = "audio")]
+    #[arg(long, conflicts_with_all = ["audio", "music"])]
+    pub beat: bool,
+
+    /// Take the lightbar color from the screen. REGION is "monitor N", "focused"
+    /// or "X,Y,WIDTHxHEIGHT" (overrides region in the config's [screen] table)
+    #[arg(long, value_name = "REGION")]
+    pub screen: Option<Option<String>>,
+
+    /// Run against a simulated controller instead of real hardware
+    #[arg(long)]
+    pub mock: bool,
+
+    /// Like --mock, and log every report that would have been sent: what it sets the
+    /// LEDs to (add --trace-hid for the bytes)
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Open a window showing the simulated controller's lightbar, player LEDs
+    /// and mic LED (implies --mock, and goes with --dry-run)
+    #[cfg(feature = "preview")]
+    #[arg(long)]
+    pub preview: bool,
+
+    /// Milliseconds to crossfade when the effect, palette or whatever drives the
+    /// lightbar changes (0 cuts straight over; overrides transition_ms in the
+    /// config, 1000 when neither is set)
+    #[arg(long, value_name = "MS")]
+    pub transition: Option<u64>,
+
+    /// Duration of the fade to black on exit, in milliseconds (0 turns the LEDs off
+    /// instantly; overrides fade_out_ms in the config, 500 when neither is set)
+    #[arg(long, value_name = "MS")]
+    pub fade_out: Option<u64>,
+
+    /// Color the lightbar is left at on exit, a name or hex like #ff8800
+    /// (overrides exit_color in the config; "off" by default)
+    #[arg(long, value_name = "COLOR", value_parser = parse_exit_color)]
+    pub exit_color: Option<(u8, u8, u8)>,
+
+    /// Write the settings in effect (config and command line merged) as a config
+    /// template and exit. Goes to the config location unless FILE is given, and
+    /// never replaces an existing file
+    #[arg(long, value_name = "FILE")]
+    pub write_config: Option<Option<PathBuf>>,
+
+    /// Expose the lightbar as an LED-class style directory (brightness,
+    /// multi_intensity, trigger) that scripts can write to. Defaults to
+    /// $XDG_RUNTIME_DIR/dualsense-rainbow/leds
+    #[cfg(target_os = "linux")]
+    #[arg(long, value_name = "DIR")]
+    pub led_bridge: Option<Option<PathBuf>>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the animated effects (what happens without a subcommand)
+    Run,
+    /// Set the lightbar to one color and exit, e.g. `set rebeccapurple`, `set "#ff8800"`
+    /// or `set 255,136,0`
+    Set {
+        #[arg(value_parser = palette::parse_color)]
+        color: (u8, u8, u8),
+    },
+    /// Turn the lightbar, player LEDs and mic LED off and exit
+    Off,
+    /// Print the controller's connection, battery and firmware and exit
+    Status,
+    /// List every connected controller without opening any
+    List,
+    /// First-run setup: find the controller, test it, pick defaults and write the config
+    Init,
+    /// Install the udev rule that lets you open the controllers without root, then reload udev
+    #[cfg(target_os = "linux")]
+    SetupUdev {
+        /// Don't ask before installing
+        #[arg(long, short)]
+        yes: bool,
+        /// Also open /dev/uinput to the input group, for --passthrough
+        #[arg(long)]
+        uinput: bool,
+    },
+    /// Read or write raw feature reports (Bluetooth CRC handled automatically)
+    Feature {
+        #[command(subcommand)]
+        action: FeatureAction,
+    },
+    /// Work with adaptive trigger effects
+    Trigger {
+        #[command(subcommand)]
+        action: TriggerAction,
+    },
+    /// Work with keyframe animation files
+    Animation {
+        #[command(subcommand)]
+        action: AnimationAction,
+    },
+    /// Play a mini-game on the controller
+    Game {
+        #[command(subcommand)]
+        game: Game,
+    },
+    /// Send a command to an instance started with --daemon, --socket or --control,
+    /// e.g. `ctl set-color orange` or `ctl stop`. Without --address or --socket it
+    /// uses the default socket when there is one, 127.0.0.1:47820 otherwise
+    Ctl {
+        /// TCP address the running instance listens on (--control)
+        #[arg(long, value_name = "ADDR")]
+        address: Option<String>,
+
+        /// Socket or named pipe the running instance listens on (--socket)
+        #[arg(long, value_name = "PATH", conflicts_with = "address")]
+        socket: Option<PathBuf>,
+
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Play audio through the haptic actuators (USB only)
+    #[cfg(feature = "haptics")]
+    Haptics {
+        #[command(subcommand)]
+        action: HapticsAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AnimationAction {
+    /// Check animation files for mistakes and print how long each one runs
+    Check {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Game {
+    /// Wait for the lightbar to turn green, then press ✕ as fast as you can
+    Reaction {
+        /// Number of timed rounds
+        #[arg(long, default_value_t = 5)]
+        rounds: u32,
+    },
+    /// Repeat the color sequences shown on the lightbar and player LEDs with the face buttons
+    Simon,
+}
+
+#[cfg(feature = "haptics")]
+#[derive(Subcommand, Debug)]
+pub enum HapticsAction {
+    /// Play a WAV or Ogg Vorbis file through the actuators
+    Play {
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TriggerAction {
+    /// Apply a trigger effect so it can be felt right away
+    Test {
+        /// A name from the config's [triggers] table or inline DSL, e.g.
+        /// "feedback 0-3:2 4-9:8", "weapon 2-6:8", "vibration 3-9:5 @40"
+        effect: String,
+
+        /// Which trigger(s) to apply it to
+        #[arg(long, value_enum, default_value = "both")]
+        side: TriggerSide,
+
+        /// How long to hold the effect before resetting, in seconds
+        #[arg(long, value_name = "SECS", default_value_t = 10)]
+        duration: u64,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerSide {
+    Left,
+    Right,
+    Both,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum FeatureAction {
+    /// Read a feature report and print it as an annotated hexdump
+    Get {
+        /// Report ID in hex, e.g. 05 or 0x20
+        #[arg(value_parser = parse_report_id)]
+        id: u8,
+
+        /// Number of bytes to request, including the report ID
+        #[arg(long)]
+        len: Option<usize>,
+    },
+    /// Send a feature report built from hex bytes (without the report ID)
+    Set {
+        /// Report ID in hex, e.g. 08 or 0x80
+        #[arg(value_parser = parse_report_id)]
+        id: u8,
+
+        /// Payload bytes in hex, either separate ("01 ff") or joined ("01ff")
+        #[arg(value_parser = parse_hex_bytes, required = true)]
+        bytes: Vec<HexBytes>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct HexBytes(Vec<u8>);
+
+impl IntoIterator for HexBytes {
+    type Item = u8;
+    type IntoIter = std::vec::IntoIter<u8>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+fn parse_report_id(s: &str) -> Result<u8, String> {
+    let digits = s.trim_start_matches("0x").trim_start_matches("0X");
+    u8::from_str_radix(digits, 16).map_err(|_| format!("invalid report ID '{}' (expected hex like 05 or 0x20)", s))
+}
+
+fn parse_hex_bytes(s: &str) -> Result<HexBytes, String> {
+    let digits: String = s
+        .trim_start_matches("0x")
+        .trim_start_matches("0X")
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '_' | ','))
+        .collect();
+
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex byte in '{}'", s));
+    }
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err(format!("'{}' is not a whole number of hex bytes", s));
+    }
+
+    Ok(HexBytes(
+        (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap_or_default())
+            .collect(),
+    ))
+}
+
+// "01:05" or "0x01:0x05"
+pub fn parse_hid_usage(s: &str) -> Result<(u16, u16), String> {
+    let hex = |part: &str| {
+        let part = part.trim();
+        let digits = part.strip_prefix("0x").or_else(|| part.strip_prefix("0X")).unwrap_or(part);
+        u16::from_str_radix(digits, 16).map_err(|_| format!("'{}' is not a hex number", part))
+    };
+    let (page, usage) = s.split_once(':').ok_or("expected PAGE:USAGE, e.g. 01:05")?;
+    Ok((hex(page)?, hex(usage)?))
+}
+
+fn parse_speed(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(speed) if speed > 0.0 && speed <= 360.0 => Ok(speed),
+        Ok(_) => Err("speed must be above 0 and at most 360 degrees per frame".into()),
+        Err(_) => Err(format!("'{}' is not a number", s)),
+    }
+}
+
+fn parse_brightness(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(brightness) if (0.0..=1.0).contains(&brightness) => Ok(brightness),
+        Ok(_) => Err("brightness must be between 0.0 and 1.0".into()),
+        Err(_) => Err(format!("'{}' is not a number", s)),
+    }
+}
+
+fn parse_gamma(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(gamma) if (0.1..=5.0).contains(&gamma) => Ok(gamma),
+        Ok(_) => Err("gamma must be between 0.1 and 5.0".into()),
+        Err(_) => Err(format!("'{}' is not a number", s)),
+    }
+}
+
+// A color for the lightbar on exit, "off" leaving it dark
+pub fn parse_exit_color(s: &str) -> Result<(u8, u8, u8), String> {
+    if s.trim().eq_ignore_ascii_case("off") {
+        return Ok((0, 0, 0));
+    }
+    palette::parse_color(s)
+}
+
+// "x-x-x" style pattern (leftmost LED first) or a plain 0-31 bitmask. Five characters
+// are always a pattern, so "00100" is the middle LED and not the number 100
+pub fn parse_player_mask(s: &str) -> Result<u8, String> {
+    if s.chars().count() == 5 {
+        return s.chars().enumerate().try_fold(0u8, |mask, (i, c)| match c {
+            'x' | 'X' | '1' | '#' => Ok(mask | (1 << i)),
+            '-' | '.' | '0' | '_' => Ok(mask),
+            _ => Err(format!("invalid LED character '{}' (use 'x' for on, '-' for off)", c)),
+        });
+    }
+
+    match s.parse::<u8>() {
+        Ok(mask) if mask <= 0x1F => Ok(mask),
+        Ok(_) => Err("bitmask must be between 0 and 31".into()),
+        Err(_) => Err(format!("expected 5 LEDs like \"-x-x-\" or a 0-31 bitmask, got '{}'", s)),
+    }
+}
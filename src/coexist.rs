@@ -0,0 +1,59 @@
+// Detects other programs that drive the controller (Steam Input, DS4Windows, ...).
+// Two writers fighting over the lightbar shows up as flicker, so they are
+// reported up front and --cooperate can restrict us to the LED fields
+use crate::processes::{self, ProcessInfo};
+
+// Process name (lowercase, no ".exe") and what to call it
+const KNOWN_WRITERS: &[(&str, &str)] = &[
+    ("steam", "Steam Input"),
+    ("ds4windows", "DS4Windows"),
+    ("ds4drv", "ds4drv"),
+    ("dsx", "DSX"),
+    ("dualsensex", "DualSenseX"),
+    ("inputmapper", "InputMapper"),
+    ("rewasd", "reWASD"),
+];
+
+pub struct OtherWriter {
+    pub name: String,
+    pub pid: u32,
+    // The process has the controller's device node open right now (Linux only)
+    pub holds_device: bool,
+}
+
+pub fn detect(device_path: &str) -> Vec<OtherWriter> {
+    let running = processes::running();
+
+    running
+        .iter()
+        .filter_map(|process| {
+            let known = KNOWN_WRITERS.iter().find(|(name, _)| process.is(name)).map(|(_, label)| label.to_string());
+            let holds_device = holds_device(process, device_path);
+            if known.is_none() && !holds_device {
+                return None;
+            }
+            Some(OtherWriter {
+                name: known.unwrap_or_else(|| process.name.clone()),
+                pid: process.pid,
+                holds_device,
+            })
+        })
+        .collect()
+}
+
+// Looks for the hidraw node among the process's open files
+#[cfg(target_os = "linux")]
+fn holds_device(process: &ProcessInfo, device_path: &str) -> bool {
+    // Processes of other users can't be inspected, which is fine: they could not open our node either
+    let Ok(fds) = std::fs::read_dir(format!("/proc/{}/fd", process.pid)) else {
+        return false;
+    };
+    fds.flatten()
+        .filter_map(|fd| std::fs::read_link(fd.path()).ok())
+        .any(|target| target.as_os_str() == device_path)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn holds_device(_process: &ProcessInfo, _device_path: &str) -> bool {
+    false
+}
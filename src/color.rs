@@ -0,0 +1,473 @@
+// Color conversions shared by the effects, and parsing colors people type
+use std::fmt;
+use std::str::FromStr;
+
+// Converts HSV to RGB to create the rainbow effect
+pub fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (u8, u8, u8) {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = v - c;
+
+    let (r, g, b) = if h < 60.0 {
+        (c, x, 0.0)
+    } else if h < 120.0 {
+        (x, c, 0.0)
+    } else if h < 180.0 {
+        (0.0, c, x)
+    } else if h < 240.0 {
+        (0.0, x, c)
+    } else if h < 300.0 {
+        (x, 0.0, c)
+    } else {
+        (c, 0.0, x)
+    };
+
+    (
+        ((r + m) * 255.0) as u8,
+        ((g + m) * 255.0) as u8,
+        ((b + m) * 255.0) as u8,
+    )
+}
+
+// Hue in degrees of an RGB color, 0 for grays
+pub fn rgb_to_hue(r: u8, g: u8, b: u8) -> f32 {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    if delta == 0.0 {
+        return 0.0;
+    }
+
+    let hue = if max == r {
+        60.0 * ((g - b) / delta)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    hue.rem_euclid(360.0)
+}
+
+// Turns a color around the hue wheel by `degrees`, keeping its saturation and value
+pub fn rotate_hue((r, g, b): (u8, u8, u8), degrees: f32) -> (u8, u8, u8) {
+    let max = r.max(g).max(b);
+    if max == 0 {
+        return (0, 0, 0);
+    }
+    let saturation = (max - r.min(g).min(b)) as f32 / max as f32;
+    let hue = (rgb_to_hue(r, g, b) + degrees).rem_euclid(360.0);
+    hsv_to_rgb(hue, saturation, max as f32 / 255.0)
+}
+
+// The color of a black body at `kelvin` (clamped to 1000-40000), from Tanner Helland's fit
+// to the CIE data. About 6500 K is white, lower is warmer: 2700 K is an incandescent bulb
+pub fn kelvin_to_rgb(kelvin: f32) -> (u8, u8, u8) {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+    let r = if t <= 66.0 { 255.0 } else { 329.69873 * (t - 60.0).powf(-0.13320476) };
+    let g = if t <= 66.0 { 99.4708 * t.ln() - 161.11957 } else { 288.12217 * (t - 60.0).powf(-0.07551485) };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.51773 * (t - 10.0).ln() - 305.0448
+    };
+    let channel = |c: f32| c.clamp(0.0, 255.0).round() as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+// Where two colors are blended. Mixing the RGB values is the cheapest, but goes through
+// gray between opposite colors and dims on the way from red to green; HSV keeps full
+// color by going round the wheel, but brightness jumps between hues. OKLab is built so
+// equal steps look equal, and OKLCH is the same space going round the hue instead of
+// straight through it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    #[default]
+    Rgb,
+    Hsv,
+    Oklab,
+    Oklch,
+}
+
+impl FromStr for ColorSpace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "rgb" | "srgb" => Ok(ColorSpace::Rgb),
+            "hsv" => Ok(ColorSpace::Hsv),
+            "oklab" => Ok(ColorSpace::Oklab),
+            "oklch" => Ok(ColorSpace::Oklch),
+            _ => Err(format!("unknown color space '{}' (expected rgb, hsv, oklab or oklch)", s)),
+        }
+    }
+}
+
+impl ColorSpace {
+    // `t` from 0.0 (all `from`) to 1.0 (all `to`)
+    pub fn mix(self, from: (u8, u8, u8), to: (u8, u8, u8), t: f32) -> (u8, u8, u8) {
+        let t = t.clamp(0.0, 1.0);
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        match self {
+            ColorSpace::Rgb => {
+                let mix = |a: u8, b: u8| lerp(a as f32, b as f32).round() as u8;
+                (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+            },
+            ColorSpace::Hsv => {
+                let ((h1, s1, v1), (h2, s2, v2)) = (rgb_to_hsv(from), rgb_to_hsv(to));
+                // A gray has no hue of its own, it takes the other color's
+                let h1 = if s1 == 0.0 { h2 } else { h1 };
+                let h2 = if s2 == 0.0 { h1 } else { h2 };
+                hsv_to_rgb((h1 + shortest_turn(h1, h2) * t).rem_euclid(360.0), lerp(s1, s2), lerp(v1, v2))
+            },
+            ColorSpace::Oklab => {
+                let ((l1, a1, b1), (l2, a2, b2)) = (to_oklab(from), to_oklab(to));
+                from_oklab((lerp(l1, l2), lerp(a1, a2), lerp(b1, b2)))
+            },
+            ColorSpace::Oklch => {
+                let ((l1, a1, b1), (l2, a2, b2)) = (to_oklab(from), to_oklab(to));
+                let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+                let (h1, h2) = (b1.atan2(a1).to_degrees(), b2.atan2(a2).to_degrees());
+                // Below this chroma the hue is noise
+                let h1 = if c1 < 1e-4 { h2 } else { h1 };
+                let h2 = if c2 < 1e-4 { h1 } else { h2 };
+                let (c, h) = (lerp(c1, c2), (h1 + shortest_turn(h1, h2) * t).to_radians());
+                from_oklab((lerp(l1, l2), c * h.cos(), c * h.sin()))
+            },
+        }
+    }
+}
+
+// Degrees from hue `from` to hue `to` the short way round, -180..180
+fn shortest_turn(from: f32, to: f32) -> f32 {
+    (to - from + 540.0).rem_euclid(360.0) - 180.0
+}
+
+fn rgb_to_hsv((r, g, b): (u8, u8, u8)) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let saturation = if max == 0 { 0.0 } else { (max - r.min(g).min(b)) as f32 / max as f32 };
+    (rgb_to_hue(r, g, b), saturation, max as f32 / 255.0)
+}
+
+fn to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn from_linear(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let c = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    (c * 255.0).round() as u8
+}
+
+// Björn Ottosson's matrices, from linear sRGB through LMS cone responses
+fn to_oklab((r, g, b): (u8, u8, u8)) -> (f32, f32, f32) {
+    let (r, g, b) = (to_linear(r), to_linear(g), to_linear(b));
+    let l = (0.412221 * r + 0.536333 * g + 0.051446 * b).cbrt();
+    let m = (0.211903 * r + 0.680700 * g + 0.107397 * b).cbrt();
+    let s = (0.088302 * r + 0.281719 * g + 0.629979 * b).cbrt();
+    (
+        0.210454 * l + 0.793618 * m - 0.004072 * s,
+        1.977998 * l - 2.428592 * m + 0.450594 * s,
+        0.025904 * l + 0.782772 * m - 0.808676 * s,
+    )
+}
+
+// Colors outside the sRGB gamut (OKLCH can get there going round the hue) are clipped
+fn from_oklab((l, a, b): (f32, f32, f32)) -> (u8, u8, u8) {
+    let l_ = (l + 0.396338 * a + 0.215804 * b).powi(3);
+    let m_ = (l - 0.105561 * a - 0.063854 * b).powi(3);
+    let s_ = (l - 0.089484 * a - 1.291486 * b).powi(3);
+    (
+        from_linear(4.076742 * l_ - 3.307712 * m_ + 0.230970 * s_),
+        from_linear(-1.268438 * l_ + 2.609757 * m_ - 0.341319 * s_),
+        from_linear(-0.004196 * l_ - 0.703419 * m_ + 1.707615 * s_),
+    )
+}
+
+// The last step before a color goes into a report: brightness scaling, then a gamma
+// curve. The lightbar's LEDs are driven linearly, so the low end of the range looks far
+// brighter than its share and dim colors wash out; a gamma above 1 spreads the steps
+// the way the eye sees them. The curve is a lookup table built once
+#[derive(Clone, Debug)]
+pub struct ColorCorrection {
+    lut: [u8; 256],
+}
+
+impl ColorCorrection {
+    // 1.0 leaves values alone, 2.2 is the usual display curve
+    pub fn new(gamma: f32) -> Self {
+        let mut lut = [0u8; 256];
+        for (i, out) in lut.iter_mut().enumerate() {
+            *out = ((i as f32 / 255.0).powf(gamma) * 255.0).round() as u8;
+        }
+        Self { lut }
+    }
+
+    // `brightness` from 0.0 to 1.0 is applied before the curve, so it dims perceptually
+    pub fn apply(&self, (r, g, b): (u8, u8, u8), brightness: f32) -> (u8, u8, u8) {
+        let correct = |c: u8| self.lut[(c as f32 * brightness.clamp(0.0, 1.0)) as usize];
+        (correct(r), correct(g), correct(b))
+    }
+}
+
+impl Default for ColorCorrection {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+// An RGB color as written on the command line or in a config: `#ff8800` (or the short
+// `#f80`), `255,136,0`, or a CSS color name like `rebeccapurple`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    pub fn rgb(self) -> (u8, u8, u8) {
+        (self.r, self.g, self.b)
+    }
+
+    // Looks up a CSS named color, case-insensitive and ignoring spaces ("Rebecca Purple")
+    pub fn named(name: &str) -> Option<Self> {
+        let name = name.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_lowercase();
+        CSS_COLORS
+            .binary_search_by_key(&name.as_str(), |&(known, _)| known)
+            .ok()
+            .map(|i| {
+                let (r, g, b) = CSS_COLORS[i].1;
+                Self::new(r, g, b)
+            })
+    }
+
+    fn from_hex(digits: &str) -> Result<Self, String> {
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("'#{}' has characters that aren't hex digits", digits));
+        }
+        let channel = |hex: &str| u8::from_str_radix(hex, 16).unwrap_or_default();
+        match digits.len() {
+            6 => Ok(Self::new(channel(&digits[0..2]), channel(&digits[2..4]), channel(&digits[4..6]))),
+            // #f80 is #ff8800
+            3 => {
+                let double = |i: usize| channel(&digits[i..i + 1]) * 17;
+                Ok(Self::new(double(0), double(1), double(2)))
+            },
+            len => Err(format!("'#{}' has {} hex digits, expected 6 like #ff8800 (or 3 like #f80)", digits, len)),
+        }
+    }
+
+    fn from_components(s: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        if parts.len() != 3 {
+            return Err(format!("'{}' has {} components, expected red, green and blue like 255,136,0", s, parts.len()));
+        }
+        let channel = |part: &str| {
+            part.parse::<u8>()
+                .map_err(|_| format!("'{}' in '{}' is not a number from 0 to 255", part, s))
+        };
+        Ok(Self::new(channel(parts[0])?, channel(parts[1])?, channel(parts[2])?))
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("empty color".into());
+        }
+        if let Some(digits) = s.strip_prefix('#') {
+            return Self::from_hex(digits);
+        }
+        if s.contains(',') {
+            return Self::from_components(s);
+        }
+        if let Some(color) = Self::named(s) {
+            return Ok(color);
+        }
+        // Hex without the #, as long as it can't be a misspelled name
+        if s.len() == 6 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Self::from_hex(s);
+        }
+        Err(format!("unknown color '{}' (use a CSS name like rebeccapurple, hex like #ff8800 or R,G,B like 255,136,0)", s))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl From<Color> for (u8, u8, u8) {
+    fn from(color: Color) -> Self {
+        color.rgb()
+    }
+}
+
+impl From<(u8, u8, u8)> for Color {
+    fn from((r, g, b): (u8, u8, u8)) -> Self {
+        Self::new(r, g, b)
+    }
+}
+
+// The CSS Color Module Level 4 named colors, sorted for binary search
+const CSS_COLORS: [(&str, (u8, u8, u8)); 148] = [
+    ("aliceblue", (0xf0, 0xf8, 0xff)),
+    ("antiquewhite", (0xfa, 0xeb, 0xd7)),
+    ("aqua", (0x00, 0xff, 0xff)),
+    ("aquamarine", (0x7f, 0xff, 0xd4)),
+    ("azure", (0xf0, 0xff, 0xff)),
+    ("beige", (0xf5, 0xf5, 0xdc)),
+    ("bisque", (0xff, 0xe4, 0xc4)),
+    ("black", (0x00, 0x00, 0x00)),
+    ("blanchedalmond", (0xff, 0xeb, 0xcd)),
+    ("blue", (0x00, 0x00, 0xff)),
+    ("blueviolet", (0x8a, 0x2b, 0xe2)),
+    ("brown", (0xa5, 0x2a, 0x2a)),
+    ("burlywood", (0xde, 0xb8, 0x87)),
+    ("cadetblue", (0x5f, 0x9e, 0xa0)),
+    ("chartreuse", (0x7f, 0xff, 0x00)),
+    ("chocolate", (0xd2, 0x69, 0x1e)),
+    ("coral", (0xff, 0x7f, 0x50)),
+    ("cornflowerblue", (0x64, 0x95, 0xed)),
+    ("cornsilk", (0xff, 0xf8, 0xdc)),
+    ("crimson", (0xdc, 0x14, 0x3c)),
+    ("cyan", (0x00, 0xff, 0xff)),
+    ("darkblue", (0x00, 0x00, 0x8b)),
+    ("darkcyan", (0x00, 0x8b, 0x8b)),
+    ("darkgoldenrod", (0xb8, 0x86, 0x0b)),
+    ("darkgray", (0xa9, 0xa9, 0xa9)),
+    ("darkgreen", (0x00, 0x64, 0x00)),
+    ("darkgrey", (0xa9, 0xa9, 0xa9)),
+    ("darkkhaki", (0xbd, 0xb7, 0x6b)),
+    ("darkmagenta", (0x8b, 0x00, 0x8b)),
+    ("darkolivegreen", (0x55, 0x6b, 0x2f)),
+    ("darkorange", (0xff, 0x8c, 0x00)),
+    ("darkorchid", (0x99, 0x32, 0xcc)),
+    ("darkred", (0x8b, 0x00, 0x00)),
+    ("darksalmon", (0xe9, 0x96, 0x7a)),
+    ("darkseagreen", (0x8f, 0xbc, 0x8f)),
+    ("darkslateblue", (0x48, 0x3d, 0x8b)),
+    ("darkslategray", (0x2f, 0x4f, 0x4f)),
+    ("darkslategrey", (0x2f, 0x4f, 0x4f)),
+    ("darkturquoise", (0x00, 0xce, 0xd1)),
+    ("darkviolet", (0x94, 0x00, 0xd3)),
+    ("deeppink", (0xff, 0x14, 0x93)),
+    ("deepskyblue", (0x00, 0xbf, 0xff)),
+    ("dimgray", (0x69, 0x69, 0x69)),
+    ("dimgrey", (0x69, 0x69, 0x69)),
+    ("dodgerblue", (0x1e, 0x90, 0xff)),
+    ("firebrick", (0xb2, 0x22, 0x22)),
+    ("floralwhite", (0xff, 0xfa, 0xf0)),
+    ("forestgreen", (0x22, 0x8b, 0x22)),
+    ("fuchsia", (0xff, 0x00, 0xff)),
+    ("gainsboro", (0xdc, 0xdc, 0xdc)),
+    ("ghostwhite", (0xf8, 0xf8, 0xff)),
+    ("gold", (0xff, 0xd7, 0x00)),
+    ("goldenrod", (0xda, 0xa5, 0x20)),
+    ("gray", (0x80, 0x80, 0x80)),
+    ("green", (0x00, 0x80, 0x00)),
+    ("greenyellow", (0xad, 0xff, 0x2f)),
+    ("grey", (0x80, 0x80, 0x80)),
+    ("honeydew", (0xf0, 0xff, 0xf0)),
+    ("hotpink", (0xff, 0x69, 0xb4)),
+    ("indianred", (0xcd, 0x5c, 0x5c)),
+    ("indigo", (0x4b, 0x00, 0x82)),
+    ("ivory", (0xff, 0xff, 0xf0)),
+    ("khaki", (0xf0, 0xe6, 0x8c)),
+    ("lavender", (0xe6, 0xe6, 0xfa)),
+    ("lavenderblush", (0xff, 0xf0, 0xf5)),
+    ("lawngreen", (0x7c, 0xfc, 0x00)),
+    ("lemonchiffon", (0xff, 0xfa, 0xcd)),
+    ("lightblue", (0xad, 0xd8, 0xe6)),
+    ("lightcoral", (0xf0, 0x80, 0x80)),
+    ("lightcyan", (0xe0, 0xff, 0xff)),
+    ("lightgoldenrodyellow", (0xfa, 0xfa, 0xd2)),
+    ("lightgray", (0xd3, 0xd3, 0xd3)),
+    ("lightgreen", (0x90, 0xee, 0x90)),
+    ("lightgrey", (0xd3, 0xd3, 0xd3)),
+    ("lightpink", (0xff, 0xb6, 0xc1)),
+    ("lightsalmon", (0xff, 0xa0, 0x7a)),
+    ("lightseagreen", (0x20, 0xb2, 0xaa)),
+    ("lightskyblue", (0x87, 0xce, 0xfa)),
+    ("lightslategray", (0x77, 0x88, 0x99)),
+    ("lightslategrey", (0x77, 0x88, 0x99)),
+    ("lightsteelblue", (0xb0, 0xc4, 0xde)),
+    ("lightyellow", (0xff, 0xff, 0xe0)),
+    ("lime", (0x00, 0xff, 0x00)),
+    ("limegreen", (0x32, 0xcd, 0x32)),
+    ("linen", (0xfa, 0xf0, 0xe6)),
+    ("magenta", (0xff, 0x00, 0xff)),
+    ("maroon", (0x80, 0x00, 0x00)),
+    ("mediumaquamarine", (0x66, 0xcd, 0xaa)),
+    ("mediumblue", (0x00, 0x00, 0xcd)),
+    ("mediumorchid", (0xba, 0x55, 0xd3)),
+    ("mediumpurple", (0x93, 0x70, 0xdb)),
+    ("mediumseagreen", (0x3c, 0xb3, 0x71)),
+    ("mediumslateblue", (0x7b, 0x68, 0xee)),
+    ("mediumspringgreen", (0x00, 0xfa, 0x9a)),
+    ("mediumturquoise", (0x48, 0xd1, 0xcc)),
+    ("mediumvioletred", (0xc7, 0x15, 0x85)),
+    ("midnightblue", (0x19, 0x19, 0x70)),
+    ("mintcream", (0xf5, 0xff, 0xfa)),
+    ("mistyrose", (0xff, 0xe4, 0xe1)),
+    ("moccasin", (0xff, 0xe4, 0xb5)),
+    ("navajowhite", (0xff, 0xde, 0xad)),
+    ("navy", (0x00, 0x00, 0x80)),
+    ("oldlace", (0xfd, 0xf5, 0xe6)),
+    ("olive", (0x80, 0x80, 0x00)),
+    ("olivedrab", (0x6b, 0x8e, 0x23)),
+    ("orange", (0xff, 0xa5, 0x00)),
+    ("orangered", (0xff, 0x45, 0x00)),
+    ("orchid", (0xda, 0x70, 0xd6)),
+    ("palegoldenrod", (0xee, 0xe8, 0xaa)),
+    ("palegreen", (0x98, 0xfb, 0x98)),
+    ("paleturquoise", (0xaf, 0xee, 0xee)),
+    ("palevioletred", (0xdb, 0x70, 0x93)),
+    ("papayawhip", (0xff, 0xef, 0xd5)),
+    ("peachpuff", (0xff, 0xda, 0xb9)),
+    ("peru", (0xcd, 0x85, 0x3f)),
+    ("pink", (0xff, 0xc0, 0xcb)),
+    ("plum", (0xdd, 0xa0, 0xdd)),
+    ("powderblue", (0xb0, 0xe0, 0xe6)),
+    ("purple", (0x80, 0x00, 0x80)),
+    ("rebeccapurple", (0x66, 0x33, 0x99)),
+    ("red", (0xff, 0x00, 0x00)),
+    ("rosybrown", (0xbc, 0x8f, 0x8f)),
+    ("royalblue", (0x41, 0x69, 0xe1)),
+    ("saddlebrown", (0x8b, 0x45, 0x13)),
+    ("salmon", (0xfa, 0x80, 0x72)),
+    ("sandybrown", (0xf4, 0xa4, 0x60)),
+    ("seagreen", (0x2e, 0x8b, 0x57)),
+    ("seashell", (0xff, 0xf5, 0xee)),
+    ("sienna", (0xa0, 0x52, 0x2d)),
+    ("silver", (0xc0, 0xc0, 0xc0)),
+    ("skyblue", (0x87, 0xce, 0xeb)),
+    ("slateblue", (0x6a, 0x5a, 0xcd)),
+    ("slategray", (0x70, 0x80, 0x90)),
+    ("slategrey", (0x70, 0x80, 0x90)),
+    ("snow", (0xff, 0xfa, 0xfa)),
+    ("springgreen", (0x00, 0xff, 0x7f)),
+    ("steelblue", (0x46, 0x82, 0xb4)),
+    ("tan", (0xd2, 0xb4, 0x8c)),
+    ("teal", (0x00, 0x80, 0x80)),
+    ("thistle", (0xd8, 0xbf, 0xd8)),
+    ("tomato", (0xff, 0x63, 0x47)),
+    ("turquoise", (0x40, 0xe0, 0xd0)),
+    ("violet", (0xee, 0x82, 0xee)),
+    ("wheat", (0xf5, 0xde, 0xb3)),
+    ("white", (0xff, 0xff, 0xff)),
+    ("whitesmoke", (0xf5, 0xf5, 0xf5)),
+    ("yellow", (0xff, 0xff, 0x00)),
+    ("yellowgreen", (0x9a, 0xcd, 0x32)),
+];
@@ -0,0 +1,70 @@
+// Turns a value into a color with a small DSL of "KEY:COLOR" pairs:
+//
+//   "0:green 60:yellow 90:red"        numbers blend between the surrounding stops
+//   "ok:green degraded:orange down:red"   anything else has to match exactly
+//
+// Colors are names or hex codes as in palette::parse_color. Numbers below the
+// first stop or above the last one get that stop's color.
+use serde_json::Value;
+use std::str::FromStr;
+
+use crate::effects::blend_rgb;
+use crate::palette::parse_color;
+
+#[derive(Clone, Debug)]
+pub struct ColorMap {
+    // Sorted by value
+    stops: Vec<(f64, (u8, u8, u8))>,
+    labels: Vec<(String, (u8, u8, u8))>,
+}
+
+impl FromStr for ColorMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut stops = Vec::new();
+        let mut labels = Vec::new();
+        for pair in s.split_whitespace() {
+            let (key, color) = pair.split_once(':').ok_or_else(|| format!("expected KEY:COLOR, got '{}'", pair))?;
+            let color = parse_color(color)?;
+            match key.parse::<f64>() {
+                Ok(value) => stops.push((value, color)),
+                Err(_) => labels.push((key.to_ascii_lowercase(), color)),
+            }
+        }
+        if stops.is_empty() && labels.is_empty() {
+            return Err("color map is empty".into());
+        }
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self { stops, labels })
+    }
+}
+
+impl ColorMap {
+    pub fn color(&self, value: &Value) -> Option<(u8, u8, u8)> {
+        match value {
+            Value::Number(number) => self.interpolate(number.as_f64()?),
+            Value::Bool(flag) => self.label(&flag.to_string()).or_else(|| self.interpolate(*flag as u8 as f64)),
+            Value::String(text) => self.label(text).or_else(|| self.interpolate(text.trim().parse().ok()?)),
+            _ => None,
+        }
+    }
+
+    fn label(&self, text: &str) -> Option<(u8, u8, u8)> {
+        let text = text.trim().to_ascii_lowercase();
+        self.labels.iter().find(|(label, _)| *label == text).map(|(_, color)| *color)
+    }
+
+    fn interpolate(&self, value: f64) -> Option<(u8, u8, u8)> {
+        let (first, last) = (self.stops.first()?, self.stops.last()?);
+        if value <= first.0 {
+            return Some(first.1);
+        }
+        if value >= last.0 {
+            return Some(last.1);
+        }
+        let upper = self.stops.iter().position(|(stop, _)| *stop >= value)?;
+        let ((low, from), (high, to)) = (self.stops[upper - 1], self.stops[upper]);
+        Some(blend_rgb(from, to, ((value - low) / (high - low)) as f32))
+    }
+}
@@ -0,0 +1,255 @@
+// User configuration, read from ~/.config/dualsense-rainbow/config.toml
+// (or the platform equivalent) unless --config points elsewhere
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(feature = "notifications")]
+use crate::notifications::NotificationConfig;
+use crate::adaptive::AdaptiveFpsConfig;
+use crate::assistant::AssistantConfig;
+#[cfg(feature = "audio")]
+use crate::audio::AudioConfig;
+use crate::battery_alert::BatteryAlertConfig;
+use crate::battery_saver::BatterySaverConfig;
+use crate::discord::DiscordConfig;
+use crate::dmx::DmxConfig;
+use crate::effects::EffectKind;
+use crate::lava::LavaConfig;
+use crate::motion::MotionConfig;
+use crate::mqtt::MqttConfig;
+use crate::night::NightConfig;
+use crate::profile::Profile;
+use crate::reactive::ReactiveConfig;
+use dualsense_rainbow::retry::RetryPolicy;
+use crate::schedule::ScheduleRule;
+use crate::screen::ScreenConfig;
+use crate::shuffle::ShuffleConfig;
+use crate::sun::Location;
+use crate::sysmon::SystemConfig;
+use dualsense_rainbow::stats::StatsdConfig;
+use crate::unread::UnreadConfig;
+use crate::watch_json::WatchJsonConfig;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    // Named trigger effects written in the trigger DSL, e.g. `heavy = "feedback 0-9:8"`
+    pub triggers: BTreeMap<String, String>,
+    // Effects applied while running, either a name from [triggers] or inline DSL
+    pub left_trigger: Option<String>,
+    pub right_trigger: Option<String>,
+    // Same as --cooperate: share the controller with Steam Input, DS4Windows and the like
+    pub cooperate: Option<bool>,
+    // Same as --idle-dim: seconds without input before the lightbar dims
+    pub idle_dim_secs: Option<f32>,
+    // Multiplies the brightness while dimmed, 0.3 by default
+    pub idle_dim_brightness: Option<f32>,
+    // Same as --idle-off: minutes without input before the LEDs turn off
+    pub idle_off_minutes: Option<f32>,
+    // Games (process names) that get the controller's LEDs to themselves while running
+    pub pause_for: Vec<String>,
+    // Seasonal palettes on matching dates, on unless set to false
+    pub holiday_themes: Option<bool>,
+    // Region for national days, e.g. "IT" or "US"; taken from LANG when unset
+    pub locale: Option<String>,
+    // Settings used while no profile is active, same fields as a profile (written by `init`)
+    pub defaults: Profile,
+    // Same as --fps: frames rendered and sent per second
+    pub fps: Option<u32>,
+    // Same as --gamma: curve applied to the lightbar after brightness
+    pub gamma: Option<f32>,
+    // Same as --color-space: rgb, hsv, oklab or oklch
+    pub color_space: Option<String>,
+    // Same as --device: the controller to open when several are connected
+    #[serde(alias = "serial")]
+    pub device: Option<String>,
+    // Same as --exit-color: what the lightbar shows after exiting, e.g. "#202020" or "off"
+    pub exit_color: Option<String>,
+    // Same as --fade-out: milliseconds the LEDs take to fade on exit
+    pub fade_out_ms: Option<u64>,
+    // Same as --transition: milliseconds to crossfade between effects and color sources
+    pub transition_ms: Option<u64>,
+    // Same as --bt-report-interval: minimum milliseconds between Bluetooth output reports
+    pub bt_report_interval_ms: Option<u64>,
+    // Named setting bundles, e.g. [profiles.work] speed = 0.5
+    pub profiles: BTreeMap<String, Profile>,
+    // Profiles to use while an application has focus, e.g. `"rocketleague.exe" = "team-blue"`;
+    // `*` and `?` match any characters here and in [processes]
+    pub apps: BTreeMap<String, String>,
+    // Profiles to use while a program runs, focused or not, e.g. `"doom*" = "red-pulse"`
+    pub processes: BTreeMap<String, String>,
+    // Rules that switch profiles, e.g. { cron = "0 9 * * MON-FRI", profile = "work" }
+    // or { sun = "sunset -30m", profile = "evening" }
+    pub schedule: Vec<ScheduleRule>,
+    // Where sun rules find the sun, e.g. { latitude = 52.52, longitude = 13.40 }
+    pub location: Option<Location>,
+    // Send the periodic stats to a StatsD daemon, e.g. { address = "127.0.0.1:8125" }
+    pub statsd: Option<StatsdConfig>,
+    // Smart-home style HTTP API, e.g. { token = "long-random-string" }
+    pub assistant: Option<AssistantConfig>,
+    // Discord bot taking commands like "!pad color #ff00aa" in the listed channels
+    pub discord: Option<DiscordConfig>,
+    // Home Assistant light through MQTT discovery, e.g. { broker = "192.168.1.10:1883" }
+    pub mqtt: Option<MqttConfig>,
+    // JSON endpoints whose values are shown as colors, e.g. { url = "...", pointer = "/load", map = "0:green 90:red" }
+    pub watch_json: Vec<WatchJsonConfig>,
+    // Take the lightbar's color from a lighting desk over sACN or Art-Net, e.g. { universe = 1, channel = 1 }
+    pub dmx: Option<DmxConfig>,
+    // Pulse once per unread desktop notification every minute
+    pub unread: Option<UnreadConfig>,
+    // Warmer colors at night like f.lux, on once the table is present (or with --night),
+    // e.g. { schedule = { "07:00" = 6500, "22:00" = 2700 } }
+    pub night: Option<NightConfig>,
+    // Retries before a failed write counts, e.g. { retries = 3, backoff_ms = 5 }
+    pub write_retry: RetryPolicy,
+    // Red triple blink every few minutes on a low battery, on once the table is present
+    pub battery_alert: Option<BatteryAlertConfig>,
+    // Dimmer, slower and static below a battery level over Bluetooth
+    pub battery_saver: BatterySaverConfig,
+    // Fewer frames while writes can't keep up, e.g. { min_fps = 20 }
+    pub adaptive_fps: AdaptiveFpsConfig,
+    // Region, sampling strategy and rate for --screen
+    pub screen: ScreenConfig,
+    // Effect pool and timing for --shuffle
+    pub shuffle: ShuffleConfig,
+    // What --effect reactive listens to, e.g. { flash_button = "cross", hue_stick = "left" }
+    pub reactive: ReactiveConfig,
+    // Tilt sensitivity and smoothing for --effect motion
+    pub motion: MotionConfig,
+    // What --effect system shows, e.g. { metric = "gpu-temp", map = "40:green 75:yellow 85:red" }
+    pub system: SystemConfig,
+    // Blob size, spread and glow for --effect lava
+    pub lava: LavaConfig,
+    // Desktop notifications, on for every event once the table is present
+    #[cfg(feature = "notifications")]
+    pub notifications: Option<NotificationConfig>,
+    // Frequency bands for --audio and what each drives
+    #[cfg(feature = "audio")]
+    pub audio: AudioConfig,
+    // Audio clips played through the haptic actuators on button presses, e.g. `cross = "click.wav"`
+    #[cfg(feature = "haptics")]
+    pub haptics: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("dualsense-rainbow").join("config.toml"))
+    }
+
+    // An explicitly requested file must exist, the default location is optional
+    pub fn load(explicit: Option<&Path>) -> Result<Self, String> {
+        let path = match explicit {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read config {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("invalid config {}: {}", path.display(), e))
+    }
+}
+
+// How often the config file's modification time is checked
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+// Notices when the config file is saved so its settings can be applied while running
+pub struct ConfigWatch {
+    path: PathBuf,
+    last_poll: Instant,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatch {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = Self::mtime(&path);
+        Self { path, last_poll: Instant::now(), modified }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // The new config once the file has changed. A file that disappears is ignored,
+    // editors that save by renaming remove it for a moment
+    pub fn poll(&mut self) -> Option<Result<Config, String>> {
+        if self.last_poll.elapsed() < WATCH_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+
+        let modified = Self::mtime(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(Config::load(Some(&self.path)))
+    }
+
+    fn mtime(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+}
+
+// What --write-config dumps: the settings in effect, config and command line merged
+pub struct Template {
+    pub effect: EffectKind,
+    pub speed: f32,
+    pub fps: u32,
+    pub brightness: f32,
+    pub device: Option<String>,
+    pub exit_color: (u8, u8, u8),
+    pub fade_out_ms: u64,
+}
+
+impl Template {
+    pub fn render(&self) -> String {
+        let device = match &self.device {
+            // Quoted by toml: Windows device paths are full of backslashes
+            Some(device) => format!("device = {}", toml::Value::String(device.clone())),
+            None => "# device = \"a0:b1:c2:d3:e4:f5\"".to_string(),
+        };
+        let exit_color = match self.exit_color {
+            (0, 0, 0) => "off".to_string(),
+            (r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        };
+        format!(
+            "# dualsense-rainbow settings, see the README for everything else that goes here\n\
+             \n\
+             # Frames per second, 1-240\n\
+             fps = {}\n\
+             # Controller to open when several are connected: a serial as printed on connect, or a number from `list`\n\
+             {}\n\
+             # Left on the lightbar after exiting, \"off\" or a color\n\
+             exit_color = \"{}\"\n\
+             # Milliseconds the LEDs take to fade on exit\n\
+             fade_out_ms = {}\n\
+             \n\
+             [defaults]\n\
+             effect = \"{}\"\n\
+             # Hue degrees per frame\n\
+             speed = {:?}\n\
+             # 0.0-1.0\n\
+             brightness = {:?}\n",
+            self.fps, device, exit_color, self.fade_out_ms, self.effect.name(), self.speed, self.brightness,
+        )
+    }
+
+    // Writes the template to `path`, never over an existing file
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        if path.exists() {
+            return Err(format!("{} already exists, move it away first", path.display()));
+        }
+        let text = self.render();
+        toml::from_str::<Config>(&text).map_err(|e| format!("the template doesn't load: {}", e))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+        }
+        fs::write(path, text).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+    }
+}
@@ -0,0 +1,401 @@
+// Line-based control protocol on a local TCP port or a Unix socket (a named pipe on
+// Windows), so scripts and other programs can change a running instance without
+// restarting it. One command per line, each answered with "ok" or "error: <reason>":
+//
+//   effect breathe                    (or set-effect breathe)
+//   set-color #ff8800                 (the solid effect in that color)
+//   set-param speed=2.0 brightness=0.5
+//   set-palette #ff0000 #0000ff      (set-palette off goes back to the rainbow, set-palette
+//                                     sunset picks a built-in palette or a palette file)
+//   apply-profile night               (apply-profile auto hands back to [apps], [processes] and [[schedule]])
+//   health 37/100, progress 0.62, clear   (see meter.rs)
+//   on, off
+//   status                            (answered with "ok on=true brightness=0.80 ... fps=60.0 sent=1234 ...")
+//   stop                              (fades out and exits)
+use crossbeam_channel::{Receiver, Sender, bounded, unbounded};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+#[cfg(windows)]
+use std::time::Duration;
+#[cfg(windows)]
+use tracing::error;
+
+use crate::effects::EffectKind;
+use crate::meter::{Message, Reading};
+use crate::mic_led::MicPattern;
+use crate::net;
+use crate::palette::{Palette, parse_color};
+use dualsense_rainbow::stats::Stats;
+use crate::tuning::Param;
+
+// Loopback only: anyone who can reach the port can drive the lightbar
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:47820";
+// Commands are short, even set-palette with a dozen colors
+const MAX_LINE: usize = 4096;
+// A named pipe that keeps failing to accept is broken, not busy
+#[cfg(windows)]
+const ACCEPT_RETRY: Duration = Duration::from_secs(1);
+#[cfg(windows)]
+const MAX_ACCEPT_FAILURES: u32 = 10;
+
+#[derive(Debug)]
+pub enum Setting {
+    Level(Param, f32),
+    PlayerLeds(u8),
+    MicLed(MicPattern),
+}
+
+#[derive(Debug)]
+pub enum ControlCommand {
+    Effect(EffectKind),
+    // The solid effect in one color, crossfading unless `instant`
+    SetColor { color: (u8, u8, u8), instant: bool },
+    SetParams(Vec<Setting>),
+    // None goes back to the plain color wheel
+    SetPalette(Option<Palette>),
+    // None releases a pinned profile
+    ApplyProfile(Option<String>),
+    // None clears the meter
+    Meter(Option<Reading>),
+    // Lights on or off, like a smart bulb
+    Power(bool),
+    Status,
+    // Exit like on Ctrl+C
+    Stop,
+}
+
+// What `status` reports
+pub struct Status {
+    pub on: bool,
+    pub brightness: f32,
+    pub color: (u8, u8, u8),
+    pub effect: EffectKind,
+    pub profile: Option<String>,
+    pub stats: Stats,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (r, g, b) = self.color;
+        write!(f, "on={} brightness={:.2} color=#{:02x}{:02x}{:02x} effect={}", self.on, self.brightness, r, g, b, self.effect.name())?;
+        if let Some(profile) = &self.profile {
+            write!(f, " profile={}", profile)?;
+        }
+        write!(f, " {}", self.stats)
+    }
+}
+
+impl FromStr for ControlCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let command = words.next().ok_or("empty command")?;
+        let rest: Vec<&str> = words.collect();
+
+        match command.to_ascii_lowercase().as_str() {
+            "effect" | "set-effect" => match rest.as_slice() {
+                [name] => Ok(ControlCommand::Effect(name.parse()?)),
+                _ => Err(format!("usage: {} <NAME>", command)),
+            },
+            "set-color" if rest.is_empty() => Err("usage: set-color <COLOR>".into()),
+            // Colors can be written with spaces, e.g. "255, 136, 0" or "hot pink"
+            "set-color" => Ok(ControlCommand::SetColor { color: parse_color(&rest.join(" "))?, instant: false }),
+            "set-param" if rest.is_empty() => Err("usage: set-param <NAME>=<VALUE>...".into()),
+            "set-param" => rest.iter().map(|pair| parse_setting(pair)).collect::<Result<_, _>>().map(ControlCommand::SetParams),
+            "set-palette" => match rest.as_slice() {
+                [] => Err("usage: set-palette <COLOR>... or set-palette off".into()),
+                [off] if off.eq_ignore_ascii_case("off") => Ok(ControlCommand::SetPalette(None)),
+                [name] if parse_color(name).is_err() => Ok(ControlCommand::SetPalette(Some(Palette::named(name)?))),
+                colors => {
+                    let colors = colors.iter().map(|c| parse_color(c)).collect::<Result<_, _>>()?;
+                    Ok(ControlCommand::SetPalette(Some(Palette::new(colors))))
+                },
+            },
+            "apply-profile" => match rest.as_slice() {
+                [auto] if auto.eq_ignore_ascii_case("auto") => Ok(ControlCommand::ApplyProfile(None)),
+                [name] => Ok(ControlCommand::ApplyProfile(Some(name.to_string()))),
+                _ => Err("usage: apply-profile <NAME> or apply-profile auto".into()),
+            },
+            "health" | "progress" | "clear" => line.parse().map(|Message(reading)| ControlCommand::Meter(reading)),
+            "on" | "off" if rest.is_empty() => Ok(ControlCommand::Power(command.eq_ignore_ascii_case("on"))),
+            "status" if rest.is_empty() => Ok(ControlCommand::Status),
+            "stop" if rest.is_empty() => Ok(ControlCommand::Stop),
+            _ => Err(format!(
+                "unknown command '{}' (expected effect, set-color, set-param, set-palette, apply-profile, health, progress, clear, on, off, status or stop)",
+                command
+            )),
+        }
+    }
+}
+
+fn parse_setting(pair: &str) -> Result<Setting, String> {
+    let (name, value) = pair.split_once('=').ok_or_else(|| format!("expected NAME=VALUE, got '{}'", pair))?;
+    match name.to_ascii_lowercase().as_str() {
+        "player_leds" => Ok(Setting::PlayerLeds(crate::cli::parse_player_mask(value)?)),
+        "mic_led" => Ok(Setting::MicLed(value.parse()?)),
+        _ => {
+            let param = name.parse()?;
+            let value = value.parse().map_err(|_| format!("invalid number '{}' for {}", value, name))?;
+            Ok(Setting::Level(param, value))
+        },
+    }
+}
+
+type Answer = Result<Option<Status>, String>;
+
+// What `submit` answers once the main loop has stopped taking commands
+pub const SHUTTING_DOWN: &str = "shutting down";
+
+// A parsed command waiting for the main loop, which answers through `reply`
+pub struct Request {
+    pub command: ControlCommand,
+    reply: Sender<Answer>,
+}
+
+impl Request {
+    pub fn reply(self, result: Result<(), String>) {
+        // The client may have hung up already
+        let _ = self.reply.send(result.map(|()| None));
+    }
+
+    pub fn reply_status(self, status: Status) {
+        let _ = self.reply.send(Ok(Some(status)));
+    }
+}
+
+// Hands a command to the main loop and waits for its answer
+pub fn submit(requests: &Sender<Request>, command: ControlCommand) -> Answer {
+    let (reply, answer) = bounded(1);
+    requests.send(Request { command, reply }).map_err(|_| SHUTTING_DOWN.to_string())?;
+    answer.recv().unwrap_or_else(|_| Err(SHUTTING_DOWN.into()))
+}
+
+// Collects commands from every front end (the line protocol, the assistant API)
+// for the main loop to apply between frames
+pub struct ControlServer {
+    sender: Sender<Request>,
+    requests: Receiver<Request>,
+}
+
+impl Default for ControlServer {
+    fn default() -> Self {
+        let (sender, requests) = unbounded();
+        Self { sender, requests }
+    }
+}
+
+impl ControlServer {
+    // Accepts the line protocol on `address`
+    pub fn listen(&self, address: &str) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let sender = self.sender.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                let Ok(writer) = stream.try_clone() else { continue };
+                thread::spawn(move || serve(stream, writer, sender));
+            }
+        });
+        Ok(address)
+    }
+
+    // Accepts the line protocol on a Unix socket at `path`. A socket file left behind by
+    // an instance that didn't exit cleanly is replaced; the returned guard removes it again
+    #[cfg(unix)]
+    pub fn listen_socket(&self, path: &Path) -> io::Result<SocketGuard> {
+        use std::os::unix::net::UnixListener;
+
+        if path.exists() && send_socket(path, "status").is_err() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let sender = self.sender.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                let Ok(writer) = stream.try_clone() else { continue };
+                thread::spawn(move || serve(stream, writer, sender));
+            }
+        });
+        Ok(SocketGuard { path: path.to_path_buf() })
+    }
+
+    // Accepts the line protocol on the named pipe `path` (\\.\pipe\NAME)
+    #[cfg(windows)]
+    pub fn listen_socket(&self, path: &Path) -> io::Result<SocketGuard> {
+        let first = pipe::create(path, true)?;
+        let sender = self.sender.clone();
+        let path_buf = path.to_path_buf();
+
+        thread::spawn(move || {
+            let mut instance = Some(first);
+            let mut failures = 0;
+            loop {
+                let pipe = match instance.take().map_or_else(|| pipe::create(&path_buf, false), Ok) {
+                    Ok(pipe) => pipe,
+                    Err(_) => return,
+                };
+                let stream = match pipe::accept(pipe) {
+                    Ok(stream) => {
+                        failures = 0;
+                        stream
+                    },
+                    Err(e) => {
+                        failures += 1;
+                        if failures == MAX_ACCEPT_FAILURES {
+                            error!("control pipe {}: {}", path_buf.display(), e);
+                            return;
+                        }
+                        thread::sleep(ACCEPT_RETRY);
+                        continue;
+                    },
+                };
+                let sender = sender.clone();
+                let Ok(writer) = stream.try_clone() else { continue };
+                thread::spawn(move || serve(stream, writer, sender));
+            }
+        });
+        Ok(SocketGuard { path: path.to_path_buf() })
+    }
+
+    pub fn sender(&self) -> Sender<Request> {
+        self.sender.clone()
+    }
+
+    pub fn requests(&self) -> impl Iterator<Item = Request> + '_ {
+        self.requests.try_iter()
+    }
+}
+
+// Removes the socket file once the instance stops listening. Named pipes go away
+// on their own
+pub struct SocketGuard {
+    path: PathBuf,
+}
+
+impl SocketGuard {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// A line longer than MAX_LINE drops the client
+fn serve(reader: impl Read, mut writer: impl Write, requests: Sender<Request>) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    while let Ok(true) = net::read_line(&mut reader, &mut line, MAX_LINE) {
+        let Ok(line) = std::str::from_utf8(&line) else { return };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let result = line.parse().and_then(|command| submit(&requests, command));
+        let written = match result {
+            Ok(Some(status)) => writeln!(writer, "ok {}", status),
+            Ok(None) => writeln!(writer, "ok"),
+            Err(e) => writeln!(writer, "error: {}", e),
+        };
+        if written.is_err() {
+            return;
+        }
+    }
+}
+
+// Client side of `dualsense-rainbow ctl`: sends one command and returns
+// whatever followed "ok" (the status line, usually nothing)
+pub fn send(address: &str, command: &str) -> Result<String, String> {
+    let stream = TcpStream::connect(address)
+        .map_err(|e| format!("cannot reach a running instance at {}: {}", address, e))?;
+    exchange(stream, command)
+}
+
+// The same over a Unix socket or named pipe
+pub fn send_socket(path: &Path, command: &str) -> Result<String, String> {
+    #[cfg(unix)]
+    let stream = std::os::unix::net::UnixStream::connect(path);
+    #[cfg(windows)]
+    let stream = std::fs::OpenOptions::new().read(true).write(true).open(path);
+    let stream = stream.map_err(|e| format!("cannot reach a running instance at {}: {}", path.display(), e))?;
+    exchange(stream, command)
+}
+
+fn exchange(mut stream: impl Read + Write, command: &str) -> Result<String, String> {
+    writeln!(stream, "{}", command).map_err(|e| e.to_string())?;
+
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer).map_err(|e| e.to_string())?;
+    match answer.trim() {
+        "" => Err("connection closed without an answer".into()),
+        "ok" => Ok(String::new()),
+        answer if answer.starts_with("ok ") => Ok(answer[3..].to_string()),
+        answer => Err(answer.trim_start_matches("error: ").to_string()),
+    }
+}
+
+// Named pipe server instances: each one takes a single client, so a fresh instance is
+// created for the next client before the connected one is handed off
+#[cfg(windows)]
+mod pipe {
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::{FromRawHandle, RawHandle};
+    use std::path::Path;
+    use std::ptr;
+    use winapi::shared::winerror::ERROR_PIPE_CONNECTED;
+    use winapi::um::errhandlingapi::GetLastError;
+    use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+    use winapi::um::namedpipeapi::{ConnectNamedPipe, CreateNamedPipeW};
+    use winapi::um::winbase::{
+        FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX, PIPE_READMODE_BYTE, PIPE_TYPE_BYTE,
+        PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+
+    // `first` fails when another program already owns the name
+    pub fn create(path: &Path, first: bool) -> io::Result<File> {
+        let name: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+        let flags = PIPE_ACCESS_DUPLEX | if first { FILE_FLAG_FIRST_PIPE_INSTANCE } else { 0 };
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                flags,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT,
+                PIPE_UNLIMITED_INSTANCES,
+                4096,
+                4096,
+                0,
+                ptr::null_mut(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { File::from_raw_handle(handle as RawHandle) })
+    }
+
+    // Blocks until a client opens the pipe
+    pub fn accept(pipe: File) -> io::Result<File> {
+        use std::os::windows::io::AsRawHandle;
+
+        let connected = unsafe { ConnectNamedPipe(pipe.as_raw_handle() as _, ptr::null_mut()) };
+        // A client that connected between creating and waiting is fine too
+        if connected == 0 && unsafe { GetLastError() } != ERROR_PIPE_CONNECTED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(pipe)
+    }
+}
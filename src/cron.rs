@@ -0,0 +1,115 @@
+// Standard 5-field cron expressions: minute hour day-of-month month day-of-week
+//
+// Fields accept `*`, numbers, ranges (`1-5`), lists (`1,15`), steps (`*/15`, `9-17/2`)
+// and English names for months (JAN-DEC) and weekdays (SUN-SAT, 0 and 7 are Sunday).
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use std::str::FromStr;
+
+const MONTHS: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,   // Bit per minute 0-59
+    hours: u32,     // Bit per hour 0-23
+    days: u32,      // Bit per day of month 1-31
+    months: u16,    // Bit per month 1-12
+    weekdays: u8,   // Bit per weekday, 0 = Sunday
+    any_day: bool,  // Day-of-month was `*`
+    any_weekday: bool,
+}
+
+impl CronExpr {
+    pub fn matches(&self, at: NaiveDateTime) -> bool {
+        let day_ok = self.days & (1 << at.day()) != 0;
+        let weekday_ok = self.weekdays & (1 << at.weekday().num_days_from_sunday()) != 0;
+
+        // Like cron: when both day fields are restricted, either one may match
+        let date_ok = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day_ok,
+            (true, false) => weekday_ok,
+            (false, false) => day_ok || weekday_ok,
+        };
+
+        date_ok
+            && self.minutes & (1 << at.minute()) != 0
+            && self.hours & (1 << at.hour()) != 0
+            && self.months & (1 << at.month()) != 0
+    }
+}
+
+impl FromStr for CronExpr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("'{}' needs 5 fields: minute hour day month weekday", s));
+        };
+
+        let mut weekdays = parse_field(weekday, 0, 7, &WEEKDAYS, 0)?;
+        // 7 is another name for Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, &[], 0)?,
+            hours: parse_field(hour, 0, 23, &[], 0)? as u32,
+            days: parse_field(day, 1, 31, &[], 0)? as u32,
+            months: parse_field(month, 1, 12, &MONTHS, 1)? as u16,
+            weekdays: weekdays as u8,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+}
+
+// Returns a bitmask with a bit set for every value the field selects
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], name_base: u32) -> Result<u64, String> {
+    let value = |token: &str| -> Result<u32, String> {
+        let upper = token.to_ascii_uppercase();
+        if let Some(index) = names.iter().position(|name| *name == upper) {
+            return Ok(index as u32 + name_base);
+        }
+        token
+            .parse::<u32>()
+            .ok()
+            .filter(|v| (min..=max).contains(v))
+            .ok_or_else(|| format!("'{}' is out of range {}-{} in '{}'", token, min, max, field))
+    };
+
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().ok().filter(|&s| s > 0)
+                    .ok_or_else(|| format!("invalid step '{}' in '{}'", step, field))?;
+                (range, step)
+            },
+            None => (part, 1),
+        };
+
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (value(a)?, value(b)?),
+                // "5/10" means every 10 starting at 5
+                None if step > 1 => (value(range)?, max),
+                None => {
+                    let v = value(range)?;
+                    (v, v)
+                },
+            },
+        };
+        if first > last {
+            return Err(format!("range '{}' is backwards in '{}'", range, field));
+        }
+
+        for v in (first..=last).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
@@ -0,0 +1,77 @@
+// --daemon: starts a copy of the program in the background, detached from the terminal,
+// that takes commands on a Unix socket (a named pipe on Windows) from `ctl`. Its output
+// goes to a log file next to the socket
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// Set in the background copy's environment, so it runs instead of starting another one
+const CHILD_ENV: &str = "DUALSENSE_RAINBOW_DAEMON";
+
+pub fn is_child() -> bool {
+    std::env::var_os(CHILD_ENV).is_some()
+}
+
+// $XDG_RUNTIME_DIR/dualsense-rainbow.sock, or in the temp directory without one
+#[cfg(unix)]
+pub fn default_socket() -> PathBuf {
+    dirs::runtime_dir().unwrap_or_else(std::env::temp_dir).join("dualsense-rainbow.sock")
+}
+
+#[cfg(windows)]
+pub fn default_socket() -> PathBuf {
+    PathBuf::from(r"\\.\pipe\dualsense-rainbow")
+}
+
+pub fn log_path() -> PathBuf {
+    #[cfg(unix)]
+    let dir = dirs::runtime_dir().unwrap_or_else(std::env::temp_dir);
+    #[cfg(windows)]
+    let dir = std::env::temp_dir();
+    dir.join("dualsense-rainbow.log")
+}
+
+// Runs this program again with the same arguments in the background and returns its
+// process ID. Refuses when something already answers on `socket`
+pub fn spawn(socket: &Path) -> Result<u32, String> {
+    if crate::control::send_socket(socket, "status").is_ok() {
+        return Err(format!("a daemon is already listening on {}", socket.display()));
+    }
+
+    let exe = std::env::current_exe().map_err(|e| format!("cannot find the program to start: {}", e))?;
+    let log_path = log_path();
+    let log = File::create(&log_path).map_err(|e| format!("cannot create {}: {}", log_path.display(), e))?;
+    let errors = log.try_clone().map_err(|e| e.to_string())?;
+
+    let mut command = Command::new(exe);
+    command
+        .args(std::env::args_os().skip(1))
+        .env(CHILD_ENV, "1")
+        .stdin(Stdio::null())
+        .stdout(log)
+        .stderr(errors);
+    detach(&mut command);
+    let child = command.spawn().map_err(|e| format!("cannot start the daemon: {}", e))?;
+    Ok(child.id())
+}
+
+// A session of its own, so closing the terminal doesn't take it along
+#[cfg(unix)]
+fn detach(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+}
+
+#[cfg(windows)]
+fn detach(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    use winapi::um::winbase::{CREATE_NEW_PROCESS_GROUP, DETACHED_PROCESS};
+
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+}
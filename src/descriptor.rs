@@ -0,0 +1,116 @@
+// Checks the report layout the pad declares in its HID report descriptor against the
+// one its model's reports are written for. The descriptor only gives report IDs and
+// sizes (the fields themselves are vendor-defined bytes), but a firmware revision that
+// moves things around changes those too, and it beats writing RGB into the wrong bytes
+// without a word.
+use hidapi::HidDevice;
+use std::collections::BTreeMap;
+
+use crate::lightbar::ReportSizes;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Input,
+    Output,
+    Feature,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Kind::Input => "input",
+            Kind::Output => "output",
+            Kind::Feature => "feature",
+        })
+    }
+}
+
+// Payload size in bytes (without the report ID) of every report, by kind and ID
+struct Reports {
+    sizes: BTreeMap<(Kind, u8), usize>,
+}
+
+impl Reports {
+    fn size(&self, kind: Kind, id: u8) -> Option<usize> {
+        self.sizes.get(&(kind, id)).copied()
+    }
+}
+
+// Walks the descriptor's items, adding up the bits of every main item per report.
+// Only the global items that affect sizes are tracked
+fn parse(descriptor: &[u8]) -> Result<Reports, String> {
+    #[derive(Clone, Copy, Default)]
+    struct Globals {
+        report_size: u32,
+        report_count: u32,
+        report_id: u8,
+    }
+
+    let mut bits: BTreeMap<(Kind, u8), u32> = BTreeMap::new();
+    let mut globals = Globals::default();
+    let mut stack = Vec::new();
+    let mut i = 0;
+    while i < descriptor.len() {
+        let prefix = descriptor[i];
+        // Long item: size, tag, data. None are defined, skip them
+        if prefix == 0xFE {
+            let len = *descriptor.get(i + 1).ok_or("truncated long item")? as usize;
+            i += 3 + len;
+            continue;
+        }
+
+        let len = [0, 1, 2, 4][(prefix & 0x03) as usize];
+        let data = descriptor.get(i + 1..i + 1 + len).ok_or("truncated item")?;
+        let value = data.iter().rev().fold(0u32, |value, &byte| value << 8 | byte as u32);
+        i += 1 + len;
+
+        match (prefix >> 2 & 0x03, prefix >> 4) {
+            // Main items
+            (0, tag @ (0x8 | 0x9 | 0xB)) => {
+                let kind = match tag {
+                    0x8 => Kind::Input,
+                    0x9 => Kind::Output,
+                    _ => Kind::Feature,
+                };
+                *bits.entry((kind, globals.report_id)).or_default() += globals.report_size * globals.report_count;
+            },
+            // Global items
+            (1, 0x7) => globals.report_size = value,
+            (1, 0x8) => globals.report_id = u8::try_from(value).map_err(|_| "report ID out of range")?,
+            (1, 0x9) => globals.report_count = value,
+            (1, 0xA) => stack.push(globals),
+            (1, 0xB) => globals = stack.pop().ok_or("pop without push")?,
+            _ => {},
+        }
+    }
+
+    if bits.is_empty() {
+        return Err("no reports declared".into());
+    }
+    Ok(Reports { sizes: bits.into_iter().map(|(key, bits)| (key, bits.div_ceil(8) as usize)).collect() })
+}
+
+// Reads the descriptor of an opened pad and compares it with the layout in `expected`.
+// An error says why the built-in layout couldn't be confirmed; it is still what gets
+// written, there is no other to fall back to
+pub fn verify(device: &HidDevice, expected: ReportSizes) -> Result<(), String> {
+    let mut buf = [0u8; hidapi::MAX_REPORT_DESCRIPTOR_SIZE];
+    let len = device
+        .get_report_descriptor(&mut buf)
+        .map_err(|e| format!("cannot read the report descriptor: {}", e))?;
+    let reports = parse(&buf[..len]).map_err(|e| format!("cannot parse the report descriptor: {}", e))?;
+
+    let mut problems = Vec::new();
+    for (kind, (id, expected)) in [(Kind::Output, expected.output), (Kind::Input, expected.input)] {
+        match reports.size(kind, id) {
+            Some(size) if size == expected => {},
+            Some(size) => problems.push(format!("{} report 0x{:02X} is {} bytes, expected {}", kind, id, size, expected)),
+            None => problems.push(format!("no {} report 0x{:02X}", kind, id)),
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("the report descriptor doesn't match the known layout ({}), a different firmware revision?", problems.join(", ")))
+    }
+}
@@ -0,0 +1,683 @@
+// Opening the controller over hidapi and keeping its output state: every lightbar,
+// player LED, mic LED and trigger change goes out through one controller, which
+// handles rate limits, retries, reconnects and the input reports coming back
+use crossbeam_channel::Receiver;
+use hidapi::{DeviceInfo, HidApi, HidDevice};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::capture::CaptureWriter;
+use crate::input::{BatteryStatus, ControllerState, InputEvent, InputEvents};
+use crate::lightbar::{DualSenseReports, DualShock4Reports, Lightbar};
+use crate::report::{MuteLed, OutputState, PlayerLeds};
+use crate::trace::{Direction, HidTracer};
+use crate::transport::{MockTransport, Transport};
+use crate::triggers::TriggerEffect;
+use crate::error::Error;
+use crate::{descriptor, passthrough, retry, stats};
+
+// Vendor ID and Product IDs for the DualSense controllers
+pub const DUALSENSE_VID: u16 = 0x054C;
+pub const DUALSENSE_PID: u16 = 0x0CE6;
+pub const DUALSENSE_EDGE_PID: u16 = 0x0DF2;
+// The DualShock 4 comes from the same vendor
+pub const DUALSHOCK4_PID: u16 = 0x05C4;
+pub const DUALSHOCK4_V2_PID: u16 = 0x09CC;
+
+// The controller models that can be opened. The Edge takes the same output report as
+// the DualSense over USB and Bluetooth (Linux drives both with one code path) and only
+// adds its Fn buttons and back paddles to the input report. The DualShock 4 has
+// reports of its own (see ds4.rs) and only a lightbar to drive
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Model {
+    #[default]
+    DualSense,
+    DualSenseEdge,
+    DualShock4,
+    DualShock4V2,
+}
+
+impl Model {
+    pub const ALL: [Model; 4] = [Model::DualSense, Model::DualSenseEdge, Model::DualShock4, Model::DualShock4V2];
+
+    pub fn from_product_id(product_id: u16) -> Option<Self> {
+        Model::ALL.into_iter().find(|model| model.product_id() == product_id)
+    }
+
+    pub fn product_id(self) -> u16 {
+        match self {
+            Model::DualSense => DUALSENSE_PID,
+            Model::DualSenseEdge => DUALSENSE_EDGE_PID,
+            Model::DualShock4 => DUALSHOCK4_PID,
+            Model::DualShock4V2 => DUALSHOCK4_V2_PID,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Model::DualSense => "DualSense",
+            Model::DualSenseEdge => "DualSense Edge",
+            Model::DualShock4 => "DualShock 4",
+            Model::DualShock4V2 => "DualShock 4 (v2)",
+        }
+    }
+
+    // Whether it has the player LEDs, mic LED and adaptive triggers on top of the lightbar
+    pub fn is_dualsense(self) -> bool {
+        matches!(self, Model::DualSense | Model::DualSenseEdge)
+    }
+
+    // The report formats this model speaks
+    pub fn reports(self) -> Box<dyn Lightbar> {
+        if self.is_dualsense() {
+            Box::new(DualSenseReports)
+        } else {
+            Box::new(DualShock4Reports::default())
+        }
+    }
+}
+
+// Minimum spacing between output reports in --cooperate mode
+pub const COOPERATE_WRITE_INTERVAL: Duration = Duration::from_millis(100);
+
+// A struct to manage the controller, a DualSense or any other supported model
+pub struct DualSenseController {
+    device: Box<dyn Transport>,
+    path: String,
+    usb_mode: bool,
+    model: Model,
+    // Builds and parses the reports for `model`
+    reports: Box<dyn Lightbar>,
+    // Kept so a reconnect to another model can hand it to the new reports
+    bt_report_interval: Duration,
+    output: OutputState,
+    last_output: Option<OutputState>,
+    output_seq: u8,
+    min_write_interval: Option<Duration>,
+    // Extra spacing on top of min_write_interval while the battery saver is on
+    throttle: Option<Duration>,
+    last_write: Option<Instant>,
+    stats: stats::StatsTracker,
+    retry: retry::RetryPolicy,
+    // When writes started failing, cleared by the next successful one
+    stalled_since: Option<Instant>,
+    tracer: Option<HidTracer>,
+    // --record
+    recorder: Option<CaptureWriter>,
+    input: InputEvents,
+    // Which HID collection to open, kept for reconnects
+    selection: HidSelection,
+    // The collection that was opened, None for a simulated pad
+    collection: Option<Collection>,
+    // Result of checking the report descriptor against the built-in layout
+    layout: Result<(), String>,
+    // Serial number, or the path when there is none; None for a simulated pad
+    identity: Option<String>,
+    // Serial number (or path) a reconnect has to find again, set when several pads
+    // are open so one never reopens as another
+    pinned: Option<String>,
+    // --passthrough, fed with every input report
+    passthrough: Option<passthrough::VirtualPad>,
+}
+
+impl DualSenseController {
+    // Opens the first DualSense matching `selection`
+    pub fn open(selection: HidSelection) -> Result<Self, Error> {
+        let api = HidApi::new()?;
+        Self::open_info(&api, find_dualsense(&api, None, selection)?, selection)
+    }
+
+    // Opens every DualSense matching `selection`, each pinned to its own pad
+    pub fn open_all(selection: HidSelection) -> Result<Vec<Self>, Error> {
+        let api = HidApi::new()?;
+        let mut controllers: Vec<Self> = Vec::new();
+        for device_info in candidates(&api, selection)? {
+            // A pad plugged in while paired shows up twice with the same serial
+            let identity = identity(device_info);
+            if controllers.iter().any(|c| c.pinned.as_ref() == Some(&identity)) {
+                continue;
+            }
+            let mut controller = Self::open_info(&api, device_info, selection)?;
+            controller.pinned = Some(identity);
+            controllers.push(controller);
+        }
+        Ok(controllers)
+    }
+
+    // Opens the pad `device` names: its serial number (the Bluetooth address, as printed on
+    // connect), its HID path or its number in `list`, counting from 1. It stays pinned to
+    // that pad so a reconnect never picks up another one
+    pub fn open_device(device: &str, selection: HidSelection) -> Result<Self, Error> {
+        let api = HidApi::new()?;
+        let device = device.trim();
+        let wanted = match device.parse::<usize>() {
+            Ok(number) => {
+                let found = detect(&api);
+                let detected = number
+                    .checked_sub(1)
+                    .and_then(|index| found.get(index))
+                    .ok_or_else(|| Error::DeviceNotFound(format!("no controller number {}, {} connected", number, found.len())))?;
+                detected.serial.clone().unwrap_or_else(|| detected.path.clone())
+            },
+            Err(_) => device.to_string(),
+        };
+        let device_info = candidates(&api, selection)?
+            .into_iter()
+            .find(|d| identity(d).eq_ignore_ascii_case(&wanted) || d.path().to_string_lossy() == wanted)
+            .ok_or_else(|| Error::DeviceNotFound(format!("no controller {}", device)))?;
+        let mut controller = Self::open_info(&api, device_info, selection)?;
+        controller.pinned = Some(identity(device_info));
+        Ok(controller)
+    }
+
+    fn open_info(api: &HidApi, device_info: &DeviceInfo, selection: HidSelection) -> Result<Self, Error> {
+        let path = device_info.path().to_string_lossy().into_owned();
+        let device = device_info.open_device(api).map_err(|e| Error::open_failed(path.clone(), e))?;
+
+        // Determine connection mode based on interface number
+        let usb_mode = device_info.interface_number() == 3;
+        let model = Model::from_product_id(device_info.product_id()).unwrap_or_default();
+        let reports = model.reports();
+        let layout = descriptor::verify(&device, reports.report_sizes(usb_mode));
+
+        if !usb_mode {
+            enable_full_input_reports(&device, reports.as_ref());
+        }
+
+        let mut controller = Self::with_transport(Box::new(device), path, usb_mode);
+        controller.selection = selection;
+        controller.model = model;
+        controller.reports = reports;
+        controller.identity = Some(identity(device_info));
+        controller.collection = Some(Collection::of(device_info));
+        controller.layout = layout;
+        Ok(controller)
+    }
+
+    // A simulated controller on USB, nothing needs to be plugged in
+    pub fn mock(transport: MockTransport) -> Self {
+        Self::with_transport(Box::new(transport), "mock".to_string(), true)
+    }
+
+    fn with_transport(device: Box<dyn Transport>, path: String, usb_mode: bool) -> Self {
+        Self {
+            device,
+            path,
+            usb_mode,
+            model: Model::default(),
+            reports: Model::default().reports(),
+            bt_report_interval: Duration::ZERO,
+            output: OutputState::default(),
+            last_output: None,
+            output_seq: 0,
+            min_write_interval: None,
+            throttle: None,
+            last_write: None,
+            stats: stats::StatsTracker::default(),
+            retry: retry::RetryPolicy::default(),
+            stalled_since: None,
+            tracer: None,
+            recorder: None,
+            input: InputEvents::default(),
+            selection: HidSelection::default(),
+            collection: None,
+            layout: Ok(()),
+            identity: None,
+            pinned: None,
+            passthrough: None,
+        }
+    }
+
+    // Closes the handle and opens the controller again, preferring the same device path.
+    // Whatever state was last set is sent again on the next update
+    pub fn reopen(&mut self) -> Result<(), Error> {
+        let result = self.open_again();
+        match &result {
+            Ok(()) => self.stats.reconnected(),
+            Err(e) => self.stats.reconnect_failed(e),
+        }
+        result
+    }
+
+    fn open_again(&mut self) -> Result<(), Error> {
+        // A failed attempt waits for a full watchdog window before the next one
+        self.stalled_since = Some(Instant::now());
+
+        let api = HidApi::new()?;
+        let device_info = match &self.pinned {
+            Some(pinned) => candidates(&api, self.selection)?
+                .into_iter()
+                .find(|d| identity(d) == *pinned)
+                .ok_or_else(|| Error::DeviceNotFound(format!("DualSense {} not found", pinned)))?,
+            None => find_dualsense(&api, Some(&self.path), self.selection)?,
+        };
+        let device = device_info
+            .open_device(&api)
+            .map_err(|e| Error::open_failed(device_info.path().to_string_lossy().into_owned(), e))?;
+
+        self.path = device_info.path().to_string_lossy().into_owned();
+        self.usb_mode = device_info.interface_number() == 3;
+        let model = Model::from_product_id(device_info.product_id()).unwrap_or_default();
+        if model != self.model {
+            self.model = model;
+            self.reports = model.reports();
+            self.reports.set_poll_interval(self.bt_report_interval);
+        }
+        self.identity = Some(identity(device_info));
+        self.collection = Some(Collection::of(device_info));
+        self.layout = descriptor::verify(&device, self.reports.report_sizes(self.usb_mode));
+        if !self.usb_mode {
+            enable_full_input_reports(&device, self.reports.as_ref());
+        }
+        self.device = Box::new(device);
+        self.last_output = None;
+        self.stalled_since = None;
+        if let Some(pad) = &mut self.passthrough {
+            pad.reattach(&self.path);
+        }
+        Ok(())
+    }
+
+    // How long writes have been failing, zero while they go through
+    pub fn stalled_for(&self) -> Duration {
+        self.stalled_since.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    // Returns a channel receiving every input event decoded from now on
+    pub fn subscribe(&mut self) -> Receiver<InputEvent> {
+        self.input.subscribe()
+    }
+
+    // Drains pending input reports without blocking and dispatches their events.
+    // A failed read counts as a stall like a failed write: the pad is usually gone
+    pub fn poll_input(&mut self) -> Result<(), Error> {
+        let mut buf = [0u8; 78];
+        loop {
+            let len = match self.device.read_timeout(&mut buf, 0) {
+                Ok(len) => len,
+                Err(e) => {
+                    self.stalled_since.get_or_insert_with(Instant::now);
+                    return Err(e.into());
+                },
+            };
+            if len == 0 {
+                return Ok(());
+            }
+
+            self.handle_input(&buf[..len]);
+        }
+    }
+
+    fn handle_input(&mut self, report: &[u8]) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(Direction::In, report);
+        }
+
+        if let Some(state) = self.reports.parse_input(report) {
+            if let Some(pad) = &mut self.passthrough {
+                pad.forward(&state);
+            }
+            self.input.update(state);
+        }
+    }
+
+    // Sleeps until `deadline`. With a virtual pad attached, input is forwarded as it
+    // arrives meanwhile instead of once a frame
+    pub fn wait_until(&mut self, deadline: Instant) {
+        if self.passthrough.is_none() {
+            thread::sleep(deadline.saturating_duration_since(Instant::now()));
+            return;
+        }
+        let mut buf = [0u8; 78];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return;
+            }
+            match self.device.read_timeout(&mut buf, remaining.as_millis().max(1) as i32) {
+                Ok(0) => {},
+                Ok(len) => self.handle_input(&buf[..len]),
+                // Reported by the next poll_input
+                Err(_) => {
+                    thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    return;
+                },
+            }
+        }
+    }
+
+    pub fn set_passthrough(&mut self, pad: passthrough::VirtualPad) {
+        self.passthrough = Some(pad);
+    }
+
+    pub fn set_tracer(&mut self, tracer: HidTracer) {
+        self.tracer = Some(tracer);
+    }
+
+    // Saves every output report written from now on
+    pub fn set_recorder(&mut self, recorder: CaptureWriter) {
+        self.recorder = Some(recorder);
+    }
+
+    // Player and mic LED changes are sent together with the next lightbar update
+    pub fn set_player_leds(&mut self, player_leds: PlayerLeds) {
+        self.output.player_leds = player_leds;
+    }
+
+    pub fn set_triggers(&mut self, left: TriggerEffect, right: TriggerEffect) {
+        self.output.left_trigger = left;
+        self.output.right_trigger = right;
+    }
+
+    pub fn set_mute_led(&mut self, mute_led: MuteLed) {
+        self.output.mute_led = mute_led;
+    }
+
+    // Rumble strength of the heavy (left) and light (right) motor, 0 stops them. Like the
+    // other setters it goes out with the next lightbar update, and not at all in --cooperate
+    // mode, where the motors belong to the other program
+    pub fn set_rumble(&mut self, low: u8, high: u8) {
+        self.output.rumble = (low, high);
+    }
+
+    // Spaces out Bluetooth output reports. The DualSense report has no documented
+    // poll-interval field, so congested links are relieved by sending less often; the
+    // DualShock 4 also gets the interval in its report
+    pub fn set_bt_report_interval(&mut self, interval: Duration) {
+        self.bt_report_interval = interval;
+        self.reports.set_poll_interval(interval);
+        if !self.usb_mode && !interval.is_zero() {
+            self.min_write_interval = Some(interval);
+        }
+    }
+
+    pub fn set_retry_policy(&mut self, policy: retry::RetryPolicy) {
+        self.retry = policy;
+    }
+
+    // Shares the controller with another program: only the LED fields are
+    // written, and no more often than COOPERATE_WRITE_INTERVAL
+    pub fn set_cooperative(&mut self) {
+        self.output.leds_only = true;
+        self.min_write_interval = Some(self.min_write_interval.unwrap_or_default().max(COOPERATE_WRITE_INTERVAL));
+    }
+
+    // Forgets what was last sent, so the next update goes out even if nothing changed
+    pub fn resend(&mut self) {
+        self.last_output = None;
+    }
+
+    pub fn set_throttle(&mut self, interval: Option<Duration>) {
+        self.throttle = interval;
+    }
+
+    // Time left before the rate limit allows another output report
+    pub fn write_slot_remaining(&self) -> Duration {
+        match (self.min_write_interval.max(self.throttle), self.last_write) {
+            (Some(interval), Some(last)) => interval.saturating_sub(last.elapsed()),
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn is_usb(&self) -> bool {
+        self.usb_mode
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    // OS device path, e.g. /dev/hidraw3 on Linux
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // Serial number, the path if the pad has none
+    pub fn identity(&self) -> Option<&str> {
+        self.identity.as_deref()
+    }
+
+    // Everything that goes into the next output report
+    pub fn output(&self) -> OutputState {
+        self.output
+    }
+
+    pub fn collection(&self) -> Option<Collection> {
+        self.collection
+    }
+
+    // Why the report descriptor didn't confirm the built-in layout when the pad was
+    // last opened. The built-in offsets are used either way
+    pub fn layout(&self) -> Result<(), &str> {
+        self.layout.as_ref().copied().map_err(String::as_str)
+    }
+
+    pub fn get_feature_report(&mut self, id: u8, len: usize) -> Result<Vec<u8>, Error> {
+        let mut buf = vec![0u8; len.max(1)];
+        buf[0] = id;
+        let read = self.device.get_feature_report(&mut buf)?;
+        buf.truncate(read);
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(Direction::In, &buf);
+        }
+        Ok(buf)
+    }
+
+    pub fn send_feature_report(&mut self, data: &[u8]) -> Result<(), Error> {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(Direction::Out, data);
+        }
+        self.device.send_feature_report(data)?;
+        Ok(())
+    }
+
+    pub fn lightbar(&self) -> (u8, u8, u8) {
+        self.output.lightbar
+    }
+
+    pub fn set_lightbar(&mut self, r: u8, g: u8, b: u8) -> Result<(), Error> {
+        self.output.lightbar = (r, g, b);
+        self.stats.frame(Instant::now());
+
+        // Avoid sending the same state repeatedly (reduces flickering)
+        if self.last_output == Some(self.output) {
+            return Ok(());
+        }
+
+        // Rate limited: keep the new state pending, a later frame will send it
+        if self.write_slot_remaining() > Duration::ZERO {
+            return Ok(());
+        }
+
+        let report = self.reports.output_report(self.usb_mode, self.output_seq, &self.output);
+        self.output_seq = self.output_seq.wrapping_add(1);
+        self.write_output_report(&report)?;
+        self.last_output = Some(self.output);
+        Ok(())
+    }
+
+    // Writes an output report as it is, with the retries, tracing and stats of any other.
+    // The state set through the setters is sent again with the next lightbar update
+    pub fn write_output_report(&mut self, report: &[u8]) -> Result<(), Error> {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(Direction::Out, report);
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(self.usb_mode, report);
+        }
+
+        let write_start = Instant::now();
+        self.last_write = Some(write_start);
+
+        let mut attempt = 0;
+        let written = loop {
+            match self.device.write(report) {
+                Err(_) if attempt < self.retry.retries => {
+                    thread::sleep(self.retry.delay(attempt));
+                    attempt += 1;
+                },
+                result => break result,
+            }
+        };
+        match written {
+            Ok(_) => {
+                self.last_output = None;
+                self.stats.write_ok(write_start.elapsed());
+                self.stalled_since = None;
+                Ok(())
+            },
+            Err(e) => {
+                self.stats.write_failed(&e);
+                self.stalled_since.get_or_insert(write_start);
+                Err(Error::WriteFailed(e))
+            }
+        }
+    }
+
+    pub fn stats(&self) -> stats::Stats {
+        self.stats.snapshot()
+    }
+
+    // Mean time spent in successful writes since the previous call
+    pub fn take_write_latency(&mut self) -> Option<Duration> {
+        self.stats.take_mean_latency()
+    }
+
+    // How long the last successful write took, once: None when no report went out since
+    // the previous call (nothing changed, or the rate limit held it back)
+    pub fn take_frame_latency(&mut self) -> Option<Duration> {
+        self.stats.take_frame_latency()
+    }
+
+    // The frame rate the caller aims for, reported in the stats next to the measured one
+    pub fn set_target_fps(&mut self, fps: f32) {
+        self.stats.set_target_fps(fps);
+    }
+
+    // Last battery state seen in an input report
+    pub fn battery(&self) -> Option<BatteryStatus> {
+        self.input.battery()
+    }
+
+    // The last input report decoded, for code that reads the pad every frame instead of
+    // following events; None until poll_input has seen one
+    pub fn state(&self) -> Option<ControllerState> {
+        self.input.state()
+    }
+}
+
+// Which of the pad's HID collections to open. Windows lists every top-level collection
+// as a device of its own and only the gamepad one takes output reports; writes to the
+// others succeed without the lightbar ever changing
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HidSelection {
+    // Usage page and usage, the gamepad collection when not given
+    pub usage: Option<(u16, u16)>,
+    pub interface: Option<i32>,
+}
+
+// Generic Desktop / Game Pad
+pub const GAMEPAD_USAGE: (u16, u16) = (0x01, 0x05);
+
+// The HID interface and top-level collection a controller was opened on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Collection {
+    pub interface: i32,
+    pub usage_page: u16,
+    pub usage: u16,
+}
+
+impl Collection {
+    fn of(info: &DeviceInfo) -> Self {
+        Self { interface: info.interface_number(), usage_page: info.usage_page(), usage: info.usage() }
+    }
+}
+
+// A pad seen on the bus, without opening it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Detected {
+    pub model: Model,
+    pub usb: bool,
+    // Serial number (the Bluetooth address), None when the platform doesn't report one
+    pub serial: Option<String>,
+    pub path: String,
+}
+
+// Every supported pad the system sees, one entry per pad rather than per HID collection,
+// in the order `list` numbers them
+pub fn detect_all() -> Result<Vec<Detected>, Error> {
+    Ok(detect(&HidApi::new()?))
+}
+
+fn detect(api: &HidApi) -> Vec<Detected> {
+    let mut found: Vec<Detected> = api
+        .device_list()
+        .filter(|d| d.vendor_id() == DUALSENSE_VID && Model::from_product_id(d.product_id()).is_some())
+        // Zero where the platform doesn't report the usage
+        .filter(|d| matches!((d.usage_page(), d.usage()), GAMEPAD_USAGE | (0, 0)))
+        .map(|d| Detected {
+            model: Model::from_product_id(d.product_id()).unwrap_or_default(),
+            usb: d.interface_number() == 3,
+            serial: d.serial_number().filter(|serial| !serial.is_empty()).map(str::to_string),
+            path: d.path().to_string_lossy().into_owned(),
+        })
+        .collect();
+    found.dedup();
+    found
+}
+
+// First DualSense on the bus, or the one at `path` if it is still there
+pub fn find_dualsense<'a>(api: &'a HidApi, path: Option<&str>, selection: HidSelection) -> Result<&'a DeviceInfo, Error> {
+    let candidates = candidates(api, selection)?;
+    path.and_then(|path| candidates.iter().find(|d| d.path().to_string_lossy() == path))
+        .or(candidates.first())
+        .copied()
+        .ok_or_else(|| Error::DeviceNotFound("no DualSense or DualShock 4 found".into()))
+}
+
+// Every collection of every DualSense that `selection` allows
+fn candidates(api: &HidApi, selection: HidSelection) -> Result<Vec<&DeviceInfo>, Error> {
+    let dualsenses: Vec<_> = api
+        .device_list()
+        .filter(|d| d.vendor_id() == DUALSENSE_VID && Model::from_product_id(d.product_id()).is_some())
+        .filter(|d| selection.interface.is_none_or(|interface| d.interface_number() == interface))
+        .collect();
+    if dualsenses.is_empty() {
+        return Err(Error::DeviceNotFound("no DualSense or DualShock 4 found".into()));
+    }
+
+    // Some platforms report no usage at all (zero), so without an explicit choice
+    // every collection stays a candidate when none is the gamepad
+    let wanted = selection.usage.unwrap_or(GAMEPAD_USAGE);
+    let matching: Vec<_> = dualsenses.iter().copied().filter(|d| (d.usage_page(), d.usage()) == wanted).collect();
+    if !matching.is_empty() {
+        Ok(matching)
+    } else if selection.usage.is_some() {
+        let found: Vec<String> = dualsenses
+            .iter()
+            .map(|d| format!("{:04X}:{:04X} (interface {})", d.usage_page(), d.usage(), d.interface_number()))
+            .collect();
+        Err(Error::DeviceNotFound(format!("no DualSense collection with usage {:04X}:{:04X}, found {}", wanted.0, wanted.1, found.join(", "))))
+    } else {
+        Ok(dualsenses)
+    }
+}
+
+// What tells two pads apart across reconnects: the serial number (the Bluetooth
+// address), or the path when the platform doesn't report one
+fn identity(device_info: &DeviceInfo) -> String {
+    match device_info.serial_number() {
+        Some(serial) if !serial.is_empty() => serial.to_string(),
+        _ => device_info.path().to_string_lossy().into_owned(),
+    }
+}
+
+// Over Bluetooth the pad only sends the reduced input report until
+// the calibration feature report (0x05, 0x02 on the DualShock 4) has been read once
+fn enable_full_input_reports(device: &HidDevice, reports: &dyn Lightbar) {
+    let (id, len) = reports.calibration_report();
+    let mut calibration = vec![0u8; len];
+    calibration[0] = id;
+    let _ = device.get_feature_report(&mut calibration);
+}
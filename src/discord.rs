@@ -0,0 +1,353 @@
+// Discord bot bridge: messages like "!pad color #ff00aa" or "!pad effect breathe" in the
+// configured channels drive the lightbar, for community streams. Commands go through the
+// control layer, so anything after the prefix is the same as a `ctl` command, plus:
+//
+//   !pad color <COLOR>...      one color holds the effect on it, several make a palette
+//   !pad brightness <0-100>
+//   !pad help
+//
+// The bot polls the channels over the REST API, which needs no gateway connection but
+// does need the Message Content intent enabled for the bot in the developer portal.
+// Which commands someone may use comes from `everyone` and their roles, and accepted
+// commands are rate limited per user and per channel.
+use crossbeam_channel::Sender;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+use crate::control::{self, ControlCommand, Request, Setting};
+use crate::palette::{Palette, parse_color};
+use crate::tuning::Param;
+
+const API: &str = "https://discord.com/api/v10";
+const POLL_EVERY: Duration = Duration::from_secs(2);
+// Role changes show up after this long
+const MEMBER_CACHE: Duration = Duration::from_secs(300);
+
+// The [discord] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscordConfig {
+    pub token: String,
+    // Channel IDs to listen in
+    pub channels: Vec<String>,
+    pub prefix: String,
+    // Commands anyone may use; "*" allows all of them
+    pub everyone: Vec<String>,
+    // Extra commands per role (name or ID), e.g. { Moderator = ["*"], VIP = ["palette", "apply-profile"] }
+    pub roles: BTreeMap<String, Vec<String>>,
+    // Seconds between two commands from the same user
+    pub cooldown_secs: f32,
+    // Commands accepted per channel per minute, from everyone together
+    pub per_minute: usize,
+}
+
+impl Default for DiscordConfig {
+    fn default() -> Self {
+        Self {
+            token: String::new(),
+            channels: Vec::new(),
+            prefix: "!pad".to_string(),
+            everyone: ["color", "effect", "status", "help"].map(String::from).to_vec(),
+            roles: BTreeMap::new(),
+            cooldown_secs: 10.0,
+            per_minute: 20,
+        }
+    }
+}
+
+pub fn start(config: &DiscordConfig, requests: Sender<Request>) -> Result<(), String> {
+    if config.token.trim().is_empty() {
+        return Err("discord.token must be set".into());
+    }
+    if config.channels.is_empty() {
+        return Err("discord.channels must list at least one channel ID".into());
+    }
+
+    let config = config.clone();
+    thread::spawn(move || {
+        let api = Api::new(&config.token);
+        let mut bot = Bot { config, api, requests, channels: Vec::new(), roles: HashMap::new(), members: HashMap::new(), last_used: HashMap::new() };
+        if let Err(e) = bot.join() {
+            error!("discord: {}", e);
+            return;
+        }
+        let mut last_error = None;
+        loop {
+            for index in 0..bot.channels.len() {
+                match bot.poll(index) {
+                    Ok(()) => last_error = None,
+                    // A flaky connection shouldn't flood the terminal
+                    Err(e) if last_error.as_ref() != Some(&e) => {
+                        error!("discord: {}", e);
+                        last_error = Some(e);
+                    },
+                    Err(_) => {},
+                }
+            }
+            thread::sleep(POLL_EVERY);
+        }
+    });
+    Ok(())
+}
+
+struct Api {
+    agent: ureq::Agent,
+    authorization: String,
+}
+
+impl Api {
+    fn new(token: &str) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(10)))
+            .http_status_as_error(false)
+            .user_agent(concat!("DiscordBot (https://github.com/borgox/dualsense-rainbow, ", env!("CARGO_PKG_VERSION"), ")"))
+            .build()
+            .into();
+        Self { agent, authorization: format!("Bot {}", token.trim()) }
+    }
+
+    fn get(&self, path: &str) -> Result<Value, String> {
+        let url = format!("{}{}", API, path);
+        let text = self.send(|| self.agent.get(&url).header("Authorization", &self.authorization).call())?;
+        serde_json::from_str(&text).map_err(|e| format!("invalid JSON from {}: {}", path, e))
+    }
+
+    fn post(&self, path: &str, body: &Value) -> Result<(), String> {
+        let url = format!("{}{}", API, path);
+        let body = body.to_string();
+        self.send(|| {
+            self.agent
+                .post(&url)
+                .header("Authorization", &self.authorization)
+                .header("Content-Type", "application/json")
+                .send(&body)
+        })
+        .map(drop)
+    }
+
+    fn put(&self, path: &str) -> Result<(), String> {
+        let url = format!("{}{}", API, path);
+        self.send(|| self.agent.put(&url).header("Authorization", &self.authorization).send_empty()).map(drop)
+    }
+
+    // Waits out rate limits, Discord says how long in the 429 answer
+    fn send(&self, request: impl Fn() -> Result<ureq::http::Response<ureq::Body>, ureq::Error>) -> Result<String, String> {
+        for _ in 0..3 {
+            let mut response = request().map_err(|e| e.to_string())?;
+            let text = response.body_mut().read_to_string().map_err(|e| e.to_string())?;
+            match response.status().as_u16() {
+                200..=299 => return Ok(text),
+                429 => {
+                    let wait = serde_json::from_str::<Value>(&text).ok().and_then(|body| body["retry_after"].as_f64());
+                    thread::sleep(Duration::from_secs_f64(wait.unwrap_or(1.0).clamp(0.1, 60.0)));
+                },
+                401 => return Err("the bot token was rejected".into()),
+                status => {
+                    let message = serde_json::from_str::<Value>(&text).ok().and_then(|body| body["message"].as_str().map(String::from));
+                    return Err(format!("HTTP {}: {}", status, message.unwrap_or(text)));
+                },
+            }
+        }
+        Err("still rate limited".into())
+    }
+}
+
+struct Channel {
+    id: String,
+    guild: Option<String>,
+    // Newest message seen, commands sent before the bot started are ignored
+    last_message: u64,
+    accepted: VecDeque<Instant>,
+}
+
+struct Bot {
+    config: DiscordConfig,
+    api: Api,
+    requests: Sender<Request>,
+    channels: Vec<Channel>,
+    // Role ID to name, per guild
+    roles: HashMap<String, HashMap<String, String>>,
+    // Role IDs per user, and when they were fetched
+    members: HashMap<String, (Vec<String>, Instant)>,
+    last_used: HashMap<String, Instant>,
+}
+
+impl Bot {
+    fn join(&mut self) -> Result<(), String> {
+        for id in &self.config.channels {
+            let channel = self.api.get(&format!("/channels/{}", id)).map_err(|e| format!("channel {}: {}", id, e))?;
+            let guild = channel["guild_id"].as_str().map(String::from);
+            if let Some(guild) = &guild
+                && !self.roles.contains_key(guild)
+            {
+                let roles = self.api.get(&format!("/guilds/{}/roles", guild))?;
+                let names = roles
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|role| Some((role["id"].as_str()?.to_string(), role["name"].as_str()?.to_string())))
+                    .collect();
+                self.roles.insert(guild.clone(), names);
+            }
+
+            let latest = self.api.get(&format!("/channels/{}/messages?limit=1", id))?;
+            let last_message = latest[0]["id"].as_str().and_then(|id| id.parse().ok()).unwrap_or(0);
+            info!("discord: listening in #{}", channel["name"].as_str().unwrap_or(id));
+            self.channels.push(Channel { id: id.clone(), guild, last_message, accepted: VecDeque::new() });
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self, index: usize) -> Result<(), String> {
+        let channel = &self.channels[index];
+        let path = format!("/channels/{}/messages?after={}&limit=50", channel.id, channel.last_message);
+        let mut messages: Vec<(u64, Value)> = match self.api.get(&path)? {
+            Value::Array(messages) => messages
+                .into_iter()
+                .filter_map(|message| Some((message["id"].as_str()?.parse().ok()?, message)))
+                .collect(),
+            _ => return Err("unexpected answer to the message list".into()),
+        };
+        messages.sort_by_key(|(id, _)| *id);
+
+        for (id, message) in messages {
+            self.channels[index].last_message = id;
+            if message["author"]["bot"].as_bool() == Some(true) {
+                continue;
+            }
+            let Some(command) = message["content"].as_str().and_then(|text| self.strip_prefix(text)) else { continue };
+            let command = command.to_string();
+            self.handle(index, &message, &command)?;
+        }
+        Ok(())
+    }
+
+    // The command after the prefix, None for other messages
+    fn strip_prefix<'a>(&self, text: &'a str) -> Option<&'a str> {
+        let rest = text.trim().strip_prefix(&self.config.prefix)?;
+        match rest.is_empty() || rest.starts_with(char::is_whitespace) {
+            true => Some(rest.trim()),
+            false => None,
+        }
+    }
+
+    fn handle(&mut self, index: usize, message: &Value, text: &str) -> Result<(), String> {
+        let channel_id = self.channels[index].id.clone();
+        let message_id = message["id"].as_str().unwrap_or_default().to_string();
+        let user = message["author"]["id"].as_str().unwrap_or_default().to_string();
+        let name = permission_name(text);
+
+        if !self.allowed(index, &user, &name)? {
+            return self.reply(&channel_id, &message_id, &format!("you are not allowed to use `{}`", name));
+        }
+        if name == "help" {
+            let allowed = self.allowed_commands(index, &user)?;
+            let mut allowed: Vec<_> = allowed.into_iter().collect();
+            allowed.sort();
+            let text = format!("usage: `{} <command>`, you can use: {}", self.config.prefix, allowed.join(", "));
+            return self.reply(&channel_id, &message_id, &text);
+        }
+
+        // Rate limits, a rejected command just gets an hourglass
+        let now = Instant::now();
+        let cooldown = Duration::from_secs_f32(self.config.cooldown_secs.max(0.0));
+        let channel = &mut self.channels[index];
+        while channel.accepted.front().is_some_and(|at| now.duration_since(*at) >= Duration::from_secs(60)) {
+            channel.accepted.pop_front();
+        }
+        let busy = self.last_used.get(&user).is_some_and(|at| now.duration_since(*at) < cooldown);
+        if busy || channel.accepted.len() >= self.config.per_minute {
+            return self.react(&channel_id, &message_id, "%E2%8F%B3");
+        }
+        channel.accepted.push_back(now);
+        self.last_used.insert(user, now);
+
+        let result = parse_command(text).and_then(|command| control::submit(&self.requests, command));
+        match result {
+            Ok(Some(status)) => self.reply(&channel_id, &message_id, &format!("`{}`", status)),
+            Ok(None) => self.react(&channel_id, &message_id, "%E2%9C%85"),
+            Err(e) => self.reply(&channel_id, &message_id, &e),
+        }
+    }
+
+    fn allowed(&mut self, index: usize, user: &str, command: &str) -> Result<bool, String> {
+        let allowed = self.allowed_commands(index, user)?;
+        Ok(allowed.contains("*") || allowed.contains(command))
+    }
+
+    // Commands from `everyone` plus those of each of the user's roles
+    fn allowed_commands(&mut self, index: usize, user: &str) -> Result<HashSet<String>, String> {
+        let mut allowed: HashSet<String> = self.config.everyone.iter().map(|name| name.to_ascii_lowercase()).collect();
+        let Some(guild) = self.channels[index].guild.clone() else { return Ok(allowed) };
+        if self.config.roles.is_empty() {
+            return Ok(allowed);
+        }
+
+        let fresh = self.members.get(user).is_some_and(|(_, at)| at.elapsed() < MEMBER_CACHE);
+        if !fresh {
+            let member = self.api.get(&format!("/guilds/{}/members/{}", guild, user))?;
+            let ids = member["roles"].as_array().into_iter().flatten().filter_map(|id| id.as_str().map(String::from)).collect();
+            self.members.insert(user.to_string(), (ids, Instant::now()));
+        }
+
+        let names = &self.roles[&guild];
+        for id in &self.members[user].0 {
+            let name = names.get(id);
+            for (role, commands) in &self.config.roles {
+                if role == id || name.is_some_and(|name| name.eq_ignore_ascii_case(role)) {
+                    allowed.extend(commands.iter().map(|name| name.to_ascii_lowercase()));
+                }
+            }
+        }
+        Ok(allowed)
+    }
+
+    fn reply(&self, channel: &str, message: &str, text: &str) -> Result<(), String> {
+        let body = json!({
+            "content": text,
+            "message_reference": { "message_id": message },
+            "allowed_mentions": { "parse": [] },
+        });
+        self.api.post(&format!("/channels/{}/messages", channel), &body)
+    }
+
+    // `emoji` is URL-encoded
+    fn react(&self, channel: &str, message: &str, emoji: &str) -> Result<(), String> {
+        self.api.put(&format!("/channels/{}/messages/{}/reactions/{}/@me", channel, message, emoji))
+    }
+}
+
+// The name permissions are checked against: the first word, "colour" counts as "color"
+fn permission_name(text: &str) -> String {
+    match text.split_whitespace().next().map(str::to_ascii_lowercase) {
+        Some(word) if word == "colour" => "color".to_string(),
+        Some(word) => word,
+        None => "help".to_string(),
+    }
+}
+
+fn parse_command(text: &str) -> Result<ControlCommand, String> {
+    let mut words = text.split_whitespace();
+    match words.next().map(str::to_ascii_lowercase).as_deref() {
+        Some("color" | "colour") => {
+            let colors: Vec<_> = words.map(parse_color).collect::<Result<_, _>>()?;
+            if colors.is_empty() {
+                return Err("usage: color <COLOR>...".into());
+            }
+            Ok(ControlCommand::SetPalette(Some(Palette::new(colors))))
+        },
+        Some("brightness") => {
+            let percent = words
+                .next()
+                .and_then(|value| value.trim_end_matches('%').parse::<f32>().ok())
+                .filter(|percent| (0.0..=100.0).contains(percent))
+                .ok_or("usage: brightness <0-100>")?;
+            Ok(ControlCommand::SetParams(vec![Setting::Level(Param::Brightness, percent / 100.0)]))
+        },
+        _ => text.parse(),
+    }
+}
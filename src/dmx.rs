@@ -0,0 +1,120 @@
+// DMX input: the [dmx] table patches the lightbar into a lighting rig as a tiny RGB
+// fixture, three consecutive channels of one universe, over either protocol a desk
+// speaks on the network:
+//
+//   sACN (E1.31)  UDP 5568, unicast or the universe's multicast group 239.255.x.y
+//   Art-Net       UDP 6454, ArtDmx packets, unicast or broadcast
+//
+// Colors go through the --follow machinery, so the effect takes over again once the
+// desk stops sending.
+use crossbeam_channel::{Sender, unbounded};
+use serde::Deserialize;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::Duration;
+use tracing::{error, warn};
+
+use crate::follow::Follower;
+
+const SACN_PORT: u16 = 5568;
+const ARTNET_PORT: u16 = 6454;
+// Art-Net senders only have to repeat unchanged levels every 4 seconds
+const STALE_AFTER: Duration = Duration::from_secs(6);
+
+// The [dmx] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DmxConfig {
+    // sACN universe, or Art-Net port-address (net, sub-net and universe as one number)
+    pub universe: u16,
+    // First of the red, green and blue channels, 1-510
+    pub channel: u16,
+}
+
+impl Default for DmxConfig {
+    fn default() -> Self {
+        Self { universe: 1, channel: 1 }
+    }
+}
+
+// Listens for both protocols; one port being taken (by another DMX program, say) only
+// costs that protocol
+pub fn listen(config: &DmxConfig) -> Result<Follower, String> {
+    if !(1..=510).contains(&config.channel) {
+        return Err(format!("channel must be between 1 and 510, got {}", config.channel));
+    }
+    if config.universe > 63999 {
+        return Err(format!("universe must be between 0 and 63999, got {}", config.universe));
+    }
+
+    let (tx, colors) = unbounded();
+    let mut listening = Vec::new();
+    for (name, port) in [("sACN", SACN_PORT), ("Art-Net", ARTNET_PORT)] {
+        match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)) {
+            Ok(socket) => {
+                if port == SACN_PORT {
+                    let group = Ipv4Addr::new(239, 255, (config.universe >> 8) as u8, config.universe as u8);
+                    if let Err(e) = socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED) {
+                        warn!("dmx: cannot join {} ({}), only unicast sACN will arrive", group, e);
+                    }
+                }
+                receive(socket, config.clone(), tx.clone());
+                listening.push(port);
+            },
+            Err(e) => error!("dmx: {} port {}: {}", name, port, e),
+        }
+    }
+    if listening.is_empty() {
+        return Err("neither the sACN nor the Art-Net port could be opened".into());
+    }
+
+    let address = SocketAddr::from((Ipv4Addr::UNSPECIFIED, listening[0]));
+    Ok(Follower::from_channel(address, colors).stale_after(STALE_AFTER))
+}
+
+fn receive(socket: UdpSocket, config: DmxConfig, colors: Sender<(u8, u8, u8)>) {
+    thread::spawn(move || {
+        let mut buf = [0u8; 1024];
+        while let Ok(len) = socket.recv(&mut buf) {
+            let packet = &buf[..len];
+            let Some((universe, slots)) = parse_sacn(packet).or_else(|| parse_artnet(packet)) else {
+                continue;
+            };
+            if universe != config.universe {
+                continue;
+            }
+            // Slot 1 is the first byte of `slots`
+            let at = config.channel as usize - 1;
+            if let Some(&[r, g, b]) = slots.get(at..at + 3)
+                && colors.send((r, g, b)).is_err()
+            {
+                break;
+            }
+        }
+    });
+}
+
+// An E1.31 data packet with DMX levels: universe and slots, without the start code.
+// Preview packets are meant for visualisers and left out
+fn parse_sacn(packet: &[u8]) -> Option<(u16, &[u8])> {
+    if packet.get(4..16)? != b"ASC-E1.17\0\0\0" || packet.get(18..22)? != [0, 0, 0, 4] || packet.get(40..44)? != [0, 0, 0, 2] {
+        return None;
+    }
+    if *packet.get(125)? != 0 || packet[112] & 0x80 != 0 {
+        return None;
+    }
+    let universe = u16::from_be_bytes([packet[113], packet[114]]);
+    // The property count includes the start code
+    let count = u16::from_be_bytes([packet[123], packet[124]]) as usize;
+    Some((universe, packet.get(126..125 + count)?))
+}
+
+// An ArtDmx packet: port-address and slots
+fn parse_artnet(packet: &[u8]) -> Option<(u16, &[u8])> {
+    if packet.get(..8)? != b"Art-Net\0" || packet.get(8..10)? != [0x00, 0x50] {
+        return None;
+    }
+    let universe = u16::from_le_bytes([*packet.get(14)?, *packet.get(15)?]) & 0x7FFF;
+    let length = u16::from_be_bytes([*packet.get(16)?, *packet.get(17)?]) as usize;
+    Some((universe, packet.get(18..18 + length)?))
+}
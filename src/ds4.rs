@@ -0,0 +1,121 @@
+// Report formats of the DualShock 4. It has only the RGB lightbar (no player or mic
+// LEDs, no adaptive triggers), and its reports are laid out differently:
+//
+//   USB  output 0x05, 32 bytes: flags, rumble, RGB, flash timing
+//   BT   output 0x11, 78 bytes: the same fields two bytes later, behind a byte
+//        carrying the input poll interval, plus the same seeded CRC32 as the DualSense
+//
+// Input arrives as 0x01 over USB and as 0x11 over Bluetooth once the calibration
+// feature report (0x02) has been read; before that Bluetooth only sends a reduced 0x01.
+use crate::input::{BatteryStatus, ControllerState, Motion, TouchPoint, decode_buttons};
+use crate::report::{self, CRC_SEED_OUTPUT, OutputState};
+
+pub const USB_REPORT_ID: u8 = 0x05;
+pub const USB_REPORT_LEN: usize = 32;
+pub const BT_REPORT_ID: u8 = 0x11;
+pub const BT_REPORT_LEN: usize = 78;
+
+pub const INPUT_USB_REPORT_ID: u8 = 0x01;
+pub const INPUT_BT_REPORT_ID: u8 = 0x11;
+pub const CALIBRATION_REPORT_ID: u8 = 0x02;
+
+// Flag bits saying which fields of the output report apply
+const FLAG_RUMBLE: u8 = 0x01;
+const FLAG_LIGHTBAR: u8 = 0x02;
+const FLAG_FLASH: u8 = 0x04;
+
+// Bluetooth byte 1: HID report with CRC, the low bits the poll interval in ms
+const BT_HID_CRC: u8 = 0xC0;
+pub const MAX_POLL_INTERVAL_MS: u8 = 0x3F;
+
+// Offsets from the flags byte: the light right motor, the heavy left one, then the lightbar
+const MOTOR_RIGHT: usize = 3;
+const MOTOR_LEFT: usize = 4;
+const LIGHTBAR_RGB: usize = 5;
+
+// `poll_interval_ms` only goes into Bluetooth reports, 0 keeps the pad's default
+pub fn build_output_report(usb_mode: bool, poll_interval_ms: u8, state: &OutputState) -> Vec<u8> {
+    let (mut report, flags) = if usb_mode {
+        let mut report = vec![0; USB_REPORT_LEN];
+        report[0] = USB_REPORT_ID;
+        report[2] = 0x04;
+        (report, 1)
+    } else {
+        let mut report = vec![0; BT_REPORT_LEN];
+        report[0] = BT_REPORT_ID;
+        report[1] = BT_HID_CRC | poll_interval_ms.min(MAX_POLL_INTERVAL_MS);
+        (report, 3)
+    };
+
+    // The flash fields stay zero, which with their flag set stops flashing. Another
+    // program's rumble is left alone when only the LEDs may be touched
+    if state.leds_only {
+        report[flags] = FLAG_LIGHTBAR;
+    } else {
+        report[flags] = FLAG_RUMBLE | FLAG_LIGHTBAR | FLAG_FLASH;
+        let (low, high) = state.rumble;
+        report[flags + MOTOR_LEFT] = low;
+        report[flags + MOTOR_RIGHT] = high;
+    }
+
+    let (r, g, b) = state.lightbar;
+    report[flags + LIGHTBAR_RGB] = r;
+    report[flags + LIGHTBAR_RGB + 1] = g;
+    report[flags + LIGHTBAR_RGB + 2] = b;
+
+    if !usb_mode {
+        report::append_crc32(&mut report, CRC_SEED_OUTPUT);
+    }
+    report
+}
+
+// Parses a USB (0x01, 64 bytes), full Bluetooth (0x11) or reduced Bluetooth (0x01, 10 bytes) input report
+pub fn parse_input_report(data: &[u8]) -> Option<ControllerState> {
+    match (data.first()?, data.len()) {
+        (&INPUT_USB_REPORT_ID, len) if len >= 64 => Some(parse_full(&data[1..])),
+        (&INPUT_BT_REPORT_ID, len) if len >= 78 => Some(parse_full(&data[3..])),
+        (&INPUT_USB_REPORT_ID, len) if len >= 10 => Some(parse_basic(&data[1..])),
+        _ => None,
+    }
+}
+
+// Sticks, buttons and triggers lead every report. The top six bits of the third
+// button byte are a frame counter, not buttons
+fn parse_basic(d: &[u8]) -> ControllerState {
+    ControllerState {
+        buttons: decode_buttons(d[4], d[5], d[6] & 0x03),
+        left_stick: (d[0], d[1]),
+        right_stick: (d[2], d[3]),
+        l2: d[7],
+        r2: d[8],
+        ..Default::default()
+    }
+}
+
+fn parse_full(d: &[u8]) -> ControllerState {
+    let mut state = parse_basic(d);
+    // After a 16-bit sensor timestamp and a temperature byte
+    state.motion = Some(Motion::parse(&d[12..24]));
+
+    // Same 4-byte touch points as the DualSense, after a packet count and counter byte
+    for (finger, slot) in state.touch.iter_mut().enumerate() {
+        let p = &d[34 + finger * 4..38 + finger * 4];
+        if p[0] & 0x80 == 0 {
+            *slot = Some(TouchPoint {
+                id: p[0] & 0x7F,
+                x: p[1] as u16 | ((p[2] as u16 & 0x0F) << 8),
+                y: (p[2] as u16 >> 4) | ((p[3] as u16) << 4),
+            });
+        }
+    }
+
+    // Low nibble is the level in tenths (up to 11 on the cable, meaning full), bit 4 the cable
+    let status = d[29];
+    let cable = status & 0x10 != 0;
+    let level = status & 0x0F;
+    state.battery = Some(BatteryStatus {
+        level: (level.min(10) * 10).min(100),
+        charging: cable && level < 11,
+    });
+    state
+}
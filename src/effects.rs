@@ -0,0 +1,833 @@
+// LED animations driven by the main loop one frame at a time
+use std::f32::consts::TAU;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::animation::Animation;
+use crate::canvas::{Canvas, LedLayout};
+use dualsense_rainbow::{ColorSpace, hsv_to_rgb};
+use dualsense_rainbow::input::{Button, ControllerState, Motion, Stick, TOUCHPAD_SIZE};
+use crate::lava::{LavaSettings, Noise};
+use crate::motion::MotionSettings;
+use crate::palette::Palette;
+#[cfg(feature = "plugins")]
+use crate::plugin::{Plugged, Plugin};
+use crate::reactive::ReactiveMapping;
+#[cfg(feature = "scripting")]
+use crate::script::{Script, Scripted};
+use crate::sysmon::SystemMonitor;
+
+pub trait Effect {
+    // Paints one frame. Most effects only set the lightbar and leave the other LEDs alone
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas);
+
+    // Hue degrees per second, changed live without restarting the animation
+    fn set_speed(&mut self, speed: f32);
+
+    // The pad's latest input, handed over before every frame. Most effects ignore it
+    fn input(&mut self, _state: &ControllerState) {}
+}
+
+// What some effects need beyond speed and palette, read from the config once
+pub struct EffectSetup {
+    pub reactive: ReactiveMapping,
+    pub motion: MotionSettings,
+    pub system: SystemMonitor,
+    pub lava: LavaSettings,
+    pub animation: Option<Animation>,
+    #[cfg(feature = "scripting")]
+    pub script: Option<Script>,
+    #[cfg(feature = "plugins")]
+    pub plugin: Option<Plugin>,
+}
+
+impl EffectSetup {
+    // An animation, a script or a plugin plays instead of whatever effect a profile names
+    pub fn plays_file(&self) -> bool {
+        #[cfg(feature = "scripting")]
+        if self.script.is_some() {
+            return true;
+        }
+        #[cfg(feature = "plugins")]
+        if self.plugin.is_some() {
+            return true;
+        }
+        self.animation.is_some()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EffectKind {
+    #[default]
+    Rainbow,
+    Breathe,
+    Cycle,
+    Wave,
+    Solid,
+    Strobe,
+    Wipe,
+    Police,
+    Reactive,
+    Picker,
+    Motion,
+    System,
+    Lava,
+    // Only from --animation, which loads the file it plays, so not in ALL
+    Animation,
+    // Only from --script and --plugin, the same way
+    #[cfg(feature = "scripting")]
+    Script,
+    #[cfg(feature = "plugins")]
+    Plugin,
+}
+
+impl EffectKind {
+    pub const ALL: [EffectKind; 13] = [
+        EffectKind::Rainbow,
+        EffectKind::Breathe,
+        EffectKind::Cycle,
+        EffectKind::Wave,
+        EffectKind::Solid,
+        EffectKind::Strobe,
+        EffectKind::Wipe,
+        EffectKind::Police,
+        EffectKind::Reactive,
+        EffectKind::Picker,
+        EffectKind::Motion,
+        EffectKind::System,
+        EffectKind::Lava,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EffectKind::Rainbow => "rainbow",
+            EffectKind::Breathe => "breathe",
+            EffectKind::Cycle => "cycle",
+            EffectKind::Wave => "wave",
+            EffectKind::Solid => "solid",
+            EffectKind::Strobe => "strobe",
+            EffectKind::Wipe => "wipe",
+            EffectKind::Police => "police",
+            EffectKind::Reactive => "reactive",
+            EffectKind::Picker => "picker",
+            EffectKind::Motion => "motion",
+            EffectKind::System => "system",
+            EffectKind::Lava => "lava",
+            EffectKind::Animation => "animation",
+            #[cfg(feature = "scripting")]
+            EffectKind::Script => "script",
+            #[cfg(feature = "plugins")]
+            EffectKind::Plugin => "plugin",
+        }
+    }
+
+    // Strobe and police flash hard, which nobody should get unasked from --shuffle
+    pub fn flashing(self) -> bool {
+        matches!(self, EffectKind::Strobe | EffectKind::Police)
+    }
+
+    // These sit still until someone picks up the pad, or show the machine's state rather
+    // than an animation, so --shuffle leaves them out too
+    pub fn interactive(self) -> bool {
+        matches!(self, EffectKind::Reactive | EffectKind::Picker | EffectKind::Motion | EffectKind::System)
+    }
+
+    // `speed` is in hue degrees per second; a palette replaces the HSV wheel
+    pub fn build(self, speed: f32, palette: Option<Palette>, setup: &EffectSetup) -> Box<dyn Effect> {
+        let colors = ColorWheel { hue: 0.0, palette };
+        match self {
+            EffectKind::Rainbow => Box::new(Rainbow { colors, speed }),
+            EffectKind::Breathe => Box::new(Breathe { colors, speed, phase: 0.0 }),
+            EffectKind::Cycle => Box::new(Cycle { colors, speed, position: 0.0 }),
+            EffectKind::Wave => Box::new(Wave { colors, speed }),
+            EffectKind::Solid => Box::new(Solid { colors }),
+            EffectKind::Strobe => Box::new(Strobe { colors, speed, phase: 0.0 }),
+            EffectKind::Wipe => Box::new(Wipe { colors, speed, position: 0.0 }),
+            EffectKind::Police => Box::new(Police { phase: 0.0 }),
+            EffectKind::Reactive => Box::new(Reactive {
+                colors,
+                speed,
+                mapping: setup.reactive.clone(),
+                flash: 0.0,
+                held: false,
+                steering: false,
+                pull: 0,
+            }),
+            EffectKind::Picker => Box::new(Picker { colors, level: 1.0, locked: false, clicked: false }),
+            EffectKind::Motion => Box::new(Tilt {
+                colors,
+                speed,
+                settings: setup.motion.clone(),
+                reading: None,
+                roll: 0.0,
+                sparkle: 0.0,
+            }),
+            EffectKind::System => Box::new(Load { monitor: setup.system.clone(), color: None }),
+            EffectKind::Lava => Box::new(Lava {
+                colors,
+                speed,
+                settings: setup.lava.clone(),
+                noise: Noise::random(),
+                position: 0.0,
+            }),
+            EffectKind::Animation => match &setup.animation {
+                Some(animation) => Box::new(Keyframes { animation: animation.clone(), elapsed: Duration::ZERO }),
+                None => Box::new(Rainbow { colors, speed }),
+            },
+            #[cfg(feature = "scripting")]
+            EffectKind::Script => match &setup.script {
+                Some(script) => Box::new(Scripted::new(script.clone())),
+                None => Box::new(Rainbow { colors, speed }),
+            },
+            #[cfg(feature = "plugins")]
+            EffectKind::Plugin => match &setup.plugin {
+                Some(plugin) => Box::new(Plugged::new(plugin, speed)),
+                None => Box::new(Rainbow { colors, speed }),
+            },
+        }
+    }
+}
+
+impl FromStr for EffectKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EffectKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                let names: Vec<_> = EffectKind::ALL.iter().map(|kind| kind.name()).collect();
+                format!("unknown effect '{}' (expected one of: {})", s, names.join(", "))
+            })
+    }
+}
+
+// Where the colors come from: the full hue wheel or a palette walked in step with it
+struct ColorWheel {
+    hue: f32,
+    palette: Option<Palette>,
+}
+
+impl ColorWheel {
+    fn advance(&mut self, degrees: f32) {
+        self.hue = (self.hue + degrees).rem_euclid(360.0);
+    }
+
+    fn at(&self, hue: f32) -> (u8, u8, u8) {
+        match &self.palette {
+            Some(palette) => palette.sample(hue / 360.0),
+            None => hsv_to_rgb(hue.rem_euclid(360.0), 1.0, 1.0),
+        }
+    }
+
+    fn color(&self) -> (u8, u8, u8) {
+        self.at(self.hue)
+    }
+}
+
+// The classic smooth walk around the color wheel
+struct Rainbow {
+    colors: ColorWheel,
+    speed: f32,
+}
+
+impl Effect for Rainbow {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        canvas.lightbar = self.colors.color();
+        self.colors.advance(self.speed * dt.as_secs_f32());
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+}
+
+const BREATHE_PERIOD: Duration = Duration::from_secs(4);
+// Never fully dark, so the breath reads as dimming rather than blinking
+const BREATHE_FLOOR: f32 = 0.05;
+
+// Slow fade in and out while the hue drifts at a quarter of the speed
+struct Breathe {
+    colors: ColorWheel,
+    speed: f32,
+    phase: f32, // 0..1 through one breath
+}
+
+impl Effect for Breathe {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        let level = BREATHE_FLOOR + (1.0 - BREATHE_FLOOR) * (0.5 - 0.5 * (self.phase * TAU).cos());
+        let (r, g, b) = self.colors.color();
+
+        self.phase = (self.phase + dt.as_secs_f32() / BREATHE_PERIOD.as_secs_f32()).fract();
+        self.colors.advance(self.speed / 4.0 * dt.as_secs_f32());
+
+        let scale = |c: u8| (c as f32 * level) as u8;
+        canvas.lightbar = (scale(r), scale(g), scale(b));
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+}
+
+// Six steps around the wheel (or one per palette color), holding each one
+const CYCLE_STEPS: f32 = 6.0;
+// Share of each step spent blending into the next color
+const CYCLE_BLEND: f32 = 0.15;
+
+struct Cycle {
+    colors: ColorWheel,
+    speed: f32,
+    position: f32, // In steps
+}
+
+impl Effect for Cycle {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        let steps = match &self.colors.palette {
+            Some(palette) => palette.len() as f32,
+            None => CYCLE_STEPS,
+        };
+        let degrees_per_step = 360.0 / steps;
+
+        // Hold the color, then ease into the next one at the end of the step
+        let step = self.position.floor();
+        let into_step = self.position - step;
+        let blend = ((into_step - (1.0 - CYCLE_BLEND)) / CYCLE_BLEND).clamp(0.0, 1.0);
+        // Palette colors needn't be evenly spaced, each step holds one of them
+        let hue = |step: f32| match &self.colors.palette {
+            Some(palette) => palette.position(step as usize) * 360.0,
+            None => step * degrees_per_step,
+        };
+        canvas.lightbar = mix(self.colors.at(hue(step)), self.colors.at(hue(step + 1.0)), blend);
+
+        self.position = (self.position + self.speed * dt.as_secs_f32() / degrees_per_step).rem_euclid(steps);
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+}
+
+// The rainbow rolling across every LED: the white LEDs and then the mic LED form a row
+// that a crest of light travels along once per trip around the wheel, while the
+// lightbar shows the hue at the row's start
+struct Wave {
+    colors: ColorWheel,
+    speed: f32,
+}
+
+impl Effect for Wave {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        canvas.lightbar = self.colors.color();
+
+        let pixels = canvas.player_count() + 1;
+        let crest = self.colors.hue / 360.0;
+        let level = |i: usize| 0.5 + 0.5 * ((crest - i as f32 / pixels as f32) * TAU).cos();
+        for i in 0..canvas.player_count() {
+            canvas.set_player(i, level(i));
+        }
+        canvas.set_mic(level(pixels - 1));
+
+        self.colors.advance(self.speed * dt.as_secs_f32());
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+}
+
+// One steady color: the first palette color, or red without a palette
+struct Solid {
+    colors: ColorWheel,
+}
+
+impl Effect for Solid {
+    fn render(&mut self, _dt: Duration, canvas: &mut Canvas) {
+        canvas.lightbar = self.colors.color();
+    }
+
+    fn set_speed(&mut self, _speed: f32) {}
+}
+
+const STROBE_HZ: f32 = 8.0;
+// Share of each flash period the light is on
+const STROBE_DUTY: f32 = 0.3;
+
+// Short hard flashes, with the hue moving on between them
+struct Strobe {
+    colors: ColorWheel,
+    speed: f32,
+    phase: f32, // 0..1 through one flash
+}
+
+impl Effect for Strobe {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        canvas.lightbar = if self.phase < STROBE_DUTY { self.colors.color() } else { (0, 0, 0) };
+
+        self.phase = (self.phase + dt.as_secs_f32() * STROBE_HZ).fract();
+        self.colors.advance(self.speed * dt.as_secs_f32());
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+}
+
+// Each color in turn sweeps in over the previous one: the player LEDs fill up left to
+// right as the lightbar takes on the next color, then clear for the one after
+struct Wipe {
+    colors: ColorWheel,
+    speed: f32,
+    position: f32, // In steps, the same ones Cycle uses
+}
+
+impl Effect for Wipe {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        let steps = match &self.colors.palette {
+            Some(palette) => palette.len() as f32,
+            None => CYCLE_STEPS,
+        };
+        let degrees_per_step = 360.0 / steps;
+
+        let step = self.position.floor();
+        let progress = self.position - step;
+        canvas.lightbar = blend_rgb(
+            self.colors.at(step * degrees_per_step),
+            self.colors.at((step + 1.0) * degrees_per_step),
+            progress,
+        );
+        let leds = canvas.player_count();
+        let filled = (progress * (leds + 1) as f32) as usize;
+        for i in 0..leds {
+            canvas.set_player(i, if i < filled { 1.0 } else { 0.0 });
+        }
+
+        self.position = (self.position + self.speed * dt.as_secs_f32() / degrees_per_step).rem_euclid(steps);
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+}
+
+const POLICE_PERIOD: Duration = Duration::from_millis(800);
+// Within one period: red double flash, then blue double flash, each flash
+// an eighth of the period followed by an equal gap
+const POLICE_RED: (u8, u8, u8) = (255, 0, 0);
+const POLICE_BLUE: (u8, u8, u8) = (0, 0, 255);
+
+// Emergency lights: red on the left, blue on the right. Keeps its own pace, and
+// ignores the palette
+struct Police {
+    phase: f32, // 0..1 through one period
+}
+
+impl Effect for Police {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        let slot = (self.phase * 8.0) as usize;
+        let red = slot < 4;
+        let lit = slot.is_multiple_of(2);
+        canvas.lightbar = match (lit, red) {
+            (false, _) => (0, 0, 0),
+            (true, true) => POLICE_RED,
+            (true, false) => POLICE_BLUE,
+        };
+        // The LEDs on the side of the color that is flashing
+        let leds = canvas.player_count();
+        for i in 0..leds {
+            let side = if red { i < leds / 2 } else { i >= leds - leds / 2 };
+            canvas.set_player(i, if lit && side { 1.0 } else { 0.0 });
+        }
+
+        self.phase = (self.phase + dt.as_secs_f32() / POLICE_PERIOD.as_secs_f32()).fract();
+    }
+
+    fn set_speed(&mut self, _speed: f32) {}
+}
+
+// How far a stick has to be pushed (0.0-1.0 of its travel) before it takes over the hue
+const STICK_DEADZONE: f32 = 0.4;
+
+// Follows the pad: the rainbow walks on by itself until the mapped stick is pushed, then
+// points at the hue in that direction (up is red, going clockwise) and walks on from
+// there once it is let go. The mapped trigger's pull brightens it from the idle level,
+// and each press of the flash button flashes it, fading back over the flash time
+struct Reactive {
+    colors: ColorWheel,
+    speed: f32,
+    mapping: ReactiveMapping,
+    flash: f32, // 1.0 right after a press, down to 0.0
+    held: bool,
+    steering: bool,
+    pull: u8,
+}
+
+impl Effect for Reactive {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        let base = self.colors.color();
+        let idle = self.mapping.idle_brightness;
+        let level = match self.mapping.brightness_trigger {
+            Some(_) => idle + (1.0 - idle) * self.pull as f32 / 255.0,
+            None => 1.0,
+        };
+        let scale = |c: u8| (c as f32 * level).round() as u8;
+        let color = (scale(base.0), scale(base.1), scale(base.2));
+        canvas.lightbar = blend_rgb(color, self.mapping.flash_color, self.flash);
+
+        if !self.steering {
+            self.colors.advance(self.speed * dt.as_secs_f32());
+        }
+        self.flash = match self.mapping.flash.is_zero() {
+            true => 0.0,
+            false => (self.flash - dt.as_secs_f32() / self.mapping.flash.as_secs_f32()).max(0.0),
+        };
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    fn input(&mut self, state: &ControllerState) {
+        if let Some(button) = self.mapping.flash_button {
+            let down = state.is_pressed(button);
+            if down && !self.held {
+                self.flash = 1.0;
+            }
+            self.held = down;
+        }
+
+        self.steering = false;
+        if let Some(stick) = self.mapping.hue_stick {
+            let (x, y) = match stick {
+                Stick::Left => state.left_stick,
+                Stick::Right => state.right_stick,
+            };
+            let (x, y) = ((x as f32 - 128.0) / 127.0, (128.0 - y as f32) / 127.0);
+            if x.hypot(y) >= STICK_DEADZONE {
+                self.colors.hue = x.atan2(y).to_degrees().rem_euclid(360.0);
+                self.steering = true;
+            }
+        }
+
+        if let Some(trigger) = self.mapping.brightness_trigger {
+            self.pull = if trigger == Button::L2 { state.l2 } else { state.r2 };
+        }
+    }
+}
+
+// A color chosen on the touchpad: sliding a finger across it picks the hue (or the
+// palette position) from left to right and the brightness from bottom to top, as it
+// moves. Clicking the pad locks the color so touching it again changes nothing, and
+// lights the player LEDs to show it; the next click unlocks it. Ignores speed
+struct Picker {
+    colors: ColorWheel,
+    level: f32,
+    locked: bool,
+    clicked: bool,
+}
+
+impl Effect for Picker {
+    fn render(&mut self, _dt: Duration, canvas: &mut Canvas) {
+        let (r, g, b) = self.colors.color();
+        let scale = |c: u8| (c as f32 * self.level).round() as u8;
+        canvas.lightbar = (scale(r), scale(g), scale(b));
+        if self.locked {
+            for i in 0..canvas.player_count() {
+                canvas.set_player(i, 1.0);
+            }
+        }
+    }
+
+    fn set_speed(&mut self, _speed: f32) {}
+
+    fn input(&mut self, state: &ControllerState) {
+        let clicked = state.is_pressed(Button::Touchpad);
+        if clicked && !self.clicked {
+            self.locked = !self.locked;
+        }
+        self.clicked = clicked;
+        if self.locked {
+            return;
+        }
+
+        if let Some(point) = state.touch.iter().flatten().next() {
+            let (width, height) = TOUCHPAD_SIZE;
+            let x = (point.x as f32 / (width - 1) as f32).clamp(0.0, 1.0);
+            let y = (point.y as f32 / (height - 1) as f32).clamp(0.0, 1.0);
+            self.colors.hue = x * 360.0;
+            self.level = 1.0 - y;
+        }
+    }
+}
+
+// Accelerometer units per g, see input::Motion
+const ACCEL_PER_G: f32 = 8192.0;
+// How far off 1 g a reading has to be to count as a shake, at sensitivity 1.0
+const SHAKE_G: f32 = 0.8;
+const SPARKLE_TIME: Duration = Duration::from_millis(600);
+
+// The rainbow, moved by the pad's motion sensors: rolling it to the side shifts the hue
+// (a quarter turn either way goes half round the wheel) and shaking it sets off a burst
+// of white sparkles on the lightbar and the player LEDs
+struct Tilt {
+    colors: ColorWheel,
+    speed: f32,
+    settings: MotionSettings,
+    reading: Option<Motion>,
+    roll: f32, // Smoothed, in degrees
+    sparkle: f32, // 1.0 right after a shake, down to 0.0
+}
+
+impl Effect for Tilt {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        if let Some(reading) = self.reading {
+            let [x, y, z] = reading.accel.map(|axis| axis as f32 / ACCEL_PER_G);
+            if ((x * x + y * y + z * z).sqrt() - 1.0).abs() >= SHAKE_G / self.settings.sensitivity {
+                self.sparkle = 1.0;
+            }
+
+            let roll = x.atan2(y).to_degrees();
+            let smoothing = self.settings.smoothing.as_secs_f32();
+            let alpha = if smoothing > 0.0 { 1.0 - (-dt.as_secs_f32() / smoothing).exp() } else { 1.0 };
+            self.roll += (roll - self.roll) * alpha;
+        }
+
+        let color = self.colors.at(self.colors.hue + self.roll * 2.0 * self.settings.sensitivity);
+        canvas.lightbar = color;
+        if self.sparkle > 0.0 {
+            let flicker = self.sparkle * rand::random::<f32>();
+            canvas.lightbar = blend_rgb(color, (255, 255, 255), flicker);
+            for i in 0..canvas.player_count() {
+                canvas.set_player(i, if rand::random::<f32>() < self.sparkle { 1.0 } else { 0.0 });
+            }
+        }
+
+        self.colors.advance(self.speed * dt.as_secs_f32());
+        self.sparkle = (self.sparkle - dt.as_secs_f32() / SPARKLE_TIME.as_secs_f32()).max(0.0);
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    fn input(&mut self, state: &ControllerState) {
+        if state.motion.is_some() {
+            self.reading = state.motion;
+        }
+    }
+}
+
+// Noise units per hue degree of speed: at the default speed a new blob comes every few seconds
+const LAVA_RATE: f32 = 1.0 / 360.0;
+// The brightness follows its own stretch of the noise, this far away from the color's
+const LAVA_GLOW_OFFSET: f32 = 97.3;
+
+// Colors drifting on Perlin noise: a slow walk around the wheel (or palette) at a
+// sixteenth of the speed, with the noise pushing the color back and forth around it
+// and swelling the brightness
+struct Lava {
+    colors: ColorWheel,
+    speed: f32,
+    settings: LavaSettings,
+    noise: Noise,
+    position: f32, // In noise units
+}
+
+impl Effect for Lava {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        let wander = self.noise.at(self.position) * self.settings.spread * 180.0;
+        let (r, g, b) = self.colors.at(self.colors.hue + wander);
+        let swell = 0.5 + 0.5 * self.noise.at(self.position + LAVA_GLOW_OFFSET).clamp(-1.0, 1.0);
+        let level = 1.0 - self.settings.glow * (1.0 - swell);
+        let scale = |c: u8| (c as f32 * level) as u8;
+        canvas.lightbar = (scale(r), scale(g), scale(b));
+
+        self.position += self.speed * LAVA_RATE * dt.as_secs_f32() / self.settings.scale;
+        self.colors.advance(self.speed / 16.0 * dt.as_secs_f32());
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+}
+
+// Until the first reading arrives
+const LOAD_WAITING: (u8, u8, u8) = (24, 24, 24);
+// Time constant of the ease towards a new reading's color
+const LOAD_EASE: Duration = Duration::from_millis(800);
+
+// CPU or GPU load or temperature from the [system] table, as a color. Readings come once
+// a second or so; the lightbar eases between them instead of stepping
+struct Load {
+    monitor: SystemMonitor,
+    color: Option<(f32, f32, f32)>,
+}
+
+impl Effect for Load {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        let Some((r, g, b)) = self.monitor.color() else {
+            canvas.lightbar = LOAD_WAITING;
+            return;
+        };
+        let target = (r as f32, g as f32, b as f32);
+        let color = match self.color {
+            Some(color) => {
+                let alpha = 1.0 - (-dt.as_secs_f32() / LOAD_EASE.as_secs_f32()).exp();
+                let ease = |from: f32, to: f32| from + (to - from) * alpha;
+                (ease(color.0, target.0), ease(color.1, target.1), ease(color.2, target.2))
+            },
+            None => target,
+        };
+        self.color = Some(color);
+        canvas.lightbar = (color.0.round() as u8, color.1.round() as u8, color.2.round() as u8);
+    }
+
+    fn set_speed(&mut self, _speed: f32) {}
+}
+
+// A keyframe animation from a file, played from its start whenever it is switched to
+struct Keyframes {
+    animation: Animation,
+    elapsed: Duration,
+}
+
+impl Effect for Keyframes {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        canvas.lightbar = self.animation.color_at(self.elapsed);
+        self.elapsed += dt;
+    }
+
+    fn set_speed(&mut self, _speed: f32) {}
+}
+
+// How crossfades, palettes and animations blend colors, set once from --color-space
+static COLOR_SPACE: OnceLock<ColorSpace> = OnceLock::new();
+
+pub fn set_color_space(space: ColorSpace) {
+    let _ = COLOR_SPACE.set(space);
+}
+
+// A blend in the chosen color space, where blend_rgb always mixes the RGB values
+pub fn mix(from: (u8, u8, u8), to: (u8, u8, u8), t: f32) -> (u8, u8, u8) {
+    COLOR_SPACE.get().copied().unwrap_or_default().mix(from, to, t)
+}
+
+pub fn blend_rgb(from: (u8, u8, u8), to: (u8, u8, u8), t: f32) -> (u8, u8, u8) {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+// Scales HSV saturation: 1.0 leaves the color alone, 0.0 turns it into white at the same value
+pub fn saturate((r, g, b): (u8, u8, u8), saturation: f32) -> (u8, u8, u8) {
+    let max = r.max(g).max(b) as f32;
+    let scale = |c: u8| (max - (max - c as f32) * saturation).round() as u8;
+    (scale(r), scale(g), scale(b))
+}
+
+// Runs the current effect and crossfades from the previous one after a switch
+pub struct EffectPlayer {
+    current: Box<dyn Effect>,
+    outgoing: Option<Box<dyn Effect>>,
+    fade: Duration,
+    faded: Duration,
+    layout: LedLayout,
+}
+
+impl EffectPlayer {
+    pub fn new(effect: Box<dyn Effect>, layout: LedLayout) -> Self {
+        Self { current: effect, outgoing: None, fade: Duration::ZERO, faded: Duration::ZERO, layout }
+    }
+
+    // Swaps in a new effect, blending over `fade` (zero cuts straight over).
+    // Switching mid-fade drops the oldest effect and fades on from the one that was coming in
+    pub fn switch(&mut self, effect: Box<dyn Effect>, fade: Duration) {
+        let previous = std::mem::replace(&mut self.current, effect);
+        self.outgoing = (!fade.is_zero()).then_some(previous);
+        self.fade = fade;
+        self.faded = Duration::ZERO;
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.current.set_speed(speed);
+        if let Some(outgoing) = &mut self.outgoing {
+            outgoing.set_speed(speed);
+        }
+    }
+
+    pub fn input(&mut self, state: &ControllerState) {
+        self.current.input(state);
+        if let Some(outgoing) = &mut self.outgoing {
+            outgoing.input(state);
+        }
+    }
+
+    pub fn render(&mut self, dt: Duration) -> Canvas {
+        let mut incoming = Canvas::new(self.layout);
+        self.current.render(dt, &mut incoming);
+        let Some(outgoing) = &mut self.outgoing else {
+            return incoming;
+        };
+
+        let mut from = Canvas::new(self.layout);
+        outgoing.render(dt, &mut from);
+        self.faded += dt;
+        let t = (self.faded.as_secs_f32() / self.fade.as_secs_f32()).min(1.0);
+        if t >= 1.0 {
+            self.outgoing = None;
+        }
+        from.blend(&incoming, t)
+    }
+}
+
+// What decided the lightbar color in a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSource {
+    Effect,
+    Screen,
+    JsonWatch,
+    Follower,
+    Meter,
+    #[cfg(target_os = "linux")]
+    LedBridge,
+    // Switched off or idle
+    Dark,
+}
+
+// Eases the final lightbar color when something else takes it over, e.g. screen sync
+// starting or a JSON watch letting go, the way EffectPlayer eases effect switches
+pub struct ColorTransition {
+    fade: Duration,
+    source: ColorSource,
+    from: Option<(u8, u8, u8)>,
+    faded: Duration,
+    last: (u8, u8, u8),
+}
+
+impl ColorTransition {
+    pub fn new(fade: Duration) -> Self {
+        Self { fade, source: ColorSource::Effect, from: None, faded: Duration::ZERO, last: (0, 0, 0) }
+    }
+
+    pub fn apply(&mut self, source: ColorSource, color: (u8, u8, u8), dt: Duration) -> (u8, u8, u8) {
+        if source != self.source {
+            self.source = source;
+            self.from = (!self.fade.is_zero()).then_some(self.last);
+            self.faded = Duration::ZERO;
+        }
+
+        let color = match self.from {
+            Some(from) => {
+                self.faded += dt;
+                let t = (self.faded.as_secs_f32() / self.fade.as_secs_f32()).min(1.0);
+                if t >= 1.0 {
+                    self.from = None;
+                }
+                mix(from, color, t)
+            },
+            None => color,
+        };
+        self.last = color;
+        color
+    }
+}
@@ -0,0 +1,70 @@
+// What can go wrong opening and driving the controller. Each kind exits the program with
+// its own code, so scripts can tell a missing pad from a missing udev rule:
+//
+//   1  anything else
+//   2  bad command line (clap's own code)
+//   3  config file missing a piece or not parsing
+//   4  no controller found, or none matching --device or --hid-usage
+//   5  no permission to open the controller
+//   6  writing to the controller failed
+//   7  the connection can't do what was asked, e.g. haptics over Bluetooth
+//
+// A plain message doesn't convert on its own: whoever reports one picks the kind, so a
+// bad config value can't end up exiting with 1
+use hidapi::HidError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("{0}")]
+    Config(String),
+    #[error("{0}")]
+    DeviceNotFound(String),
+    // hidraw nodes belong to root, and only a udev rule hands them to the user
+    #[cfg_attr(
+        target_os = "linux",
+        error("no permission to open {path} ({source}). The controller's hidraw node is only readable by root until a udev rule grants access: run `dualsense-rainbow setup-udev` to install one")
+    )]
+    #[cfg_attr(not(target_os = "linux"), error("no permission to open {path} ({source})"))]
+    PermissionDenied { path: String, source: HidError },
+    #[error("write failed: {0}")]
+    WriteFailed(HidError),
+    #[error("{0}")]
+    UnsupportedTransport(String),
+    #[error(transparent)]
+    Hid(#[from] HidError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Config(_) => 3,
+            Error::DeviceNotFound(_) => 4,
+            Error::PermissionDenied { .. } => 5,
+            Error::WriteFailed(_) => 6,
+            Error::UnsupportedTransport(_) => 7,
+            Error::Hid(_) | Error::Io(_) | Error::Other(_) => 1,
+        }
+    }
+
+    // Opening `path` failed: the OS refusing is told apart from everything else
+    pub(crate) fn open_failed(path: String, source: HidError) -> Self {
+        // hidapi only passes on a message, so the node is tried again for the errno (EACCES)
+        #[cfg(target_os = "linux")]
+        if let Err(e) = std::fs::OpenOptions::new().read(true).write(true).open(&path)
+            && e.kind() == std::io::ErrorKind::PermissionDenied
+        {
+            return Error::PermissionDenied { path, source };
+        }
+        let message = source.to_string().to_ascii_lowercase();
+        // hidraw says "Permission denied", Windows "Access is denied"
+        match message.contains("permission denied") || message.contains("access is denied") {
+            true => Error::PermissionDenied { path, source },
+            false => Error::Hid(source),
+        }
+    }
+}
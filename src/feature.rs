@@ -0,0 +1,68 @@
+// `feature get` / `feature set`: raw feature report access for debugging and
+// poking at undocumented controller features
+use crate::cli::FeatureAction;
+use dualsense_rainbow::report::{self, CRC_SEED_FEATURE_GET, CRC_SEED_FEATURE_SET};
+use dualsense_rainbow::trace::hexdump;
+use dualsense_rainbow::{DualSenseController, Error, colors};
+
+// Largest feature report the DualSense is known to return
+const DEFAULT_FEATURE_LEN: usize = 64;
+
+// Names of the feature reports documented by the Linux driver and community notes
+fn feature_name(id: u8) -> Option<&'static str> {
+    match id {
+        0x05 => Some("IMU calibration"),
+        0x09 => Some("Pairing info (MAC address)"),
+        0x0A => Some("Set BT pairing"),
+        0x20 => Some("Firmware info"),
+        0x22 => Some("Hardware info"),
+        _ => None,
+    }
+}
+
+pub fn run(controller: &mut DualSenseController, action: FeatureAction) -> Result<(), Error> {
+    match action {
+        FeatureAction::Get { id, len } => {
+            let data = controller.get_feature_report(id, len.unwrap_or(DEFAULT_FEATURE_LEN))?;
+            print_report("Received", id, &data);
+
+            // Over Bluetooth the last 4 bytes are a CRC32 of everything before them
+            if !controller.is_usb() && data.len() > 4 {
+                let body = data.len() - 4;
+                let expected = report::calculate_crc32(CRC_SEED_FEATURE_GET, &data[..body]);
+                let actual = u32::from_le_bytes(data[body..].try_into().map_err(|e| Error::Other(format!("CRC32: {}", e)))?);
+                if expected == actual {
+                    println!("  {}CRC32:{} 0x{:08X} {}✓ valid{}", colors::GRAY, colors::RESET, actual, colors::GREEN, colors::RESET);
+                } else {
+                    println!("  {}CRC32:{} 0x{:08X} {}✗ expected 0x{:08X}{}",
+                             colors::GRAY, colors::RESET, actual, colors::RED, expected, colors::RESET);
+                }
+            }
+        },
+        FeatureAction::Set { id, bytes } => {
+            let payload = bytes.into_iter().flatten().collect::<Vec<u8>>();
+            let mut data = Vec::with_capacity(payload.len() + 5);
+            data.push(id);
+            data.extend_from_slice(&payload);
+
+            if !controller.is_usb() {
+                data.extend_from_slice(&[0; 4]);
+                report::append_crc32(&mut data, CRC_SEED_FEATURE_SET);
+            }
+
+            controller.send_feature_report(&data)?;
+            print_report("Sent", id, &data);
+        },
+    }
+
+    Ok(())
+}
+
+fn print_report(verb: &str, id: u8, data: &[u8]) {
+    println!("{}{}{} feature report 0x{:02X}{} ({} bytes, {})",
+             colors::BOLD, colors::CYAN, verb, id, colors::RESET,
+             data.len(), feature_name(id).unwrap_or("unknown"));
+    for line in hexdump(data) {
+        println!("  {}", line);
+    }
+}
@@ -0,0 +1,134 @@
+// Lightbar colors over the network: --follow shows colors sent by another instance
+// (--stream-to) or any other program, so several PCs can run in sync.
+//
+// Packet format, the same port takes both:
+//   UDP  one color per datagram, either 3 raw bytes (R, G, B) or text "#rrggbb"
+//   TCP  one text color per line, "#rrggbb" or "rrggbb"
+use crossbeam_channel::{Receiver, Sender, unbounded};
+use std::io::{self, BufReader};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, ToSocketAddrs, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::net::{self, ConnectionLimit};
+use crate::palette::parse_hex_color;
+
+// Any interface, the sender is usually another machine
+pub const DEFAULT_ADDRESS: &str = "0.0.0.0:47821";
+// With nothing received for this long the local effect takes over again
+const STALE_AFTER: Duration = Duration::from_secs(3);
+// Anyone on the network can connect: a color is a few bytes, and a handful of senders is plenty
+const MAX_LINE: usize = 64;
+const MAX_CONNECTIONS: usize = 8;
+
+fn parse_packet(packet: &[u8]) -> Option<(u8, u8, u8)> {
+    match packet {
+        &[r, g, b] => Some((r, g, b)),
+        text => parse_hex_color(std::str::from_utf8(text).ok()?).ok(),
+    }
+}
+
+pub struct Follower {
+    address: SocketAddr,
+    colors: Receiver<(u8, u8, u8)>,
+    latest: Option<((u8, u8, u8), Instant)>,
+    stale_after: Duration,
+}
+
+impl Follower {
+    pub fn listen(address: &str) -> io::Result<Self> {
+        let udp = UdpSocket::bind(address)?;
+        let address = udp.local_addr()?;
+        let tcp = TcpListener::bind(address)?;
+        let (tx, colors) = unbounded();
+
+        let udp_tx = tx.clone();
+        thread::spawn(move || {
+            let mut buf = [0u8; 64];
+            while let Ok(len) = udp.recv(&mut buf) {
+                if let Some(color) = parse_packet(&buf[..len])
+                    && udp_tx.send(color).is_err()
+                {
+                    break;
+                }
+            }
+        });
+        thread::spawn(move || {
+            let limit = ConnectionLimit::new(MAX_CONNECTIONS);
+            for stream in tcp.incoming().flatten() {
+                // Dropping the stream closes it
+                let Some(slot) = limit.acquire() else { continue };
+                let tx: Sender<_> = tx.clone();
+                thread::spawn(move || {
+                    let _slot = slot;
+                    let mut reader = BufReader::new(stream);
+                    let mut line = Vec::new();
+                    while let Ok(true) = net::read_line(&mut reader, &mut line, MAX_LINE) {
+                        let Ok(line) = std::str::from_utf8(&line) else { break };
+                        match parse_hex_color(line) {
+                            Ok(color) => {
+                                if tx.send(color).is_err() {
+                                    break;
+                                }
+                            },
+                            Err(e) if !line.trim().is_empty() => warn!("follow: {}", e),
+                            Err(_) => {},
+                        }
+                    }
+                });
+            }
+        });
+        Ok(Self::from_channel(address, colors))
+    }
+
+    // Colors from another source, like an OpenRGB device (see openrgb::mirror)
+    pub fn from_channel(address: SocketAddr, colors: Receiver<(u8, u8, u8)>) -> Self {
+        Self { address, colors, latest: None, stale_after: STALE_AFTER }
+    }
+
+    // For senders that repeat an unchanged color less often than STALE_AFTER
+    pub fn stale_after(mut self, after: Duration) -> Self {
+        self.stale_after = after;
+        self
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    // The newest color received, None if the sender has gone quiet
+    pub fn poll(&mut self) -> Option<(u8, u8, u8)> {
+        if let Some(color) = self.colors.try_iter().last() {
+            self.latest = Some((color, Instant::now()));
+        }
+        self.latest.filter(|(_, at)| at.elapsed() < self.stale_after).map(|(color, _)| color)
+    }
+}
+
+// Sends every lightbar color as a 3-byte UDP datagram, for --stream-to
+pub struct Streamer {
+    socket: UdpSocket,
+}
+
+impl Streamer {
+    // Broadcast addresses like 192.168.1.255 work too
+    pub fn open(address: &str) -> io::Result<Self> {
+        let target = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "address did not resolve"))?;
+        let socket = match target.is_ipv4() {
+            true => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+            false => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?,
+        };
+        socket.set_broadcast(true)?;
+        socket.connect(target)?;
+        Ok(Self { socket })
+    }
+
+    pub fn send(&self, (r, g, b): (u8, u8, u8)) {
+        // Followers may not be up yet, nothing to do about a lost datagram
+        let _ = self.socket.send(&[r, g, b]);
+    }
+}
@@ -0,0 +1,134 @@
+// Which application has the focused window, for switching profiles as you alt-tab
+//
+// Windows asks the window manager directly. Linux uses hyprctl under Hyprland,
+// swaymsg under Sway and xprop on X11; macOS asks System Events via osascript.
+use std::time::{Duration, Instant};
+
+// Often enough to follow an alt-tab, rare enough that spawning a helper doesn't matter
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// Follows the foreground application by process name
+#[derive(Default)]
+pub struct ForegroundWatch {
+    last_check: Option<Instant>,
+    app: Option<String>,
+}
+
+impl ForegroundWatch {
+    // Re-checks when due, returns true if the focused application changed
+    pub fn refresh(&mut self) -> bool {
+        if self.last_check.is_some_and(|at| at.elapsed() < CHECK_INTERVAL) {
+            return false;
+        }
+        self.last_check = Some(Instant::now());
+
+        let app = foreground_app().map(|name| normalize(&name));
+        if app == self.app {
+            return false;
+        }
+        self.app = app;
+        true
+    }
+
+    // Lowercase process name without ".exe", None if unknown
+    pub fn app(&self) -> Option<&str> {
+        self.app.as_deref()
+    }
+}
+
+pub fn normalize(name: &str) -> String {
+    name.trim().to_ascii_lowercase().trim_end_matches(".exe").to_string()
+}
+
+#[cfg(windows)]
+fn foreground_app() -> Option<String> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use std::path::Path;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::OpenProcess;
+    use winapi::um::winbase::QueryFullProcessImageNameW;
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+    use winapi::um::winuser::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    unsafe {
+        let window = GetForegroundWindow();
+        if window.is_null() {
+            return None;
+        }
+        let mut pid = 0;
+        GetWindowThreadProcessId(window, &mut pid);
+
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return None;
+        }
+        let mut buf = [0u16; 1024];
+        let mut len = buf.len() as u32;
+        let ok = QueryFullProcessImageNameW(process, 0, buf.as_mut_ptr(), &mut len);
+        CloseHandle(process);
+        if ok == 0 {
+            return None;
+        }
+
+        let path = OsString::from_wide(&buf[..len as usize]);
+        Path::new(&path).file_name().map(|name| name.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn foreground_app() -> Option<String> {
+    let pid = if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        // {"pid": 1234, ...}
+        let json = command_output("hyprctl", &["activewindow", "-j"])?;
+        json_number_after(&json, "\"pid\":")
+    } else if std::env::var_os("SWAYSOCK").is_some() {
+        // The focused node is the only one with "focused": true, its pid follows shortly after
+        let tree = command_output("swaymsg", &["-t", "get_tree"])?;
+        let focused = tree.find("\"focused\": true")?;
+        json_number_after(&tree[focused..], "\"pid\":")
+    } else {
+        // _NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007
+        let active = command_output("xprop", &["-root", "_NET_ACTIVE_WINDOW"])?;
+        let window = active.rsplit(' ').next()?.trim();
+        // _NET_WM_PID(CARDINAL) = 1234
+        let pid = command_output("xprop", &["-id", window, "_NET_WM_PID"])?;
+        pid.rsplit(' ').next()?.trim().parse().ok()
+    }?;
+
+    // exe has the full name, comm is cut at 15 characters
+    std::fs::read_link(format!("/proc/{}/exe", pid))
+        .ok()
+        .and_then(|exe| exe.file_name().map(|name| name.to_string_lossy().into_owned()))
+        .or_else(|| std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok())
+}
+
+#[cfg(target_os = "macos")]
+fn foreground_app() -> Option<String> {
+    command_output(
+        "osascript",
+        &["-e", "tell application \"System Events\" to get name of first application process whose frontmost is true"],
+    )
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn foreground_app() -> Option<String> {
+    None
+}
+
+#[cfg(unix)]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(target_os = "linux")]
+fn json_number_after(text: &str, key: &str) -> Option<u32> {
+    let rest = &text[text.find(key)? + key.len()..];
+    let digits: String = rest.trim_start().chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
@@ -0,0 +1,192 @@
+// Small built-in games that exercise input reports and LED output together
+use crossbeam_channel::Receiver;
+use rand::Rng;
+use rand::seq::IndexedRandom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dualsense_rainbow::input::{Button, InputEvent};
+use dualsense_rainbow::report::PlayerLeds;
+use dualsense_rainbow::{DualSenseController, Error, colors};
+
+// Short enough that press timestamps are accurate to about a millisecond
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+// Nobody reacts that slowly on purpose, count it as a missed round
+const REACTION_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Simon says: each face button has its own color and player LED
+const SIMON_BUTTONS: [(Button, (u8, u8, u8), u8); 4] = [
+    (Button::Square, (255, 0, 180), 0b00001),
+    (Button::Triangle, (0, 255, 60), 0b00010),
+    (Button::Cross, (0, 80, 255), 0b01000),
+    (Button::Circle, (255, 30, 0), 0b10000),
+];
+// Time allowed for each press when echoing a sequence
+const SIMON_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Reads input until one of `buttons` is pressed or `deadline` passes,
+// returning which one and when it was pressed
+fn wait_for_press(
+    controller: &mut DualSenseController,
+    events: &Receiver<InputEvent>,
+    buttons: &[Button],
+    deadline: Instant,
+    running: &AtomicBool,
+) -> Result<Option<(Button, Instant)>, Error> {
+    while running.load(Ordering::SeqCst) && Instant::now() < deadline {
+        controller.poll_input()?;
+        let now = Instant::now();
+        let pressed = events.try_iter().find_map(|event| match event {
+            InputEvent::ButtonDown(button) if buttons.contains(&button) => Some(button),
+            _ => None,
+        });
+        if let Some(button) = pressed {
+            return Ok(Some((button, now)));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(None)
+}
+
+// Shows a color for a while
+fn show(
+    controller: &mut DualSenseController,
+    color: (u8, u8, u8),
+    duration: Duration,
+    running: &AtomicBool,
+) -> Result<(), Error> {
+    controller.set_lightbar(color.0, color.1, color.2)?;
+    hold(controller, duration, running)
+}
+
+// Keeps the LEDs as they are for a while. Input is still read, so presses made
+// meanwhile stay queued for the next wait_for_press unless the caller drains them
+fn hold(controller: &mut DualSenseController, duration: Duration, running: &AtomicBool) -> Result<(), Error> {
+    let deadline = Instant::now() + duration;
+    while running.load(Ordering::SeqCst) && Instant::now() < deadline {
+        controller.poll_input()?;
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+// Waits a random 1.5-4 s, turns the lightbar green and times the press of ✕
+pub fn reaction(
+    controller: &mut DualSenseController,
+    rounds: u32,
+    running: &AtomicBool,
+) -> Result<(), Error> {
+    println!("{}{}Reaction test:{} press ✕ as soon as the lightbar turns green", colors::BOLD, colors::CYAN, colors::RESET);
+    println!("{}{} rounds, CTRL+C to stop early{}\n", colors::GRAY, rounds, colors::RESET);
+
+    let events = controller.subscribe();
+    let mut times = Vec::new();
+    let mut round = 1;
+
+    while round <= rounds && running.load(Ordering::SeqCst) {
+        // Dim white: get ready
+        controller.set_lightbar(40, 40, 40)?;
+        events.try_iter().for_each(drop);
+        let wait = Duration::from_millis(rand::rng().random_range(1500..4000));
+        if wait_for_press(controller, &events, &[Button::Cross], Instant::now() + wait, running)?.is_some() {
+            println!("  {}{}Too early!{} Wait for green", colors::BOLD, colors::RED, colors::RESET);
+            show(controller, (255, 0, 0), Duration::from_millis(800), running)?;
+            continue;
+        }
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+
+        controller.set_lightbar(0, 255, 0)?;
+        let lit = Instant::now();
+        match wait_for_press(controller, &events, &[Button::Cross], lit + REACTION_TIMEOUT, running)? {
+            Some((_, pressed)) => {
+                let time = pressed - lit;
+                times.push(time);
+                let best = times.iter().min().copied().unwrap_or(time);
+                println!("  Round {}: {}{}{} ms {}(best {} ms){}",
+                         round, colors::BOLD, time.as_millis(), colors::RESET, colors::GRAY, best.as_millis(), colors::RESET);
+            },
+            None if running.load(Ordering::SeqCst) => println!("  Round {}: {}too slow{}", round, colors::YELLOW, colors::RESET),
+            None => break,
+        }
+        show(controller, (0, 0, 0), Duration::from_millis(700), running)?;
+        round += 1;
+    }
+
+    controller.set_lightbar(0, 0, 0)?;
+    if let Some(best) = times.iter().min() {
+        let average = times.iter().sum::<Duration>() / times.len() as u32;
+        println!("\n{}{}Best: {} ms{}  {}Average: {} ms over {} rounds{}",
+                 colors::BOLD, colors::GREEN, best.as_millis(), colors::RESET,
+                 colors::GRAY, average.as_millis(), times.len(), colors::RESET);
+    }
+    Ok(())
+}
+
+// Lights the color and player LED of a Simon button, or turns both off
+fn simon_light(controller: &mut DualSenseController, button: Option<Button>) -> Result<(), Error> {
+    let (color, mask) = SIMON_BUTTONS
+        .iter()
+        .find(|(b, _, _)| Some(*b) == button)
+        .map_or(((0, 0, 0), 0), |&(_, color, mask)| (color, mask));
+    controller.set_player_leds(PlayerLeds { mask, fade: false, ..PlayerLeds::default() });
+    controller.set_lightbar(color.0, color.1, color.2)
+}
+
+// Plays a growing sequence on the lightbar and player LEDs and checks it's echoed back
+pub fn simon(controller: &mut DualSenseController, running: &AtomicBool) -> Result<(), Error> {
+    println!("{}{}Simon says:{} watch the lights, then press the same buttons in order", colors::BOLD, colors::CYAN, colors::RESET);
+    for (button, _, mask) in SIMON_BUTTONS {
+        let leds: String = (0..5).map(|i| if mask & (1 << i) != 0 { 'x' } else { '-' }).collect();
+        println!("  {}{:?}{} {}", colors::GRAY, button, colors::RESET, leds);
+    }
+    println!("{}CTRL+C to stop{}\n", colors::GRAY, colors::RESET);
+
+    let events = controller.subscribe();
+    let buttons = SIMON_BUTTONS.map(|(button, _, _)| button);
+    let mut sequence: Vec<Button> = Vec::new();
+
+    'game: while running.load(Ordering::SeqCst) {
+        sequence.push(*buttons.choose(&mut rand::rng()).unwrap_or(&Button::Cross));
+        show(controller, (0, 0, 0), Duration::from_millis(600), running)?;
+
+        // Later levels play back faster
+        let step = Duration::from_millis(600u64.saturating_sub(sequence.len() as u64 * 25).max(250));
+        for &button in &sequence {
+            simon_light(controller, Some(button))?;
+            hold(controller, step, running)?;
+            simon_light(controller, None)?;
+            show(controller, (0, 0, 0), step / 3, running)?;
+        }
+
+        events.try_iter().for_each(drop);
+        for &expected in &sequence {
+            let pressed = wait_for_press(controller, &events, &buttons, Instant::now() + SIMON_TIMEOUT, running)?;
+            match pressed {
+                Some((button, _)) if button == expected => {
+                    simon_light(controller, Some(button))?;
+                    hold(controller, Duration::from_millis(150), running)?;
+                    simon_light(controller, None)?;
+                },
+                _ if !running.load(Ordering::SeqCst) => break 'game,
+                pressed => {
+                    let what = pressed.map_or("nothing".to_string(), |(button, _)| format!("{:?}", button));
+                    println!("  {}{}✗ Expected {:?}, got {}{}", colors::BOLD, colors::RED, expected, what, colors::RESET);
+                    for _ in 0..3 {
+                        show(controller, (255, 0, 0), Duration::from_millis(200), running)?;
+                        show(controller, (0, 0, 0), Duration::from_millis(150), running)?;
+                    }
+                    break 'game;
+                },
+            }
+        }
+        println!("  {}✓ Level {}{}", colors::GREEN, sequence.len(), colors::RESET);
+    }
+
+    simon_light(controller, None)?;
+    let score = sequence.len().saturating_sub(1);
+    println!("\n{}{}Score: {} {}{}", colors::BOLD, colors::GREEN, score, if score == 1 { "level" } else { "levels" }, colors::RESET);
+    Ok(())
+}
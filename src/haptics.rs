@@ -0,0 +1,219 @@
+// Haptic waveform playback
+//
+// Over USB the DualSense also shows up as a 4-channel sound card: channels 1-2
+// feed the speaker/headset, channels 3-4 drive the left and right voice-coil
+// actuators. Audio files are decoded, converted to stereo at the device rate
+// and streamed into the haptic channels.
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use tracing::error;
+
+use dualsense_rainbow::Error;
+use dualsense_rainbow::input::{Button, InputEvent};
+
+const DEVICE_CHANNELS: u16 = 4;
+const SAMPLE_RATE: u32 = 48_000;
+// Longest clip accepted, anything longer is almost certainly the wrong file
+const MAX_CLIP_SECONDS: usize = 30;
+
+// Decoded audio, stereo at SAMPLE_RATE
+#[derive(Clone)]
+pub struct HapticClip {
+    frames: Vec<[f32; 2]>,
+}
+
+impl HapticClip {
+    // Loads a WAV or Ogg Vorbis file, picked by extension
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        let (samples, channels, rate) = match extension.as_str() {
+            "wav" | "wave" => decode_wav(path),
+            "ogg" | "oga" => decode_ogg(path),
+            _ => Err(format!("unsupported audio format '{}' (expected .wav or .ogg)", extension)),
+        }
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        if channels == 0 || rate == 0 {
+            return Err(format!("{}: no audio data", path.display()));
+        }
+
+        // Mono goes to both actuators, extra channels beyond the first two are dropped
+        let stereo: Vec<[f32; 2]> = samples
+            .chunks_exact(channels)
+            .map(|frame| [frame[0], *frame.get(1).unwrap_or(&frame[0])])
+            .collect();
+
+        let frames = resample(&stereo, rate, SAMPLE_RATE);
+        if frames.len() > MAX_CLIP_SECONDS * SAMPLE_RATE as usize {
+            return Err(format!("{}: clip is longer than {} seconds", path.display(), MAX_CLIP_SECONDS));
+        }
+        Ok(Self { frames })
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        self.frames.len() as f32 / SAMPLE_RATE as f32
+    }
+}
+
+fn decode_wav(path: &Path) -> Result<(Vec<f32>, usize, u32), String> {
+    let mut reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader.samples::<i32>().map(|s| s.map(|s| s as f32 * scale)).collect()
+        },
+    }
+    .map_err(|e| e.to_string())?;
+    Ok((samples, spec.channels as usize, spec.sample_rate))
+}
+
+fn decode_ogg(path: &Path) -> Result<(Vec<f32>, usize, u32), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut reader = lewton::inside_ogg::OggStreamReader::new(file).map_err(|e| e.to_string())?;
+    let channels = reader.ident_hdr.audio_channels as usize;
+    let rate = reader.ident_hdr.audio_sample_rate;
+
+    let mut samples = Vec::new();
+    while let Some(packet) = reader.read_dec_packet_itl().map_err(|e| e.to_string())? {
+        samples.extend(packet.into_iter().map(|s| s as f32 / 32768.0));
+    }
+    Ok((samples, channels, rate))
+}
+
+// Linear interpolation is plenty for actuators that can't reproduce much above 1 kHz
+fn resample(frames: &[[f32; 2]], from: u32, to: u32) -> Vec<[f32; 2]> {
+    if from == to || frames.len() < 2 {
+        return frames.to_vec();
+    }
+
+    let ratio = from as f64 / to as f64;
+    let len = ((frames.len() - 1) as f64 / ratio) as usize + 1;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * ratio;
+            let index = pos as usize;
+            let t = (pos - index as f64) as f32;
+            let a = frames[index];
+            let b = frames[(index + 1).min(frames.len() - 1)];
+            [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+        })
+        .collect()
+}
+
+// Output stream on the DualSense sound card, fed from a shared queue
+pub struct HapticPlayer {
+    _stream: Stream,
+    queue: Arc<Mutex<VecDeque<[f32; 2]>>>,
+}
+
+impl HapticPlayer {
+    pub fn open() -> Result<Self, Error> {
+        let host = cpal::default_host();
+        let device = host
+            .output_devices()
+            .map_err(|e| Error::Other(e.to_string()))?
+            .find(|device| {
+                device.description().is_ok_and(|d| {
+                    let name = d.name().to_ascii_lowercase();
+                    name.contains("dualsense") || name.contains("wireless controller")
+                })
+            })
+            .ok_or_else(|| Error::UnsupportedTransport("DualSense audio device not found (haptics need a USB connection)".into()))?;
+
+        let supported = device
+            .supported_output_configs()
+            .map_err(|e| Error::Other(e.to_string()))?
+            .find(|c| {
+                c.channels() == DEVICE_CHANNELS
+                    && (c.min_sample_rate()..=c.max_sample_rate()).contains(&SAMPLE_RATE)
+            })
+            .ok_or_else(|| Error::UnsupportedTransport("DualSense audio device has no 4-channel 48 kHz output".into()))?
+            .with_sample_rate(SAMPLE_RATE);
+
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let config = supported.config();
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, config, queue.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, config, queue.clone()),
+            SampleFormat::I32 => build_stream::<i32>(&device, config, queue.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, config, queue.clone()),
+            other => return Err(Error::UnsupportedTransport(format!("unsupported sample format {:?}", other))),
+        }.map_err(Error::Other)?;
+        stream.play().map_err(|e| Error::Other(e.to_string()))?;
+
+        Ok(Self { _stream: stream, queue })
+    }
+
+    // Replaces whatever is currently playing
+    pub fn play(&self, clip: &HapticClip) {
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.clear();
+            queue.extend(clip.frames.iter().copied());
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.queue.lock().is_ok_and(|queue| !queue.is_empty())
+    }
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: StreamConfig,
+    queue: Arc<Mutex<VecDeque<[f32; 2]>>>,
+) -> Result<Stream, String>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                let mut queue = queue.lock().ok();
+                for frame in data.chunks_mut(channels) {
+                    let [left, right] = queue.as_mut().and_then(|q| q.pop_front()).unwrap_or([0.0; 2]);
+                    // Keep the speaker/headset channels silent
+                    frame[0] = T::from_sample(0.0);
+                    frame[1] = T::from_sample(0.0);
+                    frame[2] = T::from_sample(left);
+                    frame[3] = T::from_sample(right);
+                }
+            },
+            |e| error!("Haptics stream error: {}", e),
+            None,
+        )
+        .map_err(|e| e.to_string())
+}
+
+// Clips bound to events through the config's [haptics] table, e.g. `cross = "click.wav"`
+pub struct HapticBindings {
+    player: HapticPlayer,
+    on_button: BTreeMap<Button, HapticClip>,
+}
+
+impl HapticBindings {
+    pub fn load(player: HapticPlayer, bindings: &BTreeMap<String, String>) -> Result<Self, String> {
+        let mut on_button = BTreeMap::new();
+        for (event, file) in bindings {
+            let button: Button = event.parse().map_err(|e| format!("[haptics] {}", e))?;
+            on_button.insert(button, HapticClip::load(Path::new(file))?);
+        }
+        Ok(Self { player, on_button })
+    }
+
+    pub fn handle(&self, event: &InputEvent) {
+        if let InputEvent::ButtonDown(button) = event
+            && let Some(clip) = self.on_button.get(button)
+        {
+            self.player.play(clip);
+        }
+    }
+}
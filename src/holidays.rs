@@ -0,0 +1,115 @@
+// Built-in seasonal palettes that take over the rainbow on matching dates
+use chrono::{Datelike, Local, NaiveDate};
+use std::time::{Duration, Instant};
+
+use crate::palette::Palette;
+
+pub struct Holiday {
+    pub name: &'static str,
+    // Region code the holiday belongs to, None for everyone
+    region: Option<&'static str>,
+    // Inclusive (month, day) range, may wrap over new year
+    start: (u32, u32),
+    end: (u32, u32),
+    colors: &'static [(u8, u8, u8)],
+}
+
+impl Holiday {
+    fn matches(&self, date: NaiveDate, region: Option<&str>) -> bool {
+        if self.region.is_some() && self.region != region {
+            return false;
+        }
+
+        let day = (date.month(), date.day());
+        if self.start <= self.end {
+            self.start <= day && day <= self.end
+        } else {
+            day >= self.start || day <= self.end
+        }
+    }
+
+    pub fn palette(&self) -> Palette {
+        Palette::new(self.colors.to_vec())
+    }
+}
+
+const ORANGE: (u8, u8, u8) = (255, 100, 0);
+const PURPLE: (u8, u8, u8) = (140, 0, 255);
+const RED: (u8, u8, u8) = (255, 0, 0);
+const GREEN: (u8, u8, u8) = (0, 200, 0);
+const WHITE: (u8, u8, u8) = (255, 255, 255);
+const BLUE: (u8, u8, u8) = (0, 40, 255);
+const GOLD: (u8, u8, u8) = (255, 180, 0);
+const YELLOW: (u8, u8, u8) = (255, 220, 0);
+
+// National days come first so they win over the seasonal ranges
+pub const HOLIDAYS: &[Holiday] = &[
+    Holiday { name: "Festa della Repubblica", region: Some("IT"), start: (6, 2), end: (6, 2), colors: &[GREEN, WHITE, RED] },
+    Holiday { name: "Independence Day", region: Some("US"), start: (7, 4), end: (7, 4), colors: &[RED, WHITE, BLUE] },
+    Holiday { name: "Canada Day", region: Some("CA"), start: (7, 1), end: (7, 1), colors: &[RED, WHITE] },
+    Holiday { name: "Fête nationale", region: Some("FR"), start: (7, 14), end: (7, 14), colors: &[BLUE, WHITE, RED] },
+    Holiday { name: "Tag der Deutschen Einheit", region: Some("DE"), start: (10, 3), end: (10, 3), colors: &[(40, 40, 40), RED, GOLD] },
+    Holiday { name: "Fiesta Nacional", region: Some("ES"), start: (10, 12), end: (10, 12), colors: &[RED, YELLOW] },
+    Holiday { name: "Independência do Brasil", region: Some("BR"), start: (9, 7), end: (9, 7), colors: &[GREEN, YELLOW, BLUE] },
+    Holiday { name: "Día de la Independencia", region: Some("MX"), start: (9, 16), end: (9, 16), colors: &[GREEN, WHITE, RED] },
+    Holiday { name: "St. Patrick's Day", region: Some("IE"), start: (3, 17), end: (3, 17), colors: &[GREEN, WHITE, ORANGE] },
+    Holiday { name: "Valentine's Day", region: None, start: (2, 14), end: (2, 14), colors: &[RED, (255, 60, 140)] },
+    Holiday { name: "Halloween", region: None, start: (10, 24), end: (10, 31), colors: &[ORANGE, PURPLE, (60, 255, 0)] },
+    Holiday { name: "Winter holidays", region: None, start: (12, 20), end: (1, 1), colors: &[RED, GREEN, WHITE, GOLD] },
+];
+
+pub fn active(date: NaiveDate, region: Option<&str>) -> Option<&'static Holiday> {
+    HOLIDAYS.iter().find(|holiday| holiday.matches(date, region))
+}
+
+// The date only matters at day granularity, no need to ask the clock every frame
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+// Tracks which holiday (if any) applies today
+pub struct HolidayThemes {
+    region: Option<String>,
+    last_check: Option<Instant>,
+    active: Option<(&'static Holiday, Palette)>,
+}
+
+impl HolidayThemes {
+    pub fn new(region: Option<String>) -> Self {
+        Self { region, last_check: None, active: None }
+    }
+
+    // Re-reads the local date when due, returns true if the active holiday changed
+    pub fn refresh(&mut self) -> bool {
+        if self.last_check.is_some_and(|at| at.elapsed() < CHECK_INTERVAL) {
+            return false;
+        }
+        self.last_check = Some(Instant::now());
+
+        let today = active(Local::now().date_naive(), self.region.as_deref());
+        if today.map(|h| h.name) == self.active.as_ref().map(|(h, _)| h.name) {
+            return false;
+        }
+        self.active = today.map(|holiday| (holiday, holiday.palette()));
+        true
+    }
+
+    pub fn active(&self) -> Option<(&'static Holiday, &Palette)> {
+        self.active.as_ref().map(|(holiday, palette)| (*holiday, palette))
+    }
+
+    pub fn palette(&self) -> Option<&Palette> {
+        self.active().map(|(_, palette)| palette)
+    }
+}
+
+// "it_IT.UTF-8" -> "IT", from the usual locale variables
+pub fn region_from_env() -> Option<String> {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+        .and_then(|locale| {
+            let locale = locale.split(['.', '@']).next()?.to_string();
+            let (_, region) = locale.split_once(['_', '-'])?;
+            Some(region.to_ascii_uppercase())
+        })
+}
@@ -0,0 +1,126 @@
+// The bits of HTTP/1.1 the built-in APIs need: one request per connection, a small
+// JSON body in and a JSON answer out
+use serde_json::{Value, json};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use crate::palette::parse_color;
+use dualsense_rainbow::stats::Stats;
+
+// Requests are tiny, anything bigger is not for us
+pub const MAX_BODY: usize = 4096;
+// The request line and headers together
+const MAX_HEADER: u64 = 8192;
+const MAX_HEADERS: usize = 64;
+// For the whole request, however slowly it trickles in
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    // Names lowercased
+    headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(known, _)| known.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+}
+
+pub fn read_request(stream: &TcpStream) -> io::Result<HttpRequest> {
+    let mut reader = BufReader::new(Deadline { stream, until: Instant::now() + READ_TIMEOUT });
+    let mut head = (&mut reader).take(MAX_HEADER);
+    let mut line = String::new();
+    read_header_line(&mut head, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if read_header_line(&mut head, &mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(io::Error::new(io::ErrorKind::FileTooLarge, "too many headers"));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let mut request = HttpRequest { method, path, headers, body: Vec::new() };
+    let length = request.header("content-length").and_then(|value| value.parse().ok()).unwrap_or(0);
+    if length > MAX_BODY {
+        return Err(io::Error::new(io::ErrorKind::FileTooLarge, "body too large"));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)?;
+    Ok(request)
+}
+
+// A line cut off by the MAX_HEADER limit means the headers didn't fit
+fn read_header_line(head: &mut io::Take<&mut BufReader<Deadline>>, line: &mut String) -> io::Result<usize> {
+    let read = head.read_line(line)?;
+    if head.limit() == 0 && !line.ends_with('\n') {
+        return Err(io::Error::new(io::ErrorKind::FileTooLarge, "headers too large"));
+    }
+    Ok(read)
+}
+
+// Reads from the stream until `until`, so a client can't hold a connection open by
+// sending a byte every few seconds
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    until: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out"));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+pub fn respond(mut stream: &TcpStream, status: &str, body: &Value) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+// A color given as a name, "#rrggbb" or [r, g, b]
+pub fn color(value: Option<&Value>) -> Result<(u8, u8, u8), String> {
+    match value {
+        Some(Value::String(name)) => parse_color(name),
+        Some(Value::Array(rgb)) if rgb.len() == 3 => {
+            let channel = |v: &Value| v.as_u64().filter(|&c| c <= 255).map(|c| c as u8).ok_or("RGB values must be 0-255");
+            Ok((channel(&rgb[0])?, channel(&rgb[1])?, channel(&rgb[2])?))
+        },
+        _ => Err("expected \"color\" as a name, \"#rrggbb\" or [r, g, b]".into()),
+    }
+}
+
+pub fn stats_json(stats: &Stats) -> Value {
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    json!({
+        "fps": stats.fps,
+        "sent": stats.sent,
+        "errors": stats.errors,
+        "reconnects": stats.reconnects,
+        "last_error": stats.last_error,
+        "latency_ms": stats.latency.map(|l| json!({ "p50": ms(l.p50), "p90": ms(l.p90), "p99": ms(l.p99), "max": ms(l.max) })),
+    })
+}
@@ -0,0 +1,70 @@
+// Dims the lightbar and then turns the LEDs off when the controller has been left alone
+// for a while, and brings them back on the next input
+use std::time::{Duration, Instant};
+
+use dualsense_rainbow::input::InputEvent;
+
+// How long dimming takes, waking up is immediate
+const DIM_FADE: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdleState {
+    Active,
+    Dimmed,
+    Asleep,
+}
+
+pub struct IdleTimer {
+    // Either can be left out
+    dim_after: Option<Duration>,
+    off_after: Option<Duration>,
+    // Multiplies the brightness once dimmed
+    dim_level: f32,
+    last_activity: Instant,
+    state: IdleState,
+}
+
+impl IdleTimer {
+    pub fn new(dim_after: Option<Duration>, dim_level: f32, off_after: Option<Duration>) -> Self {
+        Self { dim_after, off_after, dim_level, last_activity: Instant::now(), state: IdleState::Active }
+    }
+
+    // Battery reports arrive on their own, only a person touching the pad counts
+    pub fn is_activity(event: &InputEvent) -> bool {
+        !matches!(event, InputEvent::BatteryChanged(_))
+    }
+
+    pub fn activity(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    // Returns the new state when the controller dims, falls asleep or wakes up
+    pub fn update(&mut self) -> Option<IdleState> {
+        let idle = self.last_activity.elapsed();
+        let state = match (self.dim_after, self.off_after) {
+            (_, Some(off)) if idle >= off => IdleState::Asleep,
+            (Some(dim), _) if idle >= dim => IdleState::Dimmed,
+            _ => IdleState::Active,
+        };
+        if state == self.state {
+            return None;
+        }
+        self.state = state;
+        Some(state)
+    }
+
+    pub fn asleep(&self) -> bool {
+        self.state == IdleState::Asleep
+    }
+
+    // Multiplies the brightness: 1.0 while active, easing down to the dim level
+    pub fn brightness(&self) -> f32 {
+        match (self.state, self.dim_after) {
+            (IdleState::Dimmed, Some(dim)) => {
+                let t = (self.last_activity.elapsed().saturating_sub(dim).as_secs_f32() / DIM_FADE.as_secs_f32()).min(1.0);
+                1.0 - (1.0 - self.dim_level) * t
+            },
+            _ => 1.0,
+        }
+    }
+}
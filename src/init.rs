@@ -0,0 +1,303 @@
+// `init`: first-run setup. Finds the controller, checks that writes actually reach the
+// lightbar, measures how fast reports can go out, asks for a default effect, brightness
+// and whether to start at login, and writes the answers into the config file (merged
+// into an existing one, comments kept) after checking that the result loads.
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use toml_edit::{DocumentMut, Item, Table, value};
+
+use crate::config::Config;
+use crate::effects::EffectKind;
+use crate::profile::Settings;
+use dualsense_rainbow::triggers::TriggerEffect;
+use dualsense_rainbow::{DualSenseController, Error, HidSelection, colors, device, hsv_to_rgb};
+
+// Report spacings tried when measuring the send rate, fastest first
+const PROBE_INTERVALS_MS: [u64; 5] = [0, 4, 8, 16, 33];
+const PROBE_REPORTS: u32 = 60;
+// A single write blocking this long means the link can't keep up
+const SLOW_WRITE: Duration = Duration::from_millis(20);
+
+// `controller` is already open for --mock, otherwise the wizard looks for one itself
+pub fn run(
+    controller: Option<DualSenseController>,
+    selection: HidSelection,
+    config_path: Option<&Path>,
+) -> Result<(), Error> {
+    println!("{}{} Setting up DualSense Rainbow{}\n", colors::BOLD, colors::CYAN, colors::RESET);
+
+    let mut controller = match controller {
+        Some(controller) => controller,
+        None => {
+            detect()?;
+            crate::open_controller(selection, None)?
+        },
+    };
+
+    test_writes(&mut controller)?;
+    let interval = measure_rate(&mut controller);
+    let _ = controller.set_lightbar(0, 0, 0);
+
+    let effect = loop {
+        let names: Vec<_> = EffectKind::ALL.iter().map(|kind| kind.name()).collect();
+        match ask(&format!("Default effect ({})", names.join(", ")), EffectKind::default().name())?.parse::<EffectKind>() {
+            Ok(effect) => break effect,
+            Err(e) => println!("{}{}{}", colors::YELLOW, e, colors::RESET),
+        }
+    };
+    let brightness = loop {
+        match ask("Brightness in percent", "100")?.trim_end_matches('%').parse::<u32>() {
+            Ok(percent) if percent <= 100 => break percent as f64 / 100.0,
+            _ => println!("{}Enter a number from 0 to 100{}", colors::YELLOW, colors::RESET),
+        }
+    };
+    let autostart = confirm("Start at login?", false)?;
+
+    let path = match config_path {
+        Some(path) => path.to_path_buf(),
+        None => Config::default_path().ok_or_else(|| Error::Other("no config directory on this system, pass --config".into()))?,
+    };
+    write_config(&path, effect, brightness, interval).map_err(Error::Other)?;
+    println!("{}{}✓ Wrote {}{}", colors::BOLD, colors::GREEN, path.display(), colors::RESET);
+
+    if autostart {
+        match enable_autostart(config_path) {
+            Ok(location) => println!("{}{}✓ Starts at login ({}){}", colors::BOLD, colors::GREEN, location, colors::RESET),
+            Err(e) => println!("{}{}⚠ Could not set up starting at login:{} {}", colors::BOLD, colors::YELLOW, colors::RESET, e),
+        }
+    }
+
+    println!("\nAll set, run {}dualsense-rainbow{} to start the lights.", colors::BOLD, colors::RESET);
+    Ok(())
+}
+
+// Lists every DualSense the system sees, so a missing one is obvious before anything is opened
+fn detect() -> Result<(), Error> {
+    loop {
+        let found: Vec<_> = device::detect_all()?
+            .into_iter()
+            .map(|d| {
+                let connection = if d.usb { "USB" } else { "Bluetooth" };
+                format!("{} ({}, {}, {})", d.path, d.model.name(), connection, d.serial.as_deref().unwrap_or("no serial"))
+            })
+            .collect();
+
+        if !found.is_empty() {
+            println!("Controllers found:");
+            for device in &found {
+                println!("  {}•{} {}", colors::GREEN, colors::RESET, device);
+            }
+            if found.len() > 1 {
+                println!("{}The first one is used for the tests below{}", colors::GRAY, colors::RESET);
+            }
+            println!();
+            return Ok(());
+        }
+
+        println!("{}{}No DualSense found.{} Plug it in with a USB cable or pair it over Bluetooth.", colors::BOLD, colors::YELLOW, colors::RESET);
+        if cfg!(target_os = "linux") {
+            println!("{}On Linux it may also need a udev rule: `dualsense-rainbow setup-udev` installs one.{}", colors::GRAY, colors::RESET);
+        }
+        if !confirm("Search again?", true)? {
+            return Err(Error::DeviceNotFound("no controller found".into()));
+        }
+    }
+}
+
+// Flashes red, green and blue and asks whether that showed up
+fn test_writes(controller: &mut DualSenseController) -> Result<(), Error> {
+    println!("Testing the {} connection: watch the lightbar...", if controller.is_usb() { "USB" } else { "Bluetooth" });
+    for (r, g, b) in [(255, 0, 0), (0, 255, 0), (0, 0, 255)] {
+        controller.set_lightbar(r, g, b)?;
+        thread::sleep(Duration::from_millis(500));
+    }
+    controller.set_lightbar(0, 0, 0)?;
+
+    if !confirm("Did it flash red, green and blue?", true)? {
+        println!("{}Writes went through without errors but nothing showed. Another program (Steam, DS4Windows) may", colors::YELLOW);
+        println!("be driving the lights, or the wrong HID collection was opened: see \"Writes succeed but nothing");
+        println!("lights up\" in the README. Setup goes on, the answers below are kept either way.{}\n", colors::RESET);
+    }
+    Ok(())
+}
+
+// Sends bursts of reports at shrinking intervals and returns the shortest interval the
+// link handled without errors or stalls. Only Bluetooth has an interval to configure
+fn measure_rate(controller: &mut DualSenseController) -> Option<u64> {
+    print!("Measuring how fast reports can be sent... ");
+    let _ = io::stdout().flush();
+
+    let safe = PROBE_INTERVALS_MS.into_iter().find(|&interval| {
+        (0..PROBE_REPORTS).all(|i| {
+            let (r, g, b) = hsv_to_rgb((i * 6 % 360) as f32, 1.0, 1.0);
+            let started = Instant::now();
+            let ok = controller.set_lightbar(r, g, b).is_ok() && started.elapsed() < SLOW_WRITE;
+            thread::sleep(Duration::from_millis(interval));
+            ok
+        })
+    });
+
+    match safe {
+        Some(0) => println!("{}no limit needed{}", colors::GREEN, colors::RESET),
+        Some(interval) => println!("{}one report every {} ms{}", colors::YELLOW, interval, colors::RESET),
+        None => println!("{}the link struggles even at {} ms, expect stutter{}",
+                         colors::RED, PROBE_INTERVALS_MS[PROBE_INTERVALS_MS.len() - 1], colors::RESET),
+    }
+    if controller.is_usb() {
+        return None;
+    }
+    Some(safe.unwrap_or(PROBE_INTERVALS_MS[PROBE_INTERVALS_MS.len() - 1]))
+}
+
+// Merges the answers into the config file and checks the result loads before writing it
+fn write_config(path: &Path, effect: EffectKind, brightness: f64, interval: Option<u64>) -> Result<(), String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+    };
+    let mut doc: DocumentMut = text.parse().map_err(|e| format!("invalid config {}: {}", path.display(), e))?;
+
+    if let Some(interval) = interval {
+        doc.insert("bt_report_interval_ms", value(interval as i64));
+    }
+    let defaults = doc
+        .entry("defaults")
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_like_mut()
+        .ok_or("'defaults' in the config is not a table")?;
+    defaults.insert("effect", value(effect.name()));
+    defaults.insert("brightness", value(brightness));
+
+    let text = doc.to_string();
+    let config: Config = toml::from_str(&text).map_err(|e| format!("the new config doesn't load: {}", e))?;
+    let base = Settings {
+        effect: EffectKind::default(),
+        speed: 1.5,
+        brightness: 1.0,
+        saturation: 1.0,
+        palette: None,
+        player_leds: 0,
+        mic_led: Default::default(),
+        left_trigger: TriggerEffect::Off,
+        right_trigger: TriggerEffect::Off,
+    };
+    base.with_profile(&config.defaults, &config.triggers)
+        .map_err(|e| format!("the new config doesn't load: [defaults]: {}", e))?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    }
+    fs::write(path, text).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+fn prompt(question: &str, hint: &str) -> io::Result<String> {
+    print!("{}?{} {} [{}]: ", colors::CYAN, colors::RESET, question, hint);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "input closed"));
+    }
+    Ok(line.trim().to_string())
+}
+
+fn ask(question: &str, default: &str) -> io::Result<String> {
+    let answer = prompt(question, default)?;
+    Ok(if answer.is_empty() { default.to_string() } else { answer })
+}
+
+pub fn confirm(question: &str, default: bool) -> io::Result<bool> {
+    loop {
+        match prompt(question, if default { "Y/n" } else { "y/N" })?.to_ascii_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("{}Answer y or n{}", colors::YELLOW, colors::RESET),
+        }
+    }
+}
+
+// The command line that runs the tool at login, with the same config file
+fn autostart_command(config_path: Option<&Path>) -> Result<(PathBuf, Vec<String>), String> {
+    let exe = std::env::current_exe().map_err(|e| format!("cannot find this program's path: {}", e))?;
+    let args = match config_path {
+        Some(path) => {
+            let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            vec!["--config".to_string(), path.to_string_lossy().into_owned()]
+        },
+        None => Vec::new(),
+    };
+    Ok((exe, args))
+}
+
+// Registers the tool to start with the desktop session and says where
+fn enable_autostart(config_path: Option<&Path>) -> Result<String, String> {
+    let (exe, args) = autostart_command(config_path)?;
+
+    #[cfg(windows)]
+    {
+        let mut command = format!("\"{}\"", exe.display());
+        for arg in &args {
+            command.push_str(&format!(" \"{}\"", arg));
+        }
+        let key = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+        let status = std::process::Command::new("reg")
+            .args(["add", key, "/v", "DualSense Rainbow", "/t", "REG_SZ", "/d", &command, "/f"])
+            .stdout(std::process::Stdio::null())
+            .status()
+            .map_err(|e| format!("cannot run reg: {}", e))?;
+        if !status.success() {
+            return Err(format!("reg add failed ({})", status));
+        }
+        Ok(format!("{}\\DualSense Rainbow", key))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let agents = dirs::home_dir().ok_or("no home directory")?.join("Library/LaunchAgents");
+        let path = agents.join("com.github.borgox.dualsense-rainbow.plist");
+        let arguments: String = std::iter::once(exe.to_string_lossy().into_owned())
+            .chain(args)
+            .map(|arg| format!("        <string>{}</string>\n", xml_escape(&arg)))
+            .collect();
+        let plist = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n<dict>\n\
+             \x20   <key>Label</key>\n    <string>com.github.borgox.dualsense-rainbow</string>\n\
+             \x20   <key>ProgramArguments</key>\n    <array>\n{}    </array>\n\
+             \x20   <key>RunAtLoad</key>\n    <true/>\n\
+             </dict>\n</plist>\n",
+            arguments
+        );
+        fs::create_dir_all(&agents).map_err(|e| format!("cannot create {}: {}", agents.display(), e))?;
+        fs::write(&path, plist).map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+        Ok(path.display().to_string())
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        // XDG autostart, honored by GNOME, KDE and most other desktops
+        let dir = dirs::config_dir().ok_or("no config directory")?.join("autostart");
+        let path = dir.join("dualsense-rainbow.desktop");
+        let exec: Vec<String> = std::iter::once(exe.to_string_lossy().into_owned())
+            .chain(args)
+            .map(|arg| format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect();
+        let entry = format!(
+            "[Desktop Entry]\nType=Application\nName=DualSense Rainbow\nExec={}\nTerminal=false\nNoDisplay=true\nX-GNOME-Autostart-enabled=true\n",
+            exec.join(" ")
+        );
+        fs::create_dir_all(&dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+        fs::write(&path, entry).map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+        Ok(path.display().to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
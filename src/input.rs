@@ -0,0 +1,312 @@
+use crossbeam_channel::{Receiver, Sender, unbounded};
+
+// Minimum stick travel (in raw units) before a StickMoved event is emitted,
+// so resting sticks don't flood subscribers with jitter
+const STICK_EVENT_THRESHOLD: u8 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Button {
+    Square,
+    Cross,
+    Circle,
+    Triangle,
+    L1,
+    R1,
+    L2,
+    R2,
+    Create,
+    Options,
+    L3,
+    R3,
+    Ps,
+    Touchpad,
+    Mute,
+    DpadUp,
+    DpadRight,
+    DpadDown,
+    DpadLeft,
+    // DualSense Edge only: the Fn buttons below the sticks and the back paddles
+    FnLeft,
+    FnRight,
+    PaddleLeft,
+    PaddleRight,
+}
+
+impl Button {
+    pub const ALL: [Button; 23] = [
+        Button::Square, Button::Cross, Button::Circle, Button::Triangle,
+        Button::L1, Button::R1, Button::L2, Button::R2,
+        Button::Create, Button::Options, Button::L3, Button::R3,
+        Button::Ps, Button::Touchpad, Button::Mute,
+        Button::DpadUp, Button::DpadRight, Button::DpadDown, Button::DpadLeft,
+        Button::FnLeft, Button::FnRight, Button::PaddleLeft, Button::PaddleRight,
+    ];
+
+    fn mask(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+impl std::str::FromStr for Button {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_ascii_lowercase().replace(['-', '_', ' '], "");
+        Button::ALL
+            .into_iter()
+            .find(|button| format!("{:?}", button).to_ascii_lowercase() == name)
+            .ok_or_else(|| format!("unknown button '{}'", s))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+// Range of TouchPoint coordinates on the DualSense. The DualShock 4's pad is as wide and
+// a little shorter, its y stops at 942
+pub const TOUCHPAD_SIZE: (u16, u16) = (1920, 1080);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TouchPoint {
+    pub id: u8,
+    pub x: u16,
+    pub y: u16,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatteryStatus {
+    pub level: u8, // Percent, 0-100
+    pub charging: bool,
+}
+
+impl std::fmt::Display for BatteryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}%", self.level)?;
+        if self.charging {
+            write!(f, " (charging)")?;
+        }
+        Ok(())
+    }
+}
+
+// Raw motion sensor readings, uncalibrated: roughly 8192 units per g for the
+// accelerometer and 1024 per degree per second for the gyro. Axes as the pad lies in
+// the hands: x to the right, y up, z towards the player
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Motion {
+    // Pitch, yaw and roll rates
+    pub gyro: [i16; 3],
+    pub accel: [i16; 3],
+}
+
+impl Motion {
+    // Six little-endian i16s: the gyro's three axes, then the accelerometer's
+    pub(crate) fn parse(d: &[u8]) -> Self {
+        let axis = |i: usize| i16::from_le_bytes([d[i * 2], d[i * 2 + 1]]);
+        Self { gyro: [axis(0), axis(1), axis(2)], accel: [axis(3), axis(4), axis(5)] }
+    }
+}
+
+// Decoded snapshot of a single input report
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ControllerState {
+    pub(crate) buttons: u32,
+    pub left_stick: (u8, u8),
+    pub right_stick: (u8, u8),
+    pub l2: u8,
+    pub r2: u8,
+    pub touch: [Option<TouchPoint>; 2],
+    pub motion: Option<Motion>, // Not present in the reduced Bluetooth report
+    pub battery: Option<BatteryStatus>, // Not present in the reduced Bluetooth report
+}
+
+impl ControllerState {
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.buttons & button.mask() != 0
+    }
+
+    // Every button held down, D-pad directions included
+    pub fn pressed(&self) -> impl Iterator<Item = Button> + '_ {
+        Button::ALL.into_iter().filter(|button| self.is_pressed(*button))
+    }
+}
+
+// Typed events derived from consecutive input reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputEvent {
+    ButtonDown(Button),
+    ButtonUp(Button),
+    StickMoved { stick: Stick, x: u8, y: u8 },
+    TouchpadTouch { finger: usize, point: TouchPoint },
+    TouchpadRelease { finger: usize },
+    BatteryChanged(BatteryStatus),
+}
+
+// Parses a USB (0x01, 64 bytes), full Bluetooth (0x31) or reduced Bluetooth (0x01, 10 bytes) input report
+pub fn parse_input_report(data: &[u8]) -> Option<ControllerState> {
+    match (data.first()?, data.len()) {
+        (0x01, len) if len >= 64 => parse_full(&data[1..]),
+        (0x31, len) if len >= 78 => parse_full(&data[2..]),
+        (0x01, len) if len >= 10 => Some(parse_reduced(&data[1..])),
+        _ => None,
+    }
+}
+
+fn parse_full(d: &[u8]) -> Option<ControllerState> {
+    let mut state = ControllerState {
+        buttons: decode_buttons(d[7], d[8], d[9]),
+        left_stick: (d[0], d[1]),
+        right_stick: (d[2], d[3]),
+        l2: d[4],
+        r2: d[5],
+        motion: Some(Motion::parse(&d[15..27])),
+        ..Default::default()
+    };
+
+    // Two touch points, 4 bytes each: contact (bit 7 set = not touching), 12-bit x, 12-bit y
+    for (finger, slot) in state.touch.iter_mut().enumerate() {
+        let p = &d[32 + finger * 4..36 + finger * 4];
+        if p[0] & 0x80 == 0 {
+            *slot = Some(TouchPoint {
+                id: p[0] & 0x7F,
+                x: p[1] as u16 | ((p[2] as u16 & 0x0F) << 8),
+                y: (p[2] as u16 >> 4) | ((p[3] as u16) << 4),
+            });
+        }
+    }
+
+    // Low nibble is capacity in tenths, high nibble is the charging state
+    let status = d[52];
+    let capacity = status & 0x0F;
+    state.battery = Some(BatteryStatus {
+        level: (capacity * 10 + 5).min(100),
+        charging: matches!(status >> 4, 0x1 | 0x2),
+    });
+
+    Some(state)
+}
+
+fn parse_reduced(d: &[u8]) -> ControllerState {
+    ControllerState {
+        buttons: decode_buttons(d[4], d[5], d[6]),
+        left_stick: (d[0], d[1]),
+        right_stick: (d[2], d[3]),
+        l2: d[7],
+        r2: d[8],
+        ..Default::default()
+    }
+}
+
+pub(crate) fn decode_buttons(b0: u8, b1: u8, b2: u8) -> u32 {
+    let mut mask = 0;
+    let mut set = |button: Button, pressed: bool| {
+        if pressed {
+            mask |= button.mask();
+        }
+    };
+
+    // D-pad is a hat switch: 0 = up, clockwise in 45° steps, 8 = released
+    let hat = b0 & 0x0F;
+    set(Button::DpadUp, matches!(hat, 7 | 0 | 1));
+    set(Button::DpadRight, matches!(hat, 1..=3));
+    set(Button::DpadDown, matches!(hat, 3..=5));
+    set(Button::DpadLeft, matches!(hat, 5..=7));
+
+    set(Button::Square, b0 & 0x10 != 0);
+    set(Button::Cross, b0 & 0x20 != 0);
+    set(Button::Circle, b0 & 0x40 != 0);
+    set(Button::Triangle, b0 & 0x80 != 0);
+    set(Button::L1, b1 & 0x01 != 0);
+    set(Button::R1, b1 & 0x02 != 0);
+    set(Button::L2, b1 & 0x04 != 0);
+    set(Button::R2, b1 & 0x08 != 0);
+    set(Button::Create, b1 & 0x10 != 0);
+    set(Button::Options, b1 & 0x20 != 0);
+    set(Button::L3, b1 & 0x40 != 0);
+    set(Button::R3, b1 & 0x80 != 0);
+    set(Button::Ps, b2 & 0x01 != 0);
+    set(Button::Touchpad, b2 & 0x02 != 0);
+    set(Button::Mute, b2 & 0x04 != 0);
+    // Always clear on a plain DualSense
+    set(Button::FnLeft, b2 & 0x10 != 0);
+    set(Button::FnRight, b2 & 0x20 != 0);
+    set(Button::PaddleLeft, b2 & 0x40 != 0);
+    set(Button::PaddleRight, b2 & 0x80 != 0);
+    mask
+}
+
+// Tracks the last known state and fans out events to every subscriber
+#[derive(Default)]
+pub struct InputEvents {
+    state: Option<ControllerState>,
+    reported_sticks: [(u8, u8); 2],
+    subscribers: Vec<Sender<InputEvent>>,
+}
+
+impl InputEvents {
+    pub fn subscribe(&mut self) -> Receiver<InputEvent> {
+        let (tx, rx) = unbounded();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    pub fn battery(&self) -> Option<BatteryStatus> {
+        self.state.and_then(|state| state.battery)
+    }
+
+    pub fn state(&self) -> Option<ControllerState> {
+        self.state
+    }
+
+    pub fn update(&mut self, new: ControllerState) {
+        let mut events = Vec::new();
+        let old = self.state.unwrap_or_default();
+        let first = self.state.is_none();
+
+        for button in Button::ALL {
+            match (old.is_pressed(button), new.is_pressed(button)) {
+                (false, true) => events.push(InputEvent::ButtonDown(button)),
+                (true, false) => events.push(InputEvent::ButtonUp(button)),
+                _ => {}
+            }
+        }
+
+        for (i, (stick, pos)) in [(Stick::Left, new.left_stick), (Stick::Right, new.right_stick)]
+            .into_iter()
+            .enumerate()
+        {
+            let last = self.reported_sticks[i];
+            if first || last.0.abs_diff(pos.0) >= STICK_EVENT_THRESHOLD || last.1.abs_diff(pos.1) >= STICK_EVENT_THRESHOLD {
+                self.reported_sticks[i] = pos;
+                if !first {
+                    events.push(InputEvent::StickMoved { stick, x: pos.0, y: pos.1 });
+                }
+            }
+        }
+
+        for (finger, (before, after)) in old.touch.iter().zip(new.touch.iter()).enumerate() {
+            match (before, after) {
+                (_, Some(point)) if before != after => events.push(InputEvent::TouchpadTouch { finger, point: *point }),
+                (Some(_), None) => events.push(InputEvent::TouchpadRelease { finger }),
+                _ => {}
+            }
+        }
+
+        // The reduced Bluetooth report carries no battery info, keep the last known value
+        let mut new = new;
+        match (old.battery, new.battery) {
+            (before, Some(after)) if before != Some(after) => events.push(InputEvent::BatteryChanged(after)),
+            (before, None) => new.battery = before,
+            _ => {}
+        }
+
+        self.state = Some(new);
+
+        // Drop subscribers whose receiver has gone away
+        self.subscribers
+            .retain(|tx| events.iter().all(|event| tx.send(*event).is_ok()));
+    }
+}
@@ -0,0 +1,116 @@
+// Single-key hotkeys from the terminal, without waiting for Enter.
+// Only line buffering and echo are turned off, so output and CTRL+C behave as usual
+use crossbeam_channel::{Receiver, unbounded};
+use std::io::{self, IsTerminal, Read};
+use std::thread;
+
+pub struct Hotkeys {
+    keys: Receiver<u8>,
+    _mode: TerminalMode,
+}
+
+impl Hotkeys {
+    // None when stdin isn't an interactive terminal
+    pub fn start() -> Option<Self> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        let mode = TerminalMode::unbuffered()?;
+
+        let (tx, keys) = unbounded();
+        thread::spawn(move || {
+            let mut byte = [0u8; 1];
+            while io::stdin().read(&mut byte).is_ok_and(|n| n == 1) {
+                if tx.send(byte[0]).is_err() {
+                    break;
+                }
+            }
+        });
+        Some(Self { keys, _mode: mode })
+    }
+
+    pub fn pressed(&self) -> impl Iterator<Item = u8> + '_ {
+        self.keys.try_iter()
+    }
+}
+
+// Restores the original terminal settings when dropped
+#[cfg(unix)]
+struct TerminalMode(libc::termios);
+
+#[cfg(unix)]
+impl TerminalMode {
+    fn unbuffered() -> Option<Self> {
+        unsafe {
+            let mut original = std::mem::zeroed::<libc::termios>();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return None;
+            }
+            let mut unbuffered = original;
+            unbuffered.c_lflag &= !(libc::ICANON | libc::ECHO);
+            unbuffered.c_cc[libc::VMIN] = 1;
+            unbuffered.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &unbuffered) != 0 {
+                return None;
+            }
+            Some(Self(original))
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for TerminalMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0);
+        }
+    }
+}
+
+#[cfg(windows)]
+struct TerminalMode(u32);
+
+#[cfg(windows)]
+impl TerminalMode {
+    fn unbuffered() -> Option<Self> {
+        use winapi::um::consoleapi::{GetConsoleMode, SetConsoleMode};
+        use winapi::um::processenv::GetStdHandle;
+        use winapi::um::winbase::STD_INPUT_HANDLE;
+        use winapi::um::wincon::{ENABLE_ECHO_INPUT, ENABLE_LINE_INPUT};
+
+        unsafe {
+            let handle = GetStdHandle(STD_INPUT_HANDLE);
+            let mut original = 0;
+            if GetConsoleMode(handle, &mut original) == 0 {
+                return None;
+            }
+            if SetConsoleMode(handle, original & !(ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT)) == 0 {
+                return None;
+            }
+            Some(Self(original))
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for TerminalMode {
+    fn drop(&mut self) {
+        use winapi::um::consoleapi::SetConsoleMode;
+        use winapi::um::processenv::GetStdHandle;
+        use winapi::um::winbase::STD_INPUT_HANDLE;
+
+        unsafe {
+            SetConsoleMode(GetStdHandle(STD_INPUT_HANDLE), self.0);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+struct TerminalMode;
+
+#[cfg(not(any(unix, windows)))]
+impl TerminalMode {
+    fn unbuffered() -> Option<Self> {
+        None
+    }
+}
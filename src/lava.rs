@@ -0,0 +1,79 @@
+// `--effect lava`: colors from the palette (or the color wheel) drifting on 1D Perlin
+// noise, so they wander and swell like a lava lamp without ever settling into a loop
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+
+// The [lava] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LavaConfig {
+    // Size of the noise's blobs: above 1.0 the color swells slower and broader, below it churns
+    pub scale: f32,
+    // How far the color strays either side of its slow drift, 1.0 being half the palette
+    pub spread: f32,
+    // How much the brightness swells along with it, 0 keeps it steady
+    pub glow: f32,
+}
+
+impl Default for LavaConfig {
+    fn default() -> Self {
+        Self { scale: 1.0, spread: 0.35, glow: 0.4 }
+    }
+}
+
+// The checked form of LavaConfig
+#[derive(Clone, Debug)]
+pub struct LavaSettings {
+    pub scale: f32,
+    pub spread: f32,
+    pub glow: f32,
+}
+
+impl LavaSettings {
+    pub fn new(config: &LavaConfig) -> Result<Self, String> {
+        if !config.scale.is_finite() || config.scale <= 0.0 {
+            return Err(format!("scale must be a positive number, got {}", config.scale));
+        }
+        if !(0.0..=1.0).contains(&config.spread) {
+            return Err(format!("spread must be between 0.0 and 1.0, got {}", config.spread));
+        }
+        if !(0.0..=1.0).contains(&config.glow) {
+            return Err(format!("glow must be between 0.0 and 1.0, got {}", config.glow));
+        }
+        Ok(Self { scale: config.scale, spread: config.spread, glow: config.glow })
+    }
+}
+
+// Each octave runs at a frequency that isn't a whole multiple of the one before, so their
+// 256-unit periods never line up again
+const OCTAVES: [(f32, f32); 3] = [(1.0, 1.0), (2.17, 0.5), (4.61, 0.25)];
+
+// Fractal 1D Perlin noise with a shuffled gradient table, a new one for every Noise
+pub struct Noise {
+    gradients: [f32; 256],
+}
+
+impl Noise {
+    pub fn random() -> Self {
+        let mut order: Vec<u8> = (0..=255).collect();
+        order.shuffle(&mut rand::rng());
+        Self { gradients: std::array::from_fn(|i| order[i] as f32 / 127.5 - 1.0) }
+    }
+
+    // Roughly -1..1, most of the time within -0.5..0.5
+    pub fn at(&self, x: f32) -> f32 {
+        let total: f32 = OCTAVES.iter().map(|&(frequency, amplitude)| self.octave(x * frequency) * amplitude).sum();
+        total / OCTAVES.iter().map(|&(_, amplitude)| amplitude).sum::<f32>()
+    }
+
+    fn octave(&self, x: f32) -> f32 {
+        let cell = x.floor();
+        let t = x - cell;
+        let gradient = |i: f32| self.gradients[(i.rem_euclid(256.0)) as usize];
+        // Perlin's quintic fade, smooth to the second derivative at every cell edge
+        let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let (from, to) = (gradient(cell) * t, gradient(cell + 1.0) * (t - 1.0));
+        // A 1D gradient noise peaks at half a unit either side of zero
+        2.0 * (from + (to - from) * fade)
+    }
+}
@@ -0,0 +1,137 @@
+// Userspace bridge that mimics the kernel's multicolor LED class
+// (/sys/class/leds/<name>/{brightness,multi_intensity,...}) in a plain directory,
+// so scripts written against LED sysfs can drive the lightbar generically.
+//
+// Writing `brightness` or `multi_intensity` takes manual control of the lightbar,
+// writing the effect name (`rainbow`) to `trigger` hands it back to the animation.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const LED_NAME: &str = "dualsense:rgb:indicator";
+const MAX_BRIGHTNESS: u32 = 255;
+const EFFECT_TRIGGER: &str = "rainbow";
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct LedBridge {
+    dir: PathBuf,
+    last_poll: Instant,
+    modified: [Option<SystemTime>; 3],
+    brightness: u32,
+    intensity: (u32, u32, u32),
+    manual: bool,
+}
+
+impl LedBridge {
+    // $XDG_RUNTIME_DIR/dualsense-rainbow/leds, falling back to the temp dir
+    pub fn default_dir() -> PathBuf {
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir)
+            .join("dualsense-rainbow")
+            .join("leds")
+    }
+
+    pub fn create(root: &Path) -> io::Result<Self> {
+        let dir = root.join(LED_NAME);
+        fs::create_dir_all(&dir)?;
+
+        fs::write(dir.join("max_brightness"), format!("{}\n", MAX_BRIGHTNESS))?;
+        fs::write(dir.join("multi_index"), "red green blue\n")?;
+        fs::write(dir.join("brightness"), format!("{}\n", MAX_BRIGHTNESS))?;
+        fs::write(dir.join("multi_intensity"), "255 255 255\n")?;
+        fs::write(dir.join("trigger"), format!("none [{}]\n", EFFECT_TRIGGER))?;
+
+        let mut bridge = Self {
+            dir,
+            last_poll: Instant::now(),
+            modified: [None; 3],
+            brightness: MAX_BRIGHTNESS,
+            intensity: (255, 255, 255),
+            manual: false,
+        };
+        bridge.modified = bridge.read_mtimes();
+        Ok(bridge)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    // Returns the color requested through the bridge while it has manual control
+    pub fn poll(&mut self) -> Option<(u8, u8, u8)> {
+        if self.last_poll.elapsed() >= POLL_INTERVAL {
+            self.last_poll = Instant::now();
+            self.refresh();
+        }
+
+        self.manual.then(|| {
+            let scale = |c: u32| (c.min(MAX_BRIGHTNESS) * self.brightness / MAX_BRIGHTNESS) as u8;
+            (scale(self.intensity.0), scale(self.intensity.1), scale(self.intensity.2))
+        })
+    }
+
+    fn read_mtimes(&self) -> [Option<SystemTime>; 3] {
+        ["brightness", "multi_intensity", "trigger"]
+            .map(|file| fs::metadata(self.dir.join(file)).and_then(|m| m.modified()).ok())
+    }
+
+    fn refresh(&mut self) {
+        let modified = self.read_mtimes();
+        let changed = |i: usize| modified[i] != self.modified[i];
+
+        if changed(0)
+            && let Some(value) = self.read_value("brightness").and_then(|v| v.parse::<u32>().ok())
+        {
+            self.brightness = value.min(MAX_BRIGHTNESS);
+            self.manual = true;
+        }
+
+        if changed(1) {
+            let values: Vec<u32> = self
+                .read_value("multi_intensity")
+                .map(|v| v.split_whitespace().filter_map(|n| n.parse().ok()).collect())
+                .unwrap_or_default();
+            if let [r, g, b] = values[..] {
+                self.intensity = (r, g, b);
+                self.manual = true;
+            }
+        }
+
+        if changed(2) {
+            // Accept both a bare name and the "none [rainbow]" listing format
+            let selected = self.read_value("trigger").map(|v| {
+                v.split_whitespace()
+                    .find(|t| t.starts_with('['))
+                    .map(|t| t.trim_matches(|c| c == '[' || c == ']').to_string())
+                    .unwrap_or(v)
+            });
+            match selected.as_deref() {
+                Some(EFFECT_TRIGGER) => self.manual = false,
+                Some("none") => self.manual = true,
+                _ => {}
+            }
+        }
+
+        // Mirror the active mode back into the trigger listing like the kernel does
+        if changed(2) || self.manual {
+            let listing = if self.manual {
+                format!("[none] {}\n", EFFECT_TRIGGER)
+            } else {
+                format!("none [{}]\n", EFFECT_TRIGGER)
+            };
+            if self.read_value("trigger").as_deref() != Some(listing.trim()) {
+                let _ = fs::write(self.dir.join("trigger"), listing);
+            }
+        }
+
+        self.modified = self.read_mtimes();
+    }
+
+    fn read_value(&self, file: &str) -> Option<String> {
+        fs::read_to_string(self.dir.join(file))
+            .ok()
+            .map(|v| v.trim().to_string())
+    }
+}
@@ -0,0 +1,29 @@
+// The controller side of dualsense-rainbow as a library: opening the pad (DualSense,
+// DualSense Edge or DualShock 4), building output reports (with the CRC32 Bluetooth
+// needs), decoding input reports and the
+// color helpers the effects use. The binary's effects, integrations and CLI are
+// built on top of this and stay out of it
+pub mod capture;
+pub mod color;
+pub mod colors;
+pub mod descriptor;
+pub mod device;
+pub mod ds4;
+pub mod error;
+pub mod input;
+pub mod lightbar;
+pub mod manager;
+pub mod passthrough;
+pub mod report;
+pub mod retry;
+pub mod stats;
+pub mod trace;
+pub mod transport;
+pub mod triggers;
+
+pub use color::{Color, ColorCorrection, ColorSpace, hsv_to_rgb, kelvin_to_rgb, rgb_to_hue, rotate_hue};
+pub use device::{DualSenseController, HidSelection};
+pub use error::Error;
+pub use lightbar::Lightbar;
+pub use manager::DeviceManager;
+pub use report::{OutputReportBuilder, append_crc32, calculate_crc32};
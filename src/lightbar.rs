@@ -0,0 +1,86 @@
+// The part of talking to a controller that differs between families: which reports
+// carry the lightbar and input, and how they're laid out. DualSenseController keeps
+// one of these for the model it opened and does everything else (rate limits,
+// retries, reconnects, input events) the same way for all of them
+use std::time::Duration;
+
+use crate::input::{self, ControllerState};
+use crate::report::{self, OutputState};
+use crate::ds4;
+
+// Report IDs and payload sizes (without the ID) a model's descriptor should declare
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReportSizes {
+    pub input: (u8, usize),
+    pub output: (u8, usize),
+}
+
+pub trait Lightbar: Send {
+    // Output report showing `state`; `seq` counts the reports sent so far
+    fn output_report(&self, usb_mode: bool, seq: u8, state: &OutputState) -> Vec<u8>;
+
+    fn parse_input(&self, report: &[u8]) -> Option<ControllerState>;
+
+    // Feature report ID and length whose read turns on full input reports over Bluetooth
+    fn calibration_report(&self) -> (u8, usize);
+
+    fn report_sizes(&self, usb_mode: bool) -> ReportSizes;
+
+    // How often the pad should send input over Bluetooth, for families that take it
+    fn set_poll_interval(&mut self, _interval: Duration) {}
+}
+
+pub struct DualSenseReports;
+
+impl Lightbar for DualSenseReports {
+    fn output_report(&self, usb_mode: bool, seq: u8, state: &OutputState) -> Vec<u8> {
+        report::build_output_report(usb_mode, seq, state)
+    }
+
+    fn parse_input(&self, report: &[u8]) -> Option<ControllerState> {
+        input::parse_input_report(report)
+    }
+
+    fn calibration_report(&self) -> (u8, usize) {
+        (0x05, 41)
+    }
+
+    fn report_sizes(&self, usb_mode: bool) -> ReportSizes {
+        if usb_mode {
+            ReportSizes { input: (0x01, 63), output: (report::USB_REPORT_ID, report::USB_REPORT_LEN - 1) }
+        } else {
+            ReportSizes { input: (0x31, 77), output: (report::BT_REPORT_ID, report::BT_REPORT_LEN - 1) }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DualShock4Reports {
+    poll_interval_ms: u8,
+}
+
+impl Lightbar for DualShock4Reports {
+    fn output_report(&self, usb_mode: bool, _seq: u8, state: &OutputState) -> Vec<u8> {
+        ds4::build_output_report(usb_mode, self.poll_interval_ms, state)
+    }
+
+    fn parse_input(&self, report: &[u8]) -> Option<ControllerState> {
+        ds4::parse_input_report(report)
+    }
+
+    fn calibration_report(&self) -> (u8, usize) {
+        (ds4::CALIBRATION_REPORT_ID, 37)
+    }
+
+    fn report_sizes(&self, usb_mode: bool) -> ReportSizes {
+        if usb_mode {
+            ReportSizes { input: (ds4::INPUT_USB_REPORT_ID, 63), output: (ds4::USB_REPORT_ID, ds4::USB_REPORT_LEN - 1) }
+        } else {
+            ReportSizes { input: (ds4::INPUT_BT_REPORT_ID, 77), output: (ds4::BT_REPORT_ID, ds4::BT_REPORT_LEN - 1) }
+        }
+    }
+
+    fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval_ms = interval.as_millis().min(ds4::MAX_POLL_INTERVAL_MS as u128) as u8;
+    }
+}
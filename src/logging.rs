@@ -0,0 +1,130 @@
+// What the tool reports while it runs goes through `tracing`: error! and warn! for what
+// went wrong, info! for what it is doing, debug! and trace! for detail only -v and -vv
+// show. Events come out in one of two formats:
+//
+//   human  the colored lines the messages are written as, warnings and errors on stderr
+//          and the rest on stdout (the default)
+//   json   one object per line on stdout with the time, level, module, message (colors
+//          taken out) and any fields the event carries, for log collectors
+//
+// Output of commands like `list` or `status` is the answer rather than a log, and is
+// printed directly.
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::{self, Write};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::prelude::*;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+// Each -v or -q moves one level from info: -vv shows everything, -qq only errors and
+// -qqq nothing
+pub fn level(verbose: u8, quiet: u8) -> LevelFilter {
+    match verbose as i16 - quiet as i16 {
+        ..=-3 => LevelFilter::OFF,
+        -2 => LevelFilter::ERROR,
+        -1 => LevelFilter::WARN,
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+pub fn init(level: LevelFilter, format: LogFormat) {
+    // Only fails when a subscriber is already set, and then that one stays
+    let _ = tracing_subscriber::registry().with(level).with(Output { format }).try_init();
+}
+
+struct Output {
+    format: LogFormat,
+}
+
+impl<S: Subscriber> Layer<S> for Output {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        // Like println!, minus the panic when whoever reads the output has gone away
+        let _ = match self.format {
+            LogFormat::Human if *metadata.level() <= Level::WARN => writeln!(io::stderr().lock(), "{}", fields.message),
+            LogFormat::Human => writeln!(io::stdout().lock(), "{}", fields.message),
+            LogFormat::Json => {
+                let mut line = Map::new();
+                line.insert("timestamp".into(), chrono::Local::now().to_rfc3339().into());
+                line.insert("level".into(), metadata.level().as_str().into());
+                line.insert("target".into(), metadata.target().into());
+                line.insert("message".into(), strip_ansi(&fields.message).trim().into());
+                line.extend(fields.values);
+                writeln!(io::stdout().lock(), "{}", Value::Object(line))
+            },
+        };
+    }
+}
+
+// An event's message, and its other fields as JSON values
+#[derive(Default)]
+struct Fields {
+    message: String,
+    values: Map<String, Value>,
+}
+
+impl Fields {
+    fn insert(&mut self, field: &Field, value: Value) {
+        match (field.name(), value) {
+            ("message", Value::String(message)) => self.message = message,
+            (name, value) => {
+                self.values.insert(name.into(), value);
+            },
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{:?}", value).into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+}
+
+// Drops the escape sequences of `colors`, ESC [ up to the letter that ends one
+fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                chars.by_ref().find(|c| c.is_ascii_alphabetic());
+            },
+            c => plain.push(c),
+        }
+    }
+    plain
+}
@@ -1,254 +1,1270 @@
-use hidapi::{HidApi, HidDevice};
+mod adaptive;
+mod animation;
+mod assistant;
+#[cfg(feature = "audio")]
+mod audio;
+mod battery_alert;
+mod battery_saver;
+#[cfg(feature = "audio")]
+mod beat;
+mod canvas;
+mod chase;
+mod cli;
+mod coexist;
+mod colormap;
+mod config;
+mod control;
+mod cron;
+mod daemon;
+mod discord;
+mod dmx;
+mod effects;
+mod feature;
+mod follow;
+mod foreground;
+mod games;
+#[cfg(feature = "haptics")]
+mod haptics;
+mod holidays;
+mod http;
+mod idle;
+mod init;
+mod keys;
+mod lava;
+#[cfg(target_os = "linux")]
+mod led_bridge;
+mod logging;
+mod meter;
+mod mic_led;
+mod motion;
+mod mqtt;
+mod net;
+mod night;
+#[cfg(feature = "notifications")]
+mod notifications;
+mod oneshot;
+mod openrgb;
+mod palette;
+#[cfg(feature = "plugins")]
+mod plugin;
+#[cfg(all(feature = "audio", target_os = "linux"))]
+mod pipewire;
+#[cfg(feature = "preview")]
+mod preview;
+mod processes;
+mod profile;
+mod reactive;
+mod reconnect;
+mod replay;
+#[cfg(feature = "http-api")]
+mod rest;
+mod rumble;
+mod schedule;
+#[cfg(feature = "scripting")]
+mod script;
+mod screen;
+mod shuffle;
+mod sun;
+mod sysmon;
+mod title;
+mod tuning;
+#[cfg(target_os = "linux")]
+mod udev;
+mod unread;
+mod watch_json;
+#[cfg(feature = "http-api")]
+mod websocket;
+
+use clap::Parser;
+use dualsense_rainbow::capture::CaptureWriter;
+use dualsense_rainbow::device::{COOPERATE_WRITE_INTERVAL, DUALSENSE_VID};
+use dualsense_rainbow::input::{Button, InputEvent};
+use dualsense_rainbow::report::{self, MuteLed, PlayerLeds};
+use dualsense_rainbow::trace::HidTracer;
+use dualsense_rainbow::transport::MockTransport;
+use dualsense_rainbow::triggers::{self, TriggerEffect};
+use dualsense_rainbow::{Color, ColorCorrection, ColorSpace, DeviceManager, DualSenseController, Error, HidSelection, colors, manager, passthrough, rgb_to_hue, stats};
+use effects::{EffectKind, EffectPlayer};
+use mic_led::MicLedScheduler;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::process::ExitCode;
 use std::thread;
 use std::time::{Duration, Instant};
+use tracing::{debug, error, info, trace, warn};
 
-// Vendor ID and Product ID for the DualSense controller
-const DUALSENSE_VID: u16 = 0x054C;
-const DUALSENSE_PID: u16 = 0x0CE6;
-
-// ANSI Color codes for terminal output
-mod colors {
-    pub const RESET: &str = "\x1b[0m";
-    pub const BOLD: &str = "\x1b[1m";
-    pub const GREEN: &str = "\x1b[32m";
-    pub const YELLOW: &str = "\x1b[33m";
-    pub const BLUE: &str = "\x1b[34m";
-    pub const MAGENTA: &str = "\x1b[35m";
-    pub const CYAN: &str = "\x1b[36m";
-    pub const RED: &str = "\x1b[31m";
-    pub const GRAY: &str = "\x1b[90m";
+fn get_color_name(h: f32) -> (&'static str, &'static str) {
+    match h as u32 {
+        0..=30 => ("Red", colors::RED),
+        31..=90 => ("Yellow", colors::YELLOW),
+        91..=150 => ("Green", colors::GREEN),
+        151..=210 => ("Cyan", colors::CYAN),
+        211..=270 => ("Blue", colors::BLUE),
+        271..=330 => ("Magenta", colors::MAGENTA),
+        _ => ("Red", colors::RED),
+    }
 }
 
-// A struct to manage the DualSense controller
-struct DualSenseController {
-    device: HidDevice,
-    usb_mode: bool,
-    last_color: (u8, u8, u8),
-    send_count: u64,
-    error_count: u64,
+fn main() -> ExitCode {
+    let args = cli::Args::parse();
+    logging::init(logging::level(args.verbose, args.quiet), args.log_format);
+    match start(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        // Each kind of failure has its own code, see dualsense_rainbow::error
+        Err(e) => {
+            error!("{}{}✗ Error:{} {}", colors::BOLD, colors::RED, colors::RESET, e);
+            ExitCode::from(e.exit_code())
+        },
+    }
 }
 
-impl DualSenseController {
-    fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        println!("{}{} Searching for DualSense...{}", colors::BOLD, colors::CYAN, colors::RESET);
+fn start(args: cli::Args) -> Result<(), Error> {
 
-        let api = HidApi::new()?;
+    // Some platforms only allow windows on the main thread, so everything else moves to a worker
+    #[cfg(feature = "preview")]
+    if args.preview {
+        let mock = mock_transport(args.dry_run);
+        let leds = mock.leds();
+        let worker = thread::spawn(move || run(args, Some(mock)));
+        preview::show(&leds, || worker.is_finished());
+        // Closing the window ends the program, simulated LEDs need no fade-out
+        if !worker.is_finished() {
+            std::process::exit(0);
+        }
+        return match worker.join() {
+            Ok(result) => result,
+            Err(_) => Err(Error::Other("the main loop panicked".into())),
+        };
+    }
 
-        // Search for the DualSense device
-        let device_info = api
-            .device_list()
-            .find(|d| d.vendor_id() == DUALSENSE_VID && d.product_id() == DUALSENSE_PID)
-            .ok_or("DualSense not found")?;
+    run(args, None)
+}
 
-        let device = device_info.open_device(&api)?;
+// `mock` replaces the hardware with a simulated controller, --mock makes one itself
+fn run(args: cli::Args, mock: Option<MockTransport>) -> Result<(), Error> {
+    let mut config = config::Config::load(args.config.as_deref()).map_err(Error::Config)?;
 
-        // Determine connection mode based on interface number
-        let usb_mode = device_info.interface_number() == 3;
+    // Enable ANSI escape codes on Windows
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsRawHandle;
+        use std::io::stdout;
 
-        println!("{}{}✓ DualSense found!{}", colors::BOLD, colors::GREEN, colors::RESET);
-        println!("  {}Mode:{} {}{}{}",
-                 colors::GRAY, colors::RESET,
-                 colors::BOLD, if usb_mode { "USB" } else { "Bluetooth" }, colors::RESET);
-        println!("  {}Vendor ID:{} 0x{:04X}", colors::GRAY, colors::RESET, DUALSENSE_VID);
-        println!("  {}Product ID:{} 0x{:04X}", colors::GRAY, colors::RESET, DUALSENSE_PID);
-        println!("  {}Interface:{} {}\n", colors::GRAY, colors::RESET, device_info.interface_number());
+        unsafe {
+            let handle = stdout().as_raw_handle();
+            let mut mode: u32 = 0;
+            if winapi::um::consoleapi::GetConsoleMode(handle as *mut _, &mut mode) != 0 {
+                winapi::um::consoleapi::SetConsoleMode(
+                    handle as *mut _,
+                    mode | winapi::um::wincon::ENABLE_VIRTUAL_TERMINAL_PROCESSING,
+                );
+            }
+        }
+    }
 
-        Ok(Self {
-            device,
-            usb_mode,
-            last_color: (0, 0, 0),
-            send_count: 0,
-            error_count: 0,
-        })
+    // Only talks to another running instance
+    if let Some(cli::Command::Ctl { address, socket, command }) = &args.command {
+        let line = command.join(" ");
+        let default_socket = daemon::default_socket();
+        let answer = match (address, socket) {
+            (Some(address), _) => control::send(address, &line).map_err(Error::Other)?,
+            (None, Some(socket)) => control::send_socket(socket, &line).map_err(Error::Other)?,
+            (None, None) if default_socket.exists() => control::send_socket(&default_socket, &line).map_err(Error::Other)?,
+            (None, None) => control::send(control::DEFAULT_ADDRESS, &line).map_err(Error::Other)?,
+        };
+        if !answer.is_empty() {
+            println!("{}", answer);
+        }
+        return Ok(());
     }
 
-    fn set_lightbar(&mut self, r: u8, g: u8, b: u8) -> Result<(), Box<dyn std::error::Error>> {
-        // Avoid sending the same color repeatedly (reduces flickering)
-        if (r, g, b) == self.last_color {
-            return Ok(());
+    // The background copy does the rest, this one only reports where to find it
+    if args.daemon && !daemon::is_child() {
+        if !matches!(args.command, None | Some(cli::Command::Run)) {
+            return Err(Error::Other("--daemon only runs the lightbar, not other commands".into()));
         }
+        let socket = args.socket.clone().flatten().unwrap_or_else(daemon::default_socket);
+        let pid = daemon::spawn(&socket).map_err(Error::Other)?;
+        println!("{}Daemon started (PID {}), listening on {}{}", colors::GRAY, pid, socket.display(), colors::RESET);
+        println!("{}Log: {}{}", colors::GRAY, daemon::log_path().display(), colors::RESET);
+        return Ok(());
+    }
+
+    // Only reads files
+    if let Some(cli::Command::Animation { action: cli::AnimationAction::Check { files } }) = &args.command {
+        return animation::check(files).map_err(Error::Other);
+    }
+
+    // Enumerates without opening anything
+    if let Some(cli::Command::List) = &args.command {
+        return oneshot::list();
+    }
+
+    // The wizard looks for the controller itself and reports what it finds
+    if let Some(cli::Command::Init) = &args.command {
+        let controller = (args.mock || args.dry_run).then(|| open_mock(mock_transport(args.dry_run)));
+        let selection = HidSelection { usage: args.hid_usage, interface: args.hid_interface };
+        return init::run(controller, selection, args.config.as_deref());
+    }
 
-        let mut report = if self.usb_mode {
-            vec![0; 48]
-        } else {
-            vec![0; 78]
+    // Only writes the rule, which is what opening the controller may be waiting on
+    #[cfg(target_os = "linux")]
+    if let Some(cli::Command::SetupUdev { yes, uinput }) = &args.command {
+        return udev::run(*yes, *uinput);
+    }
+
+    // Haptics go through the audio device, no HID handle needed
+    #[cfg(feature = "haptics")]
+    if let Some(cli::Command::Haptics { action: cli::HapticsAction::Play { file } }) = &args.command {
+        let clip = haptics::HapticClip::load(file).map_err(Error::Other)?;
+        let player = haptics::HapticPlayer::open()?;
+        println!("{}{} Playing {} ({:.1}s)...{}", colors::BOLD, colors::CYAN, file.display(), clip.duration_secs(), colors::RESET);
+        player.play(&clip);
+        while player.is_playing() {
+            thread::sleep(Duration::from_millis(20));
+        }
+        // Let the device drain its own buffer before the stream is dropped
+        thread::sleep(Duration::from_millis(200));
+        return Ok(());
+    }
+
+    // CLI flags win over the config file
+    let resolve_trigger = |flag: &Option<String>, configured: &Option<String>| {
+        flag.as_ref()
+            .or(configured.as_ref())
+            .map_or(Ok(TriggerEffect::Off), |spec| triggers::resolve(spec, &config.triggers))
+    };
+    let built_in = profile::Settings {
+        effect: EffectKind::default(),
+        speed: 1.5, // Slower speed for smoother transition
+        brightness: 1.0,
+        saturation: 1.0,
+        palette: None,
+        player_leds: args.player_leds,
+        mic_led: args.mic_led,
+        left_trigger: resolve_trigger(&args.left_trigger, &config.left_trigger).map_err(Error::Config)?,
+        right_trigger: resolve_trigger(&args.right_trigger, &config.right_trigger).map_err(Error::Config)?,
+    };
+    let overrides = profile::Profile {
+        effect: args.color.map(|_| EffectKind::Solid).or(args.effect).map(|effect| effect.name().to_string()),
+        speed: args.speed,
+        brightness: args.brightness,
+        palette: args.color.map(|color| palette::PaletteSpec::Stops(vec![Color::from(color).to_string()]))
+            .or_else(|| args.palette.clone().map(palette::PaletteSpec::Named)),
+        ..Default::default()
+    };
+    let mut base_settings = default_settings(&built_in, &config, &overrides).map_err(Error::Config)?;
+    let animation = args.animation.as_deref().map(animation::Animation::load).transpose().map_err(Error::Other)?;
+    let fps = match args.fps.or(config.fps) {
+        Some(fps @ 1..=240) => fps,
+        Some(fps) => return Err(Error::Config(format!("fps: {} is not between 1 and 240", fps))),
+        None => 60,
+    };
+    let gamma = match args.gamma.or(config.gamma) {
+        Some(gamma) if (0.1..=5.0).contains(&gamma) => gamma,
+        Some(gamma) => return Err(Error::Config(format!("gamma: {} is not between 0.1 and 5.0", gamma))),
+        None => 1.0,
+    };
+    let color_space = match (args.color_space, &config.color_space) {
+        (Some(space), _) => space,
+        (None, Some(space)) => space.parse().map_err(|e| Error::Config(format!("color_space: {}", e)))?,
+        (None, None) => ColorSpace::default(),
+    };
+    effects::set_color_space(color_space);
+    let fade_out_ms = args.fade_out.or(config.fade_out_ms).unwrap_or(500);
+    let exit_color = match (args.exit_color, &config.exit_color) {
+        (Some(color), _) => color,
+        (None, Some(color)) => cli::parse_exit_color(color).map_err(|e| Error::Config(format!("exit_color: {}", e)))?,
+        (None, None) => (0, 0, 0),
+    };
+
+    let device = args.device.clone().or_else(|| config.device.clone());
+
+    if let Some(target) = &args.write_config {
+        let path = target.clone().or_else(|| args.config.clone()).or_else(config::Config::default_path)
+            .ok_or_else(|| Error::Other("no config directory on this platform, pass --write-config FILE".into()))?;
+        let template = config::Template {
+            effect: base_settings.effect,
+            speed: base_settings.speed,
+            fps,
+            brightness: base_settings.brightness,
+            device,
+            exit_color,
+            fade_out_ms,
         };
+        template.write(&path).map_err(Error::Other)?;
+        println!("{}✓ Wrote {}{}", colors::GREEN, path.display(), colors::RESET);
+        return Ok(());
+    }
 
-        if self.usb_mode {
-            // USB: report ID 0x02
-            report[0] = 0x02;
-            report[1] = 0xFF; // Flag to enable edits
-            report[2] = 0xF7; // Flag for LEDs and "engines"? (idk translation)
-
-            // LED RGB (offset 45-47 for USB)
-            report[45] = r;
-            report[46] = g;
-            report[47] = b;
-        } else {
-            // Bluetooth: report ID 0x31
-            report[0] = 0x31;
-            report[1] = 0x02;
-            report[2] = 0xFF;
-            report[3] = 0xF7;
-
-            // LED RGB (offset 47-49 for Bluetooth)
-            report[47] = r;
-            report[48] = g;
-            report[49] = b;
-
-            // Calculate CRC32 for Bluetooth
-            let crc = calculate_crc32(&report[0..74]);
-            report[74] = (crc & 0xFF) as u8;
-            report[75] = ((crc >> 8) & 0xFF) as u8;
-            report[76] = ((crc >> 16) & 0xFF) as u8;
-            report[77] = ((crc >> 24) & 0xFF) as u8;
-        }
-
-        match self.device.write(&report) {
-            Ok(_) => {
-                self.last_color = (r, g, b);
-                self.send_count += 1;
-                Ok(())
+    // Dont flame me for this "ui" :3
+    // (only for people: not in JSON logs, and not with -q)
+    if args.log_format == logging::LogFormat::Human && args.quiet == 0 {
+        println!("\n{}{}╔══════════════════════════════════════╗{}", colors::BOLD, colors::MAGENTA, colors::RESET);
+        println!("{}{}║  DualSense Rainbow Lightbar          ║{}", colors::BOLD, colors::MAGENTA, colors::RESET);
+        println!("{}{}╚══════════════════════════════════════╝{}\n", colors::BOLD, colors::MAGENTA, colors::RESET);
+    }
+
+    let selection = HidSelection { usage: args.hid_usage, interface: args.hid_interface };
+    let (mut controller, mirrored) = match mock {
+        Some(transport) => (open_mock(transport), Vec::new()),
+        // A second simulated pad to mirror onto
+        None if (args.mock || args.dry_run) && args.all_controllers => {
+            (open_mock(mock_transport(args.dry_run)), vec![DualSenseController::mock(MockTransport::default())])
+        },
+        None if args.mock || args.dry_run => (open_mock(mock_transport(args.dry_run)), Vec::new()),
+        None if args.all_controllers => open_all_controllers(selection)?,
+        None => (open_controller(selection, device.as_deref())?, Vec::new()),
+    };
+    let mut devices = (!mirrored.is_empty()).then(|| DeviceManager::new(mirrored, args.hue_offset));
+    if let Some(devices) = &devices {
+        info!("{}Mirroring the effect to {} more controller(s), {}° apart{}\n",
+                 colors::GRAY, devices.len(), args.hue_offset, colors::RESET);
+    }
+
+    if let Some(target) = &args.trace_hid {
+        let tracer = match target {
+            Some(path) => {
+                info!("{}Tracing HID traffic to {}{}", colors::GRAY, path.display(), colors::RESET);
+                HidTracer::to_file(path)?
             },
-            Err(e) => {
-                self.error_count += 1;
-                Err(e.into())
-            }
-        }
+            None => HidTracer::stdout(),
+        };
+        controller.set_tracer(tracer);
+    }
+    if args.debug_reports {
+        controller.set_tracer(HidTracer::changes(args.show_input));
     }
 
-    fn get_stats(&self) -> (u64, u64) {
-        (self.send_count, self.error_count)
+    if let Some(path) = &args.record {
+        let recorder = CaptureWriter::create(path, controller.model())
+            .map_err(|e| Error::Other(format!("cannot create {}: {}", path.display(), e)))?;
+        controller.set_recorder(recorder);
+        info!("{}Recording output reports to {}{}", colors::GRAY, path.display(), colors::RESET);
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    {
+        let running = running.clone();
+        // Ctrl+C, and SIGTERM/SIGHUP on Unix (service managers, closing the terminal)
+        ctrlc::set_handler(move || running.store(false, Ordering::SeqCst)).map_err(|e| Error::Other(e.to_string()))?;
     }
-}
 
-// Function to calculate CRC32 (needed for Bluetooth)
-fn calculate_crc32(data: &[u8]) -> u32 {
-    const CRC32_TABLE: [u32; 256] = generate_crc32_table();
+    if let Some(path) = &args.replay {
+        if !matches!(args.command, None | Some(cli::Command::Run)) {
+            return Err(Error::Other("--replay plays a capture instead of the effect, not with other commands".into()));
+        }
+        return replay::run(&mut controller, path, &running);
+    }
 
-    let mut crc: u32 = 0xFFFFFFFF;
-    for &byte in data {
-        let index = ((crc ^ byte as u32) & 0xFF) as usize;
-        crc = (crc >> 8) ^ CRC32_TABLE[index];
+    match args.command {
+        Some(cli::Command::Feature { action }) => return feature::run(&mut controller, action),
+        Some(cli::Command::Trigger { action: cli::TriggerAction::Test { effect, side, duration } }) => {
+            let effect = triggers::resolve(&effect, &config.triggers).map_err(Error::Other)?;
+            return test_trigger(&mut controller, effect, side, Duration::from_secs(duration), &running);
+        },
+        Some(cli::Command::Game { game: cli::Game::Reaction { rounds } }) => {
+            return games::reaction(&mut controller, rounds, &running);
+        },
+        Some(cli::Command::Game { game: cli::Game::Simon }) => return games::simon(&mut controller, &running),
+        Some(cli::Command::Set { color }) => return oneshot::set(&mut controller, color),
+        Some(cli::Command::Off) => return oneshot::off(&mut controller),
+        Some(cli::Command::Status) => return oneshot::status(&mut controller, &running),
+        Some(cli::Command::Ctl { .. } | cli::Command::Init | cli::Command::List | cli::Command::Animation { .. }) => unreachable!("handled before opening the controller"),
+        #[cfg(feature = "haptics")]
+        Some(cli::Command::Haptics { .. }) => unreachable!("handled before opening the controller"),
+        #[cfg(target_os = "linux")]
+        Some(cli::Command::SetupUdev { .. }) => unreachable!("handled before opening the controller"),
+        Some(cli::Command::Run) | None => {},
     }
-    !crc
-}
 
-const fn generate_crc32_table() -> [u32; 256] {
-    let mut table = [0u32; 256];
-    let mut i = 0;
-    while i < 256 {
-        let mut crc = i as u32;
-        let mut j = 0;
-        while j < 8 {
-            if crc & 1 != 0 {
-                crc = (crc >> 1) ^ 0xEDB88320;
-            } else {
-                crc >>= 1;
+    // Catch mistakes in any profile now rather than when a rule fires hours later
+    for name in config.profiles.keys() {
+        profile_settings(&config, &base_settings, name).map_err(Error::Config)?;
+    }
+
+    // A pinned profile overrides the schedule
+    let mut scheduler = match &args.profile {
+        Some(_) => None,
+        None if config.schedule.is_empty() => None,
+        None => {
+            let scheduler = schedule::Scheduler::new(&config.schedule, config.location.as_ref()).map_err(Error::Config)?;
+            if let Some(name) = scheduler.profiles().find(|name| !config.profiles.contains_key(*name)) {
+                return Err(Error::Config(format!("schedule refers to unknown profile '{}'", name)));
+            }
+            if let Some(today) = scheduler.sun_today() {
+                info!("{}{}{}", colors::GRAY, today, colors::RESET);
             }
-            j += 1;
+            Some(scheduler)
+        },
+    };
+
+    // Focused application -> profile; these win over the schedule while the app has focus
+    let app_profiles = processes::profile_rules(&config.apps);
+    if let Some((_, name)) = app_profiles.iter().find(|(_, name)| !config.profiles.contains_key(name)) {
+        return Err(Error::Config(format!("[apps] refers to unknown profile '{}'", name)));
+    }
+    let mut foreground = (args.profile.is_none() && !app_profiles.is_empty()).then(foreground::ForegroundWatch::default);
+    // Running program -> profile, below the focused app and above the schedule
+    let process_profiles = processes::profile_rules(&config.processes);
+    if let Some((_, name)) = process_profiles.iter().find(|(_, name)| !config.profiles.contains_key(name)) {
+        return Err(Error::Config(format!("[processes] refers to unknown profile '{}'", name)));
+    }
+    let mut process_watch = (args.profile.is_none() && !process_profiles.is_empty()).then(|| {
+        let names: Vec<String> = process_profiles.iter().map(|(name, _)| name.clone()).collect();
+        processes::ProcessWatch::new(&names)
+    });
+
+    let mut scheduled_profile = scheduler.as_mut().and_then(|s| s.current()).map(str::to_string);
+    let mut app_profile: Option<String> = None;
+    let mut process_profile: Option<String> = None;
+    // --profile, or one applied over the control port
+    let mut pinned_profile = args.profile.clone();
+    let mut active_profile = pinned_profile.clone().or_else(|| scheduled_profile.clone());
+    let mut settings = match &active_profile {
+        Some(name) => profile_settings(&config, &base_settings, name).map_err(Error::Config)?,
+        None => base_settings.clone(),
+    };
+    if let Some(name) = &active_profile {
+        info!("{}Profile:{} {}", colors::GRAY, colors::RESET, name);
+    }
+    info!("{}Speed:{} {}°/frame  {}FPS:{} {}  {}Brightness:{} {:.0}%",
+             colors::GRAY, colors::RESET, settings.speed,
+             colors::GRAY, colors::RESET, fps,
+             colors::GRAY, colors::RESET, settings.brightness * 100.0);
+    if gamma != 1.0 {
+        info!("{}Gamma:{} {}", colors::GRAY, colors::RESET, gamma);
+    }
+
+    // Passthrough hides the pad from games, there is nobody left to cooperate with
+    let cooperate = !args.passthrough && (args.cooperate || config.cooperate.unwrap_or(false));
+    if args.passthrough {
+        let pad = passthrough::VirtualPad::create(controller.path()).map_err(Error::Other)?;
+        info!("{}Passthrough: games see a virtual Xbox 360 pad ({}){}", colors::GRAY, pad.description(), colors::RESET);
+        controller.set_passthrough(pad);
+    }
+    let other_writers = coexist::detect(controller.path());
+    for writer in &other_writers {
+        let how = if writer.holds_device { "has the controller open" } else { "is running" };
+        warn!("{}{}⚠ {} (pid {}) {}{}", colors::BOLD, colors::YELLOW, writer.name, writer.pid, how, colors::RESET);
+    }
+    if cooperate {
+        controller.set_cooperative();
+        info!("{}Cooperate mode: only the LEDs are written, at most every {} ms{}",
+                 colors::GRAY, COOPERATE_WRITE_INTERVAL.as_millis(), colors::RESET);
+    } else if !other_writers.is_empty() {
+        warn!("{}  Two programs writing the lightbar makes it flicker. Close the other one, turn off its\n  \
+               PlayStation controller support, or run with --cooperate to share the controller{}\n",
+              colors::YELLOW, colors::RESET);
+    }
+
+    info!("{}{} Starting effect...{}", colors::BOLD, colors::GREEN, colors::RESET);
+    info!("{}Press CTRL+C to exit{}\n", colors::GRAY, colors::RESET);
+
+    let input_events = args.show_input.then(|| controller.subscribe());
+
+    let bt_report_interval = args.bt_report_interval.or(config.bt_report_interval_ms).unwrap_or(0);
+    controller.set_bt_report_interval(Duration::from_millis(bt_report_interval));
+    config.write_retry.validate().map_err(Error::Config)?;
+    controller.set_retry_policy(config.write_retry.clone());
+    for mirrored in devices.iter_mut().flat_map(DeviceManager::controllers_mut) {
+        mirrored.set_bt_report_interval(Duration::from_millis(bt_report_interval));
+        mirrored.set_retry_policy(config.write_retry.clone());
+        if cooperate {
+            mirrored.set_cooperative();
         }
-        table[i] = crc;
-        i += 1;
     }
-    table
-}
+    let mut player_leds = PlayerLeds {
+        mask: settings.player_leds,
+        brightness: args.player_led_brightness,
+        // Fading would smear the chase into a blur
+        fade: !args.no_player_led_fade && !args.player_led_chase,
+    };
+    controller.set_player_leds(player_leds);
+    let mut chase = args.player_led_chase.then(chase::PlayerLedChase::default);
+
+    controller.set_triggers(settings.left_trigger, settings.right_trigger);
 
-// Converts HSV to RGB to create the rainbow effect
-fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (u8, u8, u8) {
-    let c = v * s;
-    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
-    let m = v - c;
-
-    let (r, g, b) = if h < 60.0 {
-        (c, x, 0.0)
-    } else if h < 120.0 {
-        (x, c, 0.0)
-    } else if h < 180.0 {
-        (0.0, c, x)
-    } else if h < 240.0 {
-        (0.0, x, c)
-    } else if h < 300.0 {
-        (x, 0.0, c)
+    let mut mic_led = MicLedScheduler::new(settings.mic_led);
+    let mute_presses = args.mic_blink_on_mute.then(|| controller.subscribe());
+
+    #[cfg(feature = "haptics")]
+    let haptic_bindings = if config.haptics.is_empty() || cooperate {
+        None
     } else {
-        (c, 0.0, x)
+        let bindings = haptics::HapticPlayer::open()
+            .and_then(|player| haptics::HapticBindings::load(player, &config.haptics).map_err(Error::Config));
+        match bindings {
+            Ok(bindings) => Some((bindings, controller.subscribe())),
+            Err(e) => {
+                error!("{}{}✗ Haptics disabled:{} {}", colors::BOLD, colors::RED, colors::RESET, e);
+                None
+            },
+        }
     };
 
-    (
-        ((r + m) * 255.0) as u8,
-        ((g + m) * 255.0) as u8,
-        ((b + m) * 255.0) as u8,
-    )
-}
+    #[cfg(target_os = "linux")]
+    let mut led_bridge = match &args.led_bridge {
+        Some(dir) => {
+            let root = dir.clone().unwrap_or_else(led_bridge::LedBridge::default_dir);
+            let bridge = led_bridge::LedBridge::create(&root)?;
+            info!("{}LED bridge at {}{}", colors::GRAY, bridge.path().display(), colors::RESET);
+            Some(bridge)
+        },
+        None => None,
+    };
 
-fn get_color_name(h: f32) -> (&'static str, &'static str) {
-    match h as u32 {
-        0..=30 => ("Red", colors::RED),
-        31..=90 => ("Yellow", colors::YELLOW),
-        91..=150 => ("Green", colors::GREEN),
-        151..=210 => ("Cyan", colors::CYAN),
-        211..=270 => ("Blue", colors::BLUE),
-        271..=330 => ("Magenta", colors::MAGENTA),
-        _ => ("Red", colors::RED),
+    // Pinning a profile opts out of the seasonal palettes too
+    let holidays_enabled = config.holiday_themes.unwrap_or(true) && !args.no_holiday_themes && args.profile.is_none();
+    let mut holiday_themes = holidays_enabled.then(|| {
+        let region = config.locale.as_ref().map(|l| l.to_ascii_uppercase()).or_else(holidays::region_from_env);
+        holidays::HolidayThemes::new(region)
+    });
+    if let Some(themes) = &mut holiday_themes
+        && themes.refresh()
+    {
+        announce_holiday(themes);
     }
-}
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Enable ANSI escape codes on Windows
-    #[cfg(windows)]
-    {
-        use std::os::windows::io::AsRawHandle;
-        use std::io::stdout;
+    let target_fps = fps as f32;
+    let mut frame_duration = Duration::from_secs_f32(1.0 / target_fps);
+    let mut adaptive = (config.adaptive_fps.enabled && !args.no_adaptive_fps)
+        .then(|| adaptive::AdaptiveRate::new(&config.adaptive_fps, fps))
+        .transpose().map_err(Error::Config)?;
+    controller.set_target_fps(target_fps);
+    // Crossfade when a profile or holiday changes the running effect, or something
+    // else takes over the lightbar
+    let switch_fade = Duration::from_millis(args.transition.or(config.transition_ms).unwrap_or(1000));
+    let mut transition = effects::ColorTransition::new(switch_fade);
+    let correction = ColorCorrection::new(gamma);
+    let mut night = match (&config.night, args.night) {
+        (Some(night), _) => Some(night.clone()),
+        (None, true) => Some(night::NightConfig::default()),
+        (None, false) => None,
+    }
+    .map(|config| night::NightMode::new(&config).map_err(|e| Error::Config(format!("[night]: {}", e))))
+    .transpose()?;
+    if let Some(night) = &night {
+        info!("{}Night mode on, {:.0} K right now{}", colors::GRAY, night.kelvin(), colors::RESET);
+    }
 
-        unsafe {
-            let handle = stdout().as_raw_handle();
-            let mut mode: u32 = 0;
-            if winapi::um::consoleapi::GetConsoleMode(handle as *mut _, &mut mode) != 0 {
-                winapi::um::consoleapi::SetConsoleMode(
-                    handle as *mut _,
-                    mode | winapi::um::wincon::ENABLE_VIRTUAL_TERMINAL_PROCESSING,
-                );
-            }
-        }
+    let setup = effects::EffectSetup {
+        reactive: reactive::ReactiveMapping::new(&config.reactive).map_err(|e| Error::Config(format!("[reactive]: {}", e)))?,
+        motion: motion::MotionSettings::new(&config.motion).map_err(|e| Error::Config(format!("[motion]: {}", e)))?,
+        system: sysmon::SystemMonitor::new(&config.system).map_err(|e| Error::Config(format!("[system]: {}", e)))?,
+        lava: lava::LavaSettings::new(&config.lava).map_err(|e| Error::Config(format!("[lava]: {}", e)))?,
+        animation,
+        #[cfg(feature = "scripting")]
+        script: args.script.as_deref().map(script::Script::load).transpose().map_err(Error::Other)?,
+        #[cfg(feature = "plugins")]
+        plugin: args.plugin.as_deref().map(plugin::Plugin::load).transpose().map_err(Error::Other)?,
+    };
+    let mut shuffle = match args.shuffle {
+        true => Some(shuffle::Shuffle::new(&config.shuffle, args.shuffle_interval).map_err(Error::Config)?),
+        false => None,
+    };
+    let mut effect_kind = match (&shuffle, &setup.animation) {
+        (Some(shuffle), _) => shuffle.pick(None),
+        (None, Some(_)) => EffectKind::Animation,
+        (None, None) => settings.effect,
+    };
+    #[cfg(feature = "scripting")]
+    if setup.script.is_some() {
+        effect_kind = EffectKind::Script;
+    }
+    #[cfg(feature = "plugins")]
+    if setup.plugin.is_some() {
+        effect_kind = EffectKind::Plugin;
+    }
+    // A profile's own palette wins over the holiday one
+    let build_effect = |kind: EffectKind, settings: &profile::Settings, themes: Option<&holidays::HolidayThemes>| {
+        let holiday = themes.and_then(|themes| themes.palette());
+        kind.build(settings.speed * target_fps, settings.palette.clone().or_else(|| holiday.cloned()), &setup)
+    };
+    let mut effect = EffectPlayer::new(build_effect(effect_kind, &settings, holiday_themes.as_ref()), canvas::DUALSENSE);
+    if shuffle.is_some() {
+        info!("{}Shuffling effects, starting with {}{}", colors::GRAY, effect_kind.name(), colors::RESET);
+    }
+    if let Some(animation) = &setup.animation {
+        info!("{}Playing animation {} ({:.2}s){}", colors::GRAY, animation.name, animation.duration().as_secs_f32(), colors::RESET);
     }
+    #[cfg(feature = "scripting")]
+    if let Some(script) = &setup.script {
+        info!("{}Running script {}{}", colors::GRAY, script.name, colors::RESET);
+    }
+    #[cfg(feature = "plugins")]
+    if let Some(plugin) = &setup.plugin {
+        info!("{}Running plugin {}{}", colors::GRAY, plugin.name, colors::RESET);
+    }
+    let mut last_frame = Instant::now();
+    let started = last_frame;
 
-    // Dont flame me for this "ui" :3
-    println!("\n{}{}╔══════════════════════════════════════╗{}", colors::BOLD, colors::MAGENTA, colors::RESET);
-    println!("{}{}║  DualSense Rainbow Lightbar          ║{}", colors::BOLD, colors::MAGENTA, colors::RESET);
-    println!("{}{}╚══════════════════════════════════════╝{}\n", colors::BOLD, colors::MAGENTA, colors::RESET);
+    let mut saver = battery_saver::BatterySaver::new(battery_saver::BatterySaverConfig {
+        enabled: config.battery_saver.enabled && !args.no_battery_saver,
+        ..config.battery_saver.clone()
+    }).map_err(Error::Config)?;
+    // Last frame the effect painted, held while the battery saver freezes the animation
+    let mut held_frame = canvas::Canvas::new(canvas::DUALSENSE);
+    // The effect drew on the player LEDs last frame
+    let mut effect_player_leds = false;
+
+    let idle_dim = args.idle_dim.or(config.idle_dim_secs).filter(|&secs| secs > 0.0).map(Duration::from_secs_f32);
+    let idle_off = args.idle_off.or(config.idle_off_minutes).filter(|&minutes| minutes > 0.0);
+    let idle_off = idle_off.map(|minutes| Duration::from_secs_f32(minutes * 60.0));
+    let idle_dim_level = config.idle_dim_brightness.unwrap_or(0.3);
+    if !(0.0..=1.0).contains(&idle_dim_level) {
+        return Err(Error::Config(format!("idle_dim_brightness {} is outside 0.0-1.0", idle_dim_level)));
+    }
+    let mut idle = (idle_dim.is_some() || idle_off.is_some())
+        .then(|| (idle::IdleTimer::new(idle_dim, idle_dim_level, idle_off), controller.subscribe()));
 
-    let mut controller = DualSenseController::new()?;
+    let mut reconnector = Some(Duration::from_secs_f32(args.watchdog.max(0.0)))
+        .filter(|window| !window.is_zero())
+        .map(reconnect::Reconnector::new);
+    // Errors are reported once per outage, not on every frame until the pad is back
+    let mut outage_reported = false;
 
-    println!("{}{} Starting effect...{}", colors::BOLD, colors::GREEN, colors::RESET);
-    println!("{}Press CTRL+C to exit{}\n", colors::GRAY, colors::RESET);
+    let mut game_watch = (!config.pause_for.is_empty()).then(|| processes::ProcessWatch::new(&config.pause_for));
+
+    #[cfg(feature = "notifications")]
+    let mut notifier = config.notifications.clone().map(notifications::Notifier::new);
+    #[cfg(feature = "notifications")]
+    if let Some(notifier) = &mut notifier {
+        notifier.connected(if controller.is_usb() { "USB" } else { "Bluetooth" });
+    }
+
+    let mut stats_csv = match &args.stats_csv {
+        Some(path) => {
+            let csv = stats::StatsCsv::open(path).map_err(|e| Error::Other(format!("cannot open {}: {}", path.display(), e)))?;
+            info!("{}Appending stats to {}{}", colors::GRAY, path.display(), colors::RESET);
+            Some(csv)
+        },
+        None => None,
+    };
+    let mut statsd = match &config.statsd {
+        Some(statsd) => {
+            let emitter = stats::StatsdEmitter::open(statsd).map_err(|e| Error::Other(format!("statsd {}: {}", statsd.address, e)))?;
+            info!("{}Sending stats to StatsD at {}{}", colors::GRAY, statsd.address, colors::RESET);
+            Some(emitter)
+        },
+        None => None,
+    };
+    let mut stats_sampler = (stats_csv.is_some() || statsd.is_some())
+        .then(|| stats::Sampler::new(Duration::from_secs(args.stats_interval.max(1))));
+
+    let hotkeys = keys::Hotkeys::start();
+    let mut tuner = tuning::Tuner::default();
+    if hotkeys.is_some() {
+        info!("{}Keys: +/- adjust speed, Tab switches to brightness/saturation, p saves to the profile{}", colors::GRAY, colors::RESET);
+    }
+
+    // A daemon always has a socket, that's how it is reached
+    let socket = match (&args.socket, args.daemon) {
+        (Some(path), _) => Some(path.clone().unwrap_or_else(daemon::default_socket)),
+        (None, true) => Some(daemon::default_socket()),
+        (None, false) => None,
+    };
+    #[cfg(feature = "http-api")]
+    let http_api = args.http.is_some();
+    #[cfg(not(feature = "http-api"))]
+    let http_api = false;
+    let remote = args.control.is_some() || socket.is_some() || http_api || args.openrgb.is_some() || config.assistant.is_some() || config.discord.is_some() || config.mqtt.is_some();
+    let control = remote.then(control::ControlServer::default);
+    if let (Some(server), Some(address)) = (&control, &args.control) {
+        let address = address.as_deref().unwrap_or(control::DEFAULT_ADDRESS);
+        let bound = server.listen(address).map_err(|e| Error::Other(format!("control port {}: {}", address, e)))?;
+        info!("{}Listening for commands on {}{}", colors::GRAY, bound, colors::RESET);
+    }
+    let _socket = match (&control, &socket) {
+        (Some(server), Some(path)) => {
+            let guard = server.listen_socket(path).map_err(|e| Error::Other(format!("socket {}: {}", path.display(), e)))?;
+            info!("{}Listening for commands on {}{}", colors::GRAY, guard.path().display(), colors::RESET);
+            Some(guard)
+        },
+        _ => None,
+    };
+    if let (Some(server), Some(address)) = (&control, &args.openrgb) {
+        let bound = openrgb::start(address.as_deref().unwrap_or(openrgb::DEFAULT_ADDRESS), server.sender()).map_err(Error::Other)?;
+        info!("{}OpenRGB SDK server on {}{}", colors::GRAY, bound, colors::RESET);
+    }
+    // Feeds the HTTP API's /events WebSocket
+    #[cfg(feature = "http-api")]
+    let event_hub = match (&control, &args.http) {
+        (Some(server), Some(address)) => {
+            let hub = websocket::EventHub::default();
+            let bound = rest::start(address.as_deref().unwrap_or(rest::DEFAULT_ADDRESS), args.http_origin.clone(), server.sender(), hub.clone()).map_err(Error::Other)?;
+            info!("{}HTTP API on http://{}/status{}", colors::GRAY, bound, colors::RESET);
+            Some((hub, controller.subscribe()))
+        },
+        _ => None,
+    };
+    #[cfg(feature = "http-api")]
+    let mut hub_responding = None;
+    if let (Some(server), Some(api)) = (&control, &config.assistant) {
+        let bound = assistant::start(api, server.sender()).map_err(Error::Other)?;
+        info!("{}Assistant API on http://{}/api{}", colors::GRAY, bound, colors::RESET);
+    }
+    if let (Some(server), Some(bot)) = (&control, &config.discord) {
+        discord::start(bot, server.sender()).map_err(Error::Other)?;
+    }
+    if let (Some(server), Some(mqtt)) = (&control, &config.mqtt) {
+        mqtt::start(mqtt, server.sender()).map_err(|e| Error::Other(format!("[mqtt]: {}", e)))?;
+        info!("{}Home Assistant light over MQTT at {}{}", colors::GRAY, mqtt.broker, colors::RESET);
+    }
+    // Turned off over the control port, the assistant API, Discord or MQTT
+    let mut powered = true;
+    let meter_messages = args.meter_stdin.then(meter::read_stdin);
+    let mut meter: Option<meter::Reading> = None;
+    let meter_start = Instant::now();
+
+    // One source of colors at a time, and the command line wins over the config
+    if config.dmx.is_some() && (args.follow.is_some() || args.openrgb_mirror.is_some()) {
+        let flag = if args.follow.is_some() { "--follow" } else { "--openrgb-mirror" };
+        warn!("{}{}⚠ [dmx] in the config is ignored while {} is given{}", colors::BOLD, colors::YELLOW, flag, colors::RESET);
+    }
+    let mut follower = if let Some(address) = &args.follow {
+        let address = address.as_deref().unwrap_or(follow::DEFAULT_ADDRESS);
+        let follower = follow::Follower::listen(address).map_err(|e| Error::Other(format!("follow {}: {}", address, e)))?;
+        info!("{}Following colors sent to {} (UDP and TCP){}", colors::GRAY, follower.address(), colors::RESET);
+        Some(follower)
+    } else if let Some(device) = &args.openrgb_mirror {
+        let server = args.openrgb_server.as_deref().unwrap_or(openrgb::DEFAULT_SERVER);
+        let follower = openrgb::mirror(server, device).map_err(|e| Error::Other(format!("OpenRGB server {}: {}", server, e)))?;
+        info!("{}Mirroring '{}' from OpenRGB on {}{}", colors::GRAY, device, follower.address(), colors::RESET);
+        Some(follower)
+    } else if let Some(dmx) = &config.dmx {
+        let follower = dmx::listen(dmx).map_err(|e| Error::Other(format!("[dmx]: {}", e)))?;
+        let (universe, channel) = (dmx.universe, dmx.channel);
+        info!("{}DMX fixture at universe {}, channels {}-{}{}", colors::GRAY, universe, channel, channel + 2, colors::RESET);
+        Some(follower)
+    } else {
+        None
+    };
+    let mut json_watches = match config.watch_json.is_empty() {
+        true => None,
+        false => {
+            info!("{}Watching {} JSON endpoint(s){}", colors::GRAY, config.watch_json.len(), colors::RESET);
+            Some(watch_json::JsonWatches::start(&config.watch_json).map_err(Error::Config)?)
+        },
+    };
+    #[cfg(feature = "audio")]
+    let audio_mode = match (args.audio, args.music, args.beat) {
+        (_, true, _) => Some(audio::Mode::Music),
+        (_, _, true) => Some(audio::Mode::Beat),
+        (true, _, _) => Some(audio::Mode::Bands),
+        _ => None,
+    };
+    #[cfg(feature = "audio")]
+    let audio = match audio_mode {
+        Some(mode) => {
+            let audio = audio::AudioReactive::start(&config.audio, mode).map_err(|e| Error::Other(format!("audio: {}", e)))?;
+            info!("{}Reacting to audio from {}{}", colors::GRAY, audio.device(), colors::RESET);
+            Some(audio)
+        },
+        None => None,
+    };
+    let mut screen = match &args.screen {
+        Some(region) => {
+            let screen = screen::ScreenSync::start(&config.screen, region.as_deref()).map_err(|e| Error::Other(format!("screen: {}", e)))?;
+            info!("{}Following the colors of {}{}", colors::GRAY, screen.description(), colors::RESET);
+            Some(screen)
+        },
+        None => None,
+    };
+    let mut battery_alert = match &config.battery_alert {
+        Some(alert) => Some(battery_alert::BatteryAlert::new(alert).map_err(Error::Config)?),
+        None => None,
+    };
+    let mut unread = match &config.unread {
+        Some(unread) => Some(unread::UnreadIndicator::start(unread).map_err(Error::Config)?),
+        None => None,
+    };
+    let streamer = match &args.stream_to {
+        Some(address) => Some(follow::Streamer::open(address).map_err(|e| Error::Other(format!("stream to {}: {}", address, e)))?),
+        None => None,
+    };
+    // Unchanged colors are repeated now and then so followers don't time out
+    let mut last_streamed: Option<((u8, u8, u8), Instant)> = None;
 
-    let mut hue = 0.0;
-    let speed = 1.5; // Slower speed for smoother transition
-    let target_fps = 60.0;
-    let frame_duration = Duration::from_secs_f32(1.0 / target_fps);
+    // Set by apply-profile so re-applying the active profile drops live tweaks
+    let mut reload_profile = false;
+    // Saving the config applies its defaults and profiles without a restart
+    let mut config_watch = args.config.clone().or_else(config::Config::default_path).map(config::ConfigWatch::new);
 
-    let mut frame_count = 0;
     let mut last_log = Instant::now();
     let log_interval = Duration::from_secs(2);
 
     let start_time = Instant::now();
+    let mut title = title::TitleBar::new();
 
-    loop {
+    while running.load(Ordering::SeqCst) {
         let frame_start = Instant::now();
 
-        let (r, g, b) = hsv_to_rgb(hue, 1.0, 1.0);
+        if let Err(e) = controller.poll_input()
+            && !std::mem::replace(&mut outage_reported, true)
+        {
+            error!("{}{}✗ Input error:{} {}", colors::BOLD, colors::RED, colors::RESET, e);
+            #[cfg(feature = "notifications")]
+            if let Some(notifier) = &mut notifier {
+                notifier.disconnected();
+            }
+        }
+
+        #[cfg(feature = "notifications")]
+        if let Some(notifier) = &mut notifier {
+            notifier.battery(controller.battery());
+        }
+
+        let mode = match (controller.path(), controller.is_usb()) {
+            ("mock", _) => "Simulated",
+            (_, true) => "USB",
+            (_, false) => "Bluetooth",
+        };
+        let responding = controller.stalled_for() < Duration::from_secs(1);
+        title.update(&title::Connection { mode, responding, battery: controller.battery() });
+        #[cfg(feature = "http-api")]
+        if let Some((hub, events)) = &event_hub {
+            events.try_iter().for_each(|event| hub.input(&event));
+            if hub_responding != Some(responding) {
+                hub.connection(responding);
+                hub_responding = Some(responding);
+            }
+        }
+
+        if let Some(events) = &input_events {
+            for event in events.try_iter() {
+                info!("{}[input]{} {:?}", colors::GRAY, colors::RESET, event);
+            }
+        }
+
+        #[cfg(feature = "haptics")]
+        if let Some((bindings, events)) = &haptic_bindings {
+            events.try_iter().for_each(|event| bindings.handle(&event));
+        }
+
+        if let Some(events) = &mute_presses
+            && events.try_iter().any(|event| event == InputEvent::ButtonDown(Button::Mute))
+        {
+            mic_led.double_blink(frame_start);
+        }
+        let (r, g, b) = controller.lightbar();
+        controller.set_mute_led(mic_led.frame(frame_start, rgb_to_hue(r, g, b)));
+        if args.rumble_demo {
+            let (low, high) = rumble::pulse(rgb_to_hue(r, g, b));
+            controller.set_rumble(low, high);
+        }
+
+        for key in hotkeys.iter().flat_map(|keys| keys.pressed()) {
+            match tuner.key(key, &mut settings) {
+                Some(tuning::Tuned::Changed(param)) => {
+                    if param == tuning::Param::Speed {
+                        effect.set_speed(settings.speed * target_fps);
+                    }
+                    info!("{}{}⚙ {} {:.2}{}", colors::BOLD, colors::CYAN, param.name(), param.get(&settings), colors::RESET);
+                },
+                Some(tuning::Tuned::Selected(param)) => {
+                    info!("{}⚙ Adjusting {} ({:.2}){}", colors::GRAY, param.name(), param.get(&settings), colors::RESET);
+                },
+                Some(tuning::Tuned::Save) => match (&active_profile, args.config.clone().or_else(config::Config::default_path)) {
+                    (Some(name), Some(path)) => match tuning::save_to_profile(&path, name, &settings) {
+                        Ok(()) => info!("{}{}✓ Saved to profile '{}' in {}{}", colors::BOLD, colors::GREEN, name, path.display(), colors::RESET),
+                        Err(e) => error!("{}{}✗ Cannot save:{} {}", colors::BOLD, colors::RED, colors::RESET, e),
+                    },
+                    (None, _) => info!("{}No profile active, start with --profile <NAME> to save tuned values{}", colors::GRAY, colors::RESET),
+                    (_, None) => error!("{}{}✗ Cannot save:{} no config directory", colors::BOLD, colors::RED, colors::RESET),
+                },
+                None => {},
+            }
+        }
+
+        for request in control.iter().flat_map(|server| server.requests()) {
+            // A remote change counts as someone using the lights
+            if let Some((timer, _)) = &mut idle {
+                timer.activity();
+            }
+            if let control::ControlCommand::Status = request.command {
+                request.reply_status(control::Status {
+                    on: powered,
+                    brightness: settings.brightness,
+                    color: controller.lightbar(),
+                    effect: effect_kind,
+                    profile: active_profile.clone(),
+                    stats: controller.stats(),
+                });
+                continue;
+            }
+            let result = match &request.command {
+                control::ControlCommand::Effect(kind) => {
+                    effect_kind = *kind;
+                    settings.effect = *kind;
+                    effect.switch(build_effect(effect_kind, &settings, holiday_themes.as_ref()), switch_fade);
+                    Ok(())
+                },
+                control::ControlCommand::SetColor { color, instant } => {
+                    effect_kind = EffectKind::Solid;
+                    settings.effect = EffectKind::Solid;
+                    settings.palette = Some(palette::Palette::new(vec![*color]));
+                    let fade = if *instant { Duration::ZERO } else { switch_fade };
+                    effect.switch(build_effect(effect_kind, &settings, holiday_themes.as_ref()), fade);
+                    Ok(())
+                },
+                control::ControlCommand::SetParams(params) => params.iter().try_for_each(|param| {
+                    match *param {
+                        control::Setting::Level(param, value) => {
+                            param.apply(&mut settings, value)?;
+                            if param == tuning::Param::Speed {
+                                effect.set_speed(settings.speed * target_fps);
+                            }
+                        },
+                        control::Setting::PlayerLeds(mask) => {
+                            settings.player_leds = mask;
+                            player_leds.mask = mask;
+                            if chase.is_none() {
+                                controller.set_player_leds(player_leds);
+                            }
+                        },
+                        control::Setting::MicLed(pattern) => {
+                            settings.mic_led = pattern;
+                            mic_led.set_pattern(pattern);
+                        },
+                    }
+                    Ok(())
+                }),
+                control::ControlCommand::SetPalette(palette) => {
+                    settings.palette = palette.clone();
+                    effect.switch(build_effect(effect_kind, &settings, holiday_themes.as_ref()), switch_fade);
+                    Ok(())
+                },
+                control::ControlCommand::ApplyProfile(Some(name)) if !config.profiles.contains_key(name) => {
+                    Err(format!("unknown profile '{}'", name))
+                },
+                control::ControlCommand::ApplyProfile(name) => {
+                    pinned_profile = name.clone();
+                    reload_profile = true;
+                    Ok(())
+                },
+                control::ControlCommand::Power(on) => {
+                    if *on && !powered {
+                        controller.set_player_leds(player_leds);
+                    }
+                    powered = *on;
+                    Ok(())
+                },
+                control::ControlCommand::Status => unreachable!("answered above"),
+                control::ControlCommand::Stop => {
+                    info!("{}{}■ Stop requested{}", colors::BOLD, colors::YELLOW, colors::RESET);
+                    running.store(false, Ordering::SeqCst);
+                    Ok(())
+                },
+                control::ControlCommand::Meter(reading) => {
+                    meter = *reading;
+                    if meter.is_none() {
+                        controller.set_player_leds(player_leds);
+                    }
+                    Ok(())
+                },
+            };
+            request.reply(result);
+        }
+
+        if let Some(messages) = &meter_messages {
+            for reading in messages.try_iter() {
+                meter = reading;
+                if meter.is_none() {
+                    controller.set_player_leds(player_leds);
+                }
+            }
+        }
+
+        // Polled even while paused or dark, so a rule due meanwhile is in effect afterwards
+        if let Some(name) = scheduler.as_mut().and_then(|s| s.poll()) {
+            scheduled_profile = Some(name.to_string());
+        }
+
+        if let Some(watch) = &mut game_watch
+            && watch.refresh()
+        {
+            match watch.found() {
+                Some(game) => info!("{}{}⏸ {} is running, pausing LED control{}", colors::BOLD, colors::YELLOW, game, colors::RESET),
+                None => {
+                    info!("{}{}▶ Resuming LED control{}", colors::BOLD, colors::GREEN, colors::RESET);
+                    controller.resend();
+                },
+            }
+        }
+
+        // Leave the lightbar to the game, and keep the effect where it was
+        if game_watch.as_ref().is_some_and(|watch| watch.found().is_some()) {
+            last_frame = frame_start;
+            thread::sleep(frame_duration);
+            continue;
+        }
+
+        if let Some((timer, events)) = &mut idle {
+            if events.try_iter().any(|event| idle::IdleTimer::is_activity(&event)) {
+                timer.activity();
+            }
+            match timer.update() {
+                Some(idle::IdleState::Dimmed) => info!("{}{}🌙 No input for a while, dimming the lights{}", colors::BOLD, colors::GRAY, colors::RESET),
+                Some(idle::IdleState::Asleep) => info!("{}{}💤 No input for a while, lights off until the next press{}", colors::BOLD, colors::GRAY, colors::RESET),
+                Some(idle::IdleState::Active) => {
+                    info!("{}{}☀ Input detected, lights back on{}", colors::BOLD, colors::GREEN, colors::RESET);
+                    controller.set_player_leds(player_leds);
+                },
+                None => {},
+            }
+        }
+
+        // Asleep or switched off: everything dark, and the effect picks up where it stopped
+        if !powered || idle.as_ref().is_some_and(|(timer, _)| timer.asleep()) {
+            controller.set_mute_led(MuteLed::Off);
+            controller.set_rumble(0, 0);
+            controller.set_player_leds(PlayerLeds { mask: 0, ..player_leds });
+            // Retried every frame in case the rate limit held it back
+            let (r, g, b) = transition.apply(effects::ColorSource::Dark, (0, 0, 0), frame_start.duration_since(last_frame));
+            let _ = controller.set_lightbar(r, g, b);
+            sync_devices(&mut devices, &controller);
+            last_frame = frame_start;
+            thread::sleep(frame_duration);
+            continue;
+        }
+
+        let dt = frame_start.duration_since(last_frame);
+        last_frame = frame_start;
+
+        if let Some(themes) = &mut holiday_themes
+            && themes.refresh()
+        {
+            announce_holiday(themes);
+            effect.switch(build_effect(effect_kind, &settings, holiday_themes.as_ref()), switch_fade);
+        }
+
+        if let Some(watch) = &mut foreground
+            && watch.refresh()
+        {
+            debug!("{}In focus: {}{}", colors::GRAY, watch.app().unwrap_or("nothing"), colors::RESET);
+            app_profile = watch
+                .app()
+                .and_then(|app| app_profiles.iter().find(|(name, _)| processes::glob(name, app)))
+                .map(|(_, profile)| profile.clone());
+        }
+        if let Some(watch) = &mut process_watch
+            && watch.refresh()
+        {
+            debug!("{}Running: {}{}", colors::GRAY, watch.found().unwrap_or("none of the [processes]"), colors::RESET);
+            process_profile = watch
+                .found()
+                .and_then(|found| process_profiles.iter().find(|(name, _)| name == found))
+                .map(|(_, profile)| profile.clone());
+        }
+
+        if let Some(reloaded) = config_watch.as_mut().and_then(|watch| watch.poll()) {
+            // Everything is checked before anything is swapped, a half-edited file changes nothing
+            let checked = reloaded.and_then(|new| {
+                let base = default_settings(&built_in, &new, &overrides)?;
+                for name in new.profiles.keys() {
+                    profile_settings(&new, &base, name)?;
+                }
+                let in_use = pinned_profile.as_deref().into_iter()
+                    .chain(app_profiles.iter().chain(&process_profiles).map(|(_, profile)| profile.as_str()))
+                    .chain(scheduler.iter().flat_map(|scheduler| scheduler.profiles()));
+                if let Some(name) = in_use.into_iter().find(|name| !new.profiles.contains_key(*name)) {
+                    return Err(format!("profile '{}' is still in use", name));
+                }
+                Ok((new, base))
+            });
+            match checked {
+                Ok((new, base)) => {
+                    let path = config_watch.as_ref().map(|watch| watch.path().display().to_string()).unwrap_or_default();
+                    info!("{}{}⟳ Reloaded {}{}", colors::BOLD, colors::CYAN, path, colors::RESET);
+                    config.defaults = new.defaults;
+                    config.profiles = new.profiles;
+                    config.triggers = new.triggers;
+                    base_settings = base;
+                    reload_profile = true;
+                },
+                Err(e) => error!("{}{}✗ Config not reloaded:{} {}", colors::BOLD, colors::RED, colors::RESET, e),
+            }
+        }
+
+        let wanted = pinned_profile.as_ref().or(app_profile.as_ref()).or(process_profile.as_ref()).or(scheduled_profile.as_ref());
+        if wanted != active_profile.as_ref() || reload_profile {
+            reload_profile = false;
+            match wanted {
+                Some(name) => info!("{}{} Switching to profile {}{}", colors::BOLD, colors::CYAN, name, colors::RESET),
+                None => info!("{}{} Back to the default settings{}", colors::BOLD, colors::CYAN, colors::RESET),
+            }
+            settings = match wanted {
+                Some(name) => profile_settings(&config, &base_settings, name).map_err(Error::Config)?,
+                None => base_settings.clone(),
+            };
+            active_profile = wanted.cloned();
+
+            player_leds.mask = settings.player_leds;
+            if chase.is_none() {
+                controller.set_player_leds(player_leds);
+            }
+            controller.set_triggers(settings.left_trigger, settings.right_trigger);
+            mic_led.set_pattern(settings.mic_led);
+
+            // Shuffling, animations, scripts and plugins pick the effect themselves
+            if shuffle.is_none() && !setup.plays_file() {
+                effect_kind = settings.effect;
+            }
+            effect.switch(build_effect(effect_kind, &settings, holiday_themes.as_ref()), switch_fade);
+        }
+
+        if let Some(next) = shuffle.as_mut().and_then(|s| s.poll(effect_kind)) {
+            info!("{}{} Shuffling to {}{}", colors::BOLD, colors::CYAN, next.name(), colors::RESET);
+            effect_kind = next;
+            let crossfade = shuffle.as_ref().map_or(switch_fade, |s| s.crossfade());
+            effect.switch(build_effect(effect_kind, &settings, holiday_themes.as_ref()), crossfade);
+        }
+        let holiday = holiday_themes.as_ref().and_then(|themes| themes.active());
+
+        if let Some(active) = saver.update(controller.is_usb(), controller.battery()) {
+            if active {
+                info!("{}{}🔋 Battery low, saver on: dimmer, slower updates{}", colors::BOLD, colors::YELLOW, colors::RESET);
+                controller.set_throttle(Some(saver.write_interval()));
+            } else {
+                info!("{}{}🔋 Battery saver off{}", colors::BOLD, colors::GREEN, colors::RESET);
+                controller.set_throttle(None);
+            }
+        }
+
+        if !saver.holds_color() {
+            if let Some(state) = controller.state() {
+                effect.input(&state);
+            }
+            held_frame = effect.render(dt);
+        }
+        // LEDs the effect painted besides the lightbar, unless a bar or the chase has them
+        match held_frame.player_mask() {
+            Some(mask) if meter.is_none() && chase.is_none() => {
+                controller.set_player_leds(PlayerLeds { mask, fade: false, ..player_leds });
+                effect_player_leds = true;
+            },
+            // Switched away from an effect that painted them
+            None if effect_player_leds && chase.is_none() => {
+                controller.set_player_leds(player_leds);
+                effect_player_leds = false;
+            },
+            _ => {},
+        }
+        if let Some(mute_led) = held_frame.mute_led() {
+            controller.set_mute_led(mute_led);
+        }
+        let (r, g, b) = effects::saturate(held_frame.lightbar, settings.saturation);
+        #[cfg(feature = "audio")]
+        let (r, g, b) = match &audio {
+            Some(audio) => audio.reaction(dt).apply((r, g, b)),
+            None => (r, g, b),
+        };
+        let idle_brightness = idle.as_ref().map_or(1.0, |(timer, _)| timer.brightness());
+        let brightness = settings.brightness * saver.brightness() * idle_brightness;
+        if let Some(night) = &mut night {
+            night.update();
+        }
+        let correct = |color: (u8, u8, u8)| {
+            let color = night.as_ref().map_or(color, |night| night.apply(color));
+            correction.apply(color, brightness)
+        };
+        let mut color = correct((r, g, b));
+        let mut source = effects::ColorSource::Effect;
+
+        if let Some((r, g, b)) = screen.as_mut().and_then(|screen| screen.poll(dt)) {
+            color = correct((r, g, b));
+            source = effects::ColorSource::Screen;
+        }
 
-        match controller.set_lightbar(r, g, b) {
+        if let Some((r, g, b)) = json_watches.as_mut().and_then(|watches| watches.poll()) {
+            color = correct((r, g, b));
+            source = effects::ColorSource::JsonWatch;
+        }
+
+        if let Some((r, g, b)) = follower.as_mut().and_then(|follower| follower.poll()) {
+            color = correct((r, g, b));
+            source = effects::ColorSource::Follower;
+        }
+
+        if let Some(reading) = meter {
+            let ((r, g, b), mask) = meter::render(reading, frame_start, meter_start);
+            color = correct((r, g, b));
+            source = effects::ColorSource::Meter;
+            controller.set_player_leds(PlayerLeds { mask, ..player_leds });
+        }
+
+        if let Some(((r, g, b), level)) = unread.as_mut().and_then(|unread| unread.overlay(frame_start)) {
+            color = effects::blend_rgb(color, correct((r, g, b)), level);
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(manual) = led_bridge.as_mut().and_then(|bridge| bridge.poll()) {
+            color = manual;
+            source = effects::ColorSource::LedBridge;
+        }
+
+        color = transition.apply(source, color, dt);
+
+        if let Some(streamer) = &streamer
+            && last_streamed.is_none_or(|(last, at)| last != color || at.elapsed() >= Duration::from_secs(1))
+        {
+            streamer.send(color);
+            last_streamed = Some((color, frame_start));
+        }
+
+        // This pad's own business, so after streaming, and without a transition: blinks
+        // that fade in and out would be missed
+        if let Some(alert) = battery_alert.as_mut().and_then(|alert| alert.overlay(controller.battery(), frame_start)) {
+            color = alert;
+        }
+        let (r, g, b) = color;
+        #[cfg(feature = "http-api")]
+        if let Some((hub, _)) = &event_hub
+            && controller.lightbar() != color
+        {
+            hub.color(color);
+        }
+        let written = controller.set_lightbar(r, g, b);
+        trace!(r, g, b, "{}Lightbar ({:3},{:3},{:3}){}", colors::GRAY, r, g, b, colors::RESET);
+        if let Some(adaptive) = &mut adaptive
+            && let Some(rate) = adaptive.update(controller.take_frame_latency())
+        {
+            let latency = adaptive.latency().as_secs_f64() * 1000.0;
+            if adaptive.frame_duration() > frame_duration {
+                warn!("{}{}⚠ Writes take {:.1} ms, lowering the frame rate to {} FPS{}", colors::BOLD, colors::YELLOW, latency, rate, colors::RESET);
+            } else {
+                info!("{}Writes keep up again ({:.1} ms), frame rate back up to {} FPS{}", colors::GRAY, latency, rate, colors::RESET);
+            }
+            frame_duration = adaptive.frame_duration();
+            controller.set_target_fps(rate);
+        }
+        sync_devices(&mut devices, &controller);
+
+        #[cfg(feature = "notifications")]
+        if let Some(notifier) = &mut notifier {
+            notifier.write_result(written.is_ok());
+        }
+
+        match written {
             Ok(_) => {
-                frame_count += 1;
+                outage_reported = false;
 
                 // Log periodico con statistiche
                 if last_log.elapsed() >= log_interval {
                     let elapsed = start_time.elapsed().as_secs();
-                    let (sent, errors) = controller.get_stats();
-                    let (color_name, color_code) = get_color_name(hue);
+                    let stats = controller.stats();
+                    let (color_name, color_code) = match (&settings.palette, holiday) {
+                        (Some(_), _) => (active_profile.as_deref().unwrap_or("Palette"), colors::CYAN),
+                        (None, Some((holiday, _))) => (holiday.name, colors::YELLOW),
+                        (None, None) => get_color_name(rgb_to_hue(r, g, b)),
+                    };
 
-                    println!("{}[{:02}:{:02}]{} {}{}●{} {} | RGB: ({:3},{:3},{:3}) | Sent: {} | Errors: {} | FPS: {:.1}",
+                    let battery = controller.battery().map(|battery| format!(" | Battery: {}", battery)).unwrap_or_default();
+                    let lowered = adaptive.as_ref().filter(|adaptive| adaptive.limited()).map(|_| format!(" (lowered from {})", fps)).unwrap_or_default();
+                    info!(color = color_name, r, g, b, sent = stats.sent, errors = stats.errors, fps = (stats.fps as f64 * 10.0).round() / 10.0,
+                          target_fps = stats.target_fps.unwrap_or(target_fps),
+                          "{}[{:02}:{:02}]{} {}{}●{} {} | RGB: ({:3},{:3},{:3}) | Sent: {} | Errors: {} | FPS: {:.1}{}{}",
                              colors::GRAY,
                              elapsed / 60,
                              elapsed % 60,
@@ -258,27 +1274,262 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                              colors::RESET,
                              color_name,
                              r, g, b,
-                             sent,
-                             errors,
-                             frame_count as f32 / last_log.elapsed().as_secs_f32()
+                             stats.sent,
+                             stats.errors,
+                             stats.fps,
+                             lowered,
+                             battery
                     );
 
-                    frame_count = 0;
                     last_log = Instant::now();
                 }
             },
             Err(e) => {
-                eprintln!("{}{}✗ Error:{} {}", colors::BOLD, colors::RED, colors::RESET, e);
+                if !std::mem::replace(&mut outage_reported, true) {
+                    error!("{}{}✗ Error:{} {}", colors::BOLD, colors::RED, colors::RESET, e);
+                }
                 thread::sleep(Duration::from_millis(100));
             }
         }
 
-        hue = (hue + speed) % 360.0;
+        // The effect keeps running meanwhile, so a reopened pad picks up at the current color
+        if let Some(reconnector) = &mut reconnector
+            && reconnector.due(controller.stalled_for())
+        {
+            warn!("{}{}⚠ Watchdog: no report written for {:.1}s, reopening the controller{}",
+                      colors::BOLD, colors::YELLOW, controller.stalled_for().as_secs_f32(), colors::RESET);
+            match controller.reopen() {
+                Ok(()) => {
+                    reconnector.succeeded();
+                    outage_reported = false;
+                    info!("{}{}✓ Controller reopened{}", colors::BOLD, colors::GREEN, colors::RESET);
+                    if let Err(e) = controller.layout() {
+                        warn!("{}⚠ {}, using the built-in offsets{}", colors::YELLOW, e, colors::RESET);
+                    }
+                },
+                Err(e) => {
+                    let wait = reconnector.failed();
+                    error!("{}{}✗ Reopen failed:{} {}, next try in {}s",
+                              colors::BOLD, colors::RED, colors::RESET, e, wait.as_secs());
+                },
+            }
+        }
+
+        if let Some(fps) = stats_sampler.as_mut().and_then(|sampler| sampler.frame()) {
+            let sample = stats::StatsSample {
+                timestamp: chrono::Local::now(),
+                fps,
+                stats: controller.stats(),
+                latency: controller.take_write_latency(),
+                battery: controller.battery(),
+            };
+            if let Some(Err(e)) = stats_csv.as_mut().map(|csv| csv.append(&sample)) {
+                error!("{}{}✗ Stats export failed:{} {}", colors::BOLD, colors::RED, colors::RESET, e);
+            }
+            if let Some(Err(e)) = statsd.as_mut().map(|statsd| statsd.send(&sample)) {
+                error!("{}{}✗ StatsD send failed:{} {}", colors::BOLD, colors::RED, colors::RESET, e);
+            }
+        }
+
+        if let Some(chase) = &mut chase
+            && meter.is_none()
+        {
+            let mask = chase.follow(controller.lightbar());
+            controller.set_player_leds(PlayerLeds { mask, ..player_leds });
+        }
 
         // Precise timing to avoid flickering
-        let frame_time = frame_start.elapsed();
-        if frame_time < frame_duration {
-            thread::sleep(frame_duration - frame_time);
+        controller.wait_until(frame_start + frame_duration);
+    }
+    info!("\n{}{} Shutting down...{}", colors::BOLD, colors::YELLOW, colors::RESET);
+    fade_out(&mut controller, &mut devices, Duration::from_millis(fade_out_ms), frame_duration, exit_color)?;
+
+    let stats = controller.stats();
+    let uptime = started.elapsed().as_secs();
+    info!("{}Ran for {:02}:{:02}:{:02}, sent {} reports with {} errors and {} reconnects{}",
+             colors::GRAY, uptime / 3600, uptime / 60 % 60, uptime % 60,
+             stats.sent, stats.errors, stats.reconnects, colors::RESET);
+
+    Ok(())
+}
+
+// The settings used while no profile is active: the config's [defaults] over the
+// built-in ones, and the command line over both
+fn default_settings(built_in: &profile::Settings, config: &config::Config, overrides: &profile::Profile) -> Result<profile::Settings, String> {
+    built_in
+        .with_profile(&config.defaults, &config.triggers)
+        .map_err(|e| format!("[defaults]: {}", e))?
+        .with_profile(overrides, &config.triggers)
+}
+
+fn profile_settings(config: &config::Config, base: &profile::Settings, name: &str) -> Result<profile::Settings, String> {
+    let profile = config.profiles.get(name).ok_or_else(|| format!("unknown profile '{}'", name))?;
+    base.with_profile(profile, &config.triggers)
+        .map_err(|e| format!("profile '{}': {}", name, e))
+}
+
+// Opens the pad and prints what was found
+fn open_controller(selection: HidSelection, device: Option<&str>) -> Result<DualSenseController, Error> {
+    info!("{}{} Searching for DualSense...{}", colors::BOLD, colors::CYAN, colors::RESET);
+    let controller = match device {
+        Some(device) => DualSenseController::open_device(device, selection)?,
+        None => DualSenseController::open(selection)?,
+    };
+
+    info!("{}{}✓ {} found!{}", colors::BOLD, colors::GREEN, controller.model().name(), colors::RESET);
+    print_details(&controller);
+    Ok(controller)
+}
+
+// --all-controllers: the first pad runs the effect, the others mirror it
+fn open_all_controllers(selection: HidSelection) -> Result<(DualSenseController, Vec<DualSenseController>), Error> {
+    info!("{}{} Searching for DualSense controllers...{}", colors::BOLD, colors::CYAN, colors::RESET);
+    let mut controllers = DualSenseController::open_all(selection)?;
+    if controllers.is_empty() {
+        return Err(Error::DeviceNotFound("no DualSense or DualShock 4 found".into()));
+    }
+
+    info!("{}{}✓ {} DualSense controller(s) found!{}", colors::BOLD, colors::GREEN, controllers.len(), colors::RESET);
+    for controller in &controllers {
+        print_details(controller);
+    }
+    let primary = controllers.remove(0);
+    Ok((primary, controllers))
+}
+
+fn print_details(controller: &DualSenseController) {
+    info!("  {}Model:{} {}", colors::GRAY, colors::RESET, controller.model().name());
+    info!("  {}Mode:{} {}{}{}",
+             colors::GRAY, colors::RESET,
+             colors::BOLD, if controller.is_usb() { "USB" } else { "Bluetooth" }, colors::RESET);
+    info!("  {}Vendor ID:{} 0x{:04X}", colors::GRAY, colors::RESET, DUALSENSE_VID);
+    info!("  {}Product ID:{} 0x{:04X}", colors::GRAY, colors::RESET, controller.model().product_id());
+    if !controller.model().is_dualsense() {
+        info!("  {}Only the lightbar is driven, player LED, mic LED and trigger settings are ignored{}",
+                 colors::GRAY, colors::RESET);
+    }
+    if let Some(identity) = controller.identity() {
+        info!("  {}Serial:{} {}", colors::GRAY, colors::RESET, identity);
+    }
+    if let Some(collection) = controller.collection() {
+        info!("  {}Interface:{} {}", colors::GRAY, colors::RESET, collection.interface);
+        info!("  {}Usage:{} {:04X}:{:04X}", colors::GRAY, colors::RESET, collection.usage_page, collection.usage);
+    }
+    match controller.layout() {
+        Ok(()) => info!("  {}Layout:{} matches the report descriptor\n", colors::GRAY, colors::RESET),
+        Err(e) => warn!("  {}{}⚠ Layout:{}{} {}, using the built-in offsets{}\n",
+                           colors::BOLD, colors::YELLOW, colors::RESET, colors::YELLOW, e, colors::RESET),
+    }
+}
+
+// Brings the --all-controllers pads up to date with `controller`
+fn sync_devices(devices: &mut Option<DeviceManager>, controller: &DualSenseController) {
+    let Some(devices) = devices else {
+        return;
+    };
+    for event in devices.sync(controller) {
+        match event {
+            manager::SyncEvent::Lost { identity, error } => {
+                error!("{}{}✗ Controller {}:{} {}", colors::BOLD, colors::RED, identity, colors::RESET, error);
+            },
+            manager::SyncEvent::Reconnected { identity } => {
+                info!("{}{}✓ Controller {} reconnected{}", colors::BOLD, colors::GREEN, identity, colors::RESET);
+            },
         }
     }
-}
\ No newline at end of file
+}
+
+// A simulated controller on USB, nothing needs to be plugged in
+fn open_mock(transport: MockTransport) -> DualSenseController {
+    info!("{}{}✓ Using a simulated DualSense (no hardware){}\n", colors::BOLD, colors::GREEN, colors::RESET);
+    DualSenseController::mock(transport)
+}
+
+// --dry-run's simulated controller logs what it is sent
+fn mock_transport(dry_run: bool) -> MockTransport {
+    match dry_run {
+        true => MockTransport::default().on_write(log_dry_run),
+        false => MockTransport::default(),
+    }
+}
+
+fn log_dry_run(data: &[u8]) {
+    let id = data.first().copied().unwrap_or(0);
+    match report::parse_output_report(data) {
+        Some(leds) => {
+            let (r, g, b) = leds.lightbar;
+            info!(report = id, r, g, b, player_leds = leds.player_leds, "{}→ 0x{:02X}{} lightbar #{:02X}{:02X}{:02X}, player LEDs {:05b}, mute LED {:?}",
+                  colors::GRAY, id, colors::RESET, r, g, b, leds.player_leds, leds.mute_led);
+        },
+        None => info!(report = id, "{}→ 0x{:02X}{} {} bytes", colors::GRAY, id, colors::RESET, data.len()),
+    }
+}
+
+// `trigger test`: holds an effect on the chosen trigger(s), then resets them
+fn test_trigger(
+    controller: &mut DualSenseController,
+    effect: TriggerEffect,
+    side: cli::TriggerSide,
+    duration: Duration,
+    running: &AtomicBool,
+) -> Result<(), Error> {
+    let bytes: Vec<String> = effect.to_bytes().iter().map(|b| format!("{:02X}", b)).collect();
+    println!("{}{}Testing trigger effect:{} {}", colors::BOLD, colors::CYAN, colors::RESET, effect);
+    println!("  {}Parameter bytes:{} {}", colors::GRAY, colors::RESET, bytes.join(" "));
+    println!("{}Press the trigger to feel it, CTRL+C to stop early{}\n", colors::GRAY, colors::RESET);
+
+    let left = if side == cli::TriggerSide::Right { TriggerEffect::Off } else { effect };
+    let right = if side == cli::TriggerSide::Left { TriggerEffect::Off } else { effect };
+    controller.set_triggers(left, right);
+    controller.set_lightbar(255, 255, 255)?;
+
+    let start = Instant::now();
+    while running.load(Ordering::SeqCst) && start.elapsed() < duration {
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    controller.set_triggers(TriggerEffect::Off, TriggerEffect::Off);
+    controller.set_lightbar(0, 0, 0)?;
+    println!("{}Triggers reset{}", colors::GRAY, colors::RESET);
+    Ok(())
+}
+
+fn announce_holiday(themes: &holidays::HolidayThemes) {
+    match themes.active() {
+        Some((holiday, _)) => info!("{}{} {} theme active{}", colors::BOLD, colors::YELLOW, holiday.name, colors::RESET),
+        None => info!("{}Holiday theme over, back to the rainbow{}", colors::GRAY, colors::RESET),
+    }
+}
+
+// Fades the lightbar from its current color to `exit_color`, then turns every other LED off
+fn fade_out(
+    controller: &mut DualSenseController,
+    devices: &mut Option<DeviceManager>,
+    duration: Duration,
+    frame_duration: Duration,
+    exit_color: (u8, u8, u8),
+) -> Result<(), Error> {
+    let from = controller.lightbar();
+    let start = Instant::now();
+    controller.set_rumble(0, 0);
+
+    while start.elapsed() < duration {
+        let (r, g, b) = effects::mix(from, exit_color, start.elapsed().as_secs_f32() / duration.as_secs_f32());
+
+        // A failed frame only makes the fade less smooth, the final report is what matters
+        let _ = controller.set_lightbar(r, g, b);
+        sync_devices(devices, controller);
+        thread::sleep(frame_duration);
+    }
+
+    // The final report must not be swallowed by the Bluetooth rate limit
+    thread::sleep(controller.write_slot_remaining());
+
+    controller.set_mute_led(MuteLed::Off);
+    controller.set_player_leds(PlayerLeds { mask: 0, ..PlayerLeds::default() });
+    controller.set_triggers(TriggerEffect::Off, TriggerEffect::Off);
+    let (r, g, b) = exit_color;
+    let result = controller.set_lightbar(r, g, b);
+    sync_devices(devices, controller);
+    result
+}
@@ -0,0 +1,85 @@
+// Several controllers showing one effect. The first pad is driven as usual; every
+// frame its state is copied to the others, so they all run off the same clock and
+// stay in phase, each one's lightbar optionally turned further around the hue wheel
+use std::time::Duration;
+
+use crate::color::rotate_hue;
+use crate::device::DualSenseController;
+
+// A pad that stopped taking reports is reopened after this long, then every
+// RECONNECT_AFTER while it stays away
+const RECONNECT_AFTER: Duration = Duration::from_secs(5);
+
+// What happened to one of the mirrored controllers during a sync
+#[derive(Debug)]
+pub enum SyncEvent {
+    Lost { identity: String, error: String },
+    Reconnected { identity: String },
+}
+
+pub struct DeviceManager {
+    controllers: Vec<DualSenseController>,
+    // Hue degrees between neighbouring controllers, 0 keeps them all the same color
+    hue_offset: f32,
+    // Whether each controller's last write failed, so losses are reported once
+    failing: Vec<bool>,
+}
+
+impl DeviceManager {
+    // `controllers` are the pads besides the one the effect is driven on
+    pub fn new(controllers: Vec<DualSenseController>, hue_offset: f32) -> Self {
+        let failing = vec![false; controllers.len()];
+        Self { controllers, hue_offset, failing }
+    }
+
+    pub fn len(&self) -> usize {
+        self.controllers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.controllers.is_empty()
+    }
+
+    pub fn controllers(&self) -> impl Iterator<Item = &DualSenseController> {
+        self.controllers.iter()
+    }
+
+    // For settings that apply to every pad, e.g. the retry policy or rate limits
+    pub fn controllers_mut(&mut self) -> impl Iterator<Item = &mut DualSenseController> {
+        self.controllers.iter_mut()
+    }
+
+    // Copies `primary`'s LEDs and trigger effects to every other controller. The
+    // n-th one gets the lightbar turned by n times the hue offset
+    pub fn sync(&mut self, primary: &DualSenseController) -> Vec<SyncEvent> {
+        let output = primary.output();
+        let mut events = Vec::new();
+        for (i, controller) in self.controllers.iter_mut().enumerate() {
+            let identity = controller.identity().unwrap_or(controller.path()).to_string();
+
+            if self.failing[i] && controller.stalled_for() >= RECONNECT_AFTER {
+                if controller.reopen().is_ok() {
+                    self.failing[i] = false;
+                    events.push(SyncEvent::Reconnected { identity });
+                }
+                continue;
+            }
+
+            // Inputs are drained so the pad's buffer doesn't fill up, a dead handle shows in the write
+            let _ = controller.poll_input();
+            controller.set_player_leds(output.player_leds);
+            controller.set_mute_led(output.mute_led);
+            controller.set_triggers(output.left_trigger, output.right_trigger);
+            let (r, g, b) = rotate_hue(output.lightbar, self.hue_offset * (i + 1) as f32);
+            match controller.set_lightbar(r, g, b) {
+                Ok(()) => self.failing[i] = false,
+                Err(e) if !self.failing[i] => {
+                    self.failing[i] = true;
+                    events.push(SyncEvent::Lost { identity, error: e.to_string() });
+                },
+                Err(_) => {},
+            }
+        }
+        events
+    }
+}
@@ -0,0 +1,119 @@
+// Shows a value on the lightbar and player LEDs instead of the running effect, so game
+// mods and build scripts can send "health 37/100" or "progress 0.62" without picking colors:
+//
+//   health 37/100   red -> yellow -> green, pulsing below a quarter
+//   progress 0.62   blue that turns green once finished (also "62%" or "31/50")
+//   clear           back to the effect
+//
+// The player LEDs fill from the left like a five-segment bar.
+use crossbeam_channel::{Receiver, unbounded};
+use std::f32::consts::TAU;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+use std::thread;
+use std::time::Instant;
+use tracing::error;
+
+// Below this much health the bar pulses as a warning
+const LOW_HEALTH: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reading {
+    Health(f32),
+    Progress(f32),
+}
+
+// A "clear" message parses to None
+pub struct Message(pub Option<Reading>);
+
+impl FromStr for Message {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let kind = words.next().ok_or("empty message")?.to_ascii_lowercase();
+        let value = words.next();
+        if words.next().is_some() {
+            return Err(format!("unexpected text after '{}'", line.trim()));
+        }
+
+        match (kind.as_str(), value) {
+            ("clear", None) => Ok(Message(None)),
+            ("health", Some(value)) => Ok(Message(Some(Reading::Health(parse_fraction(value)?)))),
+            ("progress", Some(value)) => Ok(Message(Some(Reading::Progress(parse_fraction(value)?)))),
+            ("health" | "progress", None) => Err(format!("{} needs a value like 37/100, 0.37 or 37%", kind)),
+            _ => Err(format!("unknown message '{}' (expected health, progress or clear)", kind)),
+        }
+    }
+}
+
+// "37/100", "37%" or "0.37", clamped to 0.0-1.0
+fn parse_fraction(s: &str) -> Result<f32, String> {
+    let invalid = || format!("invalid value '{}' (expected 37/100, 0.37 or 37%)", s);
+    let number = |n: &str| n.parse::<f32>().ok().filter(|n| n.is_finite()).ok_or_else(invalid);
+
+    let fraction = if let Some((current, max)) = s.split_once('/') {
+        let max = number(max)?;
+        if max <= 0.0 {
+            return Err(invalid());
+        }
+        number(current)? / max
+    } else if let Some(percent) = s.strip_suffix('%') {
+        number(percent)? / 100.0
+    } else {
+        number(s)?
+    };
+    Ok(fraction.clamp(0.0, 1.0))
+}
+
+// Reads messages from stdin on a background thread. Bad lines are reported and skipped
+pub fn read_stdin() -> Receiver<Option<Reading>> {
+    let (tx, messages) = unbounded();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.parse::<Message>() {
+                Ok(Message(reading)) => {
+                    if tx.send(reading).is_err() {
+                        break;
+                    }
+                },
+                Err(e) => error!("meter: {}", e),
+            }
+        }
+    });
+    messages
+}
+
+// Color and player LED mask for a reading at time `now`
+pub fn render(reading: Reading, now: Instant, start: Instant) -> ((u8, u8, u8), u8) {
+    let t = now.duration_since(start).as_secs_f32();
+    let (fraction, color) = match reading {
+        Reading::Health(health) => {
+            // Hue 0 (red) at empty up to 120 (green) at full
+            let (r, g, b) = dualsense_rainbow::hsv_to_rgb(health * 120.0, 1.0, 1.0);
+            let level = if health < LOW_HEALTH {
+                // Faster and deeper pulse the closer it gets to zero
+                let rate = 1.0 + (LOW_HEALTH - health) / LOW_HEALTH * 2.0;
+                0.35 + 0.65 * (0.5 + 0.5 * (t * rate * TAU).cos())
+            } else {
+                1.0
+            };
+            let scale = |c: u8| (c as f32 * level) as u8;
+            (health, (scale(r), scale(g), scale(b)))
+        },
+        Reading::Progress(progress) if progress >= 1.0 => (1.0, (0, 255, 0)),
+        Reading::Progress(progress) => {
+            // Dim blue filling up to bright cyan
+            let level = 0.3 + 0.7 * progress;
+            (progress, (0, (120.0 * progress * level) as u8, (255.0 * level) as u8))
+        },
+    };
+
+    let segments = (fraction * 5.0).ceil() as u8;
+    let mask = ((1u16 << segments) - 1) as u8;
+    (color, mask)
+}
@@ -0,0 +1,97 @@
+use std::f32::consts::TAU;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use dualsense_rainbow::report::MuteLed;
+
+// One breathe cycle (off -> on -> off)
+const BREATHE_PERIOD: Duration = Duration::from_secs(4);
+// Length of each on/off step of the double-blink
+const BLINK_STEP: Duration = Duration::from_millis(120);
+
+// Base pattern for the mic LED
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MicPattern {
+    #[default]
+    Off,
+    On,
+    Pulse,   // Controller's own pulsing mode
+    Breathe, // Software-driven slow breathe
+    Sync,    // Breathes once per trip around the color wheel, brightest on red
+}
+
+impl FromStr for MicPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(MicPattern::Off),
+            "on" => Ok(MicPattern::On),
+            "pulse" => Ok(MicPattern::Pulse),
+            "breathe" => Ok(MicPattern::Breathe),
+            "sync" => Ok(MicPattern::Sync),
+            _ => Err(format!("unknown mic LED pattern '{}' (expected off, on, pulse, breathe or sync)", s)),
+        }
+    }
+}
+
+// Decides the mic LED state for each frame, ticked by the main loop alongside the lightbar
+pub struct MicLedScheduler {
+    pattern: MicPattern,
+    start: Instant,
+    blink_started: Option<Instant>,
+    breathe_level: f32,
+}
+
+impl MicLedScheduler {
+    pub fn new(pattern: MicPattern) -> Self {
+        Self {
+            pattern,
+            start: Instant::now(),
+            blink_started: None,
+            breathe_level: 0.0,
+        }
+    }
+
+    pub fn set_pattern(&mut self, pattern: MicPattern) {
+        self.pattern = pattern;
+    }
+
+    // Overlays a quick on-off-on-off blink on top of the base pattern
+    pub fn double_blink(&mut self, now: Instant) {
+        self.blink_started = Some(now);
+    }
+
+    // `hue` is the lightbar's current hue in degrees, followed by the sync pattern
+    pub fn frame(&mut self, now: Instant, hue: f32) -> MuteLed {
+        if let Some(started) = self.blink_started {
+            let step = now.duration_since(started).as_millis() / BLINK_STEP.as_millis();
+            if step < 4 {
+                return if step.is_multiple_of(2) { MuteLed::On } else { MuteLed::Off };
+            }
+            self.blink_started = None;
+        }
+
+        match self.pattern {
+            MicPattern::Off => MuteLed::Off,
+            MicPattern::On => MuteLed::On,
+            MicPattern::Pulse => MuteLed::Pulse,
+            MicPattern::Breathe => {
+                let phase = now.duration_since(self.start).as_secs_f32() / BREATHE_PERIOD.as_secs_f32();
+                self.dither(0.5 - 0.5 * (phase * TAU).cos())
+            },
+            MicPattern::Sync => self.dither(0.5 + 0.5 * (hue / 360.0 * TAU).cos()),
+        }
+    }
+
+    // The LED is either on or off, so a level in between becomes a duty cycle across frames
+    fn dither(&mut self, level: f32) -> MuteLed {
+        self.breathe_level += level;
+        if self.breathe_level >= 1.0 {
+            self.breathe_level -= 1.0;
+            MuteLed::On
+        } else {
+            MuteLed::Off
+        }
+    }
+}
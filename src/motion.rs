@@ -0,0 +1,36 @@
+// How `--effect motion` reads the motion sensors: how strongly tilting shifts the hue
+// and how hard a shake has to be to set off a sparkle, and how much the tilt is smoothed
+use serde::Deserialize;
+use std::time::Duration;
+
+// The [motion] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct MotionConfig {
+    // Scales the hue shift per degree of tilt and lowers the shake threshold, 1.0 by default
+    pub sensitivity: f32,
+    // Time constant of the tilt's low-pass filter, 0 follows every reading
+    pub smoothing_ms: u64,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        Self { sensitivity: 1.0, smoothing_ms: 150 }
+    }
+}
+
+// The checked form of MotionConfig
+#[derive(Clone, Debug)]
+pub struct MotionSettings {
+    pub sensitivity: f32,
+    pub smoothing: Duration,
+}
+
+impl MotionSettings {
+    pub fn new(config: &MotionConfig) -> Result<Self, String> {
+        if !config.sensitivity.is_finite() || config.sensitivity <= 0.0 {
+            return Err(format!("sensitivity must be a positive number, got {}", config.sensitivity));
+        }
+        Ok(Self { sensitivity: config.sensitivity, smoothing: Duration::from_millis(config.smoothing_ms) })
+    }
+}
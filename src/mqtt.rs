@@ -0,0 +1,340 @@
+// Home Assistant over MQTT: the controller shows up as an RGB light through MQTT
+// discovery, so it can sit in a room, follow scenes and dim with the other lights at night.
+//
+//   homeassistant/light/<node_id>/config   discovery, retained
+//   <topic>/state                          {"state": "ON", "brightness": 204, "effect": "rainbow", ...}, retained
+//   <topic>/set                            the same shape from Home Assistant, JSON schema
+//   <topic>/availability                   "online", or "offline" through the broker's last will
+//
+// Only as much of MQTT 3.1.1 as that needs: QoS 0, one subscription, keepalive pings.
+// Commands go through the control layer like everything else remote.
+use crossbeam_channel::Sender;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+use crate::control::{self, ControlCommand, Request, Setting, Status};
+use crate::effects::EffectKind;
+use crate::tuning::Param;
+
+const KEEPALIVE: Duration = Duration::from_secs(30);
+// How often the state is checked for changes made some other way
+const STATE_EVERY: Duration = Duration::from_secs(5);
+const RETRY_EVERY: Duration = Duration::from_secs(10);
+// Commands are tiny, anything bigger is not for us
+const MAX_PACKET: usize = 4096;
+
+// The [mqtt] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    // host:port of the broker
+    pub broker: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // Base of the state, command and availability topics
+    pub topic: String,
+    pub discovery_prefix: String,
+    // Unique per controller when several instances share a broker
+    pub node_id: String,
+    // What Home Assistant calls the light
+    pub name: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            broker: String::new(),
+            username: None,
+            password: None,
+            topic: "dualsense-rainbow".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+            node_id: "dualsense_rainbow".to_string(),
+            name: "DualSense lightbar".to_string(),
+        }
+    }
+}
+
+impl MqttConfig {
+    fn state_topic(&self) -> String {
+        format!("{}/state", self.topic)
+    }
+
+    fn command_topic(&self) -> String {
+        format!("{}/set", self.topic)
+    }
+
+    fn availability_topic(&self) -> String {
+        format!("{}/availability", self.topic)
+    }
+}
+
+pub fn start(config: &MqttConfig, requests: Sender<Request>) -> Result<(), String> {
+    if config.broker.trim().is_empty() {
+        return Err("mqtt.broker must be set, e.g. \"192.168.1.10:1883\"".into());
+    }
+
+    let config = config.clone();
+    thread::spawn(move || {
+        let mut last_error = None;
+        loop {
+            // Only new errors are printed, a broker that stays away would fill the log
+            if let Err(e) = run(&config, &requests) {
+                let e = e.to_string();
+                if last_error.as_ref() != Some(&e) {
+                    error!("mqtt {}: {}", config.broker, e);
+                    last_error = Some(e);
+                }
+            }
+            thread::sleep(RETRY_EVERY);
+        }
+    });
+    Ok(())
+}
+
+// One connection to the broker, until it drops
+fn run(config: &MqttConfig, requests: &Sender<Request>) -> io::Result<()> {
+    let mut stream = TcpStream::connect(&config.broker)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    connect(&mut stream, config)?;
+
+    publish(&mut stream, &discovery_topic(config), discovery(config).to_string().as_bytes(), true)?;
+    publish(&mut stream, &config.availability_topic(), b"online", true)?;
+    subscribe(&mut stream, &config.command_topic())?;
+
+    let mut last_sent = Instant::now();
+    let mut last_state: Option<Value> = None;
+    let mut last_check = None::<Instant>;
+    loop {
+        match read_packet(&mut stream) {
+            // PUBLISH: topic, then the payload (QoS 0, so no packet ID)
+            Ok(Some((0x30, body))) => {
+                let topic_len = body.get(..2).map_or(0, |len| u16::from_be_bytes([len[0], len[1]]) as usize);
+                if let Some(payload) = body.get(2 + topic_len..) {
+                    if let Err(e) = command(payload, requests) {
+                        error!("mqtt: {}", e);
+                    }
+                    // Answer with the new state right away
+                    last_check = None;
+                }
+            },
+            Ok(_) => {},
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {},
+            Err(e) => return Err(e),
+        }
+
+        if last_check.is_none_or(|at| at.elapsed() >= STATE_EVERY) {
+            last_check = Some(Instant::now());
+            let status = match control::submit(requests, ControlCommand::Status) {
+                Ok(Some(status)) => status,
+                _ => return Ok(()),
+            };
+            let state = state(&status);
+            if last_state.as_ref() != Some(&state) {
+                publish(&mut stream, &config.state_topic(), state.to_string().as_bytes(), true)?;
+                last_state = Some(state);
+                last_sent = Instant::now();
+            }
+        }
+
+        if last_sent.elapsed() >= KEEPALIVE / 2 {
+            stream.write_all(&[0xC0, 0x00])?;
+            last_sent = Instant::now();
+        }
+    }
+}
+
+fn discovery_topic(config: &MqttConfig) -> String {
+    format!("{}/light/{}/config", config.discovery_prefix, config.node_id)
+}
+
+fn discovery(config: &MqttConfig) -> Value {
+    json!({
+        "name": config.name,
+        "unique_id": config.node_id,
+        "schema": "json",
+        "state_topic": config.state_topic(),
+        "command_topic": config.command_topic(),
+        "availability_topic": config.availability_topic(),
+        "brightness": true,
+        "supported_color_modes": ["rgb"],
+        "effect": true,
+        "effect_list": EffectKind::ALL.map(EffectKind::name),
+        "device": {
+            "identifiers": [config.node_id],
+            "name": config.name,
+            "model": "DualSense",
+            "manufacturer": "Sony",
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        },
+    })
+}
+
+// The running color only means something to Home Assistant while it holds still. It
+// comes back at full brightness, Home Assistant keeps the two apart
+fn state(status: &Status) -> Value {
+    let mut state = json!({
+        "state": if status.on { "ON" } else { "OFF" },
+        "brightness": (status.brightness * 255.0).round() as u8,
+        "color_mode": "rgb",
+        "effect": status.effect.name(),
+    });
+    if status.effect == EffectKind::Solid && status.brightness > 0.0 {
+        let full = |c: u8| (c as f32 / status.brightness).round().min(255.0) as u8;
+        let (r, g, b) = status.color;
+        state["color"] = json!({ "r": full(r), "g": full(g), "b": full(b) });
+    }
+    state
+}
+
+// {"state": "ON", "brightness": 0-255, "color": {"r", "g", "b"}, "effect": NAME}, any of
+// them left out. "transition" is accepted and ignored, crossfades use transition_ms
+fn command(payload: &[u8], requests: &Sender<Request>) -> Result<(), String> {
+    let body: Value = serde_json::from_slice(payload).map_err(|e| format!("invalid command JSON: {}", e))?;
+    let mut commands = Vec::new();
+    if let Some(name) = body.get("effect").and_then(Value::as_str) {
+        commands.push(ControlCommand::Effect(name.parse()?));
+    }
+    if let Some(color) = body.get("color") {
+        let channel = |name: &str| {
+            color.get(name).and_then(Value::as_u64).filter(|&c| c <= 255).map(|c| c as u8).ok_or("color channels must be 0-255")
+        };
+        commands.push(ControlCommand::SetColor { color: (channel("r")?, channel("g")?, channel("b")?), instant: false });
+    }
+    if let Some(brightness) = body.get("brightness") {
+        let level = brightness.as_f64().filter(|v| (0.0..=255.0).contains(v)).ok_or("brightness must be 0-255")?;
+        commands.push(ControlCommand::SetParams(vec![Setting::Level(Param::Brightness, (level / 255.0) as f32)]));
+    }
+    match body.get("state").and_then(Value::as_str) {
+        Some("ON") => commands.push(ControlCommand::Power(true)),
+        Some("OFF") => commands.push(ControlCommand::Power(false)),
+        Some(other) => return Err(format!("unknown state '{}'", other)),
+        None => {},
+    }
+    commands.into_iter().try_for_each(|command| control::submit(requests, command).map(|_| ()))
+}
+
+fn connect(stream: &mut TcpStream, config: &MqttConfig) -> io::Result<()> {
+    // Clean session, and a retained "offline" as the last will
+    let mut flags = 0x02 | 0x04 | 0x20;
+    let mut body = Vec::new();
+    put_str(&mut body, "MQTT");
+    body.push(4);
+    if config.username.is_some() {
+        flags |= 0x80;
+    }
+    if config.password.is_some() {
+        flags |= 0x40;
+    }
+    body.push(flags);
+    body.extend_from_slice(&(KEEPALIVE.as_secs() as u16).to_be_bytes());
+    put_str(&mut body, &format!("{}-{}", config.node_id, std::process::id()));
+    put_str(&mut body, &config.availability_topic());
+    put_str(&mut body, "offline");
+    for field in [&config.username, &config.password].into_iter().flatten() {
+        put_str(&mut body, field);
+    }
+    write_packet(stream, 0x10, &body)?;
+
+    // Waits through the read timeout, a broker answers CONNECT right away
+    let answer = loop {
+        match read_packet(stream) {
+            Ok(Some(packet)) => break packet,
+            Ok(None) => continue,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "no answer to CONNECT"));
+            },
+            Err(e) => return Err(e),
+        }
+    };
+    match answer {
+        (0x20, body) if body.get(1) == Some(&0) => Ok(()),
+        (0x20, body) => {
+            let reason = match body.get(1) {
+                Some(4) => "bad username or password".to_string(),
+                Some(5) => "not authorized".to_string(),
+                code => format!("refused with code {:?}", code),
+            };
+            Err(io::Error::new(io::ErrorKind::PermissionDenied, reason))
+        },
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "expected CONNACK")),
+    }
+}
+
+fn publish(stream: &mut TcpStream, topic: &str, payload: &[u8], retain: bool) -> io::Result<()> {
+    let mut body = Vec::new();
+    put_str(&mut body, topic);
+    body.extend_from_slice(payload);
+    write_packet(stream, 0x30 | retain as u8, &body)
+}
+
+fn subscribe(stream: &mut TcpStream, topic: &str) -> io::Result<()> {
+    // Packet ID 1, QoS 0
+    let mut body = vec![0, 1];
+    put_str(&mut body, topic);
+    body.push(0);
+    write_packet(stream, 0x82, &body)
+}
+
+fn put_str(body: &mut Vec<u8>, s: &str) {
+    body.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    body.extend_from_slice(s.as_bytes());
+}
+
+fn write_packet(stream: &mut TcpStream, header: u8, body: &[u8]) -> io::Result<()> {
+    let mut packet = vec![header];
+    // Remaining length, 7 bits per byte
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        packet.push(if len > 0 { byte | 0x80 } else { byte });
+        if len == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    stream.write_all(&packet)
+}
+
+// Packet type (flags masked off, apart from nothing a QoS 0 client needs) and body.
+// None for packets that carry nothing for us
+fn read_packet(stream: &mut TcpStream) -> io::Result<Option<(u8, Vec<u8>)>> {
+    let mut header = [0u8; 1];
+    stream.read_exact(&mut header)?;
+    // The read timeout only tells an idle connection apart: once a packet has started, giving
+    // up halfway would leave the rest of it to be misread as the next one
+    let timeout = stream.read_timeout()?;
+    stream.set_read_timeout(None)?;
+    let body = read_body(stream);
+    stream.set_read_timeout(timeout)?;
+    let body = body?;
+    Ok(match header[0] & 0xF0 {
+        // SUBACK and PINGRESP
+        0x90 | 0xD0 => None,
+        kind => Some((kind, body)),
+    })
+}
+
+// Remaining length, then that many bytes
+fn read_body(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut len = 0usize;
+    for shift in (0..4).map(|i| i * 7) {
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte)?;
+        len |= ((byte[0] & 0x7F) as usize) << shift;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    if len > MAX_PACKET {
+        return Err(io::Error::new(io::ErrorKind::FileTooLarge, "packet too large"));
+    }
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body)?;
+    Ok(body)
+}
@@ -0,0 +1,56 @@
+// Limits for the front ends that take connections from the network: a peer decides how
+// long a line is and how many connections it opens, so both are capped before anything
+// is allocated or a thread started for them
+use std::io::{self, BufRead, Read};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Reads one line into `line`, without its line ending. Ok(false) at the end of the stream,
+// an error once the line runs past `max` bytes without ending
+pub fn read_line(reader: &mut impl BufRead, line: &mut Vec<u8>, max: usize) -> io::Result<bool> {
+    line.clear();
+    if reader.by_ref().take(max as u64 + 1).read_until(b'\n', line)? == 0 {
+        return Ok(false);
+    }
+    match line.last() {
+        Some(b'\n') => {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        },
+        _ if line.len() > max => return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long")),
+        _ => {},
+    }
+    Ok(true)
+}
+
+// How many connections a server serves at once
+#[derive(Clone)]
+pub struct ConnectionLimit {
+    open: Arc<AtomicUsize>,
+    max: usize,
+}
+
+impl ConnectionLimit {
+    pub fn new(max: usize) -> Self {
+        Self { open: Arc::new(AtomicUsize::new(0)), max }
+    }
+
+    // A slot for one more connection, None with all of them taken. Dropping the slot
+    // frees it again
+    pub fn acquire(&self) -> Option<ConnectionSlot> {
+        self.open
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| (open < self.max).then_some(open + 1))
+            .ok()
+            .map(|_| ConnectionSlot(self.open.clone()))
+    }
+}
+
+pub struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
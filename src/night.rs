@@ -0,0 +1,96 @@
+// Night mode, f.lux for the lightbar: every color is multiplied by the white of a color
+// temperature that follows the local time, cool during the day and warm at night. The
+// [night] schedule gives the temperature at times of day and is interpolated in between,
+// round midnight too; it is followed in mireds (a million over the kelvins), which is
+// how the eye judges the steps
+use chrono::{Local, NaiveTime, Timelike};
+use dualsense_rainbow::kelvin_to_rgb;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+// How often the clock is looked at; the temperature moves far too slowly to see a step
+const CHECK_EVERY: Duration = Duration::from_secs(10);
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+// The [night] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct NightConfig {
+    // Color temperature in kelvins by time of day, e.g. { "07:00" = 6500, "22:00" = 2700 }
+    pub schedule: BTreeMap<String, u32>,
+}
+
+impl Default for NightConfig {
+    fn default() -> Self {
+        let schedule = [("06:00", 2700), ("08:00", 6500), ("19:00", 6500), ("21:00", 3400), ("23:00", 2700)];
+        Self { schedule: schedule.into_iter().map(|(at, kelvin)| (at.to_string(), kelvin)).collect() }
+    }
+}
+
+pub struct NightMode {
+    // Seconds into the day and mireds, sorted by time
+    schedule: Vec<(u32, f32)>,
+    kelvin: f32,
+    white: (f32, f32, f32),
+    checked: Option<Instant>,
+}
+
+impl NightMode {
+    pub fn new(config: &NightConfig) -> Result<Self, String> {
+        let mut schedule = config
+            .schedule
+            .iter()
+            .map(|(at, &kelvin)| {
+                let time = NaiveTime::parse_from_str(at.trim(), "%H:%M")
+                    .map_err(|_| format!("'{}' is not a time of day like 21:30", at))?;
+                if !(1000..=10000).contains(&kelvin) {
+                    return Err(format!("{} at {}: the temperature must be between 1000 and 10000 K", kelvin, at));
+                }
+                Ok((time.num_seconds_from_midnight(), 1e6 / kelvin as f32))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if schedule.is_empty() {
+            return Err("the schedule is empty".into());
+        }
+        schedule.sort_by_key(|&(at, _)| at);
+
+        let mut night = Self { schedule, kelvin: 6500.0, white: (1.0, 1.0, 1.0), checked: None };
+        night.update();
+        Ok(night)
+    }
+
+    pub fn kelvin(&self) -> f32 {
+        self.kelvin
+    }
+
+    // Called every frame, follows the clock every few seconds
+    pub fn update(&mut self) {
+        if self.checked.is_some_and(|at| at.elapsed() < CHECK_EVERY) {
+            return;
+        }
+        self.checked = Some(Instant::now());
+        self.kelvin = 1e6 / self.mireds_at(Local::now().time().num_seconds_from_midnight());
+        let (r, g, b) = kelvin_to_rgb(self.kelvin);
+        self.white = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+    }
+
+    pub fn apply(&self, (r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+        let scale = |c: u8, by: f32| (c as f32 * by).round() as u8;
+        (scale(r, self.white.0), scale(g, self.white.1), scale(b, self.white.2))
+    }
+
+    fn mireds_at(&self, now: u32) -> f32 {
+        // The last point before now and the first after it, either of them on the other
+        // side of midnight
+        let next = self.schedule.partition_point(|&(at, _)| at <= now);
+        let (from_at, from) = self.schedule[(next + self.schedule.len() - 1) % self.schedule.len()];
+        let (to_at, to) = self.schedule[next % self.schedule.len()];
+        let span = (to_at + SECONDS_PER_DAY - from_at) % SECONDS_PER_DAY;
+        if span == 0 {
+            return from;
+        }
+        let into = (now + SECONDS_PER_DAY - from_at) % SECONDS_PER_DAY;
+        from + (to - from) * into as f32 / span as f32
+    }
+}
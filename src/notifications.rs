@@ -0,0 +1,91 @@
+// Native desktop notifications for controller events, for when the terminal isn't in sight
+use notify_rust::Notification;
+use serde::Deserialize;
+use tracing::error;
+
+use dualsense_rainbow::colors;
+use dualsense_rainbow::input::BatteryStatus;
+
+// Consecutive failed writes before they count as persistent (a few seconds of retries)
+const WRITE_FAILURE_STREAK: u32 = 30;
+
+// The [notifications] table of the config; every event is on unless disabled
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationConfig {
+    pub connect: bool,
+    pub disconnect: bool,
+    // Battery percentage to warn at, 0 turns the warning off
+    pub low_battery: u8,
+    pub write_failures: bool,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self { connect: true, disconnect: true, low_battery: 20, write_failures: true }
+    }
+}
+
+// Turns the per-frame state into one notification per event instead of one per frame
+pub struct Notifier {
+    config: NotificationConfig,
+    disconnected: bool,
+    low_battery_sent: bool,
+    failed_writes: u32,
+}
+
+impl Notifier {
+    pub fn new(config: NotificationConfig) -> Self {
+        Self { config, disconnected: false, low_battery_sent: false, failed_writes: 0 }
+    }
+
+    pub fn connected(&mut self, mode: &str) {
+        self.disconnected = false;
+        if self.config.connect {
+            show("DualSense connected", &format!("Connected over {}", mode));
+        }
+    }
+
+    pub fn disconnected(&mut self) {
+        if self.config.disconnect && !self.disconnected {
+            show("DualSense disconnected", "The controller stopped responding");
+        }
+        self.disconnected = true;
+    }
+
+    // Warns once when the level drops to the threshold, again only after charging back above it
+    pub fn battery(&mut self, battery: Option<BatteryStatus>) {
+        let Some(battery) = battery else {
+            return;
+        };
+        let low = !battery.charging && battery.level <= self.config.low_battery;
+        if low && !self.low_battery_sent {
+            show("DualSense battery low", &format!("{}% left", battery.level));
+        }
+        self.low_battery_sent = low;
+    }
+
+    pub fn write_result(&mut self, ok: bool) {
+        if ok {
+            self.failed_writes = 0;
+            return;
+        }
+
+        self.failed_writes += 1;
+        // A disconnect already explains the failures
+        if self.config.write_failures && self.failed_writes == WRITE_FAILURE_STREAK && !self.disconnected {
+            show("DualSense writes failing", "The lightbar can't be updated, check the connection");
+        }
+    }
+}
+
+fn show(summary: &str, body: &str) {
+    let result = Notification::new()
+        .appname("dualsense-rainbow")
+        .summary(summary)
+        .body(body)
+        .show();
+    if let Err(e) = result {
+        error!("{}{}✗ Notification failed:{} {}", colors::BOLD, colors::RED, colors::RESET, e);
+    }
+}
@@ -0,0 +1,87 @@
+// The subcommands that do one thing and exit: `set` and `off` for scripts and key
+// bindings, `status` and `list` to see what is connected
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dualsense_rainbow::device;
+use dualsense_rainbow::report::{MuteLed, PlayerLeds};
+use dualsense_rainbow::{DualSenseController, Error, colors};
+
+// Firmware info feature report: build date and time as text, then versions
+const FIRMWARE_INFO_REPORT: u8 = 0x20;
+const FIRMWARE_INFO_LEN: usize = 64;
+// How long `status` waits for an input report to learn the battery level
+const BATTERY_WAIT: Duration = Duration::from_secs(1);
+
+pub fn set(controller: &mut DualSenseController, (r, g, b): (u8, u8, u8)) -> Result<(), Error> {
+    controller.set_lightbar(r, g, b)?;
+    println!("{}Lightbar set to #{:02x}{:02x}{:02x}{}", colors::GRAY, r, g, b, colors::RESET);
+    Ok(())
+}
+
+pub fn off(controller: &mut DualSenseController) -> Result<(), Error> {
+    controller.set_player_leds(PlayerLeds::default());
+    controller.set_mute_led(MuteLed::Off);
+    controller.set_lightbar(0, 0, 0)?;
+    println!("{}LEDs off{}", colors::GRAY, colors::RESET);
+    Ok(())
+}
+
+// Waits briefly for an input report, which carries the battery level
+pub fn status(controller: &mut DualSenseController, running: &AtomicBool) -> Result<(), Error> {
+    let start = Instant::now();
+    while controller.battery().is_none() && start.elapsed() < BATTERY_WAIT && running.load(Ordering::SeqCst) {
+        controller.poll_input()?;
+        thread::sleep(Duration::from_millis(20));
+    }
+    let connection = match (controller.path(), controller.is_usb()) {
+        ("mock", _) => "simulated",
+        (_, true) => "USB",
+        (_, false) => "Bluetooth",
+    };
+    println!("{}Status{}", colors::BOLD, colors::RESET);
+    println!("  {}Connection:{} {}", colors::GRAY, colors::RESET, connection);
+    match controller.battery() {
+        Some(battery) => println!("  {}Battery:{} {}", colors::GRAY, colors::RESET, battery),
+        None => println!("  {}Battery:{} unknown (no input report yet)", colors::GRAY, colors::RESET),
+    }
+
+    let firmware = match controller.model().is_dualsense() {
+        true => firmware(controller).unwrap_or_else(|e| format!("unknown ({})", e)),
+        false => "unknown (only read from the DualSense)".to_string(),
+    };
+    println!("  {}Firmware:{} {}", colors::GRAY, colors::RESET, firmware);
+    Ok(())
+}
+
+// Version and build date from the firmware info report, laid out as the Linux driver reads it
+fn firmware(controller: &mut DualSenseController) -> Result<String, Error> {
+    let data = controller.get_feature_report(FIRMWARE_INFO_REPORT, FIRMWARE_INFO_LEN)?;
+    if data.len() < 32 {
+        return Err(Error::Other(format!("firmware info is {} bytes", data.len())));
+    }
+    let text = |range: std::ops::Range<usize>| String::from_utf8_lossy(&data[range]).trim_matches(char::from(0)).trim().to_string();
+    let version = u32::from_le_bytes(data[28..32].try_into().map_err(|e| Error::Other(format!("firmware version: {}", e)))?);
+    Ok(format!("0x{:08x} (built {} {})", version, text(1..12), text(12..20)))
+}
+
+pub fn list() -> Result<(), Error> {
+    let found = device::detect_all()?;
+    if found.is_empty() {
+        println!("{}No DualSense or DualShock 4 found{}", colors::YELLOW, colors::RESET);
+        return Ok(());
+    }
+    for (number, detected) in found.iter().enumerate() {
+        println!("{}{}.{} {}{}{} ({}, {})",
+                 colors::GREEN, number + 1, colors::RESET,
+                 colors::BOLD, detected.model.name(), colors::RESET,
+                 if detected.usb { "USB" } else { "Bluetooth" },
+                 detected.serial.as_deref().unwrap_or("no serial"));
+        println!("  {}{}{}", colors::GRAY, detected.path, colors::RESET);
+    }
+    if found.len() > 1 {
+        println!("{}Pick one with --device and its number, serial or path, or drive them all with --all-controllers{}", colors::GRAY, colors::RESET);
+    }
+    Ok(())
+}
@@ -0,0 +1,418 @@
+// --openrgb: speaks the OpenRGB SDK protocol so the lightbar shows up in OpenRGB as a
+// gamepad, next to the keyboard and the case fans. OpenRGB adds it like any remote
+// machine (SDK Client tab, this address) and drives it from its own effects and plugins.
+//
+// The device has one zone with one LED. "Direct" mode is the lightbar in whatever color
+// OpenRGB sends; the other modes are the built-in effects. Only protocol version 1 is
+// offered, newer clients step down to it.
+//
+// The other way round, --openrgb-mirror connects to a running OpenRGB server as a client
+// and shows one of its devices on the lightbar, the same way --follow shows another PC.
+use crossbeam_channel::{Sender, unbounded};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::control::{self, ControlCommand, Request, Status};
+use crate::effects::EffectKind;
+use crate::follow::Follower;
+use crate::net::ConnectionLimit;
+
+// OpenRGB's own server takes 6742
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:6743";
+pub const DEFAULT_SERVER: &str = "127.0.0.1:6742";
+// The SDK has no change notifications, the device is asked for its colors this often
+const MIRROR_EVERY: Duration = Duration::from_millis(33);
+const RETRY_EVERY: Duration = Duration::from_secs(5);
+// Payload lengths come from the peer: requests for one LED are tiny, while controller data
+// from a server with a lot of LEDs can run to tens of kilobytes
+const MAX_REQUEST: usize = 4096;
+const MAX_ANSWER: usize = 1 << 20;
+// OpenRGB keeps one connection per client
+const MAX_CONNECTIONS: usize = 8;
+
+const PROTOCOL_VERSION: u32 = 1;
+
+// Packet IDs
+const REQUEST_CONTROLLER_COUNT: u32 = 0;
+const REQUEST_CONTROLLER_DATA: u32 = 1;
+const REQUEST_PROTOCOL_VERSION: u32 = 40;
+const SET_CLIENT_NAME: u32 = 50;
+const DEVICE_LIST_UPDATED: u32 = 100;
+const RGBCONTROLLER_RESIZEZONE: u32 = 1000;
+const RGBCONTROLLER_UPDATELEDS: u32 = 1050;
+const RGBCONTROLLER_UPDATEZONELEDS: u32 = 1051;
+const RGBCONTROLLER_UPDATESINGLELED: u32 = 1052;
+const RGBCONTROLLER_SETCUSTOMMODE: u32 = 1100;
+const RGBCONTROLLER_UPDATEMODE: u32 = 1101;
+
+const DEVICE_TYPE_GAMEPAD: u32 = 10;
+const MODE_FLAG_HAS_PER_LED_COLOR: u32 = 1 << 5;
+const MODE_COLORS_NONE: u32 = 0;
+const MODE_COLORS_PER_LED: u32 = 1;
+const ZONE_TYPE_SINGLE: u32 = 0;
+
+// Mode 0 is Direct, the rest are the effects other than solid, which Direct stands in for
+fn modes() -> impl Iterator<Item = EffectKind> {
+    EffectKind::ALL.into_iter().filter(|&kind| kind != EffectKind::Solid)
+}
+
+pub fn start(address: &str, requests: Sender<Request>) -> Result<SocketAddr, String> {
+    let listener = TcpListener::bind(address).map_err(|e| format!("OpenRGB server {}: {}", address, e))?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+
+    thread::spawn(move || {
+        let limit = ConnectionLimit::new(MAX_CONNECTIONS);
+        for stream in listener.incoming().flatten() {
+            let Some(slot) = limit.acquire() else { continue };
+            let requests = requests.clone();
+            thread::spawn(move || {
+                let _slot = slot;
+                let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+                if let Err(e) = serve(stream, &requests)
+                    && e.kind() != io::ErrorKind::UnexpectedEof
+                {
+                    error!("openrgb {}: {}", peer, e);
+                }
+            });
+        }
+    });
+    Ok(address)
+}
+
+// One client, for as long as it stays connected
+fn serve(mut stream: TcpStream, requests: &Sender<Request>) -> io::Result<()> {
+    let mut version = 0;
+    loop {
+        let mut header = [0u8; 16];
+        stream.read_exact(&mut header)?;
+        if &header[..4] != b"ORGB" {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an OpenRGB SDK client"));
+        }
+        let device = u32_at(&header, 4);
+        let id = u32_at(&header, 8);
+        let data = read_payload(&mut stream, &header, MAX_REQUEST)?;
+
+        let command = match id {
+            REQUEST_PROTOCOL_VERSION => {
+                version = data.get(..4).map_or(0, |_| u32_at(&data, 0)).min(PROTOCOL_VERSION);
+                send(&mut stream, 0, id, &PROTOCOL_VERSION.to_le_bytes())?;
+                continue;
+            },
+            REQUEST_CONTROLLER_COUNT => {
+                send(&mut stream, 0, id, &1u32.to_le_bytes())?;
+                continue;
+            },
+            REQUEST_CONTROLLER_DATA => {
+                let status = match control::submit(requests, ControlCommand::Status) {
+                    Ok(Some(status)) => status,
+                    _ => return Ok(()),
+                };
+                // The version can come along with the request as well
+                let version = data.get(..4).map_or(version, |_| u32_at(&data, 0).min(PROTOCOL_VERSION));
+                send(&mut stream, device, id, &controller_data(&status, version))?;
+                continue;
+            },
+            // Colors are RGBColor, 0x00BBGGRR. Only the first counts, there is one LED
+            RGBCONTROLLER_UPDATELEDS => data.get(6..9).map(color),
+            RGBCONTROLLER_UPDATEZONELEDS => data.get(10..13).map(color),
+            RGBCONTROLLER_UPDATESINGLELED => data.get(4..7).filter(|_| u32_at(&data, 0) == 0).map(color),
+            // Direct: nothing to do until the colors arrive
+            RGBCONTROLLER_SETCUSTOMMODE => None,
+            RGBCONTROLLER_UPDATEMODE => match data.get(4..8).map(|_| u32_at(&data, 4) as usize) {
+                Some(0) | None => None,
+                Some(mode) => modes().nth(mode - 1).map(ControlCommand::Effect),
+            },
+            // Nothing to resize, and the client name isn't used
+            SET_CLIENT_NAME | RGBCONTROLLER_RESIZEZONE => None,
+            _ => None,
+        };
+        if let Some(command) = command
+            && let Err(e) = control::submit(requests, command)
+        {
+            if e == control::SHUTTING_DOWN {
+                return Ok(());
+            }
+            error!("openrgb: {}", e);
+        }
+    }
+}
+
+fn color(bytes: &[u8]) -> ControlCommand {
+    ControlCommand::SetColor { color: (bytes[0], bytes[1], bytes[2]), instant: true }
+}
+
+// Reads the payload announced in `header`, refusing one longer than `max`
+fn read_payload(stream: &mut TcpStream, header: &[u8; 16], max: usize) -> io::Result<Vec<u8>> {
+    let length = u32_at(header, 12) as usize;
+    if length > max {
+        return Err(io::Error::new(io::ErrorKind::FileTooLarge, "packet too large"));
+    }
+    let mut data = vec![0u8; length];
+    stream.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn send(stream: &mut TcpStream, device: u32, id: u32, data: &[u8]) -> io::Result<()> {
+    let mut packet = b"ORGB".to_vec();
+    for field in [device, id, data.len() as u32] {
+        packet.extend_from_slice(&field.to_le_bytes());
+    }
+    packet.extend_from_slice(data);
+    stream.write_all(&packet)
+}
+
+// The RGBController description OpenRGB lists the device from
+fn controller_data(status: &Status, version: u32) -> Vec<u8> {
+    let (r, g, b) = status.color;
+    let current = [r, g, b, 0];
+    let active = match status.effect {
+        EffectKind::Solid => 0,
+        kind => modes().position(|mode| mode == kind).map_or(0, |i| i + 1),
+    };
+
+    let mut data = Vec::new();
+    put_u32(&mut data, DEVICE_TYPE_GAMEPAD);
+    put_str(&mut data, "DualSense lightbar");
+    if version >= 1 {
+        put_str(&mut data, "Sony");
+    }
+    put_str(&mut data, "DualSense lightbar through dualsense-rainbow");
+    put_str(&mut data, env!("CARGO_PKG_VERSION"));
+    put_str(&mut data, "");
+    put_str(&mut data, "dualsense-rainbow");
+
+    let names: Vec<&str> = std::iter::once("Direct").chain(modes().map(EffectKind::name)).collect();
+    data.extend_from_slice(&(names.len() as u16).to_le_bytes());
+    put_u32(&mut data, active as u32);
+    for (value, name) in names.into_iter().enumerate() {
+        let direct = value == 0;
+        put_str(&mut data, name);
+        put_u32(&mut data, value as u32);
+        put_u32(&mut data, if direct { MODE_FLAG_HAS_PER_LED_COLOR } else { 0 });
+        // Speed min and max, colors min and max, speed, direction
+        for _ in 0..6 {
+            put_u32(&mut data, 0);
+        }
+        put_u32(&mut data, if direct { MODE_COLORS_PER_LED } else { MODE_COLORS_NONE });
+        // No mode-specific colors
+        data.extend_from_slice(&0u16.to_le_bytes());
+    }
+
+    // One zone of one LED, without a matrix map
+    data.extend_from_slice(&1u16.to_le_bytes());
+    put_str(&mut data, "Lightbar");
+    put_u32(&mut data, ZONE_TYPE_SINGLE);
+    for leds in [1, 1, 1] {
+        put_u32(&mut data, leds);
+    }
+    data.extend_from_slice(&0u16.to_le_bytes());
+
+    data.extend_from_slice(&1u16.to_le_bytes());
+    put_str(&mut data, "Lightbar");
+    put_u32(&mut data, 0);
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&current);
+
+    // The size leads, and counts itself
+    let mut packet = ((data.len() + 4) as u32).to_le_bytes().to_vec();
+    packet.extend_from_slice(&data);
+    packet
+}
+
+fn put_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+// Length with the terminating NUL, then the NUL-terminated text
+fn put_str(data: &mut Vec<u8>, s: &str) {
+    data.extend_from_slice(&(s.len() as u16 + 1).to_le_bytes());
+    data.extend_from_slice(s.as_bytes());
+    data.push(0);
+}
+
+// Mirrors `device`, a name (any part of it, ignoring case) or index as OpenRGB lists it,
+// from the server at `server`. Every LED counts, the lightbar shows their average
+pub fn mirror(server: &str, device: &str) -> io::Result<Follower> {
+    let address = server
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "address did not resolve"))?;
+    let (tx, colors) = unbounded();
+    let device = device.to_string();
+
+    thread::spawn(move || {
+        let mut last_error = None;
+        loop {
+            match run_mirror(address, &device, &tx) {
+                // Nobody is following any more
+                Ok(()) => return,
+                // Devices were added or removed, find ours again
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    // OpenRGB not running is worth one line, not one every few seconds
+                    let e = e.to_string();
+                    if last_error.as_ref() != Some(&e) {
+                        error!("openrgb {}: {}", address, e);
+                        last_error = Some(e);
+                    }
+                },
+            }
+            thread::sleep(RETRY_EVERY);
+        }
+    });
+    Ok(Follower::from_channel(address, colors))
+}
+
+fn run_mirror(address: SocketAddr, device: &str, colors: &Sender<(u8, u8, u8)>) -> io::Result<()> {
+    let mut stream = TcpStream::connect_timeout(&address, Duration::from_secs(3))?;
+    stream.set_read_timeout(Some(Duration::from_secs(3)))?;
+    send(&mut stream, 0, SET_CLIENT_NAME, b"dualsense-rainbow\0")?;
+    // Servers from before protocol versions never answer this one
+    let version = match request(&mut stream, 0, REQUEST_PROTOCOL_VERSION, &PROTOCOL_VERSION.to_le_bytes()) {
+        Ok(data) if data.len() >= 4 => u32_at(&data, 0).min(PROTOCOL_VERSION),
+        Ok(_) => 0,
+        Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => 0,
+        Err(e) => return Err(e),
+    };
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed controller data");
+
+    let count = request(&mut stream, 0, REQUEST_CONTROLLER_COUNT, &[])?;
+    let count = count.get(..4).map(|_| u32_at(&count, 0)).ok_or_else(invalid)?;
+    let mut names = Vec::new();
+    for index in 0..count {
+        let data = request(&mut stream, index, REQUEST_CONTROLLER_DATA, &version.to_le_bytes())?;
+        names.push(parse_controller(&data, version).ok_or_else(invalid)?.name);
+    }
+    let index = match device.parse::<usize>() {
+        Ok(index) if index < names.len() => index,
+        _ => names
+            .iter()
+            .position(|name| name.to_lowercase().contains(&device.to_lowercase()))
+            .ok_or_else(|| {
+                let found = if names.is_empty() { "none".to_string() } else { names.join(", ") };
+                io::Error::new(io::ErrorKind::NotFound, format!("no device matching '{}' (found: {})", device, found))
+            })?,
+    };
+    info!("openrgb: mirroring {}", names[index]);
+
+    loop {
+        let data = request(&mut stream, index as u32, REQUEST_CONTROLLER_DATA, &version.to_le_bytes())?;
+        let controller = parse_controller(&data, version).ok_or_else(invalid)?;
+        if let Some(color) = average(&controller.colors)
+            && colors.send(color).is_err()
+        {
+            return Ok(());
+        }
+        thread::sleep(MIRROR_EVERY);
+    }
+}
+
+// Sends a request and waits for its answer
+fn request(stream: &mut TcpStream, device: u32, id: u32, data: &[u8]) -> io::Result<Vec<u8>> {
+    send(stream, device, id, data)?;
+    loop {
+        let mut header = [0u8; 16];
+        stream.read_exact(&mut header)?;
+        if &header[..4] != b"ORGB" {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an OpenRGB SDK server"));
+        }
+        let answer = read_payload(stream, &header, MAX_ANSWER)?;
+        match u32_at(&header, 8) {
+            DEVICE_LIST_UPDATED => return Err(io::Error::new(io::ErrorKind::Interrupted, "device list changed")),
+            answered if answered == id => return Ok(answer),
+            _ => {},
+        }
+    }
+}
+
+fn average(colors: &[(u8, u8, u8)]) -> Option<(u8, u8, u8)> {
+    let n = colors.len() as u32;
+    if n == 0 {
+        return None;
+    }
+    let sum = colors.iter().fold((0, 0, 0), |(r, g, b), &(cr, cg, cb)| (r + cr as u32, g + cg as u32, b + cb as u32));
+    Some(((sum.0 / n) as u8, (sum.1 / n) as u8, (sum.2 / n) as u8))
+}
+
+// What the mirror needs out of an RGBController description, see controller_data
+struct Controller {
+    name: String,
+    colors: Vec<(u8, u8, u8)>,
+}
+
+fn parse_controller(data: &[u8], version: u32) -> Option<Controller> {
+    let mut reader = Reader { data, at: 0 };
+    // Size, device type
+    reader.skip(8)?;
+    let name = reader.str()?;
+    // Vendor (version 1 on), description, version, serial, location
+    reader.skip_strs(if version >= 1 { 5 } else { 4 })?;
+
+    let modes = reader.u16()?;
+    reader.skip(4)?;
+    for _ in 0..modes {
+        reader.skip_strs(1)?;
+        // Value, flags, speed min and max, colors min and max, speed, direction, color mode
+        reader.skip(9 * 4)?;
+        let colors = reader.u16()? as usize;
+        reader.skip(colors * 4)?;
+    }
+
+    let zones = reader.u16()?;
+    for _ in 0..zones {
+        reader.skip_strs(1)?;
+        // Type, LEDs min, max and count
+        reader.skip(4 * 4)?;
+        let matrix = reader.u16()? as usize;
+        reader.skip(matrix)?;
+    }
+
+    let leds = reader.u16()?;
+    for _ in 0..leds {
+        reader.skip_strs(1)?;
+        reader.skip(4)?;
+    }
+
+    let count = reader.u16()? as usize;
+    let colors = reader.take(count * 4)?.chunks_exact(4).map(|c| (c[0], c[1], c[2])).collect();
+    Some(Controller { name, colors })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.at..self.at + len)?;
+        self.at += len;
+        Some(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    // Length with the NUL, then the text
+    fn str(&mut self) -> Option<String> {
+        let len = self.u16()? as usize;
+        let bytes = self.take(len)?;
+        Some(String::from_utf8_lossy(bytes.strip_suffix(&[0]).unwrap_or(bytes)).into_owned())
+    }
+
+    fn skip_strs(&mut self, count: usize) -> Option<()> {
+        (0..count).try_for_each(|_| self.str().map(|_| ()))
+    }
+}
@@ -0,0 +1,281 @@
+// Palettes: looping gradients that effects sample instead of the full HSV wheel.
+//
+// A palette is a list of color stops, each a color with an optional position along the
+// loop, as in a CSS gradient: "#ff8800 25%". Stops without one are spread evenly between
+// their neighbours (the first one sits at 0%), and the last stop blends back into the
+// first. Besides a list in the config, a palette can be the name of a built-in one or of
+// a file in the config directory's palettes/ folder, or the path of a TOML (or .json)
+// file with the stops, e.g. `colors = ["#1a0033", "#ff00aa 40%", "#00e5ff 70%"]`. The
+// path of an image gives its dominant colors instead, found with k-means
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use dualsense_rainbow::{Color, rgb_to_hue};
+use crate::effects;
+
+pub const BUILT_IN: [(&str, &[&str]); 5] = [
+    ("sunset", &["#2b0a3d", "#8a1c5c 20%", "#e8413c 45%", "#ff8a1e 65%", "#ffc94a 80%"]),
+    ("ocean", &["#001a33", "#004f7a 30%", "#00a5b5 55%", "#3fe0c5 70%", "#0a3d8a 85%"]),
+    ("cyberpunk", &["#ff00aa", "#7a00ff 30%", "#00e5ff 55%", "#ff2a6d 80%"]),
+    ("fire", &["#ff0000", "#ff4000 35%", "#ffa000 55%", "#ff2000 75%"]),
+    ("forest", &["#0b3d0b", "#2e7d32 30%", "#8bc34a 50%", "#1b5e20 75%"]),
+];
+
+// Paths with these extensions are images to take the colors from
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "gif", "webp", "bmp"];
+// How many colors k-means looks for; small patches of color are dropped afterwards
+const IMAGE_COLORS: usize = 6;
+// Share of the image a color needs to cover to make it into the palette
+const IMAGE_MIN_SHARE: f32 = 0.02;
+
+// A color and where it sits, when that is given
+type Stop = (Option<f32>, (u8, u8, u8));
+
+// A palette file: the stops, as in a profile's `palette` list
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct PaletteFile {
+    colors: Vec<String>,
+}
+
+// A looping set of colors that effects can sample instead of the full HSV wheel
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    // Positions 0.0..1.0 in increasing order, the first at 0.0
+    stops: Vec<(f32, (u8, u8, u8))>,
+}
+
+impl Palette {
+    // Colors evenly spread around the loop
+    pub fn new(colors: Vec<(u8, u8, u8)>) -> Self {
+        assert!(!colors.is_empty(), "a palette needs at least one color");
+        let count = colors.len() as f32;
+        Self { stops: colors.into_iter().enumerate().map(|(i, color)| (i as f32 / count, color)).collect() }
+    }
+
+    // Stops like "#ff8800" or "#ff8800 25%"
+    pub fn from_stops(stops: &[impl AsRef<str>]) -> Result<Self, String> {
+        if stops.is_empty() {
+            return Err("palette needs at least one color".into());
+        }
+        let parsed: Vec<Stop> = stops.iter().map(|stop| parse_stop(stop.as_ref())).collect::<Result<_, _>>()?;
+
+        // Fill in the missing positions between the known ones, the end of the loop being 100%
+        let mut positions: Vec<Option<f32>> = parsed.iter().map(|(position, _)| *position).collect();
+        positions[0] = Some(positions[0].unwrap_or(0.0));
+        let mut known = 0;
+        for i in 1..=positions.len() {
+            let at = match positions.get(i) {
+                Some(Some(at)) => *at,
+                Some(None) => continue,
+                None => 1.0,
+            };
+            let from = positions[known].unwrap_or_default();
+            for (k, position) in positions.iter_mut().enumerate().take(i).skip(known + 1) {
+                *position = Some(from + (at - from) * (k - known) as f32 / (i - known) as f32);
+            }
+            known = i;
+        }
+
+        let stops: Vec<_> = positions.into_iter().flatten().zip(parsed.iter().map(|(_, color)| *color)).collect();
+        if stops[0].0 != 0.0 {
+            return Err(format!("the first color must be at 0%, not {}%", stops[0].0 * 100.0));
+        }
+        if let Some(pair) = stops.windows(2).find(|pair| pair[1].0 < pair[0].0 || pair[1].0 >= 1.0) {
+            return Err(format!("color stops must increase from 0% to below 100%, {}% comes after {}%", pair[1].0 * 100.0, pair[0].0 * 100.0));
+        }
+        Ok(Self { stops })
+    }
+
+    // A built-in palette, one in the config's palettes/ folder, a palette file or an image
+    pub fn named(name: &str) -> Result<Self, String> {
+        let name = name.trim();
+        if let Some((_, stops)) = BUILT_IN.iter().find(|(known, _)| known.eq_ignore_ascii_case(name)) {
+            return Self::from_stops(stops);
+        }
+        let path = PathBuf::from(name);
+        let image = path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        if image {
+            return Self::from_image(&path);
+        }
+        if path.extension().is_some() || path.components().count() > 1 {
+            return Self::load(&path);
+        }
+        match user_dir().map(|dir| dir.join(format!("{}.toml", name))) {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => {
+                let names: Vec<_> = BUILT_IN.iter().map(|(name, _)| *name).collect();
+                Err(format!("unknown palette '{}' (built in: {}, or a palette file)", name, names.join(", ")))
+            },
+        }
+    }
+
+    fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("cannot read palette {}: {}", path.display(), e))?;
+        let file: PaletteFile = match path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+            true => serde_json::from_str(&text).map_err(|e| e.to_string()),
+            false => toml::from_str(&text).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("invalid palette {}: {}", path.display(), e))?;
+        Self::from_stops(&file.colors).map_err(|e| format!("invalid palette {}: {}", path.display(), e))
+    }
+
+    // The image's dominant colors, round the hue wheel so they blend like a rainbow
+    pub fn from_image(path: &Path) -> Result<Self, String> {
+        let image = image::open(path).map_err(|e| format!("cannot read image {}: {}", path.display(), e))?;
+        // Small enough for k-means to take no time, big enough to keep every color that matters
+        let pixels: Vec<[f32; 3]> = image
+            .thumbnail(96, 96)
+            .to_rgba8()
+            .pixels()
+            .filter(|pixel| pixel[3] >= 128)
+            .map(|pixel| [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32])
+            .collect();
+        if pixels.is_empty() {
+            return Err(format!("image {} has no opaque pixels", path.display()));
+        }
+
+        let clusters = kmeans(&pixels, IMAGE_COLORS);
+        let mut colors: Vec<(u8, u8, u8)> = clusters
+            .iter()
+            .filter(|(_, count)| *count as f32 / pixels.len() as f32 >= IMAGE_MIN_SHARE)
+            .map(|(center, _)| (center[0].round() as u8, center[1].round() as u8, center[2].round() as u8))
+            .collect();
+        // Near-black looks like the lightbar is off, so a dark background stays out of it
+        let lit: Vec<_> = colors.iter().copied().filter(|&(r, g, b)| r.max(g).max(b) >= 32).collect();
+        if !lit.is_empty() {
+            colors = lit;
+        }
+        colors.sort_by(|&(r1, g1, b1), &(r2, g2, b2)| rgb_to_hue(r1, g1, b1).total_cmp(&rgb_to_hue(r2, g2, b2)));
+        Ok(Self::new(colors))
+    }
+
+    pub fn len(&self) -> usize {
+        self.stops.len()
+    }
+
+    // Where the `index`th color sits, 0.0..1.0, counting on round the loop
+    pub fn position(&self, index: usize) -> f32 {
+        self.stops[index % self.stops.len()].0
+    }
+
+    // Position 0.0..1.0 walks through every color and blends back into the first
+    pub fn sample(&self, position: f32) -> (u8, u8, u8) {
+        let position = position.rem_euclid(1.0);
+        let next = self.stops.partition_point(|&(at, _)| at <= position);
+        let (from_at, from) = self.stops[next - 1];
+        let (to_at, to) = self.stops.get(next).copied().unwrap_or((1.0, self.stops[0].1));
+        let t = if to_at > from_at { (position - from_at) / (to_at - from_at) } else { 0.0 };
+        effects::mix(from, to, t)
+    }
+}
+
+// Up to k cluster centers and how many pixels each has. The first center is the mean
+// and each next one the pixel farthest from those so far, so the same image always gives
+// the same colors and an image with fewer than k colors gives fewer clusters
+fn kmeans(pixels: &[[f32; 3]], k: usize) -> Vec<([f32; 3], usize)> {
+    let distance = |a: &[f32; 3], b: &[f32; 3]| (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f32>();
+    let nearest = |centers: &[[f32; 3]], pixel: &[f32; 3]| {
+        (0..centers.len()).min_by(|&a, &b| distance(&centers[a], pixel).total_cmp(&distance(&centers[b], pixel))).unwrap_or(0)
+    };
+
+    let mean = pixels.iter().fold([0.0; 3], |sum, pixel| [sum[0] + pixel[0], sum[1] + pixel[1], sum[2] + pixel[2]]);
+    let mut centers = vec![mean.map(|channel| channel / pixels.len() as f32)];
+    while centers.len() < k {
+        let farthest = pixels.iter().max_by(|a, b| distance(&centers[nearest(&centers, a)], a).total_cmp(&distance(&centers[nearest(&centers, b)], b)));
+        match farthest {
+            Some(pixel) if distance(&centers[nearest(&centers, pixel)], pixel) >= 1.0 => centers.push(*pixel),
+            _ => break,
+        }
+    }
+
+    let mut counts = vec![0; centers.len()];
+    for _ in 0..16 {
+        let mut sums = vec![[0.0; 3]; centers.len()];
+        counts = vec![0; centers.len()];
+        for pixel in pixels {
+            let i = nearest(&centers, pixel);
+            (0..3).for_each(|c| sums[i][c] += pixel[c]);
+            counts[i] += 1;
+        }
+        for (i, center) in centers.iter_mut().enumerate() {
+            if counts[i] > 0 {
+                *center = sums[i].map(|sum| sum / counts[i] as f32);
+            }
+        }
+    }
+    centers.into_iter().zip(counts).filter(|(_, count)| *count > 0).collect()
+}
+
+// Where `palette = "name"` looks for name.toml
+fn user_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("dualsense-rainbow").join("palettes"))
+}
+
+// "warm white", "#ff8800 25%" or "255,136,0 50%": the position, if any, and the color
+fn parse_stop(stop: &str) -> Result<Stop, String> {
+    let stop = stop.trim();
+    let Some((color, percent)) = stop.rsplit_once(' ').and_then(|(color, position)| Some((color, position.strip_suffix('%')?))) else {
+        return Ok((None, parse_color(stop)?));
+    };
+    let percent: f32 = percent.parse().map_err(|_| format!("invalid position '{}%' in '{}'", percent, stop))?;
+    Ok((Some(percent / 100.0), parse_color(color)?))
+}
+
+// What a palette setting can be: a list of stops or a name (built in or a file)
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum PaletteSpec {
+    Stops(Vec<String>),
+    Named(String),
+}
+
+impl PaletteSpec {
+    pub fn resolve(&self) -> Result<Palette, String> {
+        match self {
+            PaletteSpec::Stops(stops) => Palette::from_stops(stops),
+            PaletteSpec::Named(name) => Palette::named(name),
+        }
+    }
+}
+
+// Names a voice assistant is likely to pass along, tuned for the lightbar: its LEDs
+// wash out the CSS values of some (CSS orange shows up nearly yellow)
+const NAMED_COLORS: [(&str, (u8, u8, u8)); 16] = [
+    ("red", (255, 0, 0)),
+    ("orange", (255, 100, 0)),
+    ("yellow", (255, 220, 0)),
+    ("green", (0, 255, 0)),
+    ("cyan", (0, 255, 255)),
+    ("teal", (0, 160, 140)),
+    ("blue", (0, 0, 255)),
+    ("indigo", (75, 0, 200)),
+    ("purple", (160, 0, 255)),
+    ("violet", (200, 80, 255)),
+    ("magenta", (255, 0, 255)),
+    ("pink", (255, 80, 160)),
+    ("white", (255, 255, 255)),
+    ("warm white", (255, 180, 100)),
+    ("gold", (255, 170, 0)),
+    ("lime", (150, 255, 0)),
+];
+
+// One of NAMED_COLORS, or anything Color parses (hex, R,G,B or a CSS name), case-insensitive
+pub fn parse_color(s: &str) -> Result<(u8, u8, u8), String> {
+    let name = s.trim().to_ascii_lowercase();
+    match NAMED_COLORS.iter().find(|(known, _)| *known == name) {
+        Some((_, color)) => Ok(*color),
+        None => s.parse::<Color>().map(Color::rgb),
+    }
+}
+
+// "#ff8800" or "ff8800"
+pub fn parse_hex_color(s: &str) -> Result<(u8, u8, u8), String> {
+    let digits = s.trim().trim_start_matches('#');
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid color '{}' (expected hex like #ff8800)", s));
+    }
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).unwrap_or_default();
+    Ok((channel(0), channel(2), channel(4)))
+}
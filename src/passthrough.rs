@@ -0,0 +1,400 @@
+// --passthrough: games get a virtual Xbox 360 pad fed from the DualSense's input, while
+// the DualSense itself is kept away from them, so only this program writes its lighting.
+// The virtual pad is made through uinput on Linux (where the pad's own event devices are
+// grabbed) and through the ViGEmBus driver on Windows.
+use tracing::{error, info};
+
+use crate::input::{Button, ControllerState};
+
+// XInput button bits, the layout both backends are fed with
+const DPAD_UP: u16 = 0x0001;
+const DPAD_DOWN: u16 = 0x0002;
+const DPAD_LEFT: u16 = 0x0004;
+const DPAD_RIGHT: u16 = 0x0008;
+const START: u16 = 0x0010;
+const BACK: u16 = 0x0020;
+const LEFT_THUMB: u16 = 0x0040;
+const RIGHT_THUMB: u16 = 0x0080;
+const LEFT_SHOULDER: u16 = 0x0100;
+const RIGHT_SHOULDER: u16 = 0x0200;
+const GUIDE: u16 = 0x0400;
+const A: u16 = 0x1000;
+const B: u16 = 0x2000;
+const X: u16 = 0x4000;
+const Y: u16 = 0x8000;
+
+// Which Xbox button each DualSense button becomes. The touchpad click doubles as Back,
+// the mute button has no counterpart
+const BUTTONS: [(Button, u16); 16] = [
+    (Button::Cross, A),
+    (Button::Circle, B),
+    (Button::Square, X),
+    (Button::Triangle, Y),
+    (Button::L1, LEFT_SHOULDER),
+    (Button::R1, RIGHT_SHOULDER),
+    (Button::Create, BACK),
+    (Button::Touchpad, BACK),
+    (Button::Options, START),
+    (Button::Ps, GUIDE),
+    (Button::L3, LEFT_THUMB),
+    (Button::R3, RIGHT_THUMB),
+    (Button::DpadUp, DPAD_UP),
+    (Button::DpadDown, DPAD_DOWN),
+    (Button::DpadLeft, DPAD_LEFT),
+    (Button::DpadRight, DPAD_RIGHT),
+];
+
+// An Xbox 360 pad's state, sticks with up and right positive like XInput
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Gamepad {
+    buttons: u16,
+    left_trigger: u8,
+    right_trigger: u8,
+    left_stick: (i16, i16),
+    right_stick: (i16, i16),
+}
+
+impl Gamepad {
+    fn from_state(state: &ControllerState) -> Self {
+        let buttons = BUTTONS
+            .iter()
+            .filter(|(button, _)| state.is_pressed(*button))
+            .fold(0, |bits, (_, bit)| bits | bit);
+        Self {
+            buttons,
+            left_trigger: state.l2,
+            right_trigger: state.r2,
+            left_stick: stick(state.left_stick),
+            right_stick: stick(state.right_stick),
+        }
+    }
+}
+
+// 0-255 with 128 centered and down positive, to the full i16 range with up positive
+fn stick((x, y): (u8, u8)) -> (i16, i16) {
+    let axis = |v: u8| ((v as i32 - 128) * 32767 / 127).clamp(-32768, 32767);
+    (axis(x) as i16, (-axis(y)).clamp(-32768, 32767) as i16)
+}
+
+pub struct VirtualPad {
+    backend: backend::Backend,
+    last: Option<Gamepad>,
+    failing: bool,
+}
+
+impl VirtualPad {
+    // Creates the virtual pad and hides the DualSense at `device_path` from games where
+    // the platform allows it
+    pub fn create(device_path: &str) -> Result<Self, String> {
+        Ok(Self { backend: backend::Backend::create(device_path)?, last: None, failing: false })
+    }
+
+    pub fn description(&self) -> String {
+        self.backend.description()
+    }
+
+    // The controller was opened again, possibly under another path
+    pub fn reattach(&mut self, device_path: &str) {
+        self.backend.reattach(device_path);
+        self.last = None;
+    }
+
+    // Hands the latest input to the virtual pad, skipping reports that change nothing
+    pub fn forward(&mut self, state: &ControllerState) {
+        let pad = Gamepad::from_state(state);
+        if self.last == Some(pad) {
+            return;
+        }
+        match self.backend.send(&pad) {
+            Ok(()) => {
+                if self.failing {
+                    info!("Virtual pad is receiving input again");
+                }
+                self.failing = false;
+                self.last = Some(pad);
+            },
+            Err(e) => {
+                if !self.failing {
+                    error!("Virtual pad update failed: {}", e);
+                }
+                self.failing = true;
+            },
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod backend {
+    use super::*;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::path::{Path, PathBuf};
+    use tracing::warn;
+
+    const EV_SYN: u16 = 0x00;
+    const EV_KEY: u16 = 0x01;
+    const EV_ABS: u16 = 0x03;
+    const SYN_REPORT: u16 = 0;
+
+    const ABS_X: u16 = 0x00;
+    const ABS_Y: u16 = 0x01;
+    const ABS_Z: u16 = 0x02;
+    const ABS_RX: u16 = 0x03;
+    const ABS_RY: u16 = 0x04;
+    const ABS_RZ: u16 = 0x05;
+    const ABS_HAT0X: u16 = 0x10;
+    const ABS_HAT0Y: u16 = 0x11;
+
+    // The key codes the kernel's xpad driver reports, so the pad maps like a real one
+    const KEYS: [(u16, u16); 11] = [
+        (A, 0x130),              // BTN_A
+        (B, 0x131),              // BTN_B
+        (X, 0x133),              // BTN_X
+        (Y, 0x134),              // BTN_Y
+        (LEFT_SHOULDER, 0x136),  // BTN_TL
+        (RIGHT_SHOULDER, 0x137), // BTN_TR
+        (BACK, 0x13a),           // BTN_SELECT
+        (START, 0x13b),          // BTN_START
+        (GUIDE, 0x13c),          // BTN_MODE
+        (LEFT_THUMB, 0x13d),     // BTN_THUMBL
+        (RIGHT_THUMB, 0x13e),    // BTN_THUMBR
+    ];
+
+    // Microsoft Xbox 360 wired controller
+    const VENDOR: u16 = 0x045e;
+    const PRODUCT: u16 = 0x028e;
+    const BUS_USB: u16 = 0x03;
+
+    const fn ioc(dir: u64, ty: u8, nr: u8, size: usize) -> u64 {
+        (dir << 30) | ((size as u64) << 16) | ((ty as u64) << 8) | nr as u64
+    }
+    const fn iow<T>(ty: u8, nr: u8) -> u64 {
+        ioc(1, ty, nr, size_of::<T>())
+    }
+
+    const UI_DEV_CREATE: u64 = ioc(0, b'U', 1, 0);
+    const UI_DEV_DESTROY: u64 = ioc(0, b'U', 2, 0);
+    const UI_DEV_SETUP: u64 = iow::<libc::uinput_setup>(b'U', 3);
+    const UI_ABS_SETUP: u64 = iow::<libc::uinput_abs_setup>(b'U', 4);
+    const UI_SET_EVBIT: u64 = iow::<libc::c_int>(b'U', 100);
+    const UI_SET_KEYBIT: u64 = iow::<libc::c_int>(b'U', 101);
+    const UI_SET_ABSBIT: u64 = iow::<libc::c_int>(b'U', 103);
+    const EVIOCGRAB: u64 = iow::<libc::c_int>(b'E', 0x90);
+
+    // Integer arguments go by value, structs by address
+    fn ioctl(file: &File, request: u64, arg: libc::c_ulong) -> std::io::Result<()> {
+        match unsafe { libc::ioctl(file.as_raw_fd(), request as _, arg) } {
+            -1 => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    pub struct Backend {
+        uinput: File,
+        // The DualSense's own event devices, held grabbed so games only see the virtual pad
+        grabbed: Vec<File>,
+    }
+
+    impl Backend {
+        pub fn create(device_path: &str) -> Result<Self, String> {
+            let uinput = OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open("/dev/uinput")
+                .map_err(|e| format!("cannot open /dev/uinput ({}), see the README for the udev rule", e))?;
+            setup(&uinput).map_err(|e| format!("cannot create the virtual pad: {}", e))?;
+
+            let mut backend = Self { uinput, grabbed: Vec::new() };
+            backend.reattach(device_path);
+            Ok(backend)
+        }
+
+        pub fn description(&self) -> String {
+            format!("uinput, {} DualSense input device(s) hidden from games", self.grabbed.len())
+        }
+
+        pub fn reattach(&mut self, device_path: &str) {
+            self.grabbed = event_devices(device_path)
+                .into_iter()
+                .filter_map(|path| {
+                    let file = File::open(&path).ok()?;
+                    match ioctl(&file, EVIOCGRAB, 1) {
+                        Ok(()) => Some(file),
+                        Err(e) => {
+                            warn!("Cannot grab {}: {}", path.display(), e);
+                            None
+                        },
+                    }
+                })
+                .collect();
+        }
+
+        pub fn send(&mut self, pad: &Gamepad) -> std::io::Result<()> {
+            let hat = |negative: u16, positive: u16| {
+                (pad.buttons & positive != 0) as i32 - (pad.buttons & negative != 0) as i32
+            };
+            let mut events: Vec<(u16, u16, i32)> = KEYS
+                .iter()
+                .map(|&(bit, code)| (EV_KEY, code, (pad.buttons & bit != 0) as i32))
+                .collect();
+            // evdev axes point down, XInput's up
+            events.extend([
+                (EV_ABS, ABS_X, pad.left_stick.0 as i32),
+                (EV_ABS, ABS_Y, -(pad.left_stick.1 as i32)),
+                (EV_ABS, ABS_RX, pad.right_stick.0 as i32),
+                (EV_ABS, ABS_RY, -(pad.right_stick.1 as i32)),
+                (EV_ABS, ABS_Z, pad.left_trigger as i32),
+                (EV_ABS, ABS_RZ, pad.right_trigger as i32),
+                (EV_ABS, ABS_HAT0X, hat(DPAD_LEFT, DPAD_RIGHT)),
+                (EV_ABS, ABS_HAT0Y, hat(DPAD_UP, DPAD_DOWN)),
+                (EV_SYN, SYN_REPORT, 0),
+            ]);
+
+            // The kernel drops values that didn't change, so the whole state can go every time
+            let mut bytes = Vec::with_capacity(events.len() * size_of::<libc::input_event>());
+            for (type_, code, value) in events {
+                let event = libc::input_event {
+                    time: libc::timeval { tv_sec: 0, tv_usec: 0 },
+                    type_,
+                    code,
+                    value,
+                };
+                let raw = unsafe {
+                    std::slice::from_raw_parts(&event as *const _ as *const u8, size_of::<libc::input_event>())
+                };
+                bytes.extend_from_slice(raw);
+            }
+            self.uinput.write_all(&bytes)
+        }
+    }
+
+    impl Drop for Backend {
+        fn drop(&mut self) {
+            let _ = ioctl(&self.uinput, UI_DEV_DESTROY, 0);
+        }
+    }
+
+    fn setup(uinput: &File) -> std::io::Result<()> {
+        ioctl(uinput, UI_SET_EVBIT, EV_KEY as libc::c_ulong)?;
+        for (_, code) in KEYS {
+            ioctl(uinput, UI_SET_KEYBIT, code as libc::c_ulong)?;
+        }
+
+        ioctl(uinput, UI_SET_EVBIT, EV_ABS as libc::c_ulong)?;
+        let axes = [
+            (ABS_X, -32768, 32767, 16, 128),
+            (ABS_Y, -32768, 32767, 16, 128),
+            (ABS_RX, -32768, 32767, 16, 128),
+            (ABS_RY, -32768, 32767, 16, 128),
+            (ABS_Z, 0, 255, 0, 0),
+            (ABS_RZ, 0, 255, 0, 0),
+            (ABS_HAT0X, -1, 1, 0, 0),
+            (ABS_HAT0Y, -1, 1, 0, 0),
+        ];
+        for (code, minimum, maximum, fuzz, flat) in axes {
+            ioctl(uinput, UI_SET_ABSBIT, code as libc::c_ulong)?;
+            let abs = libc::uinput_abs_setup {
+                code,
+                absinfo: libc::input_absinfo { value: 0, minimum, maximum, fuzz, flat, resolution: 0 },
+            };
+            ioctl(uinput, UI_ABS_SETUP, &abs as *const libc::uinput_abs_setup as libc::c_ulong)?;
+        }
+
+        let mut setup = libc::uinput_setup {
+            id: libc::input_id { bustype: BUS_USB, vendor: VENDOR, product: PRODUCT, version: 0x0110 },
+            name: [0; libc::UINPUT_MAX_NAME_SIZE],
+            ff_effects_max: 0,
+        };
+        for (dst, src) in setup.name.iter_mut().zip(b"Microsoft X-Box 360 pad") {
+            *dst = *src as libc::c_char;
+        }
+        ioctl(uinput, UI_DEV_SETUP, &setup as *const libc::uinput_setup as libc::c_ulong)?;
+        ioctl(uinput, UI_DEV_CREATE, 0)
+    }
+
+    // /dev/input/event* nodes the kernel driver made for the pad behind a /dev/hidrawN path:
+    // the gamepad itself, its touchpad and its motion sensors
+    fn event_devices(device_path: &str) -> Vec<PathBuf> {
+        let Some(name) = Path::new(device_path).file_name() else {
+            return Vec::new();
+        };
+        let inputs = Path::new("/sys/class/hidraw").join(name).join("device/input");
+        let Ok(inputs) = fs::read_dir(inputs) else {
+            return Vec::new();
+        };
+        let mut devices: Vec<PathBuf> = inputs
+            .flatten()
+            .filter_map(|input| fs::read_dir(input.path()).ok())
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+            .map(|entry| Path::new("/dev/input").join(entry.file_name()))
+            .collect();
+        devices.sort();
+        devices
+    }
+}
+
+#[cfg(windows)]
+mod backend {
+    use super::*;
+    use vigem_client::{Client, TargetId, XButtons, XGamepad, Xbox360Wired};
+
+    pub struct Backend {
+        target: Xbox360Wired<Client>,
+    }
+
+    impl Backend {
+        pub fn create(_device_path: &str) -> Result<Self, String> {
+            let client = Client::connect().map_err(|e| format!("cannot reach the ViGEmBus driver ({}), is it installed?", e))?;
+            let mut target = Xbox360Wired::new(client, TargetId::XBOX360_WIRED);
+            target.plugin().map_err(|e| format!("cannot plug in the virtual pad: {}", e))?;
+            target.wait_ready().map_err(|e| format!("virtual pad not ready: {}", e))?;
+            Ok(Self { target })
+        }
+
+        pub fn description(&self) -> String {
+            // Windows has no way to grab a HID device, hiding it is HidHide's job
+            "ViGEmBus, hide the DualSense from games with HidHide".to_string()
+        }
+
+        pub fn reattach(&mut self, _device_path: &str) {}
+
+        pub fn send(&mut self, pad: &Gamepad) -> Result<(), vigem_client::Error> {
+            self.target.update(&XGamepad {
+                buttons: XButtons(pad.buttons),
+                left_trigger: pad.left_trigger,
+                right_trigger: pad.right_trigger,
+                thumb_lx: pad.left_stick.0,
+                thumb_ly: pad.left_stick.1,
+                thumb_rx: pad.right_stick.0,
+                thumb_ry: pad.right_stick.1,
+            })
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod backend {
+    use super::*;
+
+    pub struct Backend;
+
+    impl Backend {
+        pub fn create(_device_path: &str) -> Result<Self, String> {
+            Err("--passthrough needs uinput (Linux) or ViGEmBus (Windows)".to_string())
+        }
+
+        pub fn description(&self) -> String {
+            String::new()
+        }
+
+        pub fn reattach(&mut self, _device_path: &str) {}
+
+        pub fn send(&mut self, _pad: &Gamepad) -> Result<(), String> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,158 @@
+// PipeWire capture for --audio on Linux. A plain input device can't hear what the
+// desktop is playing on most setups, PipeWire can record any node: the default output,
+// a particular sink, or a single application's stream. The recording runs through
+// pw-record, so nothing beyond the PipeWire tools is needed at build time.
+use serde_json::Value;
+use std::io::{BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use tracing::error;
+
+pub const SAMPLE_RATE: u32 = 48_000;
+pub const CHANNELS: usize = 2;
+
+// A node from pw-dump worth recording from
+#[derive(Clone)]
+struct Node {
+    name: String,
+    description: String,
+    // Sinks are recorded through their monitor ports
+    sink: bool,
+}
+
+// Nodes that carry audio: sinks, sources and application streams
+fn list_nodes() -> Result<Vec<Node>, String> {
+    let output = Command::new("pw-dump")
+        .output()
+        .map_err(|e| format!("cannot run pw-dump ({}), is PipeWire installed?", e))?;
+    let dump: Value = serde_json::from_slice(&output.stdout).map_err(|e| format!("invalid pw-dump output: {}", e))?;
+
+    let nodes = dump
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|object| object["type"] == "PipeWire:Interface:Node")
+        .filter_map(|object| {
+            let props = &object["info"]["props"];
+            let class = props["media.class"].as_str()?;
+            if !matches!(class, "Audio/Sink" | "Audio/Source" | "Stream/Output/Audio") {
+                return None;
+            }
+            let name = props["node.name"].as_str()?.to_string();
+            let description = ["node.description", "application.name", "node.nick"]
+                .iter()
+                .find_map(|key| props[key].as_str())
+                .unwrap_or(&name)
+                .to_string();
+            Some(Node { name, description, sink: class == "Audio/Sink" })
+        })
+        .collect();
+    Ok(nodes)
+}
+
+// An exact node.name, or else a case-insensitive part of a name, description or application
+fn find_node(wanted: &str) -> Result<Node, String> {
+    let nodes = list_nodes()?;
+    if let Some(node) = nodes.iter().find(|node| node.name == wanted) {
+        return Ok(node.clone());
+    }
+
+    let needle = wanted.to_ascii_lowercase();
+    let mut matches: Vec<Node> = nodes
+        .iter()
+        .filter(|node| node.name.to_ascii_lowercase().contains(&needle) || node.description.to_ascii_lowercase().contains(&needle))
+        .cloned()
+        .collect();
+    match matches.len() {
+        1 => Ok(matches.remove(0)),
+        0 => {
+            let names: Vec<String> = nodes.iter().map(|node| format!("{} ({})", node.name, node.description)).collect();
+            Err(format!("no PipeWire node matches '{}', available: {}", wanted, names.join(", ")))
+        },
+        _ => {
+            let names: Vec<&str> = matches.iter().map(|node| node.name.as_str()).collect();
+            Err(format!("'{}' matches several PipeWire nodes: {}", wanted, names.join(", ")))
+        },
+    }
+}
+
+// A running pw-record, stopped when dropped
+pub struct Recorder {
+    child: Child,
+    description: String,
+    stopping: Arc<AtomicBool>,
+}
+
+impl Recorder {
+    // Records `node`, or what the default output plays when None, and hands the
+    // interleaved stereo samples to `feed` as they arrive
+    pub fn start(node: Option<&str>, mut feed: impl FnMut(&[f32]) + Send + 'static) -> Result<Self, String> {
+        let node = node.map(find_node).transpose()?;
+
+        let rate = SAMPLE_RATE.to_string();
+        let channels = CHANNELS.to_string();
+        let mut command = Command::new("pw-record");
+        command.args(["--raw", "--format", "f32", "--rate", &rate, "--channels", &channels]);
+        if node.as_ref().is_none_or(|node| node.sink) {
+            command.args(["-P", "{ stream.capture.sink = true }"]);
+        }
+        if let Some(node) = &node {
+            command.args(["--target", &node.name]);
+        }
+        // Its own process group, so Ctrl+C reaches only us and we stop it in order
+        let mut child = command
+            .arg("-")
+            .process_group(0)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("cannot run pw-record ({}), is PipeWire installed?", e))?;
+        let stdout = child.stdout.take().ok_or("pw-record has no output")?;
+
+        let stopping = Arc::new(AtomicBool::new(false));
+        let stopped = stopping.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            let mut bytes = [0u8; 4096];
+            let mut samples = Vec::with_capacity(bytes.len() / 4);
+            let mut pending = Vec::new();
+            loop {
+                let len = match reader.read(&mut bytes) {
+                    Ok(0) | Err(_) => break,
+                    Ok(len) => len,
+                };
+                // Reads don't line up with samples, keep the leftover bytes for the next round
+                pending.extend_from_slice(&bytes[..len]);
+                let whole = pending.len() / (4 * CHANNELS) * (4 * CHANNELS);
+                samples.clear();
+                samples.extend(pending[..whole].chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])));
+                pending.drain(..whole);
+                feed(&samples);
+            }
+            if !stopped.load(Ordering::Relaxed) {
+                error!("Audio capture stopped: pw-record exited");
+            }
+        });
+
+        let description = match node {
+            Some(node) => format!("{} (PipeWire)", node.description),
+            None => "the default output (PipeWire)".to_string(),
+        };
+        Ok(Self { child, description, stopping })
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::Relaxed);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
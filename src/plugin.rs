@@ -0,0 +1,244 @@
+// Effects compiled to WebAssembly, for --plugin (the `plugins` feature). Plugins run
+// sandboxed: the only thing they can import is `dsr.log`, so they get no files, network
+// or clock of their own, every frame runs on a budget of fuel, and their memory is capped.
+//
+// The host interface, version 1. A plugin exports
+//
+//   memory
+//   dsr_version() -> i32                  the interface version it was built for, 1
+//   dsr_frame(time: f64, dt: f64) -> i32  the lightbar color as 0xRRGGBB, given the seconds
+//                                         since the effect started and since the last frame
+//
+// and may export
+//
+//   dsr_init()                 called once, before the first frame
+//   dsr_set_speed(speed: f32)  hue degrees per second, on start and whenever it changes
+//   dsr_input() -> i32         where in memory the host writes the pad's input before every
+//                              frame: 48 bytes, little-endian
+//       0  u32  buttons held, bit n for the nth of square, cross, circle, triangle, l1, r1,
+//               l2, r2, create, options, l3, r3, ps, touchpad, mute, dpad up, dpad right,
+//               dpad down, dpad left, fn left, fn right, paddle left, paddle right
+//       4  f32  left stick x and y, right stick x and y: -1.0..1.0, right and down positive
+//      20  f32  l2 and r2: 0.0..1.0
+//      28  u32  fingers on the touchpad, 0 to 2
+//      32  f32  x and y of the first finger, then of the second: 0.0..1.0
+//
+// and may import
+//
+//   dsr.log(ptr: i32, len: i32)  prints the UTF-8 text at ptr
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{error, info};
+use wasmi::{Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use dualsense_rainbow::colors;
+use dualsense_rainbow::input::{Button, ControllerState, TOUCHPAD_SIZE};
+use crate::canvas::Canvas;
+use crate::effects::Effect;
+
+const INTERFACE_VERSION: i32 = 1;
+// Roughly a wasm instruction each, a few milliseconds of work: plenty for a color, and
+// a plugin stuck in a loop is stopped instead of freezing the lightbar
+const FUEL_PER_FRAME: u64 = 1_000_000;
+const MAX_MEMORY: usize = 16 << 20;
+const INPUT_SIZE: usize = 48;
+
+// A compiled plugin, checked against the host interface
+#[derive(Clone)]
+pub struct Plugin {
+    pub name: String,
+    engine: Engine,
+    module: Module,
+}
+
+impl Plugin {
+    // A path, or a name looked up as NAME.wasm in the config directory's plugins/ folder
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = PathBuf::from(name.trim());
+        let path = match path.extension().is_some() || path.components().count() > 1 {
+            true => path,
+            false => match dirs::config_dir() {
+                Some(dir) => dir.join("dualsense-rainbow").join("plugins").join(format!("{}.wasm", name.trim())),
+                None => return Err(format!("no config directory to find plugin '{}' in", name)),
+            },
+        };
+        let wasm = fs::read(&path).map_err(|e| format!("cannot read plugin {}: {}", path.display(), e))?;
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm).map_err(|e| format!("plugin {}: {}", path.display(), e))?;
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let plugin = Self { name, engine, module };
+        // Once up front, so a plugin that doesn't fit the interface stops the start instead of a frame
+        plugin.instantiate().map_err(|e| format!("plugin {}: {}", path.display(), e))?;
+        Ok(plugin)
+    }
+
+    fn instantiate(&self) -> Result<Instance, String> {
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build();
+        let mut store = Store::new(&self.engine, Host { name: self.name.clone(), limits });
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(FUEL_PER_FRAME).map_err(|e| e.to_string())?;
+
+        let mut linker = Linker::new(&self.engine);
+        linker
+            .func_wrap("dsr", "log", |caller: Caller<'_, Host>, ptr: i32, len: i32| {
+                let Some(memory) = caller.get_export("memory").and_then(Extern::into_memory) else {
+                    return;
+                };
+                let mut text = vec![0; len.clamp(0, 1024) as usize];
+                if memory.read(&caller, ptr as u32 as usize, &mut text).is_ok() {
+                    info!("{}{}:{} {}", colors::GRAY, caller.data().name, colors::RESET, String::from_utf8_lossy(&text));
+                }
+            })
+            .map_err(|e| e.to_string())?;
+        let instance = linker.instantiate_and_start(&mut store, &self.module).map_err(|e| e.to_string())?;
+
+        let version = instance
+            .get_typed_func::<(), i32>(&store, "dsr_version")
+            .map_err(|_| "no dsr_version() -> i32 export".to_string())?
+            .call(&mut store, ())
+            .map_err(|e| e.to_string())?;
+        if version != INTERFACE_VERSION {
+            return Err(format!("built for host interface {}, this is {}", version, INTERFACE_VERSION));
+        }
+        let frame = instance
+            .get_typed_func::<(f64, f64), i32>(&store, "dsr_frame")
+            .map_err(|_| "no dsr_frame(f64, f64) -> i32 export".to_string())?;
+        let set_speed = instance.get_typed_func::<f32, ()>(&store, "dsr_set_speed").ok();
+        let input = match instance.get_typed_func::<(), i32>(&store, "dsr_input") {
+            Ok(input) => {
+                let offset = input.call(&mut store, ()).map_err(|e| e.to_string())?;
+                let memory = instance.get_memory(&store, "memory").ok_or("dsr_input() without a memory export")?;
+                Some((memory, offset as u32 as usize))
+            },
+            Err(_) => None,
+        };
+        let init = instance.get_typed_func::<(), ()>(&store, "dsr_init").ok();
+        Ok(Instance { store, init, frame, set_speed, input })
+    }
+}
+
+struct Host {
+    name: String,
+    limits: StoreLimits,
+}
+
+struct Instance {
+    store: Store<Host>,
+    init: Option<TypedFunc<(), ()>>,
+    frame: TypedFunc<(f64, f64), i32>,
+    set_speed: Option<TypedFunc<f32, ()>>,
+    // The memory and offset dsr_input() gave
+    input: Option<(Memory, usize)>,
+}
+
+// Runs a plugin as an effect, a fresh instance whenever it is switched to
+pub struct Plugged {
+    name: String,
+    instance: Result<Instance, String>,
+    input: ControllerState,
+    elapsed: Duration,
+    color: (u8, u8, u8),
+    last_error: Option<String>,
+}
+
+impl Plugged {
+    pub fn new(plugin: &Plugin, speed: f32) -> Self {
+        let instance = plugin.instantiate().and_then(|mut instance| {
+            if let Some(init) = instance.init {
+                init.call(&mut instance.store, ()).map_err(|e| e.to_string())?;
+            }
+            Ok(instance)
+        });
+        let mut plugged = Self {
+            name: plugin.name.clone(),
+            instance,
+            input: ControllerState::default(),
+            elapsed: Duration::ZERO,
+            color: (0, 0, 0),
+            last_error: None,
+        };
+        plugged.set_speed(speed);
+        plugged
+    }
+
+    fn report(&mut self, error: String) {
+        if self.last_error.as_ref() != Some(&error) {
+            error!("{}{}✗ Plugin {}:{} {}", colors::BOLD, colors::RED, self.name, colors::RESET, error);
+            self.last_error = Some(error);
+        }
+    }
+
+    fn run_frame(&mut self, dt: Duration) -> Result<(u8, u8, u8), String> {
+        let instance = self.instance.as_mut().map_err(|e| e.clone())?;
+        if let Some((memory, offset)) = instance.input {
+            memory.write(&mut instance.store, offset, &input_bytes(&self.input)).map_err(|e| e.to_string())?;
+        }
+        instance.store.set_fuel(FUEL_PER_FRAME).map_err(|e| e.to_string())?;
+        let rgb = instance.frame.call(&mut instance.store, (self.elapsed.as_secs_f64(), dt.as_secs_f64())).map_err(|e| e.to_string())?;
+        Ok(((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+    }
+}
+
+impl Effect for Plugged {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        match self.run_frame(dt) {
+            Ok(color) => self.color = color,
+            // A plugin that traps or runs out of fuel is stopped, not run into the wall every frame
+            Err(e) => {
+                self.report(e.clone());
+                self.instance = Err(e);
+            },
+        }
+        self.elapsed += dt;
+        canvas.lightbar = self.color;
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        let Ok(instance) = &mut self.instance else {
+            return;
+        };
+        if let Some(set_speed) = instance.set_speed {
+            let _ = instance.store.set_fuel(FUEL_PER_FRAME);
+            if let Err(e) = set_speed.call(&mut instance.store, speed) {
+                self.report(e.to_string());
+            }
+        }
+    }
+
+    fn input(&mut self, state: &ControllerState) {
+        self.input = *state;
+    }
+}
+
+// The input block of the interface
+fn input_bytes(state: &ControllerState) -> [u8; INPUT_SIZE] {
+    let mut bytes = [0; INPUT_SIZE];
+    // Button::ALL is in the interface's bit order
+    let buttons = Button::ALL.iter().enumerate().filter(|(_, button)| state.is_pressed(**button)).fold(0u32, |mask, (bit, _)| mask | 1 << bit);
+    bytes[0..4].copy_from_slice(&buttons.to_le_bytes());
+
+    let axis = |v: u8| ((v as f32 - 128.0) / 127.0).clamp(-1.0, 1.0);
+    let touch: Vec<_> = state.touch.iter().flatten().collect();
+    let floats = [
+        axis(state.left_stick.0),
+        axis(state.left_stick.1),
+        axis(state.right_stick.0),
+        axis(state.right_stick.1),
+        state.l2 as f32 / 255.0,
+        state.r2 as f32 / 255.0,
+    ];
+    for (i, value) in floats.iter().enumerate() {
+        bytes[4 + i * 4..8 + i * 4].copy_from_slice(&value.to_le_bytes());
+    }
+    bytes[28..32].copy_from_slice(&(touch.len() as u32).to_le_bytes());
+    for (i, point) in touch.iter().enumerate() {
+        let x = point.x as f32 / TOUCHPAD_SIZE.0 as f32;
+        let y = point.y as f32 / TOUCHPAD_SIZE.1 as f32;
+        bytes[32 + i * 8..36 + i * 8].copy_from_slice(&x.to_le_bytes());
+        bytes[36 + i * 8..40 + i * 8].copy_from_slice(&y.to_le_bytes());
+    }
+    bytes
+}
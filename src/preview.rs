@@ -0,0 +1,123 @@
+// --preview: a small window with a DualSense outline showing what the simulated
+// controller's LEDs are set to, so effects can be worked on with nothing plugged in
+use minifb::{Key, Window, WindowOptions};
+use std::f32::consts::TAU;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::error;
+
+use dualsense_rainbow::report::{LedState, MuteLed};
+
+const WIDTH: usize = 420;
+const HEIGHT: usize = 260;
+
+const BACKGROUND: u32 = 0x1E1E24;
+const BODY: u32 = 0xE6E6EC;
+const PANEL: u32 = 0x2A2A30;
+const LED_OFF: u32 = 0x4A4A50;
+const MIC_ORANGE: (u8, u8, u8) = (255, 136, 0);
+
+// Mic LED pulse period in hardware "pulse" mode, roughly what the controller does
+const MIC_PULSE_SECS: f32 = 2.0;
+
+struct Canvas(Vec<u32>);
+
+impl Canvas {
+    fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: u32) {
+        for row in y..(y + h).min(HEIGHT) {
+            let start = row * WIDTH;
+            self.0[start + x.min(WIDTH)..start + (x + w).min(WIDTH)].fill(color);
+        }
+    }
+
+    fn fill_circle(&mut self, cx: usize, cy: usize, r: usize, color: u32) {
+        let r2 = (r * r) as isize;
+        for dy in -(r as isize)..=r as isize {
+            let half = ((r2 - dy * dy) as f32).sqrt() as usize;
+            let y = cy as isize + dy;
+            if (0..HEIGHT as isize).contains(&y) {
+                self.fill_rect(cx.saturating_sub(half), y as usize, half * 2 + 1, 1, color);
+            }
+        }
+    }
+}
+
+fn rgb((r, g, b): (u8, u8, u8)) -> u32 {
+    ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+}
+
+fn draw(canvas: &mut Canvas, leds: &LedState, t: f32) {
+    canvas.0.fill(BACKGROUND);
+
+    // Body and grips
+    canvas.fill_rect(70, 40, 280, 130, BODY);
+    canvas.fill_circle(110, 170, 62, BODY);
+    canvas.fill_circle(310, 170, 62, BODY);
+    canvas.fill_circle(70, 90, 50, BODY);
+    canvas.fill_circle(350, 90, 50, BODY);
+
+    // Black center with the touchpad and sticks
+    canvas.fill_rect(135, 40, 150, 130, PANEL);
+    canvas.fill_rect(150, 44, 120, 66, 0x38383F);
+    canvas.fill_circle(165, 146, 15, 0x18181C);
+    canvas.fill_circle(255, 146, 15, 0x18181C);
+
+    // The lightbar runs down both sides of the touchpad; off reads as a dim strip
+    let lightbar = if leds.lightbar == (0, 0, 0) { LED_OFF } else { rgb(leds.lightbar) };
+    canvas.fill_rect(139, 48, 7, 70, lightbar);
+    canvas.fill_rect(274, 48, 7, 70, lightbar);
+
+    // Player LEDs below the touchpad, bit 0 leftmost
+    for i in 0..5 {
+        let lit = leds.player_leds & (1 << i) != 0;
+        canvas.fill_rect(181 + i * 12, 118, 8, 3, if lit { 0xFFFFFF } else { LED_OFF });
+    }
+
+    // Mic mute LED between the sticks
+    let level = match leds.mute_led {
+        MuteLed::Off => 0.0,
+        MuteLed::On => 1.0,
+        MuteLed::Pulse => 0.5 + 0.5 * (t / MIC_PULSE_SECS * TAU).cos(),
+    };
+    let (r, g, b) = MIC_ORANGE;
+    let mic = if level > 0.0 {
+        rgb(((r as f32 * level) as u8, (g as f32 * level) as u8, (b as f32 * level) as u8))
+    } else {
+        LED_OFF
+    };
+    canvas.fill_rect(205, 152, 10, 3, mic);
+}
+
+// Keeps the window up until it is closed (or Esc is pressed) or `done` returns true
+pub fn show(leds: &Arc<Mutex<LedState>>, done: impl Fn() -> bool) {
+    let mut window = match Window::new("DualSense preview", WIDTH, HEIGHT, WindowOptions::default()) {
+        Ok(window) => window,
+        Err(e) => {
+            error!("Cannot open the preview window: {}", e);
+            while !done() {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+            return;
+        },
+    };
+    window.set_target_fps(60);
+
+    let start = Instant::now();
+    let mut canvas = Canvas(vec![BACKGROUND; WIDTH * HEIGHT]);
+    let mut shown = None;
+
+    while window.is_open() && !window.is_key_down(Key::Escape) && !done() {
+        let state = leds.lock().map(|leds| *leds).unwrap_or_default();
+        let status = crate::title::current();
+        if shown.as_ref().is_none_or(|(lightbar, title)| *lightbar != state.lightbar || *title != status) {
+            let (r, g, b) = state.lightbar;
+            window.set_title(&format!("DualSense preview - #{:02x}{:02x}{:02x} - {}", r, g, b, status));
+            shown = Some((state.lightbar, status));
+        }
+
+        draw(&mut canvas, &state, start.elapsed().as_secs_f32());
+        if window.update_with_buffer(&canvas.0, WIDTH, HEIGHT).is_err() {
+            break;
+        }
+    }
+}
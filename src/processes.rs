@@ -0,0 +1,144 @@
+// Snapshot of running processes, for spotting other programs that talk to the controller,
+// games that should have the controller to themselves and programs with a profile
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+use crate::foreground;
+
+// Listing every process is not free, and nobody minds a game being noticed a few seconds late
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct ProcessInfo {
+    pub pid: u32,
+    // Lowercase executable name without ".exe"
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+impl ProcessInfo {
+    // True if the process is `name` itself or a script called `name` run by an interpreter
+    pub fn is(&self, name: &str) -> bool {
+        self.name == name
+            || self.args.iter().skip(1).take(2).any(|arg| {
+                let file = arg.rsplit(['/', '\\']).next().unwrap_or(arg).to_ascii_lowercase();
+                file.trim_end_matches(".py").trim_end_matches(".exe") == name
+            })
+    }
+
+    // Like `is`, with `*` and `?` in the pattern matched against the executable name
+    pub fn matches(&self, pattern: &str) -> bool {
+        match pattern.contains(['*', '?']) {
+            true => glob(pattern, &self.name),
+            false => self.is(pattern),
+        }
+    }
+}
+
+// `*` stands for any run of characters and `?` for any one; both sides already lowercase
+pub fn glob(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // The last `*` seen and where in the name it stopped taking characters
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            },
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            },
+            // Let the last `*` take one more character and try again from there
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                },
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Program name or pattern -> profile, as in [apps] and [processes]: names lowercase and
+// without ".exe", exact names ahead of patterns so they win when both match
+pub fn profile_rules(table: &BTreeMap<String, String>) -> Vec<(String, String)> {
+    let mut rules: Vec<_> = table
+        .iter()
+        .map(|(name, profile)| (foreground::normalize(name), profile.clone()))
+        .collect();
+    rules.sort_by_key(|(name, _)| name.contains(['*', '?']));
+    rules
+}
+
+pub fn running() -> Vec<ProcessInfo> {
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().without_tasks().with_cmd(UpdateKind::Always),
+    );
+
+    let own_pid = std::process::id();
+    system
+        .processes()
+        .values()
+        .filter(|process| process.pid().as_u32() != own_pid)
+        .map(|process| {
+            let name = process.name().to_string_lossy().to_ascii_lowercase();
+            ProcessInfo {
+                pid: process.pid().as_u32(),
+                name: name.trim_end_matches(".exe").to_string(),
+                args: process.cmd().iter().map(|arg| arg.to_string_lossy().into_owned()).collect(),
+            }
+        })
+        .collect()
+}
+
+// Keeps track of whether any of a list of programs is running
+pub struct ProcessWatch {
+    names: Vec<String>,
+    last_check: Option<Instant>,
+    found: Option<String>,
+}
+
+impl ProcessWatch {
+    // Names are matched like executables, case-insensitive and without ".exe", and may be
+    // patterns with `*` and `?`; the first one running is the one found
+    pub fn new(names: &[String]) -> Self {
+        let names = names
+            .iter()
+            .map(|name| name.to_ascii_lowercase().trim_end_matches(".exe").to_string())
+            .collect();
+        Self { names, last_check: None, found: None }
+    }
+
+    // Re-scans when due, returns true if the matching program changed
+    pub fn refresh(&mut self) -> bool {
+        if self.last_check.is_some_and(|at| at.elapsed() < WATCH_INTERVAL) {
+            return false;
+        }
+        self.last_check = Some(Instant::now());
+
+        let running = running();
+        let found = self
+            .names
+            .iter()
+            .find(|name| running.iter().any(|process| process.matches(name)))
+            .cloned();
+        if found == self.found {
+            return false;
+        }
+        self.found = found;
+        true
+    }
+
+    pub fn found(&self) -> Option<&str> {
+        self.found.as_deref()
+    }
+}
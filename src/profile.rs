@@ -0,0 +1,87 @@
+// Named bundles of settings from the config's [profiles] table, either pinned
+// with --profile or switched in by [[schedule]] rules
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::cli;
+use crate::effects::EffectKind;
+use crate::mic_led::MicPattern;
+use crate::palette::{Palette, PaletteSpec};
+use dualsense_rainbow::triggers::{self, TriggerEffect};
+
+// Every field is optional, unset ones keep the value from the command line / config
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    // Effect name, e.g. "breathe"
+    pub effect: Option<String>,
+    // Hue degrees per frame, the default rainbow runs at 1.5
+    pub speed: Option<f32>,
+    // Lightbar brightness from 0.0 to 1.0
+    pub brightness: Option<f32>,
+    // Color saturation from 0.0 (white) to 1.0
+    pub saturation: Option<f32>,
+    // Colors to cycle through instead of the rainbow, e.g. ["#ff8800", "#ffffff 30%"],
+    // or a built-in palette or palette file, e.g. "sunset"
+    pub palette: Option<PaletteSpec>,
+    pub player_leds: Option<String>,
+    pub mic_led: Option<String>,
+    pub left_trigger: Option<String>,
+    pub right_trigger: Option<String>,
+}
+
+// What the main loop actually runs with
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub effect: EffectKind,
+    pub speed: f32,
+    pub brightness: f32,
+    pub saturation: f32,
+    pub palette: Option<Palette>,
+    pub player_leds: u8,
+    pub mic_led: MicPattern,
+    pub left_trigger: TriggerEffect,
+    pub right_trigger: TriggerEffect,
+}
+
+impl Settings {
+    // Layers a profile over these settings
+    pub fn with_profile(&self, profile: &Profile, named_triggers: &BTreeMap<String, String>) -> Result<Self, String> {
+        let mut settings = self.clone();
+
+        if let Some(effect) = &profile.effect {
+            settings.effect = effect.parse()?;
+        }
+        if let Some(speed) = profile.speed {
+            settings.speed = speed;
+        }
+        if let Some(brightness) = profile.brightness {
+            if !(0.0..=1.0).contains(&brightness) {
+                return Err(format!("brightness {} is outside 0.0-1.0", brightness));
+            }
+            settings.brightness = brightness;
+        }
+        if let Some(saturation) = profile.saturation {
+            if !(0.0..=1.0).contains(&saturation) {
+                return Err(format!("saturation {} is outside 0.0-1.0", saturation));
+            }
+            settings.saturation = saturation;
+        }
+        if let Some(spec) = &profile.palette {
+            settings.palette = Some(spec.resolve()?);
+        }
+        if let Some(pattern) = &profile.player_leds {
+            settings.player_leds = cli::parse_player_mask(pattern)?;
+        }
+        if let Some(pattern) = &profile.mic_led {
+            settings.mic_led = pattern.parse()?;
+        }
+        if let Some(spec) = &profile.left_trigger {
+            settings.left_trigger = triggers::resolve(spec, named_triggers)?;
+        }
+        if let Some(spec) = &profile.right_trigger {
+            settings.right_trigger = triggers::resolve(spec, named_triggers)?;
+        }
+        Ok(settings)
+    }
+}
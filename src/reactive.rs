@@ -0,0 +1,89 @@
+// What `--effect reactive` listens to: a button that flashes the lightbar, a stick whose
+// angle picks the hue and a trigger whose pull sets the brightness
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::palette;
+use dualsense_rainbow::input::{Button, Stick};
+
+// The [reactive] table of the config. Each input can be "none" to leave it out
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReactiveConfig {
+    pub flash_button: String,
+    pub flash_color: String,
+    pub flash_ms: u64,
+    // "left" or "right"
+    pub hue_stick: String,
+    // "l2" or "r2"
+    pub brightness_trigger: String,
+    // Brightness with the trigger released, 0.0-1.0
+    pub idle_brightness: f32,
+}
+
+impl Default for ReactiveConfig {
+    fn default() -> Self {
+        Self {
+            flash_button: "cross".into(),
+            flash_color: "white".into(),
+            flash_ms: 300,
+            hue_stick: "left".into(),
+            brightness_trigger: "r2".into(),
+            idle_brightness: 0.3,
+        }
+    }
+}
+
+// The checked form of ReactiveConfig
+#[derive(Clone, Debug)]
+pub struct ReactiveMapping {
+    pub flash_button: Option<Button>,
+    pub flash_color: (u8, u8, u8),
+    pub flash: Duration,
+    pub hue_stick: Option<Stick>,
+    // Button::L2 or Button::R2
+    pub brightness_trigger: Option<Button>,
+    pub idle_brightness: f32,
+}
+
+impl ReactiveMapping {
+    pub fn new(config: &ReactiveConfig) -> Result<Self, String> {
+        let none = |s: &str| s.trim().eq_ignore_ascii_case("none");
+
+        let flash_button = match none(&config.flash_button) {
+            true => None,
+            false => Some(config.flash_button.parse::<Button>()?),
+        };
+        let flash_color = palette::parse_color(&config.flash_color)?;
+        let hue_stick = match config.hue_stick.trim().to_ascii_lowercase().as_str() {
+            "none" => None,
+            "left" => Some(Stick::Left),
+            "right" => Some(Stick::Right),
+            other => return Err(format!("unknown stick '{}' (expected left, right or none)", other)),
+        };
+        let brightness_trigger = match none(&config.brightness_trigger) {
+            true => None,
+            false => match config.brightness_trigger.parse::<Button>() {
+                Ok(button @ (Button::L2 | Button::R2)) => Some(button),
+                _ => {
+                    return Err(format!(
+                        "unknown trigger '{}' (expected l2, r2 or none)",
+                        config.brightness_trigger
+                    ));
+                },
+            },
+        };
+        if !(0.0..=1.0).contains(&config.idle_brightness) {
+            return Err(format!("idle_brightness must be between 0.0 and 1.0, got {}", config.idle_brightness));
+        }
+
+        Ok(Self {
+            flash_button,
+            flash_color,
+            flash: Duration::from_millis(config.flash_ms),
+            hue_stick,
+            brightness_trigger,
+            idle_brightness: config.idle_brightness,
+        })
+    }
+}
@@ -0,0 +1,33 @@
+// When to try reopening a controller that stopped answering. The first attempt comes
+// after the --watchdog window; while the pad stays gone (asleep, unplugged, out of
+// range) the wait doubles up to MAX_WAIT, so a missing controller isn't re-enumerated
+// several times a second for hours
+use std::time::Duration;
+
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+pub struct Reconnector {
+    window: Duration,
+    wait: Duration,
+}
+
+impl Reconnector {
+    pub fn new(window: Duration) -> Self {
+        Self { window, wait: window }
+    }
+
+    // `stalled_for` counts from the last failed write or reopen attempt
+    pub fn due(&self, stalled_for: Duration) -> bool {
+        stalled_for >= self.wait
+    }
+
+    pub fn succeeded(&mut self) {
+        self.wait = self.window;
+    }
+
+    // Returns how long until the next attempt
+    pub fn failed(&mut self) -> Duration {
+        self.wait = (self.wait * 2).min(MAX_WAIT.max(self.window));
+        self.wait
+    }
+}
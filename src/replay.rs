@@ -0,0 +1,69 @@
+// --replay: plays a capture from --record back on the controller with its original
+// timing. DualSense reports recorded over the other connection are converted, so a USB
+// session can be compared with the same one over Bluetooth
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dualsense_rainbow::capture::Capture;
+use dualsense_rainbow::report;
+use dualsense_rainbow::{DualSenseController, Error, colors};
+
+// Longest sleep between checks for CTRL+C during a gap in the capture
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub fn run(controller: &mut DualSenseController, path: &Path, running: &AtomicBool) -> Result<(), Error> {
+    let capture = Capture::load(path)?;
+    let model = controller.model();
+    if capture.model.is_dualsense() != model.is_dualsense() {
+        return Err(Error::Other(format!("{} was recorded from a {}, this is a {}", path.display(), capture.model.name(), model.name())));
+    }
+    println!("{}{} Replaying {} reports ({:.1}s) recorded from a {}...{}",
+             colors::BOLD, colors::CYAN, capture.reports.len(), capture.duration().as_secs_f32(), capture.model.name(), colors::RESET);
+    println!("{}Press CTRL+C to stop{}", colors::GRAY, colors::RESET);
+
+    let usb_mode = controller.is_usb();
+    let start = Instant::now();
+    let mut seq: u8 = 0;
+    let mut converted = 0;
+    let mut replayed = 0;
+    for recorded in &capture.reports {
+        let due = start + recorded.at;
+        while running.load(Ordering::SeqCst) && Instant::now() < due {
+            thread::sleep(due.saturating_duration_since(Instant::now()).min(POLL_INTERVAL));
+        }
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let data = match recorded.usb_mode == usb_mode {
+            true => recorded.data.clone(),
+            false => match report::convert_output_report(&recorded.data, usb_mode, seq) {
+                Some(data) => {
+                    converted += 1;
+                    data
+                },
+                // DualShock 4 reports differ by more than the header
+                None => {
+                    return Err(Error::UnsupportedTransport(format!(
+                        "{} was recorded over {}, and {} reports can't be converted",
+                        path.display(),
+                        if recorded.usb_mode { "USB" } else { "Bluetooth" },
+                        capture.model.name()
+                    )));
+                },
+            },
+        };
+        seq = seq.wrapping_add(1);
+        controller.write_output_report(&data)?;
+        replayed += 1;
+    }
+
+    println!("{}{}✓ Replayed {} of {} reports{}", colors::BOLD, colors::GREEN, replayed, capture.reports.len(), colors::RESET);
+    if converted > 0 {
+        println!("{}{} converted from {} to {}{}", colors::GRAY, converted,
+                 if usb_mode { "Bluetooth" } else { "USB" }, if usb_mode { "USB" } else { "Bluetooth" }, colors::RESET);
+    }
+    Ok(())
+}
@@ -0,0 +1,458 @@
+use crate::triggers::TriggerEffect;
+
+// Output report layout for the DualSense
+//
+// Both transports share a 47-byte "common" block; USB prefixes it with the
+// report ID, Bluetooth with the report ID, a sequence/tag byte and a tag byte,
+// and appends a CRC32 over the whole report.
+
+pub const USB_REPORT_ID: u8 = 0x02;
+pub const USB_REPORT_LEN: usize = 48;
+pub const BT_REPORT_ID: u8 = 0x31;
+pub const BT_REPORT_LEN: usize = 78;
+const COMMON_LEN: usize = USB_REPORT_LEN - 1;
+
+// Seed bytes prepended to the CRC32 for Bluetooth reports (the HID transaction header)
+pub const CRC_SEED_OUTPUT: u8 = 0xA2;
+pub const CRC_SEED_FEATURE_GET: u8 = 0xA3;
+pub const CRC_SEED_FEATURE_SET: u8 = 0x53;
+
+// Offsets inside the common block. Only OutputReportBuilder and parse_output_report use
+// them, everything else goes through the builder's fields
+const VALID_FLAG0: usize = 0;
+const VALID_FLAG1: usize = 1;
+const MOTOR_RIGHT: usize = 2;
+const MOTOR_LEFT: usize = 3;
+const MUTE_LED: usize = 8;
+const RIGHT_TRIGGER: usize = 10;
+const LEFT_TRIGGER: usize = 21;
+const VALID_FLAG2: usize = 38;
+const LED_BRIGHTNESS: usize = 42;
+const PLAYER_LEDS: usize = 43;
+const LIGHTBAR_RGB: usize = 44;
+
+// Valid flag 0: rumble through the compatible vibration mode, the trigger effects, and
+// the audio fields the tool leaves at zero
+const FLAG0_COMPATIBLE_VIBRATION: u8 = 0x01;
+const FLAG0_HAPTICS_SELECT: u8 = 0x02;
+const FLAG0_RIGHT_TRIGGER: u8 = 0x04;
+const FLAG0_LEFT_TRIGGER: u8 = 0x08;
+const FLAG0_ALL: u8 = 0xFF;
+
+// Valid flag 1: the LED fields, plus power save and audio fields left at zero. Release
+// LEDs hands the lightbar back to the firmware, so it is never set
+const FLAG1_MIC_MUTE_LED: u8 = 0x01;
+const FLAG1_LIGHTBAR: u8 = 0x04;
+const FLAG1_RELEASE_LEDS: u8 = 0x08;
+const FLAG1_PLAYER_LEDS: u8 = 0x10;
+
+// Valid flag 2: apply the player LED brightness field
+const FLAG2_LED_BRIGHTNESS: u8 = 0x01;
+// Player LED byte: bits 0-4 are the LEDs, bit 5 skips the fade-in
+const PLAYER_LEDS_INSTANT: u8 = 0x20;
+
+// Bluetooth header: the high nibble of byte 1 is a sequence number, byte 2 a tag that must be set
+const BT_TAG: u8 = 0x10;
+
+// Mic mute LED below the touchpad
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MuteLed {
+    #[default]
+    Off,
+    On,
+    Pulse, // Hardware-driven slow pulse
+}
+
+impl MuteLed {
+    fn value(self) -> u8 {
+        match self {
+            MuteLed::Off => 0,
+            MuteLed::On => 1,
+            MuteLed::Pulse => 2,
+        }
+    }
+
+    fn from_value(value: u8) -> Self {
+        match value {
+            1 => MuteLed::On,
+            2 => MuteLed::Pulse,
+            _ => MuteLed::Off,
+        }
+    }
+}
+
+// Brightness of the five white player LEDs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlayerLedBrightness {
+    #[default]
+    High,
+    Medium,
+    Low,
+}
+
+impl PlayerLedBrightness {
+    fn value(self) -> u8 {
+        match self {
+            PlayerLedBrightness::High => 0,
+            PlayerLedBrightness::Medium => 1,
+            PlayerLedBrightness::Low => 2,
+        }
+    }
+}
+
+impl std::str::FromStr for PlayerLedBrightness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "high" => Ok(PlayerLedBrightness::High),
+            "medium" => Ok(PlayerLedBrightness::Medium),
+            "low" => Ok(PlayerLedBrightness::Low),
+            _ => Err(format!("unknown brightness '{}' (expected high, medium or low)", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlayerLeds {
+    pub mask: u8, // Bit 0 is the leftmost LED
+    pub brightness: PlayerLedBrightness,
+    pub fade: bool, // Fade LEDs in when they change instead of switching instantly
+}
+
+impl Default for PlayerLeds {
+    fn default() -> Self {
+        Self {
+            mask: 0,
+            brightness: PlayerLedBrightness::High,
+            fade: true,
+        }
+    }
+}
+
+// Everything the controller should display, serialized into one output report
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OutputState {
+    pub lightbar: (u8, u8, u8),
+    pub mute_led: MuteLed,
+    pub player_leds: PlayerLeds,
+    pub left_trigger: TriggerEffect,
+    pub right_trigger: TriggerEffect,
+    // Rumble motors, 0-255: the heavy low-frequency one in the left grip, then the
+    // lighter high-frequency one in the right
+    pub rumble: (u8, u8),
+    // Only mark the LED fields valid, so another program driving rumble and triggers is not overridden
+    pub leds_only: bool,
+}
+
+pub fn build_output_report(usb_mode: bool, seq: u8, state: &OutputState) -> Vec<u8> {
+    let builder = OutputReportBuilder::new()
+        .lightbar(state.lightbar)
+        .player_leds(state.player_leds)
+        .mute_led(state.mute_led);
+    // Only the LED fields are marked valid, so another program driving rumble and triggers
+    // is not overridden
+    if state.leds_only {
+        return builder.build(usb_mode, seq);
+    }
+    // Zero stops the motors, which is also what every report sent before rumble was a thing did
+    let (low, high) = state.rumble;
+    builder
+        .rumble(low, high)
+        .triggers(state.left_trigger, state.right_trigger)
+        .take_over()
+        .build(usb_mode, seq)
+}
+
+// The DualSense output report, field by field. Each setter marks its field valid so the
+// controller applies it, and `build` lays the fields out for USB or Bluetooth
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OutputReportBuilder {
+    // Valid flags 0, 1 and 2
+    flags: [u8; 3],
+    // Heavy left motor, light right motor
+    rumble: (u8, u8),
+    mute_led: MuteLed,
+    left_trigger: TriggerEffect,
+    right_trigger: TriggerEffect,
+    player_leds: PlayerLeds,
+    lightbar: (u8, u8, u8),
+}
+
+impl OutputReportBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lightbar(mut self, (r, g, b): (u8, u8, u8)) -> Self {
+        self.lightbar = (r, g, b);
+        self.flags[1] |= FLAG1_LIGHTBAR;
+        self
+    }
+
+    pub fn player_leds(mut self, player_leds: PlayerLeds) -> Self {
+        self.player_leds = player_leds;
+        self.flags[1] |= FLAG1_PLAYER_LEDS;
+        self.flags[2] |= FLAG2_LED_BRIGHTNESS;
+        self
+    }
+
+    pub fn mute_led(mut self, mute_led: MuteLed) -> Self {
+        self.mute_led = mute_led;
+        self.flags[1] |= FLAG1_MIC_MUTE_LED;
+        self
+    }
+
+    pub fn rumble(mut self, low: u8, high: u8) -> Self {
+        self.rumble = (low, high);
+        self.flags[0] |= FLAG0_COMPATIBLE_VIBRATION | FLAG0_HAPTICS_SELECT;
+        self
+    }
+
+    pub fn triggers(mut self, left: TriggerEffect, right: TriggerEffect) -> Self {
+        self.left_trigger = left;
+        self.right_trigger = right;
+        self.flags[0] |= FLAG0_LEFT_TRIGGER | FLAG0_RIGHT_TRIGGER;
+        self
+    }
+
+    // Marks every field valid, set or not: the controller then takes all of them from the
+    // report, the audio fields at zero included. That is how the tool drives the pad
+    // unless it shares it
+    pub fn take_over(mut self) -> Self {
+        self.flags[0] = FLAG0_ALL;
+        self.flags[1] |= !FLAG1_RELEASE_LEDS;
+        self
+    }
+
+    // The report for the connection, with the Bluetooth header and CRC when not on USB
+    pub fn build(&self, usb_mode: bool, seq: u8) -> Vec<u8> {
+        frame(&self.block(), usb_mode, seq)
+    }
+
+    // The common block both connections share
+    fn block(&self) -> [u8; COMMON_LEN] {
+        let mut block = [0; COMMON_LEN];
+        block[VALID_FLAG0] = self.flags[0];
+        block[VALID_FLAG1] = self.flags[1];
+        block[VALID_FLAG2] = self.flags[2];
+
+        let (low, high) = self.rumble;
+        block[MOTOR_LEFT] = low;
+        block[MOTOR_RIGHT] = high;
+        block[MUTE_LED] = self.mute_led.value();
+        // Triggers the controller won't apply stay zero rather than spelling out Off
+        if self.flags[0] & FLAG0_RIGHT_TRIGGER != 0 {
+            block[RIGHT_TRIGGER..RIGHT_TRIGGER + 11].copy_from_slice(&self.right_trigger.to_bytes());
+        }
+        if self.flags[0] & FLAG0_LEFT_TRIGGER != 0 {
+            block[LEFT_TRIGGER..LEFT_TRIGGER + 11].copy_from_slice(&self.left_trigger.to_bytes());
+        }
+
+        let player = &self.player_leds;
+        block[LED_BRIGHTNESS] = player.brightness.value();
+        block[PLAYER_LEDS] = (player.mask & 0x1F) | if player.fade { 0 } else { PLAYER_LEDS_INSTANT };
+
+        let (r, g, b) = self.lightbar;
+        block[LIGHTBAR_RGB..LIGHTBAR_RGB + 3].copy_from_slice(&[r, g, b]);
+        block
+    }
+}
+
+// Puts the common block behind the report ID, and for Bluetooth the sequence number and
+// tag, with the CRC after it
+fn frame(block: &[u8; COMMON_LEN], usb_mode: bool, seq: u8) -> Vec<u8> {
+    if usb_mode {
+        let mut report = vec![USB_REPORT_ID];
+        report.extend_from_slice(block);
+        return report;
+    }
+    let mut report = vec![0; BT_REPORT_LEN];
+    report[0] = BT_REPORT_ID;
+    report[1] = (seq & 0x0F) << 4;
+    report[2] = BT_TAG;
+    report[3..3 + COMMON_LEN].copy_from_slice(block);
+    append_crc32(&mut report, CRC_SEED_OUTPUT);
+    report
+}
+
+// What an output report asks the LEDs to show
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LedState {
+    pub lightbar: (u8, u8, u8),
+    pub player_leds: u8,
+    pub mute_led: MuteLed,
+}
+
+// Reads the LED fields back out of a USB or Bluetooth output report
+pub fn parse_output_report(report: &[u8]) -> Option<LedState> {
+    let common = match (report.first()?, report.len()) {
+        (&USB_REPORT_ID, USB_REPORT_LEN) => 1,
+        (&BT_REPORT_ID, BT_REPORT_LEN) => 3,
+        _ => return None,
+    };
+    let block = &report[common..];
+    Some(LedState {
+        lightbar: (block[LIGHTBAR_RGB], block[LIGHTBAR_RGB + 1], block[LIGHTBAR_RGB + 2]),
+        player_leds: block[PLAYER_LEDS] & 0x1F,
+        mute_led: MuteLed::from_value(block[MUTE_LED]),
+    })
+}
+
+// The same output report for the other connection: the common block is kept, the header
+// (and for Bluetooth the CRC) is made anew. None when `report` is no DualSense output report
+pub fn convert_output_report(report: &[u8], usb_mode: bool, seq: u8) -> Option<Vec<u8>> {
+    let block = match (report.first()?, report.len()) {
+        (&USB_REPORT_ID, USB_REPORT_LEN) => &report[1..],
+        (&BT_REPORT_ID, BT_REPORT_LEN) => &report[3..3 + COMMON_LEN],
+        _ => return None,
+    };
+    Some(frame(block.try_into().ok()?, usb_mode, seq))
+}
+
+// Writes the seeded CRC32 of everything but the last 4 bytes into the last 4 bytes
+pub fn append_crc32(report: &mut [u8], seed: u8) {
+    let len = report.len();
+    let crc = calculate_crc32(seed, &report[..len - 4]);
+    report[len - 4..].copy_from_slice(&crc.to_le_bytes());
+}
+
+// Function to calculate CRC32 (needed for Bluetooth), seeded with the report type byte
+pub fn calculate_crc32(seed: u8, data: &[u8]) -> u32 {
+    const CRC32_TABLE: [u32; 256] = generate_crc32_table();
+
+    let mut crc: u32 = 0xFFFFFFFF;
+    for &byte in std::iter::once(&seed).chain(data) {
+        let index = ((crc ^ byte as u32) & 0xFF) as usize;
+        crc = (crc >> 8) ^ CRC32_TABLE[index];
+    }
+    !crc
+}
+
+const fn generate_crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xEDB88320;
+            } else {
+                crc >>= 1;
+            }
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Offsets below are spelled out rather than taken from the constants, so a wrong
+    // constant fails here instead of agreeing with itself
+    fn state() -> OutputState {
+        OutputState {
+            lightbar: (0x11, 0x22, 0x33),
+            mute_led: MuteLed::Pulse,
+            player_leds: PlayerLeds { mask: 0b10101, brightness: PlayerLedBrightness::Low, fade: false },
+            left_trigger: TriggerEffect::Weapon { start: 2, end: 6, strength: 7 },
+            right_trigger: TriggerEffect::Feedback { strengths: [1, 2, 3, 4, 5, 6, 7, 8, 0, 1] },
+            rumble: (0x40, 0x80),
+            leds_only: false,
+        }
+    }
+
+    // The common block, wherever the connection puts it
+    fn check_common_block(block: &[u8], state: &OutputState) {
+        assert_eq!(block[0], 0xFF, "valid flag 0");
+        assert_eq!(block[1], 0xF7, "valid flag 1");
+        assert_eq!(block[2], 0x80, "right motor");
+        assert_eq!(block[3], 0x40, "left motor");
+        assert_eq!(block[8], 2, "mute LED");
+        assert_eq!(block[10..21], state.right_trigger.to_bytes(), "right trigger");
+        assert_eq!(block[21..32], state.left_trigger.to_bytes(), "left trigger");
+        assert_eq!(block[38], 0x01, "valid flag 2");
+        assert_eq!(block[42], 2, "player LED brightness");
+        assert_eq!(block[43], 0b10101 | 0x20, "player LEDs, no fade");
+        assert_eq!(block[44..47], [0x11, 0x22, 0x33], "lightbar");
+    }
+
+    #[test]
+    fn usb_layout() {
+        let report = build_output_report(true, 0, &state());
+        assert_eq!(report.len(), 48);
+        assert_eq!(report[0], 0x02);
+        check_common_block(&report[1..], &state());
+    }
+
+    #[test]
+    fn bluetooth_layout() {
+        let report = build_output_report(false, 5, &state());
+        assert_eq!(report.len(), 78);
+        assert_eq!(report[0], 0x31);
+        assert_eq!(report[1], 0x50, "sequence number in the high nibble");
+        assert_eq!(report[2], 0x10, "tag");
+        check_common_block(&report[3..50], &state());
+        assert!(report[50..74].iter().all(|&b| b == 0), "padding before the CRC");
+        // Only four bits of sequence number
+        assert_eq!(build_output_report(false, 17, &state())[1], 0x10);
+    }
+
+    #[test]
+    fn leds_only_leaves_rumble_and_triggers() {
+        let state = OutputState { leds_only: true, ..state() };
+        let report = build_output_report(true, 0, &state);
+        assert_eq!(report[1], 0x00, "valid flag 0");
+        assert_eq!(report[2], 0x15, "valid flag 1: mic mute LED, lightbar, player LEDs");
+        assert!(report[3..5].iter().all(|&b| b == 0), "motors");
+        assert!(report[11..33].iter().all(|&b| b == 0), "triggers");
+        assert_eq!(report[45..48], [0x11, 0x22, 0x33]);
+    }
+
+    #[test]
+    fn crc32() {
+        // The standard CRC-32 check value, the seed being the first byte
+        assert_eq!(calculate_crc32(b'1', b"23456789"), 0xCBF4_3926);
+
+        // A Bluetooth report for lightbar 112233, everything else default, CRC from zlib
+        let mut expected = [0u8; 78];
+        expected[..3].copy_from_slice(&[0x31, 0x00, 0x10]);
+        expected[3..5].copy_from_slice(&[0xFF, 0xF7]);
+        expected[13] = 0x05;
+        expected[24] = 0x05;
+        expected[41] = 0x01;
+        expected[47..50].copy_from_slice(&[0x11, 0x22, 0x33]);
+        expected[74..].copy_from_slice(&[0x0E, 0xA6, 0x0D, 0x69]);
+        let state = OutputState { lightbar: (0x11, 0x22, 0x33), ..Default::default() };
+        assert_eq!(build_output_report(false, 0, &state), expected);
+
+        let mut report = expected;
+        report[74..].fill(0);
+        append_crc32(&mut report, CRC_SEED_OUTPUT);
+        assert_eq!(report, expected);
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let expected = LedState { lightbar: (0x11, 0x22, 0x33), player_leds: 0b10101, mute_led: MuteLed::Pulse };
+        for usb_mode in [true, false] {
+            assert_eq!(parse_output_report(&build_output_report(usb_mode, 3, &state())), Some(expected));
+        }
+        assert_eq!(parse_output_report(&[0x02; 10]), None);
+    }
+
+    #[test]
+    fn convert_between_connections() {
+        let usb = build_output_report(true, 0, &state());
+        let bt = build_output_report(false, 9, &state());
+        assert_eq!(convert_output_report(&usb, false, 9), Some(bt.clone()));
+        assert_eq!(convert_output_report(&bt, true, 0), Some(usb.clone()));
+        assert_eq!(convert_output_report(&usb, true, 0), Some(usb));
+        assert_eq!(convert_output_report(&[0x05; 32], true, 0), None);
+    }
+}
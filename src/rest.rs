@@ -0,0 +1,120 @@
+// --http: a plain JSON REST API for home automation setups and Stream Deck plugins,
+// without the assistant API's token or smart-bulb shape.
+//
+//   GET  /status    {"on": true, "color": "#a000ff", "brightness": 0.8, "effect": "rainbow", "profile": null, "stats": {...}}
+//   POST /color     {"color": "orange" | "#ff8800" | [255, 136, 0]}   (the solid effect in that color)
+//   POST /effect    {"effect": "breathe", "speed": 0.8}               (speed optional)
+//   GET  /events    WebSocket with the pad's events and the lightbar color, see websocket.rs
+//
+// POSTs need Content-Type: application/json and answer with the status too. Errors come back
+// as {"error": "..."} with 400 for bad JSON or values, 404, 405, 413 for oversized bodies,
+// 415 for another content type and 503 while shutting down.
+use crossbeam_channel::Sender;
+use serde_json::{Value, json};
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+
+use crate::control::{self, ControlCommand, Request, Setting};
+use crate::http::{self, read_request, respond};
+use crate::tuning::Param;
+use crate::websocket::{self, EventHub};
+
+// Loopback only: anyone who can reach the port can drive the lightbar
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:47823";
+
+// `origins` are the web pages besides our own allowed to open /events
+pub fn start(address: &str, origins: Vec<String>, requests: Sender<Request>, hub: EventHub) -> Result<SocketAddr, String> {
+    let listener = TcpListener::bind(address).map_err(|e| format!("HTTP API {}: {}", address, e))?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let (origins, requests, hub) = (origins.clone(), requests.clone(), hub.clone());
+            thread::spawn(move || {
+                let _ = serve(stream, &origins, &requests, &hub);
+            });
+        }
+    });
+    Ok(address)
+}
+
+fn serve(stream: TcpStream, origins: &[String], requests: &Sender<Request>, hub: &EventHub) -> io::Result<()> {
+    let request = match read_request(&stream) {
+        Ok(request) => request,
+        Err(e) if e.kind() == io::ErrorKind::FileTooLarge => {
+            return respond(&stream, "413 Content Too Large", &json!({ "error": e.to_string() }));
+        },
+        Err(e) => return respond(&stream, "400 Bad Request", &json!({ "error": e.to_string() })),
+    };
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/events") if websocket::is_upgrade(&request) => return websocket::serve(stream, &request, origins, hub, requests),
+        (_, "/events") => return respond(&stream, "400 Bad Request", &json!({ "error": "expected a WebSocket upgrade" })),
+        _ => {},
+    }
+
+    // A web page can POST text/plain anywhere without asking first, JSON needs a CORS
+    // preflight that nothing here answers
+    let json = request
+        .header("content-type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|kind| kind.trim().eq_ignore_ascii_case("application/json"));
+    if request.method == "POST" && matches!(request.path.as_str(), "/color" | "/effect") && !json {
+        return respond(&stream, "415 Unsupported Media Type", &json!({ "error": "expected Content-Type: application/json" }));
+    }
+
+    let body: Value = match request.body.is_empty() {
+        true => json!({}),
+        false => match serde_json::from_slice(&request.body) {
+            Ok(body) => body,
+            Err(e) => return respond(&stream, "400 Bad Request", &json!({ "error": format!("invalid JSON: {}", e) })),
+        },
+    };
+
+    let commands = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/status") => Ok(vec![]),
+        ("POST", "/color") => http::color(body.get("color")).map(|color| vec![ControlCommand::SetColor { color, instant: false }]),
+        ("POST", "/effect") => effect_commands(&body),
+        (_, "/status") => return respond(&stream, "405 Method Not Allowed", &json!({ "error": "use GET" })),
+        (_, "/color" | "/effect") => return respond(&stream, "405 Method Not Allowed", &json!({ "error": "use POST" })),
+        _ => return respond(&stream, "404 Not Found", &json!({ "error": "unknown endpoint" })),
+    };
+    let commands = match commands {
+        Ok(commands) => commands,
+        Err(e) => return respond(&stream, "400 Bad Request", &json!({ "error": e })),
+    };
+
+    // Apply the commands in order, then answer with the resulting state
+    let result = commands
+        .into_iter()
+        .chain([ControlCommand::Status])
+        .try_fold(None, |_, command| control::submit(requests, command));
+    match result {
+        Ok(Some(status)) => {
+            let (r, g, b) = status.color;
+            let body = json!({
+                "on": status.on,
+                "color": format!("#{:02x}{:02x}{:02x}", r, g, b),
+                "brightness": status.brightness,
+                "effect": status.effect.name(),
+                "profile": status.profile,
+                "stats": http::stats_json(&status.stats),
+            });
+            respond(&stream, "200 OK", &body)
+        },
+        Ok(None) => respond(&stream, "200 OK", &json!({})),
+        Err(e) if e == control::SHUTTING_DOWN => respond(&stream, "503 Service Unavailable", &json!({ "error": e })),
+        Err(e) => respond(&stream, "400 Bad Request", &json!({ "error": e })),
+    }
+}
+
+pub fn effect_commands(body: &Value) -> Result<Vec<ControlCommand>, String> {
+    let name = body.get("effect").and_then(Value::as_str).ok_or("expected \"effect\" with an effect name")?;
+    let mut commands = vec![ControlCommand::Effect(name.parse()?)];
+    if let Some(speed) = body.get("speed") {
+        let speed = speed.as_f64().ok_or("\"speed\" must be a number")?;
+        commands.push(ControlCommand::SetParams(vec![Setting::Level(Param::Speed, speed as f32)]));
+    }
+    Ok(commands)
+}
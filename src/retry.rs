@@ -0,0 +1,44 @@
+// Retries for output report writes. Some Bluetooth stacks fail a write now and then and
+// take the next one fine, so a failed write is tried again a few times, backing off
+// with some jitter, before it counts as an error and the watchdog starts its clock
+use rand::Rng;
+use serde::Deserialize;
+use std::time::Duration;
+
+// The [write_retry] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    // Extra attempts after the first failed write, 0 gives up right away
+    pub retries: u32,
+    // Wait before the first retry, doubled for every further one
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    // Random share of each wait added or taken away, 0.0-1.0
+    pub jitter: f32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { retries: 1, backoff_ms: 2, max_backoff_ms: 50, jitter: 0.5 }
+    }
+}
+
+impl RetryPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(format!("write_retry.jitter {} is outside 0.0-1.0", self.jitter));
+        }
+        Ok(())
+    }
+
+    // How long to wait before retry number `attempt` (from 0)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff_ms.saturating_mul(1 << attempt.min(16)).min(self.max_backoff_ms) as f32;
+        let jitter = match self.jitter > 0.0 {
+            true => rand::rng().random_range(-self.jitter..=self.jitter),
+            false => 0.0,
+        };
+        Duration::from_secs_f32((backoff * (1.0 + jitter)).max(0.0) / 1000.0)
+    }
+}
@@ -0,0 +1,12 @@
+// --rumble-demo: the motors pulse in time with the rainbow, peaking each time the
+// lightbar passes red. Mostly a quick way to feel that rumble works
+const PEAK: f32 = 200.0;
+// Degrees of hue either side of red the pulse lasts
+const HALF_WIDTH: f32 = 40.0;
+
+// Heavy and light motor strength for the lightbar's current hue
+pub fn pulse(hue: f32) -> (u8, u8) {
+    let from_red = hue.rem_euclid(360.0).min(360.0 - hue.rem_euclid(360.0));
+    let level = (1.0 - from_red / HALF_WIDTH).max(0.0).powi(2);
+    ((level * PEAK) as u8, (level * PEAK / 2.0) as u8)
+}
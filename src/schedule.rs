@@ -0,0 +1,122 @@
+// Switches profiles when [[schedule]] rules fire, checked once per minute. A rule fires
+// on a cron expression or at a sun event (sunrise, sunset, dawn, dusk) at [location]
+use chrono::{Duration as ChronoDuration, Local, NaiveDateTime, Timelike};
+use serde::Deserialize;
+
+use crate::cron::CronExpr;
+use crate::sun::{Event, Location, SunTime};
+
+// One [[schedule]] entry from the config, with either `cron` or `sun`
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleRule {
+    pub cron: Option<String>,
+    // e.g. "sunset", "sunrise +1h" or "dusk -15m"
+    pub sun: Option<String>,
+    pub profile: String,
+}
+
+enum When {
+    Cron(CronExpr),
+    Sun(SunTime, Location),
+}
+
+impl When {
+    fn matches(&self, at: NaiveDateTime) -> bool {
+        match self {
+            When::Cron(expr) => expr.matches(at),
+            When::Sun(time, location) => time.on(at.date(), location) == Some(at),
+        }
+    }
+}
+
+// How far back to look for the rule that should already be in effect at startup
+const CATCH_UP_MINUTES: i64 = 7 * 24 * 60;
+
+pub struct Scheduler {
+    rules: Vec<(When, String)>,
+    last_minute: Option<NaiveDateTime>,
+}
+
+impl Scheduler {
+    pub fn new(rules: &[ScheduleRule], location: Option<&Location>) -> Result<Self, String> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let when = match (&rule.cron, &rule.sun) {
+                    (Some(cron), None) => When::Cron(cron.parse().map_err(|e| format!("schedule '{}': {}", cron, e))?),
+                    (None, Some(sun)) => {
+                        let time = sun.parse().map_err(|e| format!("schedule '{}': {}", sun, e))?;
+                        let location = location.ok_or_else(|| format!("schedule '{}' needs a [location] table", sun))?;
+                        location.check().map_err(|e| format!("[location]: {}", e))?;
+                        When::Sun(time, *location)
+                    },
+                    _ => return Err(format!("schedule for '{}' needs either cron or sun", rule.profile)),
+                };
+                Ok((when, rule.profile.clone()))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { rules, last_minute: None })
+    }
+
+    pub fn profiles(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|(_, profile)| profile.as_str())
+    }
+
+    // Today's sunrise and sunset when a rule depends on them, so a wrong [location] shows
+    pub fn sun_today(&self) -> Option<String> {
+        let location = self.rules.iter().find_map(|(when, _)| match when {
+            When::Sun(_, location) => Some(location),
+            When::Cron(_) => None,
+        })?;
+        let today = Local::now().date_naive();
+        let show = |event| {
+            SunTime { event, offset_minutes: 0 }
+                .on(today, location)
+                .map_or_else(|| "none".to_string(), |at| at.format("%H:%M").to_string())
+        };
+        Some(format!("Sunrise {}, sunset {} today", show(Event::Sunrise), show(Event::Sunset)))
+    }
+
+    // The profile of the most recent rule that fired before now, so starting
+    // at 10:00 on a Monday still picks up a "0 9 * * MON-FRI" rule
+    pub fn current(&mut self) -> Option<&str> {
+        let now = minute_of(Local::now().naive_local());
+        self.last_minute = Some(now);
+
+        (0..=CATCH_UP_MINUTES)
+            .map(|back| now - ChronoDuration::minutes(back))
+            .find_map(|at| self.firing_at(at))
+    }
+
+    // Called every frame; returns a profile on the minute a rule fires. Minutes that went
+    // by without a poll, like a system suspend, are caught up on and the latest rule among
+    // them wins
+    pub fn poll(&mut self) -> Option<&str> {
+        let now = minute_of(Local::now().naive_local());
+        let minutes = match self.last_minute {
+            Some(last) if last == now => return None,
+            Some(last) if last < now => (now - last).num_minutes().min(CATCH_UP_MINUTES),
+            // First poll, or the clock went back
+            _ => 1,
+        };
+        self.last_minute = Some(now);
+
+        (0..minutes)
+            .map(|back| now - ChronoDuration::minutes(back))
+            .find_map(|at| self.firing_at(at))
+    }
+
+    // Later rules win when several fire on the same minute
+    fn firing_at(&self, at: NaiveDateTime) -> Option<&str> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(when, _)| when.matches(at))
+            .map(|(_, profile)| profile.as_str())
+    }
+}
+
+fn minute_of(at: NaiveDateTime) -> NaiveDateTime {
+    at.with_second(0).and_then(|at| at.with_nanosecond(0)).unwrap_or(at)
+}
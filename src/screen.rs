@@ -0,0 +1,822 @@
+// --screen: the lightbar takes its color from what's on screen, Ambilight style.
+//
+// The region is a monitor, a fixed rectangle of the desktop or the focused window, and
+// a grid of points from it is reduced to one color by a sampling strategy. Averaging
+// the whole desktop washes out to gray, the dominant and edge-weighted strategies keep
+// the colors that actually stand out.
+//
+// Each platform's capture is a Grabber: X11 on Linux, or on Wayland grim where the
+// compositor supports it (wlroots ones like Sway and Hyprland, XWayland shows only X
+// windows elsewhere), GDI on Windows and CoreGraphics on macOS.
+use crossbeam_channel::{Receiver, TrySendError, bounded};
+use serde::Deserialize;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+// Points sampled across the region
+const GRID_COLUMNS: u32 = 64;
+const GRID_ROWS: u32 = 36;
+// How quickly the lightbar follows a change on screen
+const SMOOTHING: Duration = Duration::from_millis(150);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    // The part of `self` inside `bounds`, None if they don't overlap
+    fn clamp_to(self, bounds: Rect) -> Option<Rect> {
+        let left = self.x.max(bounds.x);
+        let top = self.y.max(bounds.y);
+        let right = (self.x + self.width as i32).min(bounds.x + bounds.width as i32);
+        let bottom = (self.y + self.height as i32).min(bounds.y + bounds.height as i32);
+        (right > left && bottom > top).then(|| Rect { x: left, y: top, width: (right - left) as u32, height: (bottom - top) as u32 })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    // Monitors are numbered from 0 in the order the platform lists them
+    Monitor(usize),
+    // Desktop coordinates, spanning monitors if needed
+    Rect(Rect),
+    FocusedWindow,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    // "monitor 1", "focused" or "X,Y,WIDTHxHEIGHT"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        if s == "focused" || s == "focused-window" {
+            return Ok(Region::FocusedWindow);
+        }
+        if let Some(index) = s.strip_prefix("monitor") {
+            let index = index.trim_start_matches([' ', ':']);
+            return match index.is_empty() {
+                true => Ok(Region::Monitor(0)),
+                false => index.parse().map(Region::Monitor).map_err(|_| format!("invalid monitor index '{}'", index)),
+            };
+        }
+
+        let invalid = || format!("invalid screen region '{}' (expected \"monitor N\", \"focused\" or \"X,Y,WIDTHxHEIGHT\")", s);
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        let [x, y, size] = parts.as_slice() else { return Err(invalid()) };
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        let rect = Rect {
+            x: x.parse().map_err(|_| invalid())?,
+            y: y.parse().map_err(|_| invalid())?,
+            width: width.parse().map_err(|_| invalid())?,
+            height: height.parse().map_err(|_| invalid())?,
+        };
+        if rect.width == 0 || rect.height == 0 {
+            return Err(invalid());
+        }
+        Ok(Region::Rect(rect))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    // Plain mean of every point
+    Average,
+    // Mean of the most common colorful shade, dark and gray points count for little
+    Dominant,
+    // Mean weighted towards the borders, like the light behind an Ambilight TV
+    EdgeWeighted,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "average" => Ok(Strategy::Average),
+            "dominant" => Ok(Strategy::Dominant),
+            "edge-weighted" | "edges" => Ok(Strategy::EdgeWeighted),
+            _ => Err(format!("unknown sampling strategy '{}' (expected average, dominant or edge-weighted)", s)),
+        }
+    }
+}
+
+// The [screen] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScreenConfig {
+    // "monitor N", "focused" or "X,Y,WIDTHxHEIGHT"
+    pub region: String,
+    // average, dominant or edge-weighted
+    pub strategy: String,
+    // Screen grabs per second
+    pub fps: f32,
+}
+
+impl Default for ScreenConfig {
+    fn default() -> Self {
+        Self { region: "monitor 0".to_string(), strategy: "dominant".to_string(), fps: 15.0 }
+    }
+}
+
+// Reduces a GRID_COLUMNS x GRID_ROWS grid of points to one color
+pub fn sample(points: &[(u8, u8, u8)], strategy: Strategy) -> (u8, u8, u8) {
+    let weighted = |weight: &dyn Fn(usize, (u8, u8, u8)) -> f32| {
+        let mut sum = [0.0f32; 4];
+        for (index, &(r, g, b)) in points.iter().enumerate() {
+            let w = weight(index, (r, g, b));
+            sum[0] += r as f32 * w;
+            sum[1] += g as f32 * w;
+            sum[2] += b as f32 * w;
+            sum[3] += w;
+        }
+        match sum[3] > 0.0 {
+            true => ((sum[0] / sum[3]) as u8, (sum[1] / sum[3]) as u8, (sum[2] / sum[3]) as u8),
+            false => (0, 0, 0),
+        }
+    };
+
+    match strategy {
+        Strategy::Average => weighted(&|_, _| 1.0),
+        Strategy::EdgeWeighted => weighted(&|index, _| {
+            let (column, row) = (index as u32 % GRID_COLUMNS, index as u32 / GRID_COLUMNS);
+            // 0 on the border, 1 in the middle
+            let from_side = |at: u32, size: u32| 1.0 - ((at as f32 + 0.5) / size as f32 * 2.0 - 1.0).abs();
+            let inset = from_side(column, GRID_COLUMNS).min(from_side(row, GRID_ROWS));
+            (1.0 - inset).powi(4)
+        }),
+        Strategy::Dominant => {
+            // 8 levels per channel, each point weighted by how colorful and bright it is
+            let vividness = |(r, g, b): (u8, u8, u8)| {
+                let max = r.max(g).max(b) as f32 / 255.0;
+                let min = r.min(g).min(b) as f32 / 255.0;
+                let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+                0.05 + saturation * max
+            };
+            let bin = |(r, g, b): (u8, u8, u8)| ((r >> 5) as usize) << 6 | ((g >> 5) as usize) << 3 | (b >> 5) as usize;
+            let mut bins = [0.0f32; 512];
+            for &point in points {
+                bins[bin(point)] += vividness(point);
+            }
+            let best = (0..bins.len()).max_by(|&a, &b| bins[a].total_cmp(&bins[b])).unwrap_or(0);
+            weighted(&|_, point| if bin(point) == best { 1.0 } else { 0.0 })
+        },
+    }
+}
+
+// Samples the screen on its own thread, the main loop picks up the latest color
+pub struct ScreenSync {
+    colors: Receiver<(u8, u8, u8)>,
+    target: Option<(u8, u8, u8)>,
+    shown: (f32, f32, f32),
+    description: String,
+}
+
+impl ScreenSync {
+    pub fn start(config: &ScreenConfig, region: Option<&str>) -> Result<Self, String> {
+        let region: Region = region.unwrap_or(&config.region).parse()?;
+        let strategy: Strategy = config.strategy.parse()?;
+        if !config.fps.is_finite() || config.fps <= 0.0 {
+            return Err(format!("screen.fps must be positive, got {}", config.fps));
+        }
+        let interval = Duration::from_secs_f32(1.0 / config.fps);
+
+        // Display connections belong to the thread that opened them
+        let (tx, colors) = bounded(1);
+        let (ready_tx, ready) = bounded(1);
+        thread::spawn(move || {
+            let mut grabber = match open_grabber() {
+                Ok(grabber) => grabber,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                },
+            };
+            let fixed = match region {
+                Region::Monitor(index) => {
+                    let monitors = grabber.monitors();
+                    match monitors.get(index) {
+                        Some(&monitor) => Some(monitor),
+                        None => {
+                            let _ = ready_tx.send(Err(format!("no monitor {}, found {}", index, monitors.len())));
+                            return;
+                        },
+                    }
+                },
+                Region::Rect(rect) => Some(rect),
+                Region::FocusedWindow => None,
+            };
+            let _ = ready_tx.send(Ok(()));
+
+            let mut last_error = None;
+            loop {
+                let started = Instant::now();
+                let rect = fixed.or_else(|| grabber.focused_window()).and_then(|rect| rect.clamp_to(grabber.desktop()));
+                let points = match rect {
+                    Some(rect) => grabber.grab(rect, GRID_COLUMNS, GRID_ROWS),
+                    None => Err("the region is off screen".to_string()),
+                };
+                match points {
+                    Ok(points) => {
+                        last_error = None;
+                        // Only the newest color matters, drop it if the last one is still waiting
+                        if let Err(TrySendError::Disconnected(_)) = tx.try_send(sample(&points, strategy)) {
+                            break;
+                        }
+                    },
+                    Err(e) if last_error.as_ref() != Some(&e) => {
+                        error!("screen: {}", e);
+                        last_error = Some(e);
+                    },
+                    Err(_) => {},
+                }
+                thread::sleep(interval.saturating_sub(started.elapsed()));
+            }
+        });
+        ready.recv().map_err(|_| "screen capture thread failed".to_string())??;
+
+        let description = match region {
+            Region::Monitor(index) => format!("monitor {}", index),
+            Region::Rect(rect) => format!("{},{} {}x{}", rect.x, rect.y, rect.width, rect.height),
+            Region::FocusedWindow => "the focused window".to_string(),
+        };
+        Ok(Self { colors, target: None, shown: (0.0, 0.0, 0.0), description })
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    // Eases towards the newest sampled color, None until the first grab
+    pub fn poll(&mut self, dt: Duration) -> Option<(u8, u8, u8)> {
+        if let Ok(color) = self.colors.try_recv() {
+            if self.target.is_none() {
+                self.shown = (color.0 as f32, color.1 as f32, color.2 as f32);
+            }
+            self.target = Some(color);
+        }
+        let (r, g, b) = self.target?;
+        let t = 1.0 - (-dt.as_secs_f32() / SMOOTHING.as_secs_f32()).exp();
+        let (sr, sg, sb) = &mut self.shown;
+        *sr += (r as f32 - *sr) * t;
+        *sg += (g as f32 - *sg) * t;
+        *sb += (b as f32 - *sb) * t;
+        Some((sr.round() as u8, sg.round() as u8, sb.round() as u8))
+    }
+}
+
+// A platform's way of reading the screen, in desktop coordinates
+trait Grabber {
+    // The bounds of every monitor together
+    fn desktop(&self) -> Rect;
+    fn monitors(&self) -> Vec<Rect>;
+    fn focused_window(&self) -> Option<Rect>;
+    // A `columns` x `rows` grid of points evenly spread over `rect`, row by row
+    fn grab(&mut self, rect: Rect, columns: u32, rows: u32) -> Result<Vec<(u8, u8, u8)>, String>;
+}
+
+#[cfg(target_os = "linux")]
+fn open_grabber() -> Result<Box<dyn Grabber>, String> {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match Wayland::open() {
+            Ok(grabber) => return Ok(Box::new(grabber)),
+            Err(e) => tracing::warn!("screen: {}, trying XWayland", e),
+        }
+    }
+    Ok(Box::new(X11::open()?))
+}
+
+#[cfg(windows)]
+fn open_grabber() -> Result<Box<dyn Grabber>, String> {
+    Ok(Box::new(Gdi))
+}
+
+#[cfg(target_os = "macos")]
+fn open_grabber() -> Result<Box<dyn Grabber>, String> {
+    Ok(Box::new(CoreGraphics))
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+fn open_grabber() -> Result<Box<dyn Grabber>, String> {
+    Err("screen sync is not supported on this platform".into())
+}
+
+// Picks the grid's points out of a `width` x `height` image
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn sample_grid(width: usize, height: usize, columns: u32, rows: u32, pixel: impl Fn(usize, usize) -> (u8, u8, u8)) -> Vec<(u8, u8, u8)> {
+    let at = |i: u32, count: u32, size: usize| (((i as f32 + 0.5) / count as f32 * size as f32) as usize).min(size.saturating_sub(1));
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .map(|(column, row)| pixel(at(column, columns, width), at(row, rows, height)))
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+struct X11 {
+    xlib: x11_dl::xlib::Xlib,
+    display: *mut x11_dl::xlib::Display,
+    root: x11_dl::xlib::Window,
+    active_window: x11_dl::xlib::Atom,
+}
+
+#[cfg(target_os = "linux")]
+impl X11 {
+    fn open() -> Result<Self, String> {
+        use x11_dl::xlib::{Display, XErrorEvent, Xlib};
+
+        // A window closing between lookup and grab is no reason to exit
+        unsafe extern "C" fn ignore_errors(_: *mut Display, _: *mut XErrorEvent) -> i32 {
+            0
+        }
+
+        let xlib = Xlib::open().map_err(|e| format!("cannot load libX11: {}", e))?;
+        unsafe {
+            let display = (xlib.XOpenDisplay)(std::ptr::null());
+            if display.is_null() {
+                return Err("cannot open the X display (screen sync needs X11 or XWayland)".into());
+            }
+            (xlib.XSetErrorHandler)(Some(ignore_errors));
+            let root = (xlib.XDefaultRootWindow)(display);
+            let active_window = (xlib.XInternAtom)(display, c"_NET_ACTIVE_WINDOW".as_ptr(), 0);
+            Ok(Self { xlib, display, root, active_window })
+        }
+    }
+
+    fn window_rect(&self, window: x11_dl::xlib::Window) -> Option<Rect> {
+        unsafe {
+            let mut attributes = std::mem::zeroed();
+            if (self.xlib.XGetWindowAttributes)(self.display, window, &mut attributes) == 0 {
+                return None;
+            }
+            let (mut x, mut y, mut child) = (0, 0, 0);
+            (self.xlib.XTranslateCoordinates)(self.display, window, self.root, 0, 0, &mut x, &mut y, &mut child);
+            Some(Rect { x, y, width: attributes.width.max(0) as u32, height: attributes.height.max(0) as u32 })
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Grabber for X11 {
+    fn desktop(&self) -> Rect {
+        self.window_rect(self.root).unwrap_or(Rect { x: 0, y: 0, width: 0, height: 0 })
+    }
+
+    // Xinerama's screens, or the whole desktop as one monitor without it
+    fn monitors(&self) -> Vec<Rect> {
+        let Ok(xinerama) = x11_dl::xinerama::Xlib::open() else { return vec![self.desktop()] };
+        unsafe {
+            if (xinerama.XineramaIsActive)(self.display) == 0 {
+                return vec![self.desktop()];
+            }
+            let mut count = 0;
+            let screens = (xinerama.XineramaQueryScreens)(self.display, &mut count);
+            if screens.is_null() {
+                return vec![self.desktop()];
+            }
+            let monitors = std::slice::from_raw_parts(screens, count.max(0) as usize)
+                .iter()
+                .map(|screen| Rect { x: screen.x_org as i32, y: screen.y_org as i32, width: screen.width as u32, height: screen.height as u32 })
+                .collect();
+            (self.xlib.XFree)(screens.cast());
+            monitors
+        }
+    }
+
+    // From the window manager's _NET_ACTIVE_WINDOW
+    fn focused_window(&self) -> Option<Rect> {
+        unsafe {
+            let (mut kind, mut format, mut items, mut remaining) = (0, 0, 0, 0);
+            let mut data = std::ptr::null_mut();
+            let status = (self.xlib.XGetWindowProperty)(
+                self.display,
+                self.root,
+                self.active_window,
+                0,
+                1,
+                0,
+                x11_dl::xlib::XA_WINDOW,
+                &mut kind,
+                &mut format,
+                &mut items,
+                &mut remaining,
+                &mut data,
+            );
+            if status != 0 || data.is_null() {
+                return None;
+            }
+            let window = match items {
+                0 => 0,
+                _ => *(data as *const x11_dl::xlib::Window),
+            };
+            (self.xlib.XFree)(data.cast());
+            (window != 0).then(|| self.window_rect(window)).flatten()
+        }
+    }
+
+    // One single-row image per grid row keeps the copies small
+    fn grab(&mut self, rect: Rect, columns: u32, rows: u32) -> Result<Vec<(u8, u8, u8)>, String> {
+        let mut points = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            let y = rect.y + ((row as f32 + 0.5) / rows as f32 * rect.height as f32) as i32;
+            unsafe {
+                let image = (self.xlib.XGetImage)(self.display, self.root, rect.x, y, rect.width, 1, !0, x11_dl::xlib::ZPixmap);
+                if image.is_null() {
+                    return Err("cannot read the screen".into());
+                }
+                let masks = ((*image).red_mask, (*image).green_mask, (*image).blue_mask);
+                // Scales each channel to 0-255 whatever its width, 8 bits on most displays
+                let channel = |pixel: u64, mask: u64| match mask {
+                    0 => 0,
+                    _ => (((pixel & mask) >> mask.trailing_zeros()) * 255 / (mask >> mask.trailing_zeros())) as u8,
+                };
+                for column in 0..columns {
+                    let x = ((column as f32 + 0.5) / columns as f32 * rect.width as f32) as i32;
+                    let pixel = (self.xlib.XGetPixel)(image, x, 0);
+                    points.push((channel(pixel, masks.0), channel(pixel, masks.1), channel(pixel, masks.2)));
+                }
+                (self.xlib.XDestroyImage)(image);
+            }
+        }
+        Ok(points)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for X11 {
+    fn drop(&mut self) {
+        unsafe {
+            (self.xlib.XCloseDisplay)(self.display);
+        }
+    }
+}
+
+// grim, on compositors with the wlr-screencopy protocol. It takes layout coordinates,
+// which are what the rest of the module uses, and scales the grab down on the way
+#[cfg(target_os = "linux")]
+struct Wayland {
+    desktop: Rect,
+}
+
+#[cfg(target_os = "linux")]
+impl Wayland {
+    fn open() -> Result<Self, String> {
+        // One full grab at scale 1 gives the layout's size
+        let image = Self::grim(&["-s", "1"])?;
+        let (width, height, _) = parse_ppm(&image).ok_or("grim returned an unreadable image")?;
+        Ok(Self { desktop: Rect { x: 0, y: 0, width: width as u32, height: height as u32 } })
+    }
+
+    fn grim(args: &[&str]) -> Result<Vec<u8>, String> {
+        let output = std::process::Command::new("grim")
+            .args(["-t", "ppm"])
+            .args(args)
+            .arg("-")
+            .output()
+            .map_err(|e| format!("cannot run grim ({}), is it installed?", e))?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match (output.status.success(), stderr.trim()) {
+            (true, _) => Ok(output.stdout),
+            (false, "") => Err(format!("grim {}", output.status)),
+            (false, message) => Err(format!("grim: {}", message)),
+        }
+    }
+
+    // A command's JSON output, None if the tool isn't there
+    fn json(program: &str, args: &[&str]) -> Option<serde_json::Value> {
+        let output = std::process::Command::new(program).args(args).output().ok()?;
+        output.status.success().then(|| serde_json::from_slice(&output.stdout).ok()).flatten()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Grabber for Wayland {
+    fn desktop(&self) -> Rect {
+        self.desktop
+    }
+
+    // From wlr-randr, or the whole layout as one monitor without it
+    fn monitors(&self) -> Vec<Rect> {
+        let Some(outputs) = Self::json("wlr-randr", &["--json"]) else { return vec![self.desktop] };
+        let monitors: Vec<Rect> = outputs
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|output| output["enabled"].as_bool() == Some(true))
+            .filter_map(|output| {
+                let mode = output["modes"].as_array()?.iter().find(|mode| mode["current"].as_bool() == Some(true))?;
+                let scale = output["scale"].as_f64().unwrap_or(1.0);
+                let (mut width, mut height) = (mode["width"].as_f64()? / scale, mode["height"].as_f64()? / scale);
+                if output["transform"].as_str().is_some_and(|t| t.ends_with("90") || t.ends_with("270")) {
+                    (width, height) = (height, width);
+                }
+                let position = &output["position"];
+                Some(Rect { x: position["x"].as_i64()? as i32, y: position["y"].as_i64()? as i32, width: width as u32, height: height as u32 })
+            })
+            .collect();
+        if monitors.is_empty() { vec![self.desktop] } else { monitors }
+    }
+
+    // Sway and Hyprland can tell, other compositors keep it to themselves
+    fn focused_window(&self) -> Option<Rect> {
+        fn focused(node: &serde_json::Value) -> Option<&serde_json::Value> {
+            if node["focused"].as_bool() == Some(true) {
+                return Some(node);
+            }
+            ["nodes", "floating_nodes"].iter().flat_map(|key| node[key].as_array().into_iter().flatten()).find_map(focused)
+        }
+
+        if std::env::var_os("SWAYSOCK").is_some() {
+            let tree = Self::json("swaymsg", &["-t", "get_tree"])?;
+            let rect = &focused(&tree)?["rect"];
+            return Some(Rect {
+                x: rect["x"].as_i64()? as i32,
+                y: rect["y"].as_i64()? as i32,
+                width: rect["width"].as_u64()? as u32,
+                height: rect["height"].as_u64()? as u32,
+            });
+        }
+        if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            let window = Self::json("hyprctl", &["activewindow", "-j"])?;
+            let (at, size) = (&window["at"], &window["size"]);
+            return Some(Rect {
+                x: at[0].as_i64()? as i32,
+                y: at[1].as_i64()? as i32,
+                width: size[0].as_u64()? as u32,
+                height: size[1].as_u64()? as u32,
+            });
+        }
+        None
+    }
+
+    fn grab(&mut self, rect: Rect, columns: u32, rows: u32) -> Result<Vec<(u8, u8, u8)>, String> {
+        // Shrunk to about the grid's size, no need to copy every pixel out of grim
+        let scale = (columns as f32 / rect.width as f32).max(rows as f32 / rect.height as f32).min(1.0);
+        let geometry = format!("{},{} {}x{}", rect.x, rect.y, rect.width, rect.height);
+        let image = Self::grim(&["-s", &scale.to_string(), "-g", &geometry])?;
+        let (width, height, pixels) = parse_ppm(&image).ok_or("grim returned an unreadable image")?;
+        Ok(sample_grid(width, height, columns, rows, |x, y| {
+            let i = (y * width + x) * 3;
+            (pixels[i], pixels[i + 1], pixels[i + 2])
+        }))
+    }
+}
+
+// A binary PPM with 8-bit channels: width, height and the RGB bytes
+#[cfg(target_os = "linux")]
+fn parse_ppm(data: &[u8]) -> Option<(usize, usize, &[u8])> {
+    let mut fields = Vec::new();
+    let mut at = 0;
+    while fields.len() < 4 {
+        while data.get(at)?.is_ascii_whitespace() {
+            at += 1;
+        }
+        let start = at;
+        while !data.get(at)?.is_ascii_whitespace() {
+            at += 1;
+        }
+        fields.push(std::str::from_utf8(&data[start..at]).ok()?);
+    }
+    // A single whitespace byte ends the header
+    let pixels = data.get(at + 1..)?;
+    let (width, height): (usize, usize) = (fields[1].parse().ok()?, fields[2].parse().ok()?);
+    (fields[0] == "P6" && fields[3] == "255" && pixels.len() >= width * height * 3).then_some((width, height, pixels))
+}
+
+#[cfg(windows)]
+struct Gdi;
+
+#[cfg(windows)]
+impl Grabber for Gdi {
+    fn desktop(&self) -> Rect {
+        use winapi::um::winuser::{GetSystemMetrics, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN};
+
+        unsafe {
+            Rect {
+                x: GetSystemMetrics(SM_XVIRTUALSCREEN),
+                y: GetSystemMetrics(SM_YVIRTUALSCREEN),
+                width: GetSystemMetrics(SM_CXVIRTUALSCREEN).max(0) as u32,
+                height: GetSystemMetrics(SM_CYVIRTUALSCREEN).max(0) as u32,
+            }
+        }
+    }
+
+    fn monitors(&self) -> Vec<Rect> {
+        use winapi::shared::minwindef::{BOOL, LPARAM, TRUE};
+        use winapi::shared::windef::{HDC, HMONITOR, LPRECT};
+        use winapi::um::winuser::EnumDisplayMonitors;
+
+        unsafe extern "system" fn collect(_: HMONITOR, _: HDC, rect: LPRECT, monitors: LPARAM) -> BOOL {
+            unsafe {
+                let rect = *rect;
+                (*(monitors as *mut Vec<Rect>)).push(Rect {
+                    x: rect.left,
+                    y: rect.top,
+                    width: (rect.right - rect.left).max(0) as u32,
+                    height: (rect.bottom - rect.top).max(0) as u32,
+                });
+            }
+            TRUE
+        }
+
+        let mut monitors: Vec<Rect> = Vec::new();
+        unsafe {
+            EnumDisplayMonitors(std::ptr::null_mut(), std::ptr::null(), Some(collect), &mut monitors as *mut _ as LPARAM);
+        }
+        monitors
+    }
+
+    fn focused_window(&self) -> Option<Rect> {
+        use winapi::um::winuser::{GetForegroundWindow, GetWindowRect};
+
+        unsafe {
+            let window = GetForegroundWindow();
+            let mut rect = std::mem::zeroed();
+            if window.is_null() || GetWindowRect(window, &mut rect) == 0 {
+                return None;
+            }
+            Some(Rect { x: rect.left, y: rect.top, width: (rect.right - rect.left).max(0) as u32, height: (rect.bottom - rect.top).max(0) as u32 })
+        }
+    }
+
+    // GDI shrinks the region straight to the grid, averaging as it goes
+    fn grab(&mut self, rect: Rect, columns: u32, rows: u32) -> Result<Vec<(u8, u8, u8)>, String> {
+        use winapi::um::wingdi::{
+            BI_RGB, BITMAPINFO, BITMAPINFOHEADER, CreateCompatibleBitmap, CreateCompatibleDC, DIB_RGB_COLORS, DeleteDC, DeleteObject,
+            GetDIBits, HALFTONE, SRCCOPY, SelectObject, SetStretchBltMode, StretchBlt,
+        };
+        use winapi::um::winuser::{GetDC, ReleaseDC};
+
+        unsafe {
+            let screen = GetDC(std::ptr::null_mut());
+            if screen.is_null() {
+                return Err("cannot read the screen".into());
+            }
+            let memory = CreateCompatibleDC(screen);
+            let bitmap = CreateCompatibleBitmap(screen, columns as i32, rows as i32);
+            let previous = SelectObject(memory, bitmap.cast());
+            SetStretchBltMode(memory, HALFTONE);
+            let copied = StretchBlt(memory, 0, 0, columns as i32, rows as i32, screen, rect.x, rect.y, rect.width as i32, rect.height as i32, SRCCOPY);
+
+            let mut info: BITMAPINFO = std::mem::zeroed();
+            info.bmiHeader.biSize = std::mem::size_of::<BITMAPINFOHEADER>() as u32;
+            info.bmiHeader.biWidth = columns as i32;
+            // Negative height: rows top to bottom
+            info.bmiHeader.biHeight = -(rows as i32);
+            info.bmiHeader.biPlanes = 1;
+            info.bmiHeader.biBitCount = 32;
+            info.bmiHeader.biCompression = BI_RGB;
+            let mut pixels = vec![0u8; (columns * rows * 4) as usize];
+            SelectObject(memory, previous);
+            let lines = GetDIBits(memory, bitmap, 0, rows, pixels.as_mut_ptr().cast(), &mut info, DIB_RGB_COLORS);
+
+            DeleteObject(bitmap.cast());
+            DeleteDC(memory);
+            ReleaseDC(std::ptr::null_mut(), screen);
+            if copied == 0 || lines == 0 {
+                return Err("cannot read the screen".into());
+            }
+            // BGRX
+            Ok(pixels.chunks_exact(4).map(|p| (p[2], p[1], p[0])).collect())
+        }
+    }
+}
+
+// CoreGraphics. The terminal running the tool needs the Screen Recording permission,
+// without it only the wallpaper and the menu bar come through
+#[cfg(target_os = "macos")]
+struct CoreGraphics;
+
+#[cfg(target_os = "macos")]
+mod cg {
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct CGPoint {
+        pub x: f64,
+        pub y: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct CGSize {
+        pub width: f64,
+        pub height: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct CGRect {
+        pub origin: CGPoint,
+        pub size: CGSize,
+    }
+
+    pub const WINDOW_LIST_ON_SCREEN_ONLY: u32 = 1;
+    pub const NULL_WINDOW_ID: u32 = 0;
+    // One pixel per point, Retina displays would give four times the pixels for nothing
+    pub const IMAGE_NOMINAL_RESOLUTION: u32 = 1 << 4;
+    pub const BYTE_ORDER_MASK: u32 = 0x7000;
+    pub const BYTE_ORDER_32_LITTLE: u32 = 2 << 12;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    unsafe extern "C" {
+        pub fn CGGetActiveDisplayList(max: u32, displays: *mut u32, count: *mut u32) -> i32;
+        pub fn CGDisplayBounds(display: u32) -> CGRect;
+        pub fn CGWindowListCreateImage(bounds: CGRect, list: u32, window: u32, options: u32) -> *mut c_void;
+        pub fn CGImageGetWidth(image: *mut c_void) -> usize;
+        pub fn CGImageGetHeight(image: *mut c_void) -> usize;
+        pub fn CGImageGetBytesPerRow(image: *mut c_void) -> usize;
+        pub fn CGImageGetBitsPerPixel(image: *mut c_void) -> usize;
+        pub fn CGImageGetBitmapInfo(image: *mut c_void) -> u32;
+        pub fn CGImageGetDataProvider(image: *mut c_void) -> *mut c_void;
+        pub fn CGDataProviderCopyData(provider: *mut c_void) -> *const c_void;
+        pub fn CGImageRelease(image: *mut c_void);
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    unsafe extern "C" {
+        pub fn CFDataGetBytePtr(data: *const c_void) -> *const u8;
+        pub fn CFDataGetLength(data: *const c_void) -> isize;
+        pub fn CFRelease(object: *const c_void);
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Grabber for CoreGraphics {
+    fn desktop(&self) -> Rect {
+        let monitors = self.monitors();
+        let left = monitors.iter().map(|m| m.x).min().unwrap_or(0);
+        let top = monitors.iter().map(|m| m.y).min().unwrap_or(0);
+        let right = monitors.iter().map(|m| m.x + m.width as i32).max().unwrap_or(0);
+        let bottom = monitors.iter().map(|m| m.y + m.height as i32).max().unwrap_or(0);
+        Rect { x: left, y: top, width: (right - left).max(0) as u32, height: (bottom - top).max(0) as u32 }
+    }
+
+    fn monitors(&self) -> Vec<Rect> {
+        let mut displays = [0u32; 16];
+        let mut count = 0;
+        unsafe {
+            if cg::CGGetActiveDisplayList(displays.len() as u32, displays.as_mut_ptr(), &mut count) != 0 {
+                return Vec::new();
+            }
+            displays[..count as usize]
+                .iter()
+                .map(|&display| {
+                    let bounds = cg::CGDisplayBounds(display);
+                    Rect {
+                        x: bounds.origin.x as i32,
+                        y: bounds.origin.y as i32,
+                        width: bounds.size.width as u32,
+                        height: bounds.size.height as u32,
+                    }
+                })
+                .collect()
+        }
+    }
+
+    // Needs the accessibility APIs, which want yet another permission
+    fn focused_window(&self) -> Option<Rect> {
+        None
+    }
+
+    fn grab(&mut self, rect: Rect, columns: u32, rows: u32) -> Result<Vec<(u8, u8, u8)>, String> {
+        let bounds = cg::CGRect {
+            origin: cg::CGPoint { x: rect.x as f64, y: rect.y as f64 },
+            size: cg::CGSize { width: rect.width as f64, height: rect.height as f64 },
+        };
+        unsafe {
+            let image = cg::CGWindowListCreateImage(bounds, cg::WINDOW_LIST_ON_SCREEN_ONLY, cg::NULL_WINDOW_ID, cg::IMAGE_NOMINAL_RESOLUTION);
+            if image.is_null() {
+                return Err("cannot read the screen (is Screen Recording allowed for this terminal?)".into());
+            }
+            let (width, height) = (cg::CGImageGetWidth(image), cg::CGImageGetHeight(image));
+            let stride = cg::CGImageGetBytesPerRow(image);
+            let little_endian = cg::CGImageGetBitmapInfo(image) & cg::BYTE_ORDER_MASK == cg::BYTE_ORDER_32_LITTLE;
+            if cg::CGImageGetBitsPerPixel(image) != 32 {
+                cg::CGImageRelease(image);
+                return Err("unexpected screen pixel format".into());
+            }
+            let data = cg::CGDataProviderCopyData(cg::CGImageGetDataProvider(image));
+            cg::CGImageRelease(image);
+            if data.is_null() {
+                return Err("cannot read the screen".into());
+            }
+            let bytes = std::slice::from_raw_parts(cg::CFDataGetBytePtr(data), cg::CFDataGetLength(data).max(0) as usize);
+            let points = match bytes.len() >= stride * height {
+                // BGRA in memory when little-endian, ARGB otherwise
+                true => Ok(sample_grid(width, height, columns, rows, |x, y| {
+                    let p = &bytes[y * stride + x * 4..][..4];
+                    if little_endian { (p[2], p[1], p[0]) } else { (p[1], p[2], p[3]) }
+                })),
+                false => Err("cannot read the screen".to_string()),
+            };
+            cg::CFRelease(data);
+            points
+        }
+    }
+}
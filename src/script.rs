@@ -0,0 +1,221 @@
+// Effects written in Rhai, for --script (the `scripting` feature). A script defines
+//
+//   fn frame(time, dt, input) { ... }
+//
+// which runs every frame with the seconds since the effect started, the seconds since the
+// frame before and the pad's input, and returns the lightbar color: [r, g, b] with
+// channels 0..255, or a string like "#ff8800" or "orange". `this` is a map that keeps
+// whatever the script stores in it from one frame to the next (`this.hue ?? 0.0` reads a
+// value not set yet). The file is read again when it changes; a version that doesn't
+// compile is reported and the one before keeps running.
+//
+// `input` holds:
+//   buttons       every button and whether it is held, e.g. input.buttons.cross or .dpad_up
+//   left_stick    [x, y], both -1.0..1.0, right and down positive
+//   right_stick   the same
+//   l2, r2        0.0..1.0
+//   touch         [x, y] of each finger on the touchpad, both 0.0..1.0
+//   gyro, accel   the raw [x, y, z] readings, empty when the report has none
+use rhai::{AST, Array, CallFnOptions, Dynamic, Engine, Map, Scope};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{error, info};
+
+use dualsense_rainbow::colors;
+use dualsense_rainbow::input::{Button, ControllerState, TOUCHPAD_SIZE};
+use crate::canvas::Canvas;
+use crate::effects::Effect;
+use crate::palette;
+
+// How often the file is looked at for changes
+const RELOAD_CHECK: Duration = Duration::from_secs(1);
+// Enough for any sensible frame, and a runaway loop fails the frame instead of freezing the lightbar
+const MAX_OPERATIONS: u64 = 200_000;
+
+// A compiled script with a `frame` function
+#[derive(Clone)]
+pub struct Script {
+    pub name: String,
+    path: PathBuf,
+    ast: AST,
+    modified: Option<SystemTime>,
+}
+
+impl Script {
+    // A path, or a name looked up as NAME.rhai in the config directory's scripts/ folder
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = PathBuf::from(name.trim());
+        let path = match path.extension().is_some() || path.components().count() > 1 {
+            true => path,
+            false => match dirs::config_dir() {
+                Some(dir) => dir.join("dualsense-rainbow").join("scripts").join(format!("{}.rhai", name.trim())),
+                None => return Err(format!("no config directory to find script '{}' in", name)),
+            },
+        };
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let (ast, modified) = compile(&engine(), &path)?;
+        Ok(Self { name, path, ast, modified })
+    }
+}
+
+fn compile(engine: &Engine, path: &Path) -> Result<(AST, Option<SystemTime>), String> {
+    let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    let source = fs::read_to_string(path).map_err(|e| format!("cannot read script {}: {}", path.display(), e))?;
+    let ast = engine.compile(source).map_err(|e| format!("script {}: {}", path.display(), e))?;
+    if !ast.iter_functions().any(|function| function.name == "frame" && function.params.len() == 3) {
+        return Err(format!("script {} has no fn frame(time, dt, input)", path.display()));
+    }
+    Ok((ast, modified))
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine
+}
+
+// Runs a script as an effect, from its start whenever it is switched to
+pub struct Scripted {
+    script: Script,
+    engine: Engine,
+    scope: Scope<'static>,
+    state: Dynamic,
+    input: ControllerState,
+    elapsed: Duration,
+    color: (u8, u8, u8),
+    checked: Instant,
+    last_error: Option<String>,
+}
+
+impl Scripted {
+    pub fn new(script: Script) -> Self {
+        Self {
+            script,
+            engine: engine(),
+            scope: Scope::new(),
+            state: Dynamic::from_map(Map::new()),
+            input: ControllerState::default(),
+            elapsed: Duration::ZERO,
+            color: (0, 0, 0),
+            checked: Instant::now(),
+            last_error: None,
+        }
+    }
+
+    fn report(&mut self, error: String) {
+        if self.last_error.as_ref() != Some(&error) {
+            error!("{}{}✗ Script:{} {}", colors::BOLD, colors::RED, colors::RESET, error);
+            self.last_error = Some(error);
+        }
+    }
+
+    // Swaps in the file's new version once it compiles, keeping `this`
+    fn reload(&mut self) {
+        if self.checked.elapsed() < RELOAD_CHECK {
+            return;
+        }
+        self.checked = Instant::now();
+        let modified = fs::metadata(&self.script.path).and_then(|meta| meta.modified()).ok();
+        if modified == self.script.modified {
+            return;
+        }
+        self.script.modified = modified;
+        match compile(&self.engine, &self.script.path) {
+            Ok((ast, _)) => {
+                self.script.ast = ast;
+                self.last_error = None;
+                info!("{}{} Reloaded script {}{}", colors::BOLD, colors::CYAN, self.script.name, colors::RESET);
+            },
+            Err(e) => self.report(e),
+        }
+    }
+}
+
+impl Effect for Scripted {
+    fn render(&mut self, dt: Duration, canvas: &mut Canvas) {
+        self.reload();
+        let args = (self.elapsed.as_secs_f64(), dt.as_secs_f64(), input_map(&self.input));
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.state);
+        let result = self
+            .engine
+            .call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.script.ast, "frame", args)
+            .map_err(|e| e.to_string())
+            .and_then(to_color);
+        self.elapsed += dt;
+        match result {
+            Ok(color) => self.color = color,
+            Err(e) => self.report(format!("{}: {}", self.script.name, e)),
+        }
+        canvas.lightbar = self.color;
+    }
+
+    fn set_speed(&mut self, _speed: f32) {}
+
+    fn input(&mut self, state: &ControllerState) {
+        self.input = *state;
+    }
+}
+
+fn to_color(value: Dynamic) -> Result<(u8, u8, u8), String> {
+    if let Ok(name) = value.clone().into_immutable_string() {
+        return palette::parse_color(&name);
+    }
+    let channel = |value: &Dynamic| match (value.as_int(), value.as_float()) {
+        (Ok(int), _) => Some(int.clamp(0, 255) as u8),
+        (_, Ok(float)) => Some(float.clamp(0.0, 255.0).round() as u8),
+        _ => None,
+    };
+    let type_name = value.type_name();
+    match value.into_array().ok().as_deref() {
+        Some([r, g, b]) => match (channel(r), channel(g), channel(b)) {
+            (Some(r), Some(g), Some(b)) => Ok((r, g, b)),
+            _ => Err("the color's channels must be numbers".into()),
+        },
+        _ => Err(format!("frame() returned {}, not [r, g, b] or a color name", type_name)),
+    }
+}
+
+fn input_map(state: &ControllerState) -> Map {
+    let stick = |(x, y): (u8, u8)| {
+        let axis = |v: u8| Dynamic::from_float(((v as f64 - 128.0) / 127.0).clamp(-1.0, 1.0));
+        Dynamic::from_array(vec![axis(x), axis(y)])
+    };
+    let trigger = |v: u8| Dynamic::from_float(v as f64 / 255.0);
+    let triple = |values: [i16; 3]| values.iter().map(|&v| Dynamic::from_int(v as i64)).collect::<Array>();
+
+    let buttons: Map = Button::ALL.iter().map(|&button| (snake_case(&format!("{:?}", button)).into(), state.is_pressed(button).into())).collect();
+    let touch: Array = state
+        .touch
+        .iter()
+        .flatten()
+        .map(|point| {
+            let x = point.x as f64 / TOUCHPAD_SIZE.0 as f64;
+            let y = point.y as f64 / TOUCHPAD_SIZE.1 as f64;
+            Dynamic::from_array(vec![Dynamic::from_float(x), Dynamic::from_float(y)])
+        })
+        .collect();
+
+    let mut input = Map::new();
+    input.insert("buttons".into(), buttons.into());
+    input.insert("left_stick".into(), stick(state.left_stick));
+    input.insert("right_stick".into(), stick(state.right_stick));
+    input.insert("l2".into(), trigger(state.l2));
+    input.insert("r2".into(), trigger(state.r2));
+    input.insert("touch".into(), touch.into());
+    input.insert("gyro".into(), state.motion.map(|motion| triple(motion.gyro)).unwrap_or_default().into());
+    input.insert("accel".into(), state.motion.map(|motion| triple(motion.accel)).unwrap_or_default().into());
+    input
+}
+
+// "DpadUp" -> "dpad_up"
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
@@ -0,0 +1,70 @@
+// --shuffle: hops to a random effect every few minutes, crossfading between them
+use rand::seq::IndexedRandom;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+use crate::effects::EffectKind;
+
+// The [shuffle] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShuffleConfig {
+    // Effects to pick from, every built-in one when empty
+    pub effects: Vec<String>,
+    pub interval_minutes: f32,
+    pub crossfade_ms: u64,
+}
+
+impl Default for ShuffleConfig {
+    fn default() -> Self {
+        Self { effects: Vec::new(), interval_minutes: 10.0, crossfade_ms: 3000 }
+    }
+}
+
+pub struct Shuffle {
+    pool: Vec<EffectKind>,
+    interval: Duration,
+    crossfade: Duration,
+    last_switch: Instant,
+}
+
+impl Shuffle {
+    pub fn new(config: &ShuffleConfig, interval_minutes: Option<f32>) -> Result<Self, String> {
+        let pool = if config.effects.is_empty() {
+            EffectKind::ALL.into_iter().filter(|kind| !kind.flashing() && !kind.interactive()).collect()
+        } else {
+            config.effects.iter().map(|name| name.parse()).collect::<Result<_, _>>()?
+        };
+
+        let minutes = interval_minutes.unwrap_or(config.interval_minutes);
+        if !minutes.is_finite() || minutes <= 0.0 {
+            return Err(format!("shuffle interval must be a positive number of minutes, got {}", minutes));
+        }
+
+        Ok(Self {
+            pool,
+            interval: Duration::from_secs_f32(minutes * 60.0),
+            crossfade: Duration::from_millis(config.crossfade_ms),
+            last_switch: Instant::now(),
+        })
+    }
+
+    pub fn crossfade(&self) -> Duration {
+        self.crossfade
+    }
+
+    // Any effect from the pool other than `current`, unless it is the only one
+    pub fn pick(&self, current: Option<EffectKind>) -> EffectKind {
+        let candidates: Vec<_> = self.pool.iter().copied().filter(|&kind| Some(kind) != current).collect();
+        *candidates.choose(&mut rand::rng()).unwrap_or(&self.pool[0])
+    }
+
+    // Returns the next effect once the interval has passed
+    pub fn poll(&mut self, current: EffectKind) -> Option<EffectKind> {
+        if self.last_switch.elapsed() < self.interval {
+            return None;
+        }
+        self.last_switch = Instant::now();
+        Some(self.pick(Some(current)))
+    }
+}
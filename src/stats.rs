@@ -0,0 +1,325 @@
+// Write statistics kept by the controller, and periodic samples of them appended to a
+// CSV file for analysing Bluetooth reliability and battery drain under different send
+// rates, or sent to StatsD
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::input::BatteryStatus;
+
+const CSV_HEADER: &str =
+    "timestamp,fps,sent,errors,latency_ms,battery_level,charging,reconnects,latency_p50_ms,latency_p90_ms,latency_p99_ms,target_fps";
+// Most recent writes the latency percentiles are taken from
+const LATENCY_WINDOW: usize = 512;
+// The rolling frame rate covers this much time
+const FPS_WINDOW: Duration = Duration::from_secs(5);
+
+// Write latency percentiles over the last LATENCY_WINDOW successful writes
+#[derive(Clone, Copy, Debug)]
+pub struct Latency {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+// Everything the controller knows about how writing is going
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub sent: u64,
+    pub errors: u64,
+    pub reconnects: u64,
+    pub last_error: Option<String>,
+    // Frames per second over the last few seconds
+    pub fps: f32,
+    // The rate the frame loop aims for, below --fps while writes can't keep up. None
+    // when nothing set one
+    pub target_fps: Option<f32>,
+    // None until a report has been written
+    pub latency: Option<Latency>,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        write!(f, "fps={:.1}", self.fps)?;
+        if let Some(target) = self.target_fps {
+            write!(f, " target_fps={:.1}", target)?;
+        }
+        write!(f, " sent={} errors={} reconnects={}", self.sent, self.errors, self.reconnects)?;
+        if let Some(latency) = self.latency {
+            write!(
+                f,
+                " latency_p50={:.2}ms latency_p90={:.2}ms latency_p99={:.2}ms latency_max={:.2}ms",
+                ms(latency.p50),
+                ms(latency.p90),
+                ms(latency.p99),
+                ms(latency.max)
+            )?;
+        }
+        if let Some(error) = &self.last_error {
+            write!(f, " last_error={:?}", error)?;
+        }
+        Ok(())
+    }
+}
+
+// Counters and rolling windows behind Stats, updated on every write
+#[derive(Default)]
+pub struct StatsTracker {
+    sent: u64,
+    errors: u64,
+    reconnects: u64,
+    last_error: Option<String>,
+    frames: VecDeque<Instant>,
+    latencies: VecDeque<Duration>,
+    // Accumulated since the last take_mean_latency()
+    latency_sum: (Duration, u32),
+    // The latest write's, until take_frame_latency()
+    frame_latency: Option<Duration>,
+    target_fps: Option<f32>,
+}
+
+impl StatsTracker {
+    pub fn frame(&mut self, now: Instant) {
+        while self.frames.front().is_some_and(|&at| now.duration_since(at) > FPS_WINDOW) {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(now);
+    }
+
+    pub fn write_ok(&mut self, latency: Duration) {
+        self.sent += 1;
+        if self.latencies.len() == LATENCY_WINDOW {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back(latency);
+        self.latency_sum.0 += latency;
+        self.latency_sum.1 += 1;
+        self.frame_latency = Some(latency);
+    }
+
+    pub fn write_failed(&mut self, error: &dyn std::fmt::Display) {
+        self.errors += 1;
+        self.last_error = Some(error.to_string());
+    }
+
+    pub fn reconnect_failed(&mut self, error: &dyn std::fmt::Display) {
+        self.last_error = Some(format!("reopen: {}", error));
+    }
+
+    pub fn reconnected(&mut self) {
+        self.reconnects += 1;
+    }
+
+    // Mean time spent in successful writes since the previous call
+    pub fn take_mean_latency(&mut self) -> Option<Duration> {
+        let (total, count) = std::mem::take(&mut self.latency_sum);
+        (count > 0).then(|| total / count)
+    }
+
+    // How long the write since the previous call took, None if nothing was written
+    pub fn take_frame_latency(&mut self) -> Option<Duration> {
+        self.frame_latency.take()
+    }
+
+    pub fn set_target_fps(&mut self, fps: f32) {
+        self.target_fps = Some(fps);
+    }
+
+    pub fn snapshot(&self) -> Stats {
+        let fps = match (self.frames.front(), self.frames.back()) {
+            (Some(first), Some(last)) if last > first => (self.frames.len() - 1) as f32 / last.duration_since(*first).as_secs_f32(),
+            _ => 0.0,
+        };
+
+        let mut sorted: Vec<Duration> = self.latencies.iter().copied().collect();
+        sorted.sort_unstable();
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        let latency = (!sorted.is_empty()).then(|| Latency {
+            p50: percentile(0.50),
+            p90: percentile(0.90),
+            p99: percentile(0.99),
+            max: sorted[sorted.len() - 1],
+        });
+
+        Stats {
+            sent: self.sent,
+            errors: self.errors,
+            reconnects: self.reconnects,
+            last_error: self.last_error.clone(),
+            fps,
+            target_fps: self.target_fps,
+            latency,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StatsSample {
+    pub timestamp: DateTime<Local>,
+    // Average over the sampling interval
+    pub fps: f32,
+    pub stats: Stats,
+    // Mean write latency since the previous sample, None if nothing was written
+    pub latency: Option<Duration>,
+    pub battery: Option<BatteryStatus>,
+}
+
+// Counts frames and says when the next sample is due
+pub struct Sampler {
+    interval: Duration,
+    started: Instant,
+    frames: u32,
+}
+
+impl Sampler {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, started: Instant::now(), frames: 0 }
+    }
+
+    // Call once per frame; returns the frame rate over the interval once it is up
+    pub fn frame(&mut self) -> Option<f32> {
+        self.frames += 1;
+        let elapsed = self.started.elapsed();
+        if elapsed < self.interval {
+            return None;
+        }
+
+        let fps = self.frames as f32 / elapsed.as_secs_f32();
+        self.started = Instant::now();
+        self.frames = 0;
+        Some(fps)
+    }
+}
+
+pub struct StatsCsv {
+    writer: BufWriter<File>,
+}
+
+impl StatsCsv {
+    // Appends to an existing file, writing the header only when it starts out empty
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let empty = file.metadata()?.len() == 0;
+
+        let mut writer = BufWriter::new(file);
+        if empty {
+            writeln!(writer, "{}", CSV_HEADER)?;
+            writer.flush()?;
+        }
+        Ok(Self { writer })
+    }
+
+    // Flushed per row so the file is usable even if the process is killed
+    pub fn append(&mut self, sample: &StatsSample) -> io::Result<()> {
+        let ms = |d: Duration| format!("{:.3}", d.as_secs_f64() * 1000.0);
+        let latency = sample.latency.map(ms).unwrap_or_default();
+        let percentiles = match sample.stats.latency {
+            Some(latency) => [latency.p50, latency.p90, latency.p99].map(ms),
+            None => Default::default(),
+        };
+        let (level, charging) = match sample.battery {
+            Some(battery) => (battery.level.to_string(), battery.charging.to_string()),
+            None => (String::new(), String::new()),
+        };
+
+        writeln!(
+            self.writer,
+            "{},{:.1},{},{},{},{},{},{},{},{}",
+            sample.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            sample.fps,
+            sample.stats.sent,
+            sample.stats.errors,
+            latency,
+            level,
+            charging,
+            sample.stats.reconnects,
+            percentiles.join(","),
+            sample.stats.target_fps.map(|fps| format!("{:.1}", fps)).unwrap_or_default(),
+        )?;
+        self.writer.flush()
+    }
+}
+
+// The [statsd] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct StatsdConfig {
+    // host:port of the StatsD daemon, e.g. "127.0.0.1:8125"
+    pub address: String,
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+}
+
+fn default_prefix() -> String {
+    "dualsense".into()
+}
+
+// Sends each sample as one UDP datagram: counters as deltas, the rest as gauges/timings
+pub struct StatsdEmitter {
+    socket: UdpSocket,
+    prefix: String,
+    last_sent: u64,
+    last_errors: u64,
+    last_reconnects: u64,
+}
+
+impl StatsdEmitter {
+    pub fn open(config: &StatsdConfig) -> io::Result<Self> {
+        let target = config
+            .address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {}", config.address)))?;
+
+        let socket = match target.is_ipv4() {
+            true => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+            false => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?,
+        };
+        socket.connect(target)?;
+
+        Ok(Self { socket, prefix: config.prefix.clone(), last_sent: 0, last_errors: 0, last_reconnects: 0 })
+    }
+
+    pub fn send(&mut self, sample: &StatsSample) -> io::Result<()> {
+        let prefix = &self.prefix;
+        let stats = &sample.stats;
+        let mut lines = vec![
+            format!("{}.reports_sent:{}|c", prefix, stats.sent - self.last_sent),
+            format!("{}.write_errors:{}|c", prefix, stats.errors - self.last_errors),
+            format!("{}.reconnects:{}|c", prefix, stats.reconnects - self.last_reconnects),
+            format!("{}.fps:{:.1}|g", prefix, sample.fps),
+        ];
+        if let Some(target) = stats.target_fps {
+            lines.push(format!("{}.target_fps:{:.1}|g", prefix, target));
+        }
+        if let Some(latency) = sample.latency {
+            lines.push(format!("{}.write_latency:{:.3}|ms", prefix, latency.as_secs_f64() * 1000.0));
+        }
+        if let Some(latency) = stats.latency {
+            for (name, value) in [("p50", latency.p50), ("p90", latency.p90), ("p99", latency.p99)] {
+                lines.push(format!("{}.write_latency_{}:{:.3}|g", prefix, name, value.as_secs_f64() * 1000.0));
+            }
+        }
+        if let Some(battery) = sample.battery {
+            lines.push(format!("{}.battery_level:{}|g", prefix, battery.level));
+            lines.push(format!("{}.charging:{}|g", prefix, battery.charging as u8));
+        }
+
+        self.last_sent = stats.sent;
+        self.last_errors = stats.errors;
+        self.last_reconnects = stats.reconnects;
+
+        // Nobody listening shows up as ECONNREFUSED on the next send, which is not worth reporting
+        match self.socket.send(lines.join("\n").as_bytes()) {
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// Sunrise, sunset and civil twilight for [[schedule]] rules like `sun = "sunset -30m"`,
+// from the sunrise equation (NOAA's simplified form), good to a minute or two away from
+// the poles. Days when the sun doesn't rise or set there have no such event
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, Timelike};
+use serde::Deserialize;
+use std::str::FromStr;
+
+// Where the sun is watched from, the [location] table of the config
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Location {
+    // Degrees, north positive
+    pub latitude: f64,
+    // Degrees, east positive
+    pub longitude: f64,
+}
+
+impl Location {
+    pub fn check(&self) -> Result<(), String> {
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            return Err(format!("latitude must be between -90 and 90, got {}", self.latitude));
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(format!("longitude must be between -180 and 180, got {}", self.longitude));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    // Civil twilight, the sun 6° below the horizon
+    Dawn,
+    Sunrise,
+    Sunset,
+    Dusk,
+}
+
+impl Event {
+    // The sun's altitude at the event, refraction and its radius included for rise and set
+    fn altitude(self) -> f64 {
+        match self {
+            Event::Sunrise | Event::Sunset => -0.833,
+            Event::Dawn | Event::Dusk => -6.0,
+        }
+    }
+
+    fn morning(self) -> bool {
+        matches!(self, Event::Dawn | Event::Sunrise)
+    }
+}
+
+// "sunset", "sunrise +1h", "dusk -15m": an event and minutes before or after it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SunTime {
+    pub event: Event,
+    pub offset_minutes: i64,
+}
+
+impl FromStr for SunTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let split = s.find(['+', '-']).unwrap_or(s.len());
+        let event = match s[..split].trim() {
+            "dawn" => Event::Dawn,
+            "sunrise" => Event::Sunrise,
+            "sunset" => Event::Sunset,
+            "dusk" => Event::Dusk,
+            other => return Err(format!("unknown sun event '{}' (expected sunrise, sunset, dawn or dusk)", other)),
+        };
+
+        let offset = s[split..].replace(' ', "");
+        let offset_minutes = match offset.as_str() {
+            "" => 0,
+            _ => {
+                let invalid = || format!("invalid offset '{}' (expected e.g. -30m or +1h)", offset);
+                let (sign, amount) = offset.split_at(1);
+                let (number, per_unit) = match amount.strip_suffix('h') {
+                    Some(hours) => (hours, 60),
+                    None => (amount.strip_suffix('m').unwrap_or(amount), 1),
+                };
+                let minutes = number.parse::<i64>().map_err(|_| invalid())? * per_unit;
+                if sign == "-" { -minutes } else { minutes }
+            },
+        };
+        Ok(Self { event, offset_minutes })
+    }
+}
+
+impl SunTime {
+    // When it happens on `date` (local), to the minute
+    pub fn on(&self, date: NaiveDate, location: &Location) -> Option<NaiveDateTime> {
+        let at = event_time(date, location, self.event)? + chrono::Duration::minutes(self.offset_minutes);
+        at.with_second(0)
+    }
+}
+
+// Julian day of the J2000 epoch, 2000-01-01 12:00 UTC
+const J2000: f64 = 2451545.0;
+// Julian day of the Unix epoch
+const UNIX_EPOCH_JD: f64 = 2440587.5;
+// Axial tilt, degrees
+const OBLIQUITY: f64 = 23.4397;
+
+fn event_time(date: NaiveDate, location: &Location, event: Event) -> Option<NaiveDateTime> {
+    let (sin, cos) = (|deg: f64| deg.to_radians().sin(), |deg: f64| deg.to_radians().cos());
+
+    // Days from J2000 to this date's noon at the location
+    let day = (date.num_days_from_ce() - 730120) as f64;
+    let mean_noon = day - location.longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean_noon).rem_euclid(360.0);
+    let center = 1.9148 * sin(anomaly) + 0.02 * sin(2.0 * anomaly) + 0.0003 * sin(3.0 * anomaly);
+    let ecliptic = (anomaly + center + 180.0 + 102.9372).rem_euclid(360.0);
+    let transit = J2000 + mean_noon + 0.0053 * sin(anomaly) - 0.0069 * sin(2.0 * ecliptic);
+
+    let declination = (sin(ecliptic) * sin(OBLIQUITY)).asin().to_degrees();
+    let cos_hour_angle = (sin(event.altitude()) - sin(location.latitude) * sin(declination))
+        / (cos(location.latitude) * cos(declination));
+    // Polar day or night
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    let julian = if event.morning() { transit - hour_angle / 360.0 } else { transit + hour_angle / 360.0 };
+
+    let utc = DateTime::from_timestamp(((julian - UNIX_EPOCH_JD) * 86400.0).round() as i64, 0)?;
+    Some(utc.with_timezone(&Local).naive_local())
+}
@@ -0,0 +1,281 @@
+// What `--effect system` shows: CPU or GPU load or temperature, through a color map that
+// runs from green to red by default, a glance at the lightbar says whether the machine
+// is melting.
+//
+//   cpu        load of all cores together, %       sysinfo
+//   cpu-temp   hottest CPU sensor, °C              sysinfo
+//   gpu        load of the first GPU, %            NVML, or amdgpu's sysfs file on Linux
+//   gpu-temp   temperature of the first GPU, °C    NVML, or the GPU's hwmon sensor on Linux
+use serde::Deserialize;
+use serde_json::Value;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, Once};
+use std::thread;
+use std::time::Duration;
+use sysinfo::{Components, CpuRefreshKind, RefreshKind, System};
+use tracing::error;
+
+use crate::colormap::ColorMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    CpuLoad,
+    CpuTemp,
+    GpuLoad,
+    GpuTemp,
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cpu" | "cpu-load" => Ok(Metric::CpuLoad),
+            "cpu-temp" => Ok(Metric::CpuTemp),
+            "gpu" | "gpu-load" => Ok(Metric::GpuLoad),
+            "gpu-temp" => Ok(Metric::GpuTemp),
+            _ => Err(format!("unknown metric '{}' (expected cpu, cpu-temp, gpu or gpu-temp)", s)),
+        }
+    }
+}
+
+impl Metric {
+    fn default_map(self) -> &'static str {
+        match self {
+            Metric::CpuLoad | Metric::GpuLoad => "0:green 50:yellow 90:red",
+            Metric::CpuTemp | Metric::GpuTemp => "45:green 70:yellow 85:red",
+        }
+    }
+}
+
+// The [system] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct SystemConfig {
+    // cpu, cpu-temp, gpu or gpu-temp
+    pub metric: String,
+    // Color map DSL (see colormap.rs), by default green to red over a range that suits the metric
+    pub map: Option<String>,
+    pub interval_ms: u64,
+}
+
+impl Default for SystemConfig {
+    fn default() -> Self {
+        Self { metric: "cpu".to_string(), map: None, interval_ms: 1000 }
+    }
+}
+
+// Polls the metric on its own thread from the first time an effect asks for it. Clones
+// share the thread and the reading, so switching effects back and forth costs nothing
+#[derive(Clone)]
+pub struct SystemMonitor {
+    metric: Metric,
+    map: Arc<ColorMap>,
+    interval: Duration,
+    latest: Arc<Mutex<Option<f64>>>,
+    started: Arc<Once>,
+}
+
+impl SystemMonitor {
+    pub fn new(config: &SystemConfig) -> Result<Self, String> {
+        let metric: Metric = config.metric.parse()?;
+        let map = config.map.as_deref().unwrap_or(metric.default_map()).parse()?;
+        Ok(Self {
+            metric,
+            map: Arc::new(map),
+            interval: Duration::from_millis(config.interval_ms.max(100)),
+            latest: Arc::new(Mutex::new(None)),
+            started: Arc::new(Once::new()),
+        })
+    }
+
+    // The latest reading's color, None until there is one
+    pub fn color(&self) -> Option<(u8, u8, u8)> {
+        self.started.call_once(|| self.spawn());
+        let value = (*self.latest.lock().ok()?)?;
+        self.map.color(&Value::from(value))
+    }
+
+    fn spawn(&self) {
+        let (metric, interval, latest) = (self.metric, self.interval, self.latest.clone());
+        thread::spawn(move || {
+            let mut sensor = Sensor::new(metric);
+            let mut last_error = None;
+            loop {
+                match sensor.read() {
+                    Ok(value) => {
+                        last_error = None;
+                        if let Ok(mut latest) = latest.lock() {
+                            *latest = Some(value);
+                        }
+                    },
+                    Err(e) if last_error.as_ref() != Some(&e) => {
+                        error!("system: {}", e);
+                        last_error = Some(e);
+                    },
+                    Err(_) => {},
+                }
+                thread::sleep(interval);
+            }
+        });
+    }
+}
+
+struct Sensor {
+    metric: Metric,
+    system: System,
+    components: Components,
+    nvml: Option<Result<nvml::Gpu, String>>,
+}
+
+impl Sensor {
+    fn new(metric: Metric) -> Self {
+        let system = match metric {
+            Metric::CpuLoad => System::new_with_specifics(RefreshKind::nothing().with_cpu(CpuRefreshKind::nothing().with_cpu_usage())),
+            _ => System::new(),
+        };
+        let components = match metric {
+            Metric::CpuTemp => Components::new_with_refreshed_list(),
+            _ => Components::new(),
+        };
+        Self { metric, system, components, nvml: None }
+    }
+
+    fn read(&mut self) -> Result<f64, String> {
+        match self.metric {
+            // Usage is measured between two refreshes, the first reading is the poll interval's
+            Metric::CpuLoad => {
+                self.system.refresh_cpu_usage();
+                Ok(self.system.global_cpu_usage() as f64)
+            },
+            Metric::CpuTemp => {
+                self.components.refresh(false);
+                let cpu = |label: &str| {
+                    let label = label.to_ascii_lowercase();
+                    ["cpu", "package", "tctl", "tdie", "core"].iter().any(|name| label.contains(name))
+                };
+                self.components
+                    .iter()
+                    .filter(|component| cpu(component.label()))
+                    .filter_map(|component| component.temperature())
+                    .max_by(f32::total_cmp)
+                    .map(f64::from)
+                    .ok_or_else(|| "no CPU temperature sensor found".to_string())
+            },
+            Metric::GpuLoad | Metric::GpuTemp => {
+                let nvml = self.nvml.get_or_insert_with(nvml::Gpu::open);
+                match (nvml, self.metric) {
+                    (Ok(gpu), Metric::GpuLoad) => gpu.utilization().map(f64::from),
+                    (Ok(gpu), _) => gpu.temperature().map(f64::from),
+                    (Err(nvml_error), metric) => sysfs_gpu(metric).ok_or_else(|| format!("no GPU readings ({})", nvml_error)),
+                }
+            },
+        }
+    }
+}
+
+// amdgpu (and, for the temperature, most other drivers) expose the first card in sysfs
+#[cfg(target_os = "linux")]
+fn sysfs_gpu(metric: Metric) -> Option<f64> {
+    let read = |path: &std::path::Path| std::fs::read_to_string(path).ok()?.trim().parse::<f64>().ok();
+    let cards = std::fs::read_dir("/sys/class/drm").ok()?.flatten().map(|entry| entry.path());
+    let mut devices: Vec<_> = cards
+        .filter(|path| path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("card") && !n.contains('-')))
+        .map(|path| path.join("device"))
+        .collect();
+    devices.sort();
+    devices.into_iter().find_map(|device| match metric {
+        Metric::GpuLoad => read(&device.join("gpu_busy_percent")),
+        _ => {
+            let hwmon = std::fs::read_dir(device.join("hwmon")).ok()?.flatten().next()?.path();
+            // Millidegrees
+            read(&hwmon.join("temp1_input")).map(|milli| milli / 1000.0)
+        },
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sysfs_gpu(_metric: Metric) -> Option<f64> {
+    None
+}
+
+// Just enough NVML, loaded at runtime so machines without an NVIDIA driver don't need it
+mod nvml {
+    use libloading::{Library, Symbol};
+    use std::ffi::c_void;
+
+    #[cfg(windows)]
+    const LIBRARY: &str = "nvml.dll";
+    #[cfg(not(windows))]
+    const LIBRARY: &str = "libnvidia-ml.so.1";
+
+    const SUCCESS: i32 = 0;
+    const TEMPERATURE_GPU: i32 = 0;
+
+    #[repr(C)]
+    struct Utilization {
+        gpu: u32,
+        memory: u32,
+    }
+
+    pub struct Gpu {
+        library: Library,
+        device: *mut c_void,
+    }
+
+    // The handle is only used from the sensor's thread, which owns the Gpu
+    unsafe impl Send for Gpu {}
+
+    impl Gpu {
+        pub fn open() -> Result<Self, String> {
+            unsafe {
+                let library = Library::new(LIBRARY).map_err(|_| format!("{} not found, no NVIDIA driver?", LIBRARY))?;
+                let init: Symbol<unsafe extern "C" fn() -> i32> = library.get(b"nvmlInit_v2\0").map_err(|e| e.to_string())?;
+                if init() != SUCCESS {
+                    return Err("NVML failed to initialise".into());
+                }
+                let by_index: Symbol<unsafe extern "C" fn(u32, *mut *mut c_void) -> i32> =
+                    library.get(b"nvmlDeviceGetHandleByIndex_v2\0").map_err(|e| e.to_string())?;
+                let mut device = std::ptr::null_mut();
+                if by_index(0, &mut device) != SUCCESS {
+                    return Err("NVML found no GPU".into());
+                }
+                Ok(Self { library, device })
+            }
+        }
+
+        pub fn temperature(&self) -> Result<u32, String> {
+            let mut temperature = 0;
+            unsafe {
+                let get: Symbol<unsafe extern "C" fn(*mut c_void, i32, *mut u32) -> i32> =
+                    self.library.get(b"nvmlDeviceGetTemperature\0").map_err(|e| e.to_string())?;
+                match get(self.device, TEMPERATURE_GPU, &mut temperature) {
+                    SUCCESS => Ok(temperature),
+                    code => Err(format!("NVML temperature error {}", code)),
+                }
+            }
+        }
+
+        pub fn utilization(&self) -> Result<u32, String> {
+            let mut utilization = Utilization { gpu: 0, memory: 0 };
+            unsafe {
+                let get: Symbol<unsafe extern "C" fn(*mut c_void, *mut Utilization) -> i32> =
+                    self.library.get(b"nvmlDeviceGetUtilizationRates\0").map_err(|e| e.to_string())?;
+                match get(self.device, &mut utilization) {
+                    SUCCESS => Ok(utilization.gpu),
+                    code => Err(format!("NVML utilization error {}", code)),
+                }
+            }
+        }
+    }
+
+    impl Drop for Gpu {
+        fn drop(&mut self) {
+            unsafe {
+                if let Ok(shutdown) = self.library.get::<unsafe extern "C" fn() -> i32>(b"nvmlShutdown\0") {
+                    shutdown();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// Connection state and battery level in the terminal title (and the preview window's),
+// readable at a glance from the taskbar or a tab without scrolling through the log
+use std::io::IsTerminal;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use dualsense_rainbow::input::BatteryStatus;
+
+const UPDATE_EVERY: Duration = Duration::from_secs(1);
+
+// The latest status line, for windows that show it too
+static CURRENT: Mutex<String> = Mutex::new(String::new());
+
+#[cfg(feature = "preview")]
+pub fn current() -> String {
+    CURRENT.lock().map(|title| title.clone()).unwrap_or_default()
+}
+
+// What the title reports
+pub struct Connection<'a> {
+    pub mode: &'a str,
+    pub responding: bool,
+    pub battery: Option<BatteryStatus>,
+}
+
+impl std::fmt::Display for Connection<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if !self.responding {
+            return write!(f, "{} not responding", self.mode);
+        }
+        write!(f, "{}", self.mode)?;
+        match self.battery {
+            Some(BatteryStatus { level, charging: true }) => write!(f, " · ⚡ {}%", level),
+            Some(BatteryStatus { level, charging: false }) => write!(f, " · 🔋 {}%", level),
+            None => Ok(()),
+        }
+    }
+}
+
+// Sets the title when the state changes and puts the old one back when dropped
+pub struct TitleBar {
+    terminal: bool,
+    last_update: Option<Instant>,
+    shown: String,
+    #[cfg(windows)]
+    previous: Vec<u16>,
+}
+
+impl TitleBar {
+    pub fn new() -> Self {
+        let terminal = std::io::stdout().is_terminal();
+        #[cfg(not(windows))]
+        if terminal {
+            // xterm's title stack, so the shell's title comes back on exit
+            print!("\x1b[22;0t");
+        }
+        Self {
+            terminal,
+            last_update: None,
+            shown: String::new(),
+            #[cfg(windows)]
+            previous: windows::title(),
+        }
+    }
+
+    pub fn update(&mut self, connection: &Connection) {
+        if self.last_update.is_some_and(|at| at.elapsed() < UPDATE_EVERY) {
+            return;
+        }
+        self.last_update = Some(Instant::now());
+
+        let title = format!("DualSense · {}", connection);
+        if title == self.shown {
+            return;
+        }
+        if let Ok(mut current) = CURRENT.lock() {
+            current.clone_from(&title);
+        }
+        if self.terminal {
+            #[cfg(windows)]
+            windows::set_title(&title);
+            #[cfg(not(windows))]
+            {
+                print!("\x1b]0;{}\x07", title);
+                let _ = std::io::Write::flush(&mut std::io::stdout());
+            }
+        }
+        self.shown = title;
+    }
+}
+
+impl Drop for TitleBar {
+    fn drop(&mut self) {
+        if !self.terminal {
+            return;
+        }
+        #[cfg(windows)]
+        windows::restore_title(&self.previous);
+        #[cfg(not(windows))]
+        {
+            print!("\x1b[23;0t");
+            let _ = std::io::Write::flush(&mut std::io::stdout());
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use winapi::um::wincon::{GetConsoleTitleW, SetConsoleTitleW};
+
+    pub fn title() -> Vec<u16> {
+        let mut buf = vec![0u16; 1024];
+        let len = unsafe { GetConsoleTitleW(buf.as_mut_ptr(), buf.len() as u32) } as usize;
+        buf.truncate(len);
+        buf.push(0);
+        buf
+    }
+
+    pub fn set_title(title: &str) {
+        let wide: Vec<u16> = title.encode_utf16().chain([0]).collect();
+        restore_title(&wide);
+    }
+
+    // `title` is NUL-terminated
+    pub fn restore_title(title: &[u16]) {
+        unsafe {
+            SetConsoleTitleW(title.as_ptr());
+        }
+    }
+}
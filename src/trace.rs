@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::colors;
+
+// Which way a report travelled, relative to the host
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Out,
+    In,
+}
+
+impl Direction {
+    fn marker(self) -> &'static str {
+        match self {
+            Direction::Out => ">> OUT",
+            Direction::In => "<< IN ",
+        }
+    }
+}
+
+// Hexdumps raw HID reports with timestamps, to the terminal or to a file
+pub struct HidTracer {
+    sink: Box<dyn Write + Send>,
+    start: Instant,
+    colored: bool,
+    // --debug-reports: the last report of each ID and direction, to highlight what changed
+    previous: Option<HashMap<(Direction, u8), Vec<u8>>>,
+    // Input reports arrive hundreds of times a second, so --debug-reports leaves them out
+    // unless asked
+    inputs: bool,
+}
+
+impl HidTracer {
+    pub fn stdout() -> Self {
+        Self {
+            sink: Box::new(io::stdout()),
+            start: Instant::now(),
+            colored: true,
+            previous: None,
+            inputs: true,
+        }
+    }
+
+    // To the terminal, with the bytes that differ from the previous report of the same
+    // ID highlighted. Input reports only with `inputs`
+    pub fn changes(inputs: bool) -> Self {
+        Self {
+            previous: Some(HashMap::new()),
+            inputs,
+            ..Self::stdout()
+        }
+    }
+
+    pub fn to_file(path: &Path) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self {
+            sink: Box::new(BufWriter::new(file)),
+            start: Instant::now(),
+            colored: false,
+            previous: None,
+            inputs: true,
+        })
+    }
+
+    pub fn record(&mut self, direction: Direction, data: &[u8]) {
+        if direction == Direction::In && !self.inputs {
+            return;
+        }
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let report_id = data.first().copied().unwrap_or(0);
+        let previous = self.previous.as_mut().map(|previous| previous.insert((direction, report_id), data.to_vec()));
+        let changed = match &previous {
+            Some(Some(previous)) => {
+                let changed = data.iter().zip(previous).filter(|(a, b)| a != b).count();
+                format!(", {} changed", changed + data.len().abs_diff(previous.len()))
+            },
+            _ => String::new(),
+        };
+
+        // Tracing must never take the effect down, so write errors are ignored
+        let _ = if self.colored {
+            let color = match direction {
+                Direction::Out => colors::CYAN,
+                Direction::In => colors::YELLOW,
+            };
+            writeln!(self.sink, "{}[{:>12.6}]{} {}{}{}{} report 0x{:02X} ({} bytes{})",
+                     colors::GRAY, elapsed, colors::RESET,
+                     colors::BOLD, color, direction.marker(), colors::RESET,
+                     report_id, data.len(), changed)
+        } else {
+            writeln!(self.sink, "[{:>12.6}] {} report 0x{:02X} ({} bytes{})",
+                     elapsed, direction.marker(), report_id, data.len(), changed)
+        };
+
+        let lines = match previous {
+            Some(previous) => hexdump_changes(data, previous.as_deref()),
+            None => hexdump(data),
+        };
+        for line in lines {
+            let _ = writeln!(self.sink, "    {}", line);
+        }
+        let _ = self.sink.flush();
+    }
+}
+
+// Formats bytes as classic 16-per-line hexdump rows: offset, hex, ASCII
+pub fn hexdump(data: &[u8]) -> Vec<String> {
+    data.chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                .collect();
+            format!("{:04X}  {:<47}  |{}|", row * 16, hex.join(" "), ascii)
+        })
+        .collect()
+}
+
+// hexdump() with the bytes that differ from `previous` (or are past its end) highlighted.
+// Nothing is highlighted for the first report, all of it would be
+fn hexdump_changes(data: &[u8], previous: Option<&[u8]>) -> Vec<String> {
+    data.chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let hex: Vec<String> = chunk
+                .iter()
+                .enumerate()
+                .map(|(i, b)| match previous {
+                    Some(previous) if previous.get(row * 16 + i) != Some(b) => {
+                        format!("{}{}{:02X}{}", colors::BOLD, colors::MAGENTA, b, colors::RESET)
+                    },
+                    _ => format!("{:02X}", b),
+                })
+                .collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                .collect();
+            // The escape codes don't take up columns, so the padding is counted by hand
+            let padding = " ".repeat((16 - chunk.len()) * 3);
+            format!("{:04X}  {}{}  |{}|", row * 16, hex.join(" "), padding, ascii)
+        })
+        .collect()
+}
@@ -0,0 +1,86 @@
+// Where output reports go and input reports come from: a real HID device, or a
+// simulated controller for developing effects without hardware (--mock) and for checking
+// the reports an effect produces (--dry-run, or tests recording them with `on_write`)
+use hidapi::{HidDevice, HidResult};
+use std::sync::{Arc, Mutex};
+
+use crate::report::{self, LedState};
+
+pub trait Transport {
+    fn write(&self, data: &[u8]) -> HidResult<usize>;
+    fn read_timeout(&self, buf: &mut [u8], timeout_ms: i32) -> HidResult<usize>;
+    fn get_feature_report(&self, buf: &mut [u8]) -> HidResult<usize>;
+    fn send_feature_report(&self, data: &[u8]) -> HidResult<()>;
+}
+
+impl Transport for HidDevice {
+    fn write(&self, data: &[u8]) -> HidResult<usize> {
+        HidDevice::write(self, data)
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], timeout_ms: i32) -> HidResult<usize> {
+        HidDevice::read_timeout(self, buf, timeout_ms)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> HidResult<usize> {
+        HidDevice::get_feature_report(self, buf)
+    }
+
+    fn send_feature_report(&self, data: &[u8]) -> HidResult<()> {
+        HidDevice::send_feature_report(self, data)
+    }
+}
+
+type WriteHook = Box<dyn Fn(&[u8]) + Send>;
+
+// Accepts every output report and keeps the LED state it describes.
+// It never produces input, and feature reports read back as zeros
+#[derive(Default)]
+pub struct MockTransport {
+    leds: Arc<Mutex<LedState>>,
+    on_write: Option<WriteHook>,
+}
+
+impl MockTransport {
+    // Hands every output and feature report written to `f`, to log or keep them
+    pub fn on_write(mut self, f: impl Fn(&[u8]) + Send + 'static) -> Self {
+        self.on_write = Some(Box::new(f));
+        self
+    }
+
+    // Shared view of the LEDs, updated with every report written
+    #[cfg(feature = "preview")]
+    pub fn leds(&self) -> Arc<Mutex<LedState>> {
+        self.leds.clone()
+    }
+}
+
+impl Transport for MockTransport {
+    fn write(&self, data: &[u8]) -> HidResult<usize> {
+        if let Some(state) = report::parse_output_report(data)
+            && let Ok(mut leds) = self.leds.lock()
+        {
+            *leds = state;
+        }
+        if let Some(f) = &self.on_write {
+            f(data);
+        }
+        Ok(data.len())
+    }
+
+    fn read_timeout(&self, _buf: &mut [u8], _timeout_ms: i32) -> HidResult<usize> {
+        Ok(0)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> HidResult<usize> {
+        buf[1..].fill(0);
+        Ok(buf.len())
+    }
+
+    fn send_feature_report(&self, data: &[u8]) -> HidResult<()> {
+        if let Some(f) = &self.on_write {
+            f(data);
+        }
+        Ok(())
+    }
+}
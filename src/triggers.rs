@@ -0,0 +1,210 @@
+// Adaptive trigger effects and the small DSL used to author them
+//
+//   off
+//   feedback 0-3:2 4-9:8        resistance per zone (zones 0-9, strength 1-8)
+//   weapon 2-6:8                resistance between two zones that snaps on release
+//   vibration 3-9:5 @40         vibrating zones (amplitude 1-8) at 40 Hz
+//
+// Zones are ten equal steps of trigger travel, 0 being fully released.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+pub const ZONES: usize = 10;
+pub const MAX_STRENGTH: u8 = 8;
+
+// Mode bytes understood by the trigger motors
+const MODE_OFF: u8 = 0x05;
+const MODE_FEEDBACK: u8 = 0x21;
+const MODE_WEAPON: u8 = 0x25;
+const MODE_VIBRATION: u8 = 0x26;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TriggerEffect {
+    #[default]
+    Off,
+    Feedback { strengths: [u8; ZONES] },
+    Weapon { start: u8, end: u8, strength: u8 },
+    Vibration { amplitudes: [u8; ZONES], frequency: u8 },
+}
+
+impl TriggerEffect {
+    // The 11 parameter bytes written into the output report for one trigger
+    pub fn to_bytes(self) -> [u8; 11] {
+        let mut bytes = [0u8; 11];
+        match self {
+            TriggerEffect::Off => bytes[0] = MODE_OFF,
+            TriggerEffect::Feedback { strengths } => {
+                bytes[0] = MODE_FEEDBACK;
+                pack_zones(&strengths, &mut bytes);
+            },
+            TriggerEffect::Weapon { start, end, strength } => {
+                let zones: u16 = (1 << start) | (1 << end);
+                bytes[0] = MODE_WEAPON;
+                bytes[1..3].copy_from_slice(&zones.to_le_bytes());
+                bytes[3] = strength - 1;
+            },
+            TriggerEffect::Vibration { amplitudes, frequency } => {
+                bytes[0] = MODE_VIBRATION;
+                pack_zones(&amplitudes, &mut bytes);
+                bytes[9] = frequency;
+            },
+        }
+        bytes
+    }
+}
+
+// Bitmask of active zones, then a 3-bit (strength - 1) per zone
+fn pack_zones(strengths: &[u8; ZONES], bytes: &mut [u8; 11]) {
+    let mut active: u16 = 0;
+    let mut packed: u32 = 0;
+    for (zone, &strength) in strengths.iter().enumerate() {
+        if strength > 0 {
+            active |= 1 << zone;
+            packed |= ((strength - 1) as u32 & 0x07) << (3 * zone);
+        }
+    }
+    bytes[1..3].copy_from_slice(&active.to_le_bytes());
+    bytes[3..7].copy_from_slice(&packed.to_le_bytes());
+}
+
+impl FromStr for TriggerEffect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let mode = words.next().ok_or("empty trigger effect")?.to_ascii_lowercase();
+
+        let mut segments = Vec::new();
+        let mut frequency = None;
+        for word in words {
+            if let Some(hz) = word.strip_prefix('@') {
+                let hz = hz.trim_end_matches("hz").trim_end_matches("Hz");
+                frequency = Some(hz.parse::<u8>().ok().filter(|&f| f > 0)
+                    .ok_or_else(|| format!("invalid frequency '{}' (expected 1-255 Hz)", word))?);
+            } else {
+                segments.push(parse_segment(word)?);
+            }
+        }
+
+        match mode.as_str() {
+            "off" => {
+                if !segments.is_empty() || frequency.is_some() {
+                    return Err("'off' takes no zones".into());
+                }
+                Ok(TriggerEffect::Off)
+            },
+            "feedback" => {
+                reject_frequency(&mode, frequency)?;
+                Ok(TriggerEffect::Feedback { strengths: zone_strengths(&segments)? })
+            },
+            "weapon" => {
+                reject_frequency(&mode, frequency)?;
+                let [(start, end, strength)] = segments[..] else {
+                    return Err("weapon takes exactly one 'start-end:strength' segment".into());
+                };
+                if !(2..=7).contains(&start) || end <= start || end > 8 {
+                    return Err(format!("weapon zones must satisfy 2 <= start < end <= 8, got {}-{}", start, end));
+                }
+                Ok(TriggerEffect::Weapon { start, end, strength })
+            },
+            "vibration" => {
+                let frequency = frequency.ok_or("vibration needs a frequency, e.g. '@40'")?;
+                Ok(TriggerEffect::Vibration { amplitudes: zone_strengths(&segments)?, frequency })
+            },
+            _ => Err(format!("unknown trigger mode '{}' (expected off, feedback, weapon or vibration)", mode)),
+        }
+    }
+}
+
+impl fmt::Display for TriggerEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TriggerEffect::Off => write!(f, "off"),
+            TriggerEffect::Feedback { strengths } => write!(f, "feedback{}", format_zones(strengths)),
+            TriggerEffect::Weapon { start, end, strength } => write!(f, "weapon {}-{}:{}", start, end, strength),
+            TriggerEffect::Vibration { amplitudes, frequency } => {
+                write!(f, "vibration{} @{}", format_zones(amplitudes), frequency)
+            },
+        }
+    }
+}
+
+fn reject_frequency(mode: &str, frequency: Option<u8>) -> Result<(), String> {
+    match frequency {
+        Some(_) => Err(format!("{} does not take a frequency", mode)),
+        None => Ok(()),
+    }
+}
+
+// "3-9:6" or "4:2" -> (first zone, last zone, strength)
+fn parse_segment(word: &str) -> Result<(u8, u8, u8), String> {
+    let (zones, strength) = word
+        .split_once(':')
+        .ok_or_else(|| format!("expected 'zones:strength', got '{}'", word))?;
+    let (first, last) = zones.split_once('-').unwrap_or((zones, zones));
+
+    let zone = |z: &str| {
+        z.parse::<u8>().ok().filter(|&z| (z as usize) < ZONES)
+            .ok_or_else(|| format!("invalid zone '{}' in '{}' (expected 0-{})", z, word, ZONES - 1))
+    };
+    let (first, last) = (zone(first)?, zone(last)?);
+    if first > last {
+        return Err(format!("zone range '{}' is backwards", zones));
+    }
+
+    let strength = strength.parse::<u8>().ok().filter(|s| (1..=MAX_STRENGTH).contains(s))
+        .ok_or_else(|| format!("invalid strength '{}' in '{}' (expected 1-{})", strength, word, MAX_STRENGTH))?;
+
+    Ok((first, last, strength))
+}
+
+fn zone_strengths(segments: &[(u8, u8, u8)]) -> Result<[u8; ZONES], String> {
+    if segments.is_empty() {
+        return Err("at least one 'zones:strength' segment is required".into());
+    }
+
+    let mut strengths = [0u8; ZONES];
+    for &(first, last, strength) in segments {
+        for zone in first..=last {
+            let slot = &mut strengths[zone as usize];
+            if *slot != 0 {
+                return Err(format!("zone {} is defined more than once", zone));
+            }
+            *slot = strength;
+        }
+    }
+    Ok(strengths)
+}
+
+// Collapses runs of equal strength back into "first-last:strength" segments
+fn format_zones(strengths: &[u8; ZONES]) -> String {
+    let mut out = String::new();
+    let mut zone = 0;
+    while zone < ZONES {
+        let strength = strengths[zone];
+        let start = zone;
+        while zone + 1 < ZONES && strengths[zone + 1] == strength {
+            zone += 1;
+        }
+        if strength > 0 {
+            if start == zone {
+                out += &format!(" {}:{}", start, strength);
+            } else {
+                out += &format!(" {}-{}:{}", start, zone, strength);
+            }
+        }
+        zone += 1;
+    }
+    out
+}
+
+// Resolves a name defined in the config's [triggers] table, or parses the DSL directly
+pub fn resolve(spec: &str, named: &BTreeMap<String, String>) -> Result<TriggerEffect, String> {
+    match named.get(spec.trim()) {
+        Some(definition) => definition
+            .parse()
+            .map_err(|e| format!("trigger '{}': {}", spec.trim(), e)),
+        None => spec.parse(),
+    }
+}
@@ -0,0 +1,152 @@
+// Live tuning from the keyboard: +/- nudges the selected parameter, Tab picks the next one
+// and p writes the current values back into the active profile of the config file
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use toml_edit::{DocumentMut, Item, Table, value};
+
+use crate::profile::Settings;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Param {
+    #[default]
+    Speed,
+    Brightness,
+    Saturation,
+}
+
+impl Param {
+    pub fn name(self) -> &'static str {
+        match self {
+            Param::Speed => "Speed",
+            Param::Brightness => "Brightness",
+            Param::Saturation => "Saturation",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Param::Speed => Param::Brightness,
+            Param::Brightness => Param::Saturation,
+            Param::Saturation => Param::Speed,
+        }
+    }
+
+    // (step, min, max)
+    fn limits(self) -> (f32, f32, f32) {
+        match self {
+            Param::Speed => (0.25, 0.0, 20.0),
+            Param::Brightness | Param::Saturation => (0.05, 0.0, 1.0),
+        }
+    }
+
+    pub fn get(self, settings: &Settings) -> f32 {
+        match self {
+            Param::Speed => settings.speed,
+            Param::Brightness => settings.brightness,
+            Param::Saturation => settings.saturation,
+        }
+    }
+
+    fn set(self, settings: &mut Settings, value: f32) {
+        match self {
+            Param::Speed => settings.speed = value,
+            Param::Brightness => settings.brightness = value,
+            Param::Saturation => settings.saturation = value,
+        }
+    }
+
+    // Sets an exact value, rejecting anything outside the range the keys can reach
+    pub fn apply(self, settings: &mut Settings, value: f32) -> Result<(), String> {
+        let (_, min, max) = self.limits();
+        if !(min..=max).contains(&value) {
+            return Err(format!("{} {} is outside {}-{}", self.name().to_ascii_lowercase(), value, min, max));
+        }
+        self.set(settings, value);
+        Ok(())
+    }
+}
+
+impl FromStr for Param {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "speed" => Ok(Param::Speed),
+            "brightness" => Ok(Param::Brightness),
+            "saturation" => Ok(Param::Saturation),
+            _ => Err(format!("unknown parameter '{}' (expected speed, brightness or saturation)", s)),
+        }
+    }
+}
+
+pub enum Tuned {
+    Changed(Param),
+    Selected(Param),
+    Save,
+}
+
+#[derive(Default)]
+pub struct Tuner {
+    selected: Param,
+}
+
+impl Tuner {
+    // Applies a key press to the settings, None for keys that mean nothing here
+    pub fn key(&mut self, key: u8, settings: &mut Settings) -> Option<Tuned> {
+        let direction = match key {
+            b'+' | b'=' => 1.0,
+            b'-' | b'_' => -1.0,
+            b'\t' => {
+                self.selected = self.selected.next();
+                return Some(Tuned::Selected(self.selected));
+            },
+            b'p' | b'P' => return Some(Tuned::Save),
+            _ => return None,
+        };
+
+        let param = self.selected;
+        let (step, min, max) = param.limits();
+        // Snap to the step so repeated presses don't accumulate float noise
+        let stepped = ((param.get(settings) / step).round() + direction) * step;
+        param.set(settings, stepped.clamp(min, max));
+        Some(Tuned::Changed(param))
+    }
+}
+
+// Writes speed, brightness and saturation into [profiles.<name>], keeping
+// the rest of the file (comments included) as it was
+pub fn save_to_profile(path: &Path, name: &str, settings: &Settings) -> Result<(), String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+    };
+    let mut doc: DocumentMut = text.parse().map_err(|e| format!("invalid config {}: {}", path.display(), e))?;
+
+    let profiles = doc
+        .entry("profiles")
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or("'profiles' in the config is not a table")?;
+    let profile = profiles
+        .entry(name)
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_like_mut()
+        .ok_or_else(|| format!("profiles.{} in the config is not a table", name))?;
+
+    // Two decimals is plenty and keeps the file readable
+    let round = |v: f32| ((v * 100.0).round() / 100.0) as f64;
+    profile.insert("speed", value(round(settings.speed)));
+    profile.insert("brightness", value(round(settings.brightness)));
+    profile.insert("saturation", value(round(settings.saturation)));
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    }
+    fs::write(path, doc.to_string()).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
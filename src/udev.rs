@@ -0,0 +1,92 @@
+// `setup-udev`: hidraw device nodes belong to root, so without a rule opening the controller
+// fails with "Permission denied". This writes a rule letting the user at the seat open
+// every supported pad, over USB and Bluetooth, and has udev apply it to the ones already
+// plugged in. Writing to /etc goes through sudo when not run as root
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use dualsense_rainbow::device::{DUALSENSE_VID, Model};
+use dualsense_rainbow::{Error, colors};
+use crate::init;
+
+// uaccess is handled by 73-seat-late.rules, so the rule has to sort before it
+pub const RULES_PATH: &str = "/etc/udev/rules.d/70-dualsense-rainbow.rules";
+
+// KERNELS matches the parent HID device, named BUS:VID:PID.N for USB (0003) and
+// Bluetooth (0005) alike
+pub fn rules(uinput: bool) -> String {
+    let mut rules = String::from("# Written by `dualsense-rainbow setup-udev`: lets the logged-in user open the controllers\n");
+    for model in Model::ALL {
+        rules += &format!(
+            "# {}\nKERNEL==\"hidraw*\", KERNELS==\"*{:04X}:{:04X}.*\", MODE=\"0660\", TAG+=\"uaccess\"\n",
+            model.name(),
+            DUALSENSE_VID,
+            model.product_id()
+        );
+    }
+    if uinput {
+        rules += "# Virtual pads for --passthrough, for members of the input group\n";
+        rules += "KERNEL==\"uinput\", MODE=\"0660\", GROUP=\"input\", OPTIONS+=\"static_node=uinput\"\n";
+    }
+    rules
+}
+
+pub fn run(yes: bool, uinput: bool) -> Result<(), Error> {
+    let rules = rules(uinput);
+    if fs::read_to_string(RULES_PATH).is_ok_and(|installed| installed == rules) {
+        println!("{}✓ {} is already up to date{}", colors::GREEN, RULES_PATH, colors::RESET);
+        return Ok(());
+    }
+
+    println!("{}{} udev rules for {}{}\n", colors::BOLD, colors::CYAN, RULES_PATH, colors::RESET);
+    for line in rules.lines() {
+        println!("  {}", line);
+    }
+    println!();
+    let root = unsafe { libc::geteuid() } == 0;
+    if !root {
+        println!("{}Writing it and reloading udev needs root, sudo will ask for your password{}", colors::GRAY, colors::RESET);
+    }
+    if !yes && !init::confirm("Install it?", true)? {
+        return Ok(());
+    }
+
+    privileged(root, "tee", &[RULES_PATH], Some(&rules))?;
+    privileged(root, "udevadm", &["control", "--reload-rules"], None)?;
+    privileged(root, "udevadm", &["trigger", "--subsystem-match=hidraw"], None)?;
+    if uinput {
+        privileged(root, "udevadm", &["trigger", "--subsystem-match=misc", "--sysname-match=uinput"], None)?;
+    }
+    println!("{}{}✓ Installed {}{}", colors::BOLD, colors::GREEN, RULES_PATH, colors::RESET);
+    println!("{}If the controller still can't be opened, unplug it or reconnect it over Bluetooth{}", colors::GRAY, colors::RESET);
+    if uinput {
+        println!("{}For --passthrough, add yourself to the input group and log in again:{} sudo usermod -aG input $USER", colors::GRAY, colors::RESET);
+    }
+    Ok(())
+}
+
+// Runs `program` as root, through sudo unless already root, feeding it `input`
+fn privileged(root: bool, program: &str, args: &[&str], input: Option<&str>) -> Result<(), Error> {
+    let mut command = match root {
+        true => Command::new(program),
+        false => {
+            let mut sudo = Command::new("sudo");
+            sudo.arg(program);
+            sudo
+        },
+    };
+    command.args(args).stdout(Stdio::null());
+    if input.is_some() {
+        command.stdin(Stdio::piped());
+    }
+    let shown = format!("{}{} {}", if root { "" } else { "sudo " }, program, args.join(" "));
+    let mut child = command.spawn().map_err(|e| Error::Other(format!("cannot run {}: {}", shown, e)))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes()).map_err(|e| Error::Other(format!("{}: {}", shown, e)))?;
+    }
+    match child.wait()? {
+        status if status.success() => Ok(()),
+        status => Err(Error::Other(format!("{} failed ({})", shown, status))),
+    }
+}
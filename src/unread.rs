@@ -0,0 +1,155 @@
+// Unread notification indicator: once a minute the lightbar pulses once per unread
+// notification, and stays quiet while the count is zero.
+//
+// The count comes from a command that prints a number (swaync-client -c, dunstctl count
+// history, a script on Windows or macOS), or on Linux from watching the session bus:
+// every notification shown counts until it is dismissed.
+use serde::Deserialize;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Each pulse: fade in and out, then a short gap
+const PULSE: Duration = Duration::from_millis(450);
+const GAP: Duration = Duration::from_millis(250);
+// How often a count command is run
+const COMMAND_INTERVAL: Duration = Duration::from_secs(10);
+
+// The [unread] table of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnreadConfig {
+    // Shell command printing the unread count; None watches D-Bus on Linux
+    pub command: Option<String>,
+    pub every_secs: u64,
+    pub color: String,
+    // Long trains of pulses are hard to count anyway
+    pub max_pulses: u32,
+}
+
+impl Default for UnreadConfig {
+    fn default() -> Self {
+        Self { command: None, every_secs: 60, color: "white".to_string(), max_pulses: 10 }
+    }
+}
+
+pub struct UnreadIndicator {
+    count: Arc<AtomicU32>,
+    color: (u8, u8, u8),
+    every: Duration,
+    max_pulses: u32,
+    // When the current train of pulses started and how many it has
+    train: Option<(Instant, u32)>,
+    last_train: Option<Instant>,
+}
+
+impl UnreadIndicator {
+    pub fn start(config: &UnreadConfig) -> Result<Self, String> {
+        let color = crate::palette::parse_color(&config.color).map_err(|e| format!("unread.color: {}", e))?;
+        let count = Arc::new(AtomicU32::new(0));
+
+        let shared = count.clone();
+        match config.command.clone() {
+            Some(command) => {
+                thread::spawn(move || loop {
+                    if let Some(n) = run_count_command(&command) {
+                        shared.store(n, Ordering::Relaxed);
+                    }
+                    thread::sleep(COMMAND_INTERVAL);
+                });
+            },
+            None => watch_dbus(shared)?,
+        }
+
+        Ok(Self {
+            count,
+            color,
+            every: Duration::from_secs(config.every_secs.max(1)),
+            max_pulses: config.max_pulses.max(1),
+            train: None,
+            last_train: None,
+        })
+    }
+
+    // Pulse color and how far to blend towards it right now, None between pulses
+    pub fn overlay(&mut self, now: Instant) -> Option<((u8, u8, u8), f32)> {
+        if self.train.is_none() && self.last_train.is_none_or(|last| now.duration_since(last) >= self.every) {
+            let count = self.count.load(Ordering::Relaxed).min(self.max_pulses);
+            if count > 0 {
+                self.train = Some((now, count));
+                self.last_train = Some(now);
+            }
+        }
+
+        let (start, count) = self.train?;
+        let elapsed = now.duration_since(start);
+        let period = PULSE + GAP;
+        let pulse = (elapsed.as_secs_f32() / period.as_secs_f32()) as u32;
+        if pulse >= count {
+            self.train = None;
+            return None;
+        }
+        let within = elapsed.as_secs_f32() - pulse as f32 * period.as_secs_f32();
+        let level = if within < PULSE.as_secs_f32() { (within / PULSE.as_secs_f32() * PI).sin() } else { 0.0 };
+        Some((self.color, level))
+    }
+}
+
+fn run_count_command(command: &str) -> Option<u32> {
+    #[cfg(windows)]
+    let output = std::process::Command::new("cmd").args(["/C", command]).output();
+    #[cfg(not(windows))]
+    let output = std::process::Command::new("sh").args(["-c", command]).output();
+
+    let output = output.ok().filter(|output| output.status.success())?;
+    String::from_utf8_lossy(&output.stdout).split_whitespace().next()?.parse().ok()
+}
+
+// Follows org.freedesktop.Notifications through dbus-monitor: a new notification adds one,
+// one the user dismissed (or an app withdrew) takes one away. Expired popups stay unread,
+// most daemons keep them in their history
+#[cfg(target_os = "linux")]
+fn watch_dbus(count: Arc<AtomicU32>) -> Result<(), String> {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new("dbus-monitor")
+        .args(["--session", "interface='org.freedesktop.Notifications'"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("cannot run dbus-monitor ({}), set unread.command instead", e))?;
+    let stdout = child.stdout.take().ok_or("dbus-monitor has no output")?;
+
+    thread::spawn(move || {
+        // The value of the next "uint32 N" argument line
+        fn next_uint(lines: &mut impl Iterator<Item = String>) -> Option<u32> {
+            lines.find_map(|line| line.trim().strip_prefix("uint32 ").and_then(|n| n.parse().ok()))
+        }
+
+        let mut lines = BufReader::new(stdout).lines().map_while(Result::ok);
+        while let Some(line) = lines.next() {
+            if line.starts_with("method call") && line.ends_with("member=Notify") {
+                // Arguments: app_name, replaces_id, ... a non-zero replaces_id updates an existing one
+                if next_uint(&mut lines) == Some(0) {
+                    count.fetch_add(1, Ordering::Relaxed);
+                }
+            } else if line.starts_with("signal") && line.ends_with("member=NotificationClosed") {
+                let _id = next_uint(&mut lines);
+                // Reasons: 1 expired, 2 dismissed by the user, 3 closed by the app
+                if matches!(next_uint(&mut lines), Some(2 | 3)) {
+                    let _ = count.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+                }
+            }
+        }
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watch_dbus(_count: Arc<AtomicU32>) -> Result<(), String> {
+    Err("unread.command is required on this platform".into())
+}
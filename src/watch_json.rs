@@ -0,0 +1,111 @@
+// Polls JSON endpoints and shows a value from each as a color: a CI status, a server's
+// CPU load, a price. With several watches the lightbar takes turns showing each one
+use crossbeam_channel::{Receiver, unbounded};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+use crate::colormap::ColorMap;
+
+// How long each watch is shown when there are several
+const ROTATE_EVERY: Duration = Duration::from_secs(5);
+
+// One [[watch_json]] entry of the config
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WatchJsonConfig {
+    pub url: String,
+    // JSON pointer to the value, e.g. "/status/indicator" or "/items/0/load"
+    pub pointer: String,
+    // Color map DSL, e.g. "0:green 60:yellow 90:red" (see colormap.rs)
+    pub map: String,
+    #[serde(default = "default_interval")]
+    pub interval_secs: u64,
+    // Extra request headers, e.g. { Authorization = "Bearer ..." }
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+fn default_interval() -> u64 {
+    30
+}
+
+// Which watch, and the color its latest value maps to
+type Update = (usize, Option<(u8, u8, u8)>);
+
+pub struct JsonWatches {
+    updates: Receiver<Update>,
+    colors: Vec<Option<(u8, u8, u8)>>,
+    start: Instant,
+}
+
+impl JsonWatches {
+    pub fn start(configs: &[WatchJsonConfig]) -> Result<Self, String> {
+        let (tx, updates) = unbounded();
+        for (index, config) in configs.iter().enumerate() {
+            let map: ColorMap = config.map.parse().map_err(|e| format!("watch_json {}: {}", config.url, e))?;
+            if !config.pointer.is_empty() && !config.pointer.starts_with('/') {
+                return Err(format!("watch_json {}: pointer '{}' must start with '/'", config.url, config.pointer));
+            }
+
+            let (config, tx) = (config.clone(), tx.clone());
+            thread::spawn(move || {
+                let agent: ureq::Agent = ureq::Agent::config_builder()
+                    .timeout_global(Some(Duration::from_secs(10)))
+                    .build()
+                    .into();
+                let mut last_error = None;
+                loop {
+                    let color = match fetch(&agent, &config) {
+                        Ok(value) => {
+                            last_error = None;
+                            map.color(&value)
+                        },
+                        Err(e) => {
+                            // Only report when the failure changes, a flaky endpoint shouldn't flood the terminal
+                            if last_error.as_ref() != Some(&e) {
+                                error!("watch_json {}: {}", config.url, e);
+                                last_error = Some(e);
+                            }
+                            None
+                        },
+                    };
+                    if tx.send((index, color)).is_err() {
+                        break;
+                    }
+                    thread::sleep(Duration::from_secs(config.interval_secs.max(1)));
+                }
+            });
+        }
+        Ok(Self { updates, colors: vec![None; configs.len()], start: Instant::now() })
+    }
+
+    // Color to show now, None until a watch has produced one
+    pub fn poll(&mut self) -> Option<(u8, u8, u8)> {
+        for (index, color) in self.updates.try_iter() {
+            self.colors[index] = color;
+        }
+        let available: Vec<_> = self.colors.iter().flatten().collect();
+        if available.is_empty() {
+            return None;
+        }
+        let turn = (self.start.elapsed().as_secs() / ROTATE_EVERY.as_secs()) as usize;
+        Some(*available[turn % available.len()])
+    }
+}
+
+fn fetch(agent: &ureq::Agent, config: &WatchJsonConfig) -> Result<Value, String> {
+    let mut request = agent.get(&config.url);
+    for (name, value) in &config.headers {
+        request = request.header(name, value);
+    }
+    let mut response = request.call().map_err(|e| e.to_string())?;
+    let text = response.body_mut().read_to_string().map_err(|e| e.to_string())?;
+    let json: Value = serde_json::from_str(&text).map_err(|e| format!("invalid JSON: {}", e))?;
+    json.pointer(&config.pointer)
+        .cloned()
+        .ok_or_else(|| format!("nothing at {}", config.pointer))
+}
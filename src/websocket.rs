@@ -0,0 +1,314 @@
+// The HTTP API's /events WebSocket: streams what happens on the pad (buttons, sticks,
+// touchpad, battery, whether it responds) and the lightbar color as JSON messages, and
+// takes {"color": ...} and {"effect": ...} messages to change it. Enough of RFC 6455 for
+// a browser dashboard: text frames, ping/pong and close, no fragmentation or extensions.
+use crossbeam_channel::{Receiver, Sender, unbounded};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::control::{self, ControlCommand, Request};
+use crate::http::{self, HttpRequest, MAX_BODY};
+use dualsense_rainbow::input::{InputEvent, Stick};
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const TEXT: u8 = 0x1;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+// Hands every message from the main loop to each connected client. The latest
+// connection, battery and color messages are kept for clients that join later
+#[derive(Clone, Default)]
+pub struct EventHub {
+    inner: Arc<Mutex<Hub>>,
+}
+
+#[derive(Default)]
+struct Hub {
+    clients: Vec<Sender<String>>,
+    latest: BTreeMap<&'static str, String>,
+}
+
+impl EventHub {
+    fn subscribe(&self) -> Receiver<String> {
+        let (tx, rx) = unbounded();
+        if let Ok(mut hub) = self.inner.lock() {
+            for message in hub.latest.values() {
+                let _ = tx.send(message.clone());
+            }
+            hub.clients.push(tx);
+        }
+        rx
+    }
+
+    fn publish(&self, kind: &'static str, message: Value, keep: bool) {
+        let Ok(mut hub) = self.inner.lock() else { return };
+        let text = message.to_string();
+        hub.clients.retain(|client| client.send(text.clone()).is_ok());
+        if keep {
+            hub.latest.insert(kind, text);
+        }
+    }
+
+    pub fn input(&self, event: &InputEvent) {
+        let message = match *event {
+            InputEvent::ButtonDown(button) | InputEvent::ButtonUp(button) => json!({
+                "type": "button",
+                "button": format!("{:?}", button).to_ascii_lowercase(),
+                "pressed": matches!(event, InputEvent::ButtonDown(_)),
+            }),
+            InputEvent::StickMoved { stick, x, y } => json!({
+                "type": "stick",
+                "stick": if stick == Stick::Left { "left" } else { "right" },
+                "x": x,
+                "y": y,
+            }),
+            InputEvent::TouchpadTouch { finger, point } => json!({ "type": "touch", "finger": finger, "x": point.x, "y": point.y }),
+            InputEvent::TouchpadRelease { finger } => json!({ "type": "touch", "finger": finger, "released": true }),
+            InputEvent::BatteryChanged(battery) => {
+                let message = json!({ "type": "battery", "level": battery.level, "charging": battery.charging });
+                return self.publish("battery", message, true);
+            },
+        };
+        self.publish("input", message, false);
+    }
+
+    pub fn connection(&self, responding: bool) {
+        self.publish("connection", json!({ "type": "connection", "responding": responding }), true);
+    }
+
+    pub fn color(&self, (r, g, b): (u8, u8, u8)) {
+        self.publish("color", json!({ "type": "color", "color": format!("#{:02x}{:02x}{:02x}", r, g, b) }), true);
+    }
+}
+
+pub fn is_upgrade(request: &HttpRequest) -> bool {
+    request.header("upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
+
+// Answers the handshake, then runs the connection until either side closes it
+pub fn serve(stream: TcpStream, request: &HttpRequest, origins: &[String], hub: &EventHub, requests: &Sender<Request>) -> io::Result<()> {
+    if !origin_allowed(request, origins) {
+        return http::respond(&stream, "403 Forbidden", &json!({ "error": "origin not allowed, see --http-origin" }));
+    }
+    let Some(key) = request.header("sec-websocket-key") else {
+        return http::respond(&stream, "400 Bad Request", &json!({ "error": "missing Sec-WebSocket-Key" }));
+    };
+    let accept = accept_key(key);
+    write!(
+        &stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    )?;
+    // Reads block until the client says something, however long that takes
+    stream.set_read_timeout(None)?;
+
+    let writer = Arc::new(Mutex::new(stream.try_clone()?));
+    let events = hub.subscribe();
+    {
+        let writer = writer.clone();
+        thread::spawn(move || {
+            for message in events.iter() {
+                let Ok(mut writer) = writer.lock() else { return };
+                if write_frame(&mut *writer, TEXT, message.as_bytes()).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    let mut reader = stream;
+    loop {
+        let (opcode, payload) = read_frame(&mut reader)?;
+        let reply = match opcode {
+            TEXT => command(&payload, requests).err().map(|e| (TEXT, json!({ "type": "error", "error": e }).to_string().into_bytes())),
+            PING => Some((PONG, payload)),
+            CLOSE => {
+                if let Ok(mut writer) = writer.lock() {
+                    let _ = write_frame(&mut *writer, CLOSE, &[]);
+                }
+                // The event thread stops at its next write
+                let _ = reader.shutdown(std::net::Shutdown::Both);
+                return Ok(());
+            },
+            _ => None,
+        };
+        if let Some((opcode, payload)) = reply {
+            let mut writer = writer.lock().map_err(|_| io::Error::other("writer poisoned"))?;
+            write_frame(&mut *writer, opcode, &payload)?;
+        }
+    }
+}
+
+// {"color": ...} switches to that color straight away, {"effect": ...} like POST /effect
+fn command(payload: &[u8], requests: &Sender<Request>) -> Result<(), String> {
+    let body: Value = serde_json::from_slice(payload).map_err(|e| format!("invalid JSON: {}", e))?;
+    let commands = match body.get("color") {
+        Some(color) => vec![ControlCommand::SetColor { color: http::color(Some(color))?, instant: true }],
+        None => crate::rest::effect_commands(&body)?,
+    };
+    commands.into_iter().try_for_each(|command| control::submit(requests, command).map(|_| ()))
+}
+
+// One client frame: always masked, never longer than a request body may be
+fn read_frame(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    stream.read_exact(&mut head)?;
+    let opcode = head[0] & 0x0F;
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as usize
+        },
+        127 => {
+            let mut len = [0u8; 8];
+            stream.read_exact(&mut len)?;
+            usize::try_from(u64::from_be_bytes(len)).unwrap_or(usize::MAX)
+        },
+        len => len as usize,
+    };
+    if len > MAX_BODY || head[1] & 0x80 == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large or unmasked"));
+    }
+
+    let mut mask = [0u8; 4];
+    stream.read_exact(&mut mask)?;
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((opcode, payload))
+}
+
+// A whole, unmasked server frame
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        },
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        },
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
+
+// Any page the browser has open can try a WebSocket to 127.0.0.1, and the browser says
+// which in Origin. Programs outside a browser don't send one
+fn origin_allowed(request: &HttpRequest, origins: &[String]) -> bool {
+    let Some(origin) = request.header("origin") else { return true };
+    let host = origin.split_once("://").map_or(origin, |(_, host)| host);
+    request.header("host").is_some_and(|ours| ours.eq_ignore_ascii_case(host))
+        || origins.iter().any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(origin.trim_end_matches('/')))
+}
+
+// Sec-WebSocket-Accept for the client's Sec-WebSocket-Key
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, GUID).as_bytes()))
+}
+
+// Only ever hashes the handshake key, so speed doesn't matter
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([chunk[i * 4], chunk[i * 4 + 1], chunk[i * 4 + 2], chunk[i * 4 + 3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, word) in h.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - i * 6) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_sample_from_rfc_6455() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn sha1_test_vectors() {
+        let hex = |digest: [u8; 20]| digest.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        // Two blocks once padded
+        assert_eq!(
+            hex(sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}